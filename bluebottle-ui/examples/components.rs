@@ -437,7 +437,7 @@ fn clickable_card() -> Element<'static, Message> {
                 "Example Poster",
                 "Sample text",
                 bluebottle_ui::image::poster(poster, PosterSize::Small),
-                icon::filled("replay").color(color::TEXT_PRIMARY),
+                icon::filled("replay").color(color::text_primary()),
                 Message::Click,
             ),
             bluebottle_ui::card::skeleton(bluebottle_ui::image::poster_skeleton(
@@ -451,7 +451,7 @@ fn clickable_card() -> Element<'static, Message> {
                 "Example Thumbnail",
                 "Sample text",
                 bluebottle_ui::image::thumbnail(thumbnail),
-                icon::filled("replay").color(color::TEXT_PRIMARY),
+                icon::filled("replay").color(color::text_primary()),
                 Message::Click,
             ),
             bluebottle_ui::card::skeleton(bluebottle_ui::image::thumbnail_skeleton()),
@@ -463,7 +463,7 @@ fn clickable_card() -> Element<'static, Message> {
                 "Example Square",
                 "Sample text",
                 bluebottle_ui::image::square(square),
-                icon::filled("replay").color(color::TEXT_PRIMARY),
+                icon::filled("replay").color(color::text_primary()),
                 Message::Click,
             ),
             bluebottle_ui::card::skeleton(bluebottle_ui::image::square_skeleton()),
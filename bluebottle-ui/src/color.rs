@@ -1,35 +1,178 @@
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU8, Ordering};
 
 use iced::theme::{Custom, Palette};
 
-// "Core" colours
-pub const PRIMARY: iced::Color = iced::color!(0x615FFF);
+// Status colours, shared by every palette
 pub const SUCCESS: iced::Color = iced::color!(0x00BC7D);
 pub const ERROR: iced::Color = iced::color!(0xFF2056);
 pub const WARNING: iced::Color = iced::color!(0xFE9A00);
 
+static MODE: AtomicU8 = AtomicU8::new(Mode::Dark as u8);
+static ACCENT: AtomicU8 = AtomicU8::new(Accent::Indigo as u8);
+
+#[repr(u8)]
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+/// Whether the app is drawn light on dark or dark on light.
+pub enum Mode {
+    #[default]
+    Dark = 0,
+    Light = 1,
+}
+
+impl Mode {
+    pub const ALL: [Self; 2] = [Self::Dark, Self::Light];
+
+    fn palette(self) -> &'static Colors {
+        match self {
+            Self::Dark => &DARK,
+            Self::Light => &LIGHT,
+        }
+    }
+}
+
+#[repr(u8)]
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+/// The colour highlighting selected and interactive elements.
+pub enum Accent {
+    #[default]
+    Indigo = 0,
+    Sky = 1,
+    Teal = 2,
+    Violet = 3,
+    Pink = 4,
+}
+
+impl Accent {
+    pub const ALL: [Self; 5] = [
+        Self::Indigo,
+        Self::Sky,
+        Self::Teal,
+        Self::Violet,
+        Self::Pink,
+    ];
+
+    /// Returns the colour of the accent.
+    pub const fn color(self) -> iced::Color {
+        match self {
+            Self::Indigo => iced::color!(0x615FFF),
+            Self::Sky => iced::color!(0x00A6F4),
+            Self::Teal => iced::color!(0x00BBA7),
+            Self::Violet => iced::color!(0x8E51FF),
+            Self::Pink => iced::color!(0xF6339A),
+        }
+    }
+}
+
+/// The colours of a [Mode].
+struct Colors {
+    background: iced::Color,
+    secondary: iced::Color,
+    hover_highlight: iced::Color,
+    disabled: iced::Color,
+    text_default: iced::Color,
+    text_secondary: iced::Color,
+    shimmer: iced::Color,
+}
+
+const DARK: Colors = Colors {
+    background: iced::color!(0x101828),
+    secondary: iced::color!(0x162034),
+    hover_highlight: iced::color!(0x1E2939),
+    disabled: iced::color!(0x364153),
+    text_default: iced::color!(0xFFFFFF),
+    text_secondary: iced::color!(0x62748E),
+    shimmer: iced::color!(0x182236),
+};
+
+const LIGHT: Colors = Colors {
+    background: iced::color!(0xF8FAFC),
+    secondary: iced::color!(0xEEF2F7),
+    hover_highlight: iced::color!(0xE2E8F0),
+    disabled: iced::color!(0xCBD5E1),
+    text_default: iced::color!(0x0F172A),
+    text_secondary: iced::color!(0x64748B),
+    shimmer: iced::color!(0xE5EAF1),
+};
+
+/// Change the mode and accent the app is drawn with, taking effect from the next
+/// frame.
+pub fn set_appearance(mode: Mode, accent: Accent) {
+    MODE.store(mode as u8, Ordering::Relaxed);
+    ACCENT.store(accent as u8, Ordering::Relaxed);
+}
+
+/// Returns the mode the app is drawn with.
+pub fn mode() -> Mode {
+    Mode::ALL
+        .get(MODE.load(Ordering::Relaxed) as usize)
+        .copied()
+        .unwrap_or_default()
+}
+
+/// Returns the accent the app is drawn with.
+pub fn accent() -> Accent {
+    Accent::ALL
+        .get(ACCENT.load(Ordering::Relaxed) as usize)
+        .copied()
+        .unwrap_or_default()
+}
+
+// "Core" colours
+pub fn primary() -> iced::Color {
+    accent().color()
+}
+
 // Background colors
-pub const BACKGROUND: iced::Color = iced::color!(0x101828);
-pub const SECONDARY: iced::Color = iced::color!(0x162034);
-pub const HOVER_HIGHLIGHT: iced::Color = iced::color!(0x1E2939);
-pub const DISABLED: iced::Color = iced::color!(0x364153);
+pub fn background() -> iced::Color {
+    mode().palette().background
+}
+
+pub fn secondary() -> iced::Color {
+    mode().palette().secondary
+}
+
+pub fn hover_highlight() -> iced::Color {
+    mode().palette().hover_highlight
+}
+
+pub fn disabled() -> iced::Color {
+    mode().palette().disabled
+}
 
 // Text secondary colors
-pub const TEXT_DEFAULT: iced::Color = iced::color!(0xFFFFFF);
-pub const TEXT_PRIMARY: iced::Color = PRIMARY;
-pub const TEXT_SECONDARY: iced::Color = iced::color!(0x62748E);
-pub const TEXT_DARK: iced::Color = DISABLED;
-pub const TEXT_DARKER: iced::Color = HOVER_HIGHLIGHT;
+pub fn text_default() -> iced::Color {
+    mode().palette().text_default
+}
+
+pub fn text_primary() -> iced::Color {
+    primary()
+}
+
+pub fn text_secondary() -> iced::Color {
+    mode().palette().text_secondary
+}
+
+pub fn text_dark() -> iced::Color {
+    disabled()
+}
+
+pub fn text_darker() -> iced::Color {
+    hover_highlight()
+}
 
 // Special edge cases to be used _very_ sparingly
-pub const SHIMMER: iced::Color = iced::color!(0x182236);
+pub fn shimmer() -> iced::Color {
+    mode().palette().shimmer
+}
 
-/// Returns a configured color theme for an iced application.
+/// Returns the color theme for an iced application, in the current mode and
+/// accent.
 pub fn theme() -> iced::theme::Theme {
     let base_palette = Palette {
-        background: BACKGROUND,
-        text: TEXT_DEFAULT,
-        primary: PRIMARY,
+        background: background(),
+        text: text_default(),
+        primary: primary(),
         success: SUCCESS,
         warning: WARNING,
         danger: ERROR,
@@ -0,0 +1,219 @@
+use std::time::{Duration, Instant};
+
+use iced::advanced::image::{self, FilterMethod, Handle};
+use iced::advanced::layout::{Limits, Node};
+use iced::advanced::renderer::Style;
+use iced::advanced::widget::{Tree, tree};
+use iced::advanced::{Clipboard, Layout, Shell, Widget, layout};
+use iced::{Element, Event, Length, Radians, Rectangle, Size, border, mouse, window};
+
+use crate::easing;
+
+/// Artwork displaying a placeholder until the image is loaded, then
+/// cross-fading to the image.
+pub fn artwork<'a>(placeholder: Handle, image: Option<Handle>) -> Artwork<'a> {
+    Artwork {
+        placeholder,
+        image,
+        width: Length::Fill,
+        height: Length::Fill,
+        border_radius: border::Radius::default(),
+        fade_duration: Duration::from_millis(300),
+        easing: &easing::STANDARD,
+    }
+}
+
+#[derive(Clone, Copy)]
+struct State {
+    /// The image being displayed.
+    image: Option<image::Id>,
+    /// The opacity of the image over the placeholder.
+    progress: f32,
+    /// When the image was first displayed.
+    start: Instant,
+}
+
+impl State {
+    fn timed_transition(
+        &mut self,
+        image: Option<image::Id>,
+        fade_duration: Duration,
+        now: Instant,
+    ) {
+        if self.image != image {
+            self.image = image;
+            self.progress = 0.0;
+            self.start = now;
+            return;
+        }
+
+        let elapsed = now.duration_since(self.start);
+        self.progress = (elapsed.as_secs_f32() / fade_duration.as_secs_f32()).min(1.0);
+    }
+
+    fn is_fading(&self) -> bool {
+        self.image.is_some() && self.progress < 1.0
+    }
+}
+
+/// An artwork widget, cross-fading from a placeholder to the loaded image.
+pub struct Artwork<'a> {
+    placeholder: Handle,
+    image: Option<Handle>,
+    width: Length,
+    height: Length,
+    border_radius: border::Radius,
+    fade_duration: Duration,
+    easing: &'a easing::Easing,
+}
+
+impl<'a> Artwork<'a> {
+    /// Sets the width of the [Artwork].
+    pub fn width(mut self, width: impl Into<Length>) -> Self {
+        self.width = width.into();
+        self
+    }
+
+    /// Sets the height of the [Artwork].
+    pub fn height(mut self, height: impl Into<Length>) -> Self {
+        self.height = height.into();
+        self
+    }
+
+    /// Sets the border radius of the [Artwork].
+    pub fn border_radius(mut self, radius: impl Into<border::Radius>) -> Self {
+        self.border_radius = radius.into();
+        self
+    }
+
+    /// Sets how long the image takes to fade in over the placeholder.
+    pub fn fade_duration(mut self, duration: Duration) -> Self {
+        self.fade_duration = duration;
+        self
+    }
+
+    /// Set the motion easing of the fade.
+    pub fn easing(mut self, easing: &'a easing::Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    fn draw_layer<Renderer>(
+        &self,
+        renderer: &mut Renderer,
+        handle: &Handle,
+        bounds: Rectangle,
+        opacity: f32,
+    ) where
+        Renderer: image::Renderer<Handle = Handle>,
+    {
+        renderer.draw_image(
+            image::Image {
+                handle: handle.clone(),
+                filter_method: FilterMethod::Linear,
+                rotation: Radians(0.0),
+                border_radius: self.border_radius,
+                opacity,
+                snap: true,
+            },
+            bounds,
+            bounds,
+        );
+    }
+}
+
+impl<'a, Message, Theme, Renderer> Widget<Message, Theme, Renderer> for Artwork<'a>
+where
+    Message: Clone + 'a,
+    Renderer: image::Renderer<Handle = Handle> + 'a,
+{
+    fn size(&self) -> Size<Length> {
+        Size {
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    fn layout(
+        &mut self,
+        _tree: &mut Tree,
+        _renderer: &Renderer,
+        limits: &Limits,
+    ) -> Node {
+        layout::atomic(limits, self.width, self.height)
+    }
+
+    fn draw(
+        &self,
+        tree: &Tree,
+        renderer: &mut Renderer,
+        _theme: &Theme,
+        _style: &Style,
+        layout: Layout<'_>,
+        _cursor: mouse::Cursor,
+        _viewport: &Rectangle,
+    ) {
+        let bounds = layout.bounds();
+        let state = tree.state.downcast_ref::<State>();
+
+        // The image may have changed since the state last saw a redraw.
+        let image = self
+            .image
+            .as_ref()
+            .filter(|handle| state.image == Some(handle.id()));
+        let Some(image) = image else {
+            self.draw_layer(renderer, &self.placeholder, bounds, 1.0);
+            return;
+        };
+
+        if state.progress < 1.0 {
+            self.draw_layer(renderer, &self.placeholder, bounds, 1.0);
+        }
+        let opacity = self.easing.y_at_x(state.progress);
+        self.draw_layer(renderer, image, bounds, opacity);
+    }
+
+    fn tag(&self) -> tree::Tag {
+        tree::Tag::of::<State>()
+    }
+
+    fn state(&self) -> tree::State {
+        // Images already loaded when the artwork is first displayed skip the fade.
+        tree::State::new(State {
+            image: self.image.as_ref().map(Handle::id),
+            progress: 1.0,
+            start: Instant::now(),
+        })
+    }
+
+    fn update(
+        &mut self,
+        tree: &mut Tree,
+        event: &Event,
+        _layout: Layout<'_>,
+        _cursor: mouse::Cursor,
+        _renderer: &Renderer,
+        _clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, Message>,
+        _viewport: &Rectangle,
+    ) {
+        let state = tree.state.downcast_mut::<State>();
+
+        if let Event::Window(window::Event::RedrawRequested(now)) = event {
+            let image = self.image.as_ref().map(Handle::id);
+            state.timed_transition(image, self.fade_duration, *now);
+            if state.is_fading() {
+                shell.request_redraw();
+            }
+        }
+    }
+}
+
+impl<'a, Message> From<Artwork<'a>> for Element<'a, Message>
+where
+    Message: Clone + 'a,
+{
+    fn from(value: Artwork<'a>) -> Self {
+        Self::new(value)
+    }
+}
@@ -1,4 +1,4 @@
-use iced::widget::{column, row, space, text};
+use iced::widget::{column, container, row, space, text};
 use iced::{Center, Element, Length, padding};
 
 use crate::{color, font};
@@ -15,7 +15,7 @@ where
     let active_library = text(active_library)
         .size(16)
         .font(font::semibold())
-        .color(color::TEXT_SECONDARY);
+        .color(color::text_secondary());
 
     row![
         title,
@@ -44,3 +44,24 @@ where
         .padding(padding::Padding::default().vertical(8))
         .into()
 }
+
+/// Create a bottom bar, i.e. the mini-player.
+pub fn bottom<'a, Message>(
+    left: impl Into<Element<'a, Message>>,
+    center: impl Into<Element<'a, Message>>,
+    right: impl Into<Element<'a, Message>>,
+) -> Element<'a, Message>
+where
+    Message: 'a,
+{
+    row![
+        container(left.into()).width(Length::FillPortion(1)),
+        center.into(),
+        container(right.into()).align_right(Length::FillPortion(1)),
+    ]
+    .height(64)
+    .padding(padding::Padding::default().vertical(8).horizontal(16))
+    .spacing(16)
+    .align_y(Center)
+    .into()
+}
@@ -20,8 +20,8 @@ where
         container(container(icon::filled(icon)).padding([4, 16]))
             .style(move |_theme: &Theme| container::Style {
                 background: is_hovered
-                    .then_some(Background::Color(color::HOVER_HIGHLIGHT)),
-                text_color: selected.then_some(color::PRIMARY),
+                    .then_some(Background::Color(color::hover_highlight())),
+                text_color: selected.then_some(color::primary()),
                 border: border::rounded(28),
                 ..Default::default()
             })
@@ -32,8 +32,8 @@ where
         let base = button::text(theme, status);
 
         let color = match status {
-            Status::Pressed => color::TEXT_PRIMARY,
-            _ => color::TEXT_DEFAULT,
+            Status::Pressed => color::text_primary(),
+            _ => color::text_default(),
         };
 
         Style {
@@ -102,18 +102,20 @@ where
     Message: Clone + 'a,
 {
     if icon.is_some() && label.is_none() {
-        let inner = icon::filled(icon.unwrap()).size(24).color(color::TEXT_DARK);
+        let inner = icon::filled(icon.unwrap())
+            .size(24)
+            .color(color::text_dark());
         return button(inner).padding(4).style(disabled_button_style).into();
     }
 
     let mut items = row![].spacing(4).align_y(Center);
 
     if let Some(icon) = icon {
-        items = items.push(icon::filled(icon).size(24).color(color::TEXT_DARK));
+        items = items.push(icon::filled(icon).size(24).color(color::text_dark()));
     }
 
     if let Some(label) = label {
-        items = items.push(text(label).color(color::TEXT_DARK));
+        items = items.push(text(label).color(color::text_dark()));
     }
 
     button(items).style(disabled_button_style).into()
@@ -176,7 +178,7 @@ where
     let style = move |theme: &Theme, status: Status| {
         let mut base = primary_style(theme, status);
         if selected {
-            base.text_color = color::TEXT_PRIMARY;
+            base.text_color = color::text_primary();
         }
         base
     };
@@ -195,14 +197,14 @@ pub fn primary_style(theme: &Theme, status: Status) -> Style {
     let base = button::text(theme, status);
 
     let color = match status {
-        Status::Pressed => color::TEXT_PRIMARY,
-        Status::Disabled => color::TEXT_PRIMARY,
+        Status::Pressed => color::text_primary(),
+        Status::Disabled => color::text_primary(),
         _ => base.text_color,
     };
 
     let background = match status {
-        Status::Hovered => color::HOVER_HIGHLIGHT,
-        Status::Pressed => color::HOVER_HIGHLIGHT,
+        Status::Hovered => color::hover_highlight(),
+        Status::Pressed => color::hover_highlight(),
         _ => Color::TRANSPARENT,
     };
 
@@ -219,7 +221,7 @@ pub fn primary_style(theme: &Theme, status: Status) -> Style {
 pub fn text_secondary_style(theme: &Theme, status: Status) -> Style {
     let mut style = primary_style(theme, status);
     if matches!(status, Status::Active | Status::Hovered) {
-        style.text_color = color::TEXT_SECONDARY;
+        style.text_color = color::text_secondary();
     }
     style
 }
@@ -227,15 +229,15 @@ pub fn text_secondary_style(theme: &Theme, status: Status) -> Style {
 /// Button styling where the pill background is always visible.
 pub fn secondary_style(theme: &Theme, status: Status) -> Style {
     let background = match status {
-        Status::Hovered => color::HOVER_HIGHLIGHT,
-        Status::Pressed => color::HOVER_HIGHLIGHT,
-        _ => color::SECONDARY,
+        Status::Hovered => color::hover_highlight(),
+        Status::Pressed => color::hover_highlight(),
+        _ => color::secondary(),
     };
 
     let mut style = primary_style(theme, status);
     style.background = Some(Background::Color(background));
     if matches!(status, Status::Active | Status::Hovered) {
-        style.text_color = color::TEXT_SECONDARY;
+        style.text_color = color::text_secondary();
     }
     style
 }
@@ -243,12 +245,12 @@ pub fn secondary_style(theme: &Theme, status: Status) -> Style {
 fn disabled_button_style(theme: &Theme, status: Status) -> Style {
     let mut style = primary_style(theme, status);
     style.background = None;
-    style.text_color = color::TEXT_DARK;
+    style.text_color = color::text_dark();
     style
 }
 
 fn text_forced_default(_theme: &Theme) -> text::Style {
     text::Style {
-        color: Some(color::TEXT_DEFAULT),
+        color: Some(color::text_default()),
     }
 }
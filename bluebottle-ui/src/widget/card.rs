@@ -3,7 +3,7 @@ use iced::{Center, Element, Length, Theme, border};
 
 use super::button;
 use super::ellipsis_text::ellipsis_text;
-use crate::color::{TEXT_DEFAULT, TEXT_SECONDARY};
+use crate::color;
 
 /// Creates a new widget that forms the core structure of the card button.
 pub fn card<'a, Message>(
@@ -19,10 +19,13 @@ where
     let display = display.into();
     let overlay = overlay.into();
 
-    let label = ellipsis_text(label).size(14).color(TEXT_DEFAULT).height(16);
+    let label = ellipsis_text(label)
+        .size(14)
+        .color(color::text_default())
+        .height(16);
     let subtext = ellipsis_text(subtext)
         .size(12)
-        .color(TEXT_SECONDARY)
+        .color(color::text_secondary())
         .height(14);
 
     let base = column![display, label, subtext].spacing(4).align_x(Center);
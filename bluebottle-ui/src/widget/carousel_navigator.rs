@@ -18,7 +18,7 @@ where
 
     let label = text(format!("{current_page} / {total_pages}"))
         .font(font::regular())
-        .color(color::TEXT_SECONDARY)
+        .color(color::text_secondary())
         .size(14)
         .width(32)
         .align_x(Center);
@@ -2,6 +2,9 @@ use iced::widget::image;
 use iced::{ContentFit, Element};
 pub use image::{Handle, Image};
 
+use super::artwork::{Artwork, artwork};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
 /// The sizing options of the poster.
 pub enum PosterSize {
     Small,
@@ -9,13 +12,20 @@ pub enum PosterSize {
     Large,
 }
 
+impl PosterSize {
+    /// Returns the `(width, height)` of the poster in pixels.
+    pub const fn dimensions(self) -> (u32, u32) {
+        match self {
+            PosterSize::Small => (152, 224),
+            PosterSize::Medium => (175, 256),
+            PosterSize::Large => (240, 352),
+        }
+    }
+}
+
 /// An image in the poster aspect ratio.
 pub fn poster(handle: Handle, size: PosterSize) -> Image {
-    let (width, height) = match size {
-        PosterSize::Small => (152, 224),
-        PosterSize::Medium => (175, 256),
-        PosterSize::Large => (240, 352),
-    };
+    let (width, height) = size.dimensions();
 
     image(handle)
         .width(width)
@@ -23,16 +33,24 @@ pub fn poster(handle: Handle, size: PosterSize) -> Image {
         .content_fit(ContentFit::Cover)
 }
 
+/// Artwork in the poster aspect ratio, showing the placeholder until the image
+/// is loaded.
+pub fn poster_artwork<'a>(
+    placeholder: Handle,
+    image: Option<Handle>,
+    size: PosterSize,
+) -> Artwork<'a> {
+    let (width, height) = size.dimensions();
+
+    artwork(placeholder, image).width(width).height(height)
+}
+
 /// Creates a loading skeleton for the poster form factor.
 pub fn poster_skeleton<'a, Message>(size: PosterSize) -> Element<'a, Message>
 where
     Message: Clone + 'a,
 {
-    let (width, height) = match size {
-        PosterSize::Small => (152, 224),
-        PosterSize::Medium => (175, 256),
-        PosterSize::Large => (240, 352),
-    };
+    let (width, height) = size.dimensions();
 
     super::skeleton::skeleton()
         .width(width)
@@ -49,6 +67,12 @@ pub fn thumbnail(handle: Handle) -> Image {
         .into()
 }
 
+/// Artwork in the thumbnail aspect ratio, showing the placeholder until the
+/// image is loaded.
+pub fn thumbnail_artwork<'a>(placeholder: Handle, image: Option<Handle>) -> Artwork<'a> {
+    artwork(placeholder, image).width(270).height(152)
+}
+
 /// Creates a loading skeleton for the thumbnail form factor.
 pub fn thumbnail_skeleton<'a, Message>() -> Element<'a, Message>
 where
@@ -66,6 +90,12 @@ pub fn square(handle: Handle) -> Image {
         .into()
 }
 
+/// Artwork in the square aspect ratio, showing the placeholder until the image
+/// is loaded.
+pub fn square_artwork<'a>(placeholder: Handle, image: Option<Handle>) -> Artwork<'a> {
+    artwork(placeholder, image).width(152).height(152)
+}
+
 /// Creates a loading skeleton for the square form factor.
 pub fn square_skeleton<'a, Message>() -> Element<'a, Message>
 where
@@ -22,22 +22,22 @@ fn input_style(
     status: widget::text_input::Status,
 ) -> widget::text_input::Style {
     let background_color = match status {
-        widget::text_input::Status::Hovered => color::HOVER_HIGHLIGHT,
-        widget::text_input::Status::Focused { .. } => color::HOVER_HIGHLIGHT,
-        _ => color::SECONDARY,
+        widget::text_input::Status::Hovered => color::hover_highlight(),
+        widget::text_input::Status::Focused { .. } => color::hover_highlight(),
+        _ => color::secondary(),
     };
 
     let border_color = match status {
-        widget::text_input::Status::Focused { .. } => color::PRIMARY,
-        _ => color::HOVER_HIGHLIGHT,
+        widget::text_input::Status::Focused { .. } => color::primary(),
+        _ => color::hover_highlight(),
     };
 
     widget::text_input::Style {
         background: Background::Color(background_color),
         border: Border::default().rounded(28).color(border_color).width(1),
         icon: Default::default(),
-        placeholder: color::TEXT_DARK,
-        value: color::TEXT_SECONDARY,
-        selection: color::TEXT_DARK,
+        placeholder: color::text_dark(),
+        value: color::text_secondary(),
+        selection: color::text_dark(),
     }
 }
@@ -1,3 +1,4 @@
+pub mod artwork;
 pub mod bar;
 pub mod button;
 pub mod card;
@@ -8,6 +9,7 @@ pub mod image;
 pub mod input;
 pub mod pill;
 pub mod pill_box;
+pub mod poster_grid;
 pub mod rating;
 pub mod scrollable;
 pub mod search;
@@ -46,14 +46,14 @@ where
 
 fn style(_theme: &Theme, status: button::Status) -> button::Style {
     let text_color = match status {
-        button::Status::Pressed => color::TEXT_PRIMARY,
-        _ => color::TEXT_SECONDARY,
+        button::Status::Pressed => color::text_primary(),
+        _ => color::text_secondary(),
     };
 
     let background_color = match status {
-        button::Status::Pressed => color::HOVER_HIGHLIGHT,
-        button::Status::Hovered => color::HOVER_HIGHLIGHT,
-        _ => color::SECONDARY,
+        button::Status::Pressed => color::hover_highlight(),
+        button::Status::Hovered => color::hover_highlight(),
+        _ => color::secondary(),
     };
 
     button::Style {
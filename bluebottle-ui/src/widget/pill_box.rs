@@ -13,7 +13,7 @@ where
 {
     let label = text(label)
         .size(14)
-        .color(color::TEXT_DARK)
+        .color(color::text_dark())
         .font(font::regular());
 
     let grid = row(pills).spacing(4).wrap();
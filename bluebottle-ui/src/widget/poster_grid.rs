@@ -0,0 +1,262 @@
+//! A virtualised grid of posters for libraries with many thousands of items.
+//!
+//! Only the rows within (or just outside of) the visible viewport are ever built,
+//! everything else is represented by spacers of the correct height so the scrollbar
+//! still behaves as if every item was laid out.
+
+use std::ops::Range;
+use std::rc::Rc;
+
+use iced::widget::{column, container, responsive, row, sensor, space};
+use iced::{Center, Element, Length, Size, padding};
+
+use super::image::{PosterSize, poster_skeleton};
+use super::scrollable::scrollable;
+
+/// The spacing between each slot in the grid, both horizontally and vertically.
+const SPACING: f32 = 16.0;
+/// The height of the label and subtext below a poster card, including padding.
+const CARD_DETAILS_HEIGHT: f32 = 42.0;
+/// The number of extra rows to build above and below the viewport.
+const OVERSCAN_ROWS: usize = 2;
+
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+/// The scroll position and size of a [poster_grid].
+///
+/// This is reported back to the owner of the grid whenever the user scrolls or the
+/// window is resized, so it can work out which pages of items need loading.
+pub struct GridViewport {
+    /// The vertical scroll offset in pixels.
+    pub offset: f32,
+    /// The width of the visible area in pixels.
+    pub width: f32,
+    /// The height of the visible area in pixels.
+    pub height: f32,
+}
+
+impl GridViewport {
+    /// Returns the number of columns that fit within the viewport.
+    pub fn columns(&self, size: PosterSize) -> usize {
+        columns_for_width(self.width, size)
+    }
+
+    /// Returns the range of item indices which are currently visible, including the
+    /// overscan rows either side.
+    pub fn visible_items(&self, size: PosterSize, total_items: usize) -> Range<usize> {
+        let columns = self.columns(size);
+        let rows = visible_rows(self.offset, self.height, size, total_items, columns);
+        let end = (rows.end * columns).min(total_items);
+        (rows.start * columns).min(end)..end
+    }
+
+    /// Returns the range of pages that contain the visible items, given a fixed
+    /// `page_size`.
+    pub fn visible_pages(
+        &self,
+        size: PosterSize,
+        total_items: usize,
+        page_size: usize,
+    ) -> Range<usize> {
+        let items = self.visible_items(size, total_items);
+        if items.is_empty() || page_size == 0 {
+            return 0..0;
+        }
+        (items.start / page_size)..items.end.div_ceil(page_size)
+    }
+}
+
+/// Creates a new virtualised poster grid.
+///
+/// The number of columns adapts to the available width, and only the visible rows
+/// are rendered. `item` is called with the index of each visible slot and should
+/// return `None` if the item has not been loaded yet, in which case a
+/// [poster_skeleton] is shown in its place.
+///
+/// `on_viewport` is triggered whenever the grid is scrolled or resized.
+pub fn poster_grid<'a, Message>(
+    viewport: GridViewport,
+    total_items: usize,
+    size: PosterSize,
+    item: impl Fn(usize) -> Option<Element<'a, Message>> + 'a,
+    on_viewport: impl Fn(GridViewport) -> Message + 'a,
+) -> Element<'a, Message>
+where
+    Message: Clone + 'a,
+{
+    let on_viewport = Rc::new(on_viewport);
+
+    let on_resize = {
+        let on_viewport = on_viewport.clone();
+        move |bounds: Size| {
+            on_viewport(GridViewport {
+                offset: viewport.offset,
+                width: bounds.width,
+                height: bounds.height,
+            })
+        }
+    };
+
+    let grid = responsive(move |bounds| {
+        let columns = columns_for_width(bounds.width, size);
+        let total_rows = total_items.div_ceil(columns);
+        let rows =
+            visible_rows(viewport.offset, bounds.height, size, total_items, columns);
+        let row_pitch = row_pitch(size);
+
+        let mut content =
+            column![space().height(rows.start as f32 * row_pitch)].width(Length::Fill);
+
+        for row_index in rows.clone() {
+            let start = row_index * columns;
+            let end = (start + columns).min(total_items);
+            let slots = (start..end).map(|index| slot(size, item(index)));
+
+            content = content.push(
+                container(row(slots).spacing(SPACING))
+                    .width(Length::Fill)
+                    .padding(padding::bottom(SPACING))
+                    .align_x(Center),
+            );
+        }
+
+        let remaining_rows = total_rows.saturating_sub(rows.end);
+        content = content.push(space().height(remaining_rows as f32 * row_pitch));
+
+        let on_viewport = on_viewport.clone();
+        scrollable(content)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .on_scroll(move |scroll_viewport| {
+                let bounds = scroll_viewport.bounds();
+                on_viewport(GridViewport {
+                    offset: scroll_viewport.absolute_offset().y,
+                    width: bounds.width,
+                    height: bounds.height,
+                })
+            })
+            .into()
+    });
+
+    sensor(grid)
+        .on_show(on_resize.clone())
+        .on_resize(on_resize)
+        .into()
+}
+
+/// Wraps the item (or a skeleton) in a slot with a fixed size.
+fn slot<'a, Message>(
+    size: PosterSize,
+    element: Option<Element<'a, Message>>,
+) -> Element<'a, Message>
+where
+    Message: Clone + 'a,
+{
+    let slot_size = slot_size(size);
+    let element =
+        element.unwrap_or_else(|| super::card::skeleton(poster_skeleton(size)));
+
+    container(element)
+        .width(slot_size.width)
+        .height(slot_size.height)
+        .clip(true)
+        .into()
+}
+
+/// The size of a single slot in the grid, excluding spacing.
+fn slot_size(size: PosterSize) -> Size {
+    let (width, height) = size.dimensions();
+    Size::new(width as f32 + 4.0, height as f32 + CARD_DETAILS_HEIGHT)
+}
+
+/// The vertical distance between the top of one row and the next.
+fn row_pitch(size: PosterSize) -> f32 {
+    slot_size(size).height + SPACING
+}
+
+fn columns_for_width(width: f32, size: PosterSize) -> usize {
+    let slot_width = slot_size(size).width;
+    let columns = ((width + SPACING) / (slot_width + SPACING)).floor();
+    (columns as usize).max(1)
+}
+
+fn visible_rows(
+    offset: f32,
+    height: f32,
+    size: PosterSize,
+    total_items: usize,
+    columns: usize,
+) -> Range<usize> {
+    let total_rows = total_items.div_ceil(columns);
+    let row_pitch = row_pitch(size);
+
+    let first = (offset.max(0.0) / row_pitch).floor() as usize;
+    let last = ((offset.max(0.0) + height.max(0.0)) / row_pitch).ceil() as usize;
+
+    let start = first.saturating_sub(OVERSCAN_ROWS).min(total_rows);
+    let end = (last + OVERSCAN_ROWS).min(total_rows);
+    start..end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The width of a viewport fitting exactly `columns` medium posters.
+    fn width_for(columns: usize) -> f32 {
+        let slot_width = slot_size(PosterSize::Medium).width;
+        columns as f32 * (slot_width + SPACING) - SPACING
+    }
+
+    fn viewport(offset_rows: f32, columns: usize, height_rows: f32) -> GridViewport {
+        let row_pitch = row_pitch(PosterSize::Medium);
+        GridViewport {
+            offset: offset_rows * row_pitch,
+            width: width_for(columns),
+            height: height_rows * row_pitch,
+        }
+    }
+
+    #[test]
+    fn test_zero_width() {
+        let viewport = GridViewport {
+            offset: 0.0,
+            width: 0.0,
+            height: 2.0 * row_pitch(PosterSize::Medium),
+        };
+        assert_eq!(viewport.columns(PosterSize::Medium), 1);
+        assert_eq!(viewport.visible_items(PosterSize::Medium, 10), 0..4);
+        assert_eq!(viewport.visible_pages(PosterSize::Medium, 10, 3), 0..2);
+    }
+
+    #[test]
+    fn test_partial_last_row() {
+        // 10 items over 3 columns leaves a single item in the last row.
+        let viewport = viewport(3.0, 3, 1.0);
+        assert_eq!(viewport.columns(PosterSize::Medium), 3);
+        assert_eq!(viewport.visible_items(PosterSize::Medium, 10), 3..10);
+        assert_eq!(viewport.visible_pages(PosterSize::Medium, 10, 4), 0..3);
+    }
+
+    #[test]
+    fn test_scrolled_past_end() {
+        let viewport = viewport(100.0, 3, 2.0);
+        assert_eq!(viewport.visible_items(PosterSize::Medium, 10), 10..10);
+        assert_eq!(viewport.visible_pages(PosterSize::Medium, 10, 4), 0..0);
+    }
+
+    #[test]
+    fn test_empty_grid() {
+        let viewport = viewport(0.0, 3, 2.0);
+        assert_eq!(viewport.visible_items(PosterSize::Medium, 0), 0..0);
+        assert_eq!(viewport.visible_pages(PosterSize::Medium, 0, 4), 0..0);
+        assert_eq!(viewport.visible_pages(PosterSize::Medium, 10, 0), 0..0);
+    }
+
+    #[test]
+    fn test_overscan() {
+        let viewport = viewport(5.0, 3, 2.0);
+        // Rows 5 and 6 are visible, with two rows of overscan either side.
+        assert_eq!(viewport.visible_items(PosterSize::Medium, 100), 9..27);
+        assert_eq!(viewport.visible_pages(PosterSize::Medium, 100, 10), 0..3);
+    }
+}
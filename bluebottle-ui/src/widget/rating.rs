@@ -60,6 +60,6 @@ where
 fn text_part(display: &str) -> Text<'_> {
     text(display)
         .size(14)
-        .color(color::TEXT_SECONDARY)
+        .color(color::text_secondary())
         .font(font::semibold())
 }
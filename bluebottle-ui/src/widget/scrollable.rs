@@ -18,9 +18,9 @@ pub fn scrollable<'a, Message>(
 ) -> widget::Scrollable<'a, Message> {
     fn style(theme: &Theme, status: Status) -> Style {
         let color = match status {
-            Status::Active { .. } => color::HOVER_HIGHLIGHT,
-            Status::Hovered { .. } => color::HOVER_HIGHLIGHT,
-            Status::Dragged { .. } => color::HOVER_HIGHLIGHT,
+            Status::Active { .. } => color::hover_highlight(),
+            Status::Hovered { .. } => color::hover_highlight(),
+            Status::Dragged { .. } => color::hover_highlight(),
         };
 
         let mut style = widget::scrollable::default(theme, status);
@@ -149,9 +149,9 @@ where
         };
 
         let linear = Linear::new(Degrees(rotation))
-            .add_stop(0.0, color::SECONDARY)
-            .add_stop(offset, color::SHIMMER)
-            .add_stop(1.0, color::SECONDARY);
+            .add_stop(0.0, color::secondary())
+            .add_stop(offset, color::shimmer())
+            .add_stop(1.0, color::secondary());
 
         renderer.fill_quad(
             Quad {
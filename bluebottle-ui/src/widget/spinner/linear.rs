@@ -161,7 +161,7 @@ where
                 },
                 ..Quad::default()
             },
-            Background::Color(color::PRIMARY),
+            Background::Color(color::primary()),
         );
 
         match state {
@@ -175,7 +175,7 @@ where
                     },
                     ..Quad::default()
                 },
-                Background::Color(color::SECONDARY),
+                Background::Color(color::secondary()),
             ),
 
             State::Contracting { progress, .. } => renderer.fill_quad(
@@ -188,7 +188,7 @@ where
                     },
                     ..Quad::default()
                 },
-                Background::Color(color::SECONDARY),
+                Background::Color(color::secondary()),
             ),
         }
     }
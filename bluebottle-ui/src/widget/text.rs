@@ -12,7 +12,7 @@ where
     let title = text(title)
         .size(20)
         .font(font::semibold())
-        .color(color::TEXT_DEFAULT);
+        .color(color::text_default());
 
     if let Some(icon) = icon {
         let icon = icon::filled(icon);
@@ -29,7 +29,7 @@ pub fn subheading<'a>(content: impl IntoFragment<'a>) -> Text<'a> {
 
 /// A text paragraph.
 pub fn paragraph<'a>(content: impl IntoFragment<'a>) -> Text<'a> {
-    text(content).color(color::TEXT_SECONDARY)
+    text(content).color(color::text_secondary())
 }
 
 /// A label for form fields.
@@ -37,5 +37,5 @@ pub fn label<'a>(content: impl IntoFragment<'a>) -> Text<'a> {
     text(content)
         .size(12)
         .font(font::semibold())
-        .color(color::TEXT_SECONDARY)
+        .color(color::text_secondary())
 }
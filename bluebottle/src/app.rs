@@ -1,19 +1,44 @@
 use bluebottle_ui::{bar, button, color, font};
+use iced::futures::channel::mpsc;
+use iced::futures::{SinkExt, Stream};
 use iced::widget::{column, row, space};
-use iced::{Center, Element, Settings, task};
+use iced::{Center, Element, Length, Settings, Subscription, task};
 use snafu::ResultExt;
+use tokio::sync::broadcast;
 
-use crate::navigator;
+use crate::components::mini_player::{MiniPlayer, MiniPlayerMsg};
+use crate::models::profile::DeviceProfile;
+use crate::models::settings::{AccentColor, Appearance, ThemeMode};
 use crate::navigator::ActiveScreen;
-use crate::screen::{Screen, library_select, library_view, loading, settings, setup};
+use crate::screen::{
+    Screen,
+    library_select,
+    library_view,
+    loading,
+    search,
+    settings,
+    setup,
+};
+use crate::storage::settings::{self as stored_settings, SettingChange};
+use crate::storage::{appearance, library_items};
 use crate::view::View;
+use crate::{backends, backlog, janitor, live, mpris, music, navigator, sync};
 
 /// Run the Bluebottle UI iced application.
 ///
 /// This will block until the user closes the application or the system crashes.
 pub fn run_app() -> Result<(), snafu::Whatever> {
+    backends::registry::load_from_state();
     navigator::load_from_state();
-    //navigator::navigate(ActiveScreen::Loading);
+    apply_appearance(&appearance::load());
+
+    // Show the sync progress until every backend has a local copy to render.
+    let needs_sync = backends::registry::all()
+        .into_iter()
+        .any(|(backend_id, _)| !library_items::has_synced(backend_id));
+    if needs_sync {
+        navigator::navigate(ActiveScreen::Loading);
+    }
 
     let settings = Settings {
         fonts: font::required_fonts(),
@@ -23,7 +48,8 @@ pub fn run_app() -> Result<(), snafu::Whatever> {
 
     iced::application(|| Bluebottle::new(), Bluebottle::update, Bluebottle::view)
         .title("Bluebottle")
-        .theme(color::theme())
+        .subscription(Bluebottle::subscription)
+        .theme(Bluebottle::theme)
         .settings(settings)
         .run()
         .whatever_context("run Bluebottle main app")?;
@@ -37,6 +63,8 @@ struct Bluebottle {
     setup_screen: setup::SetupScreen,
     settings_screen: settings::SettingsScreen,
     loading_screen: loading::LoadingScreen,
+    search_screen: search::SearchScreen,
+    mini_player: MiniPlayer,
 }
 
 #[derive(Clone)]
@@ -46,18 +74,56 @@ enum GlobalMessage {
     Setup(setup::SetupMsg),
     LibrarySelect(library_select::LibrarySelectMsg),
     Settings(settings::SettingsMsg),
+    Search(search::SearchMsg),
+    Sync(sync::SyncEvent),
+    Janitor(janitor::JanitorEvent),
+    Live(live::LiveUpdate),
+    MiniPlayer(MiniPlayerMsg),
+    Mpris(mpris::MprisEvent),
+    SettingChanged(SettingChange),
+    Navigate(ActiveScreen),
     Null,
 }
 
 impl Bluebottle {
-    fn new() -> Self {
-        Self {
+    fn new() -> (Self, task::Task<GlobalMessage>) {
+        let mut this = Self {
             library_view_screen: library_view::LibraryViewScreen::default(),
             library_select_screen: library_select::LibrarySelectScreen::default(),
             setup_screen: setup::SetupScreen::default(),
             settings_screen: settings::SettingsScreen::default(),
             loading_screen: loading::LoadingScreen::default(),
+            search_screen: search::SearchScreen::default(),
+            mini_player: MiniPlayer::default(),
+        };
+        this.search_screen.load();
+
+        let mut task = this
+            .library_view_screen
+            .load()
+            .map(GlobalMessage::LibraryView);
+        if navigator::active() == ActiveScreen::Settings {
+            let settings = this.settings_screen.load().map(GlobalMessage::Settings);
+            task = task::Task::batch([task, settings]);
         }
+
+        // Announce the session to every backend, so it is listed as an active
+        // device and can be controlled remotely.
+        let capabilities = backends::registry::all().into_iter().map(
+            |(backend_id, backend)| {
+                task::Task::future(async move {
+                    if let Err(err) = backend.report_capabilities(DeviceProfile::mpv()).await {
+                        tracing::warn!(backend_id = %backend_id, error = %err, "failed to report session capabilities");
+                    }
+                    GlobalMessage::Null
+                })
+            },
+        );
+
+        (
+            this,
+            task::Task::batch(std::iter::once(task).chain(capabilities)),
+        )
     }
 
     fn update(&mut self, message: GlobalMessage) -> task::Task<GlobalMessage> {
@@ -80,14 +146,87 @@ impl Bluebottle {
                 .settings_screen
                 .update(msg)
                 .map(GlobalMessage::Settings),
+            GlobalMessage::Search(msg) => {
+                self.search_screen.update(msg).map(GlobalMessage::Search)
+            },
+            GlobalMessage::Sync(event) => {
+                let loading = self
+                    .loading_screen
+                    .update(loading::LoadingMsg::Sync(event.clone()))
+                    .map(GlobalMessage::Loading);
+                let library_view = self
+                    .library_view_screen
+                    .update(library_view::LibraryViewMsg::Sync(event))
+                    .map(GlobalMessage::LibraryView);
+                task::Task::batch([loading, library_view])
+            },
+            GlobalMessage::Janitor(janitor::JanitorEvent::Cleaned(stats)) => self
+                .settings_screen
+                .update(settings::SettingsMsg::AssetCacheMeasured(Ok(stats)))
+                .map(GlobalMessage::Settings),
+            GlobalMessage::Live(update) => self
+                .library_view_screen
+                .update(library_view::LibraryViewMsg::Live(update))
+                .map(GlobalMessage::LibraryView),
+            GlobalMessage::MiniPlayer(msg) => {
+                self.mini_player.update(msg).map(GlobalMessage::MiniPlayer)
+            },
+            GlobalMessage::Mpris(mpris::MprisEvent::Raise) => {
+                iced::window::latest().and_then(iced::window::gain_focus)
+            },
+            GlobalMessage::Mpris(mpris::MprisEvent::Quit) => iced::exit(),
+            GlobalMessage::SettingChanged(change) => {
+                if appearance::APPEARANCE.is_changed(&change) {
+                    apply_appearance(&appearance::load());
+                }
+                self.library_view_screen
+                    .update(library_view::LibraryViewMsg::SettingChanged(change))
+                    .map(GlobalMessage::LibraryView)
+            },
+            GlobalMessage::Navigate(screen) => {
+                navigator::navigate(screen);
+                if screen == ActiveScreen::Settings {
+                    return self.settings_screen.load().map(GlobalMessage::Settings);
+                }
+                task::Task::none()
+            },
             GlobalMessage::Null => task::Task::none(),
         }
     }
 
+    fn subscription(&self) -> Subscription<GlobalMessage> {
+        let screen = match navigator::active() {
+            ActiveScreen::Search => {
+                self.search_screen.subscription().map(GlobalMessage::Search)
+            },
+            _ => Subscription::none(),
+        };
+
+        Subscription::batch([
+            screen,
+            sync::subscription().map(GlobalMessage::Sync),
+            backlog::subscription().map(|never| match never {}),
+            janitor::subscription().map(GlobalMessage::Janitor),
+            live::subscription().map(GlobalMessage::Live),
+            music::subscription().map(|state| {
+                GlobalMessage::MiniPlayer(MiniPlayerMsg::State(Box::new(state)))
+            }),
+            mpris::subscription().map(GlobalMessage::Mpris),
+            Subscription::run(setting_changes).map(GlobalMessage::SettingChanged),
+        ])
+    }
+
+    /// Returns the theme in the appearance the user picked, which is applied
+    /// before each frame so changes take effect straight away.
+    fn theme(&self) -> iced::Theme {
+        color::theme()
+    }
+
     fn view(&self) -> Element<'_, GlobalMessage> {
         column![
             self.render_topbar(),
-            row![self.render_sidebar(), self.render_screen()],
+            row![self.render_sidebar(), self.render_screen()].height(Length::Fill),
+            self.mini_player.view().map(GlobalMessage::MiniPlayer),
         ]
         .into()
     }
@@ -120,6 +259,10 @@ impl Bluebottle {
                     .map(GlobalMessage::Settings),
                 self.settings_screen.nav_descriptor(),
             ),
+            ActiveScreen::Search => bar::top(
+                self.search_screen.nav_center().map(GlobalMessage::Search),
+                self.search_screen.nav_descriptor(),
+            ),
         }
     }
 
@@ -128,9 +271,21 @@ impl Bluebottle {
             return space().into();
         }
 
+        let active = navigator::active();
+
         let upper = column![
-            button::nav("Home", "home", false, GlobalMessage::Null,),
-            button::nav("Search", "search", false, GlobalMessage::Null,),
+            button::nav(
+                "Home",
+                "home",
+                active == ActiveScreen::LibraryView,
+                GlobalMessage::Navigate(ActiveScreen::LibraryView),
+            ),
+            button::nav(
+                "Search",
+                "search",
+                active == ActiveScreen::Search,
+                GlobalMessage::Navigate(ActiveScreen::Search),
+            ),
             button::nav("Favorites", "favorite", false, GlobalMessage::Null,),
             button::nav("Anime", "draw", false, GlobalMessage::Null),
             button::nav("TV Shows", "tv", false, GlobalMessage::Null),
@@ -142,8 +297,18 @@ impl Bluebottle {
         .align_x(Center);
 
         let lower = column![
-            button::nav("Library", "apps", false, GlobalMessage::Null,),
-            button::nav("Settings", "settings", true, GlobalMessage::Null,),
+            button::nav(
+                "Library",
+                "apps",
+                active == ActiveScreen::LibrarySelect,
+                GlobalMessage::Navigate(ActiveScreen::LibrarySelect),
+            ),
+            button::nav(
+                "Settings",
+                "settings",
+                active == ActiveScreen::Settings,
+                GlobalMessage::Navigate(ActiveScreen::Settings),
+            ),
         ]
         .spacing(4)
         .align_x(Center);
@@ -168,6 +333,7 @@ impl Bluebottle {
             ActiveScreen::Settings => {
                 self.settings_screen.view().map(GlobalMessage::Settings)
             },
+            ActiveScreen::Search => self.search_screen.view().map(GlobalMessage::Search),
         }
     }
 
@@ -180,6 +346,43 @@ impl Bluebottle {
                 library_select::LibrarySelectScreen::HIDE_SIDEBAR
             },
             ActiveScreen::Settings => settings::SettingsScreen::HIDE_SIDEBAR,
+            ActiveScreen::Search => search::SearchScreen::HIDE_SIDEBAR,
         }
     }
 }
+
+/// Forwards settings saved anywhere in the app, so views showing them stay current.
+fn setting_changes() -> impl Stream<Item = SettingChange> {
+    iced::stream::channel(8, async |mut output: mpsc::Sender<SettingChange>| {
+        let mut changes = stored_settings::subscribe();
+        loop {
+            match changes.recv().await {
+                Ok(change) => {
+                    if output.send(change).await.is_err() {
+                        return;
+                    }
+                },
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!(skipped, "missed setting changes");
+                },
+                Err(broadcast::error::RecvError::Closed) => return,
+            }
+        }
+    })
+}
+
+/// Draw the UI in the appearance's theme and accent.
+fn apply_appearance(appearance: &Appearance) {
+    let mode = match appearance.theme {
+        ThemeMode::Dark => color::Mode::Dark,
+        ThemeMode::Light => color::Mode::Light,
+    };
+    let accent = match appearance.accent {
+        AccentColor::Indigo => color::Accent::Indigo,
+        AccentColor::Sky => color::Accent::Sky,
+        AccentColor::Teal => color::Accent::Teal,
+        AccentColor::Violet => color::Accent::Violet,
+        AccentColor::Pink => color::Accent::Pink,
+    };
+    color::set_appearance(mode, accent);
+}
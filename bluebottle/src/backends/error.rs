@@ -0,0 +1,63 @@
+use reqwest::StatusCode;
+
+#[derive(Debug, snafu::Snafu)]
+#[snafu(visibility(pub(crate)))]
+/// An error returned by a [Backend](super::Backend) operation.
+pub enum BackendError {
+    #[snafu(display("{}", source))]
+    Connection { source: reqwest::Error },
+    #[snafu(display(
+        "({}) {}: {}",
+        status_code.as_u16(),
+        status_code.canonical_reason().unwrap_or(""),
+        message,
+    ))]
+    Request {
+        /// The status code of the request that failed.
+        status_code: StatusCode,
+        /// Additional context message from the service.
+        message: String,
+    },
+    #[snafu(display("{}", source))]
+    WebSocket {
+        source: tokio_tungstenite::tungstenite::Error,
+    },
+    #[snafu(display("server returned an invalid response payload"))]
+    InvalidResponse,
+    #[snafu(display("item cannot be played: {}", reason))]
+    NotPlayable {
+        /// Why the server could not provide a stream.
+        reason: String,
+    },
+}
+
+impl BackendError {
+    /// Returns `true` if the operation may succeed when retried later, i.e. the
+    /// server could not be reached or is temporarily unavailable.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Connection { .. } | Self::WebSocket { .. } => true,
+            Self::Request { status_code, .. } => {
+                status_code.is_server_error()
+                    || *status_code == StatusCode::REQUEST_TIMEOUT
+                    || *status_code == StatusCode::TOO_MANY_REQUESTS
+                    // The session may be restored by signing in again.
+                    || *status_code == StatusCode::UNAUTHORIZED
+            },
+            Self::InvalidResponse | Self::NotPlayable { .. } => false,
+        }
+    }
+}
+
+impl From<reqwest::Error> for BackendError {
+    fn from(source: reqwest::Error) -> Self {
+        if let Some(status_code) = source.status() {
+            Self::Request {
+                status_code,
+                message: source.to_string(),
+            }
+        } else {
+            Self::Connection { source }
+        }
+    }
+}
@@ -1,17 +1,35 @@
+use std::collections::HashSet;
 use std::sync::LazyLock;
+use std::time::Duration;
 
-use reqwest::header::HeaderValue;
-use reqwest::{Method, header};
+use parking_lot::Mutex;
+use reqwest::header::{HeaderMap, HeaderValue};
+use reqwest::{Method, StatusCode, header};
+use snafu::ResultExt;
+
+use super::error::ConnectionSnafu;
+use super::{BackendError, BackendId};
+use crate::storage::cache_settings;
+use crate::storage::content_cache::{self, CachePolicy, Validators};
 
 static ACCEPT_INVALID_CERTS: LazyLock<bool> =
     LazyLock::new(|| std::env::var("BLUEBOTTLE_ACCEPT_INVALID_CERTS").is_ok());
 static ACCEPT_INVALID_HOSTNAME: LazyLock<bool> =
     LazyLock::new(|| std::env::var("BLUEBOTTLE_ACCEPT_INVALID_HOSTNAME").is_ok());
+/// The stale responses being revalidated in the background, keyed by backend and
+/// URL, so concurrent reads of the same response only revalidate it once.
+static REVALIDATING: LazyLock<Mutex<HashSet<(BackendId, String)>>> =
+    LazyLock::new(Default::default);
+
+/// How long cached responses are fresh for when the server does not say.
+const DEFAULT_FRESHNESS: Duration = Duration::from_secs(5 * 60);
 
 /// The core HTTP client for all backends.
 pub struct HttpClient {
     client: reqwest::Client,
     base_url: url::Url,
+    /// The backend responses are cached for, if they are cached.
+    cache_backend_id: Option<BackendId>,
 }
 
 impl HttpClient {
@@ -28,7 +46,17 @@ impl HttpClient {
             .build()
             .expect("create new http client");
 
-        Self { client, base_url }
+        Self {
+            client,
+            base_url,
+            cache_backend_id: None,
+        }
+    }
+
+    /// Cache the responses of requests sent with [HttpClient::send_cached] in the
+    /// content cache of the backend.
+    pub fn enable_content_cache(&mut self, backend_id: BackendId) {
+        self.cache_backend_id = Some(backend_id);
     }
 
     fn set_default_headers(&mut self, headers: header::HeaderMap) {
@@ -71,7 +99,7 @@ impl HttpClient {
 
     /// Create a new POST request.
     pub fn post(&self, endpoint: &str) -> reqwest::RequestBuilder {
-        self.request(Method::PUT, endpoint)
+        self.request(Method::POST, endpoint)
     }
 
     /// Create a new PUT request.
@@ -84,10 +112,227 @@ impl HttpClient {
         self.request(Method::DELETE, endpoint)
     }
 
+    /// Returns the full URL of the endpoint.
+    pub fn url(&self, endpoint: &str) -> url::Url {
+        self.base_url.join(endpoint).expect("join endpoint to base")
+    }
+
     fn request(&self, method: Method, endpoint: &str) -> reqwest::RequestBuilder {
-        let url = self.base_url.join(endpoint).expect("join endpoint to base");
-        self.client.request(method, url)
+        self.client.request(method, self.url(endpoint))
     }
+
+    /// Send the request, returning the response body from the content cache if
+    /// possible.
+    ///
+    /// Fresh responses are returned without contacting the server. Stale
+    /// responses are returned straight away and revalidated in the background,
+    /// while expired responses are revalidated first, but still returned if the
+    /// server cannot be reached. Only `GET` requests are cached.
+    pub async fn send_cached(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<Vec<u8>, BackendError> {
+        let (client, request) = request.build_split();
+        let request = request?;
+        let backend_id = self
+            .cache_backend_id
+            .filter(|_| request.method() == Method::GET);
+        let Some(backend_id) = backend_id else {
+            let response = client
+                .execute(request)
+                .await
+                .context(ConnectionSnafu)?
+                .error_for_status()?;
+            return Ok(response.bytes().await?.to_vec());
+        };
+
+        let url = request.url().to_string();
+        let Some(entry) = content_cache::try_get_body(backend_id, &url).await else {
+            return match revalidate(backend_id, &client, request, None).await? {
+                Revalidated::Modified(body) => Ok(body),
+                // Nothing was cached for the server to refer to.
+                Revalidated::NotModified => Err(BackendError::InvalidResponse),
+            };
+        };
+
+        if !entry.is_stale() {
+            return Ok(entry.value);
+        }
+
+        if !entry.is_expired() {
+            if let Some(guard) = RevalidationGuard::acquire(backend_id, url) {
+                let validators = entry.validators;
+                // The guard is moved into the task, released once it finishes.
+                tokio::spawn(async move {
+                    let result =
+                        revalidate(backend_id, &client, request, Some(&validators))
+                            .await;
+                    if let Err(err) = result {
+                        tracing::debug!(url = %guard.key.1, error = %err, "failed to revalidate response");
+                    }
+                });
+            }
+            return Ok(entry.value);
+        }
+
+        match revalidate(backend_id, &client, request, Some(&entry.validators)).await {
+            Ok(Revalidated::Modified(body)) => Ok(body),
+            Ok(Revalidated::NotModified) => Ok(entry.value),
+            Err(err) if err.is_retryable() => {
+                tracing::debug!(url = %url, error = %err, "serving expired response");
+                Ok(entry.value)
+            },
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/// Marks a response as being revalidated in the background until dropped.
+struct RevalidationGuard {
+    key: (BackendId, String),
+}
+
+impl RevalidationGuard {
+    /// Returns `None` if the response is already being revalidated.
+    fn acquire(backend_id: BackendId, url: String) -> Option<Self> {
+        let key = (backend_id, url);
+        if !REVALIDATING.lock().insert(key.clone()) {
+            return None;
+        }
+        Some(Self { key })
+    }
+}
+
+impl Drop for RevalidationGuard {
+    fn drop(&mut self) {
+        REVALIDATING.lock().remove(&self.key);
+    }
+}
+
+/// The outcome of revalidating a cached response.
+enum Revalidated {
+    /// The response changed, carrying the new body.
+    Modified(Vec<u8>),
+    /// The cached response is still current.
+    NotModified,
+}
+
+/// Send the request, conditionally on the response having changed if
+/// `validators` are given, and update the content cache with the response.
+async fn revalidate(
+    backend_id: BackendId,
+    client: &reqwest::Client,
+    mut request: reqwest::Request,
+    validators: Option<&Validators>,
+) -> Result<Revalidated, BackendError> {
+    if let Some(validators) = validators {
+        add_conditional_headers(request.headers_mut(), validators);
+    }
+
+    let url = request.url().to_string();
+    let response = client.execute(request).await.context(ConnectionSnafu)?;
+    let default_stale_for = cache_settings::load_async().await.content_ttl;
+    let policy = cache_policy(response.headers(), default_stale_for);
+
+    if validators.is_some() && response.status() == StatusCode::NOT_MODIFIED {
+        match policy {
+            Some(policy) => content_cache::renew_body(backend_id, &url, policy).await,
+            None => content_cache::remove_body(backend_id, &url).await,
+        }
+        return Ok(Revalidated::NotModified);
+    }
+
+    let response = response.error_for_status()?;
+    let body = response.bytes().await?.to_vec();
+    match policy {
+        Some(policy) => {
+            content_cache::insert_body(backend_id, &url, body.clone(), policy).await
+        },
+        None => content_cache::remove_body(backend_id, &url).await,
+    }
+
+    Ok(Revalidated::Modified(body))
+}
+
+fn add_conditional_headers(headers: &mut HeaderMap, validators: &Validators) {
+    let etag = validators.etag.as_deref().map(HeaderValue::from_str);
+    if let Some(Ok(etag)) = etag {
+        headers.insert(header::IF_NONE_MATCH, etag);
+    }
+    let last_modified = validators
+        .last_modified
+        .as_deref()
+        .map(HeaderValue::from_str);
+    if let Some(Ok(last_modified)) = last_modified {
+        headers.insert(header::IF_MODIFIED_SINCE, last_modified);
+    }
+}
+
+/// Returns how long the response may be cached for according to its headers,
+/// or `None` if it must not be stored.
+///
+/// Responses without a `Cache-Control` header are fresh for [DEFAULT_FRESHNESS],
+/// then served while they are revalidated for `default_stale_for`.
+fn cache_policy(
+    headers: &HeaderMap,
+    default_stale_for: Duration,
+) -> Option<CachePolicy> {
+    let mut policy = CachePolicy {
+        fresh_for: DEFAULT_FRESHNESS,
+        stale_for: default_stale_for,
+        validators: Validators {
+            etag: header_string(headers, header::ETAG),
+            last_modified: header_string(headers, header::LAST_MODIFIED),
+        },
+    };
+
+    let directives = headers
+        .get_all(header::CACHE_CONTROL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','));
+    let mut no_cache = false;
+    let mut must_revalidate = false;
+    for directive in directives {
+        let (name, value) = match directive.split_once('=') {
+            Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+            None => (directive.trim(), None),
+        };
+        let seconds = || value?.parse().ok().map(Duration::from_secs);
+
+        match name.to_ascii_lowercase().as_str() {
+            "no-store" => return None,
+            "no-cache" => no_cache = true,
+            "must-revalidate" => must_revalidate = true,
+            "max-age" => policy.fresh_for = seconds().unwrap_or(Duration::ZERO),
+            "stale-while-revalidate" => {
+                policy.stale_for = seconds().unwrap_or(Duration::ZERO)
+            },
+            _ => {},
+        }
+    }
+
+    // Responses must be revalidated before every use.
+    if no_cache {
+        policy.fresh_for = Duration::ZERO;
+    }
+    // Stale responses must not be served before they are revalidated.
+    if no_cache || must_revalidate {
+        policy.stale_for = Duration::ZERO;
+    }
+    // The response may have already spent some of its freshness in shared caches.
+    if let Some(age) = header_string(headers, header::AGE)
+        .and_then(|age| age.parse().ok())
+        .map(Duration::from_secs)
+    {
+        policy.fresh_for = policy.fresh_for.saturating_sub(age);
+    }
+
+    Some(policy)
+}
+
+fn header_string(headers: &HeaderMap, name: header::HeaderName) -> Option<String> {
+    headers.get(name)?.to_str().ok().map(String::from)
 }
 
 fn create_default_builder() -> reqwest::ClientBuilder {
@@ -119,3 +364,76 @@ where
     header.set_sensitive(true);
     header
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STALE_FOR: Duration = Duration::from_secs(3600);
+
+    fn header_map(pairs: &[(header::HeaderName, &str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.clone(), HeaderValue::from_str(value).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_revalidation_guard() {
+        let backend_id = BackendId::now_v7();
+        let url = "http://localhost/Items".to_string();
+
+        let guard = RevalidationGuard::acquire(backend_id, url.clone()).unwrap();
+        assert!(RevalidationGuard::acquire(backend_id, url.clone()).is_none());
+        // Other responses are revalidated independently.
+        let other =
+            RevalidationGuard::acquire(backend_id, "http://localhost/Users".into());
+        assert!(other.is_some());
+
+        drop(guard);
+        assert!(RevalidationGuard::acquire(backend_id, url).is_some());
+    }
+
+    #[test]
+    fn test_cache_policy_defaults() {
+        let headers = header_map(&[
+            (header::ETAG, "\"abc\""),
+            (header::LAST_MODIFIED, "Tue, 01 Oct 2024 00:00:00 GMT"),
+        ]);
+        let policy = cache_policy(&headers, STALE_FOR).unwrap();
+        assert_eq!(policy.fresh_for, DEFAULT_FRESHNESS);
+        assert_eq!(policy.stale_for, STALE_FOR);
+        assert_eq!(policy.validators.etag.as_deref(), Some("\"abc\""));
+        assert_eq!(
+            policy.validators.last_modified.as_deref(),
+            Some("Tue, 01 Oct 2024 00:00:00 GMT"),
+        );
+    }
+
+    #[test]
+    fn test_cache_policy_cache_control() {
+        let headers = header_map(&[
+            (header::CACHE_CONTROL, "private, max-age=600"),
+            (header::CACHE_CONTROL, "stale-while-revalidate=\"30\""),
+            (header::AGE, "100"),
+        ]);
+        let policy = cache_policy(&headers, STALE_FOR).unwrap();
+        assert_eq!(policy.fresh_for, Duration::from_secs(500));
+        assert_eq!(policy.stale_for, Duration::from_secs(30));
+        assert!(policy.validators.is_empty());
+
+        let headers = header_map(&[(header::CACHE_CONTROL, "max-age=600, No-Cache")]);
+        let policy = cache_policy(&headers, STALE_FOR).unwrap();
+        assert_eq!(policy.fresh_for, Duration::ZERO);
+        assert_eq!(policy.stale_for, Duration::ZERO);
+
+        let headers =
+            header_map(&[(header::CACHE_CONTROL, "max-age=60, must-revalidate")]);
+        let policy = cache_policy(&headers, STALE_FOR).unwrap();
+        assert_eq!(policy.fresh_for, Duration::from_secs(60));
+        assert_eq!(policy.stale_for, Duration::ZERO);
+
+        let headers = header_map(&[(header::CACHE_CONTROL, "no-store")]);
+        assert!(cache_policy(&headers, STALE_FOR).is_none());
+    }
+}
@@ -6,6 +6,8 @@ use super::Context;
 use crate::backends::http::HttpClient;
 
 static USER_AUTHENTICATION_ENDPOINT: &str = "/Users/AuthenticateByName";
+/// The client name shown on the server's dashboard.
+static CLIENT_NAME: &str = "Bluebottle";
 
 /// Creates a new backend [Context] for the Jellyfin media library.
 pub async fn create_backend_context(
@@ -13,7 +15,9 @@ pub async fn create_backend_context(
     username: String,
     password: String,
 ) -> Result<Context, CreateContextError> {
-    let client = HttpClient::new(url.clone());
+    let device_id = uuid::Uuid::now_v7().simple().to_string();
+    let mut client = HttpClient::new(url.clone());
+    client.add_token_auth(&authorization(&device_id, None));
 
     let payload = json!({
       "Username": username,
@@ -35,10 +39,52 @@ pub async fn create_backend_context(
 
     Ok(Context {
         server_url: url,
-        access_token: payload.access_token,
+        access_token: Some(payload.access_token),
+        access_token_ref: None,
+        device_id,
     })
 }
 
+/// Build the `MediaBrowser` authorization header, which identifies the client
+/// and device of the session on the server's dashboard.
+pub(super) fn authorization(device_id: &str, access_token: Option<&str>) -> String {
+    let mut header = format!(
+        r#"MediaBrowser Client="{CLIENT_NAME}", Device="{}", DeviceId="{}", Version="{}""#,
+        header_safe(&device_name()),
+        header_safe(device_id),
+        env!("CARGO_PKG_VERSION"),
+    );
+    if let Some(access_token) = access_token {
+        header.push_str(&format!(r#", Token="{}""#, header_safe(access_token)));
+    }
+    header
+}
+
+/// Derive a stable device ID for contexts saved before device IDs were stored.
+pub(super) fn legacy_device_id(access_token: &str) -> String {
+    // FNV-1a, the token must not be recoverable from the ID shown on the dashboard.
+    let hash = access_token
+        .bytes()
+        .fold(0xcbf29ce484222325_u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        });
+    format!("{hash:016x}")
+}
+
+fn device_name() -> String {
+    std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .unwrap_or_else(|_| "Desktop".to_string())
+}
+
+/// Remove characters which would break the header's quoted values.
+fn header_safe(value: &str) -> String {
+    value
+        .chars()
+        .filter(|c| !c.is_control() && *c != '"' && *c != ',')
+        .collect()
+}
+
 #[derive(Debug, snafu::Snafu)]
 /// An error preventing the system from creating a new [Context] instance.
 pub enum CreateContextError {
@@ -78,3 +124,27 @@ impl From<reqwest::Error> for CreateContextError {
 struct AuthenticationBody {
     access_token: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_authorization_header() {
+        let header = authorization("device-1", Some("token"));
+        assert!(header.starts_with(r#"MediaBrowser Client="Bluebottle", Device=""#));
+        assert!(header.contains(r#"DeviceId="device-1""#));
+        assert!(header.ends_with(r#", Token="token""#));
+
+        let header = authorization("device-1", None);
+        assert!(!header.contains("Token"));
+    }
+
+    #[test]
+    fn test_legacy_device_id() {
+        let device_id = legacy_device_id("token");
+        assert_eq!(device_id, legacy_device_id("token"));
+        assert_ne!(device_id, legacy_device_id("other"));
+        assert_eq!(device_id.len(), 16);
+    }
+}
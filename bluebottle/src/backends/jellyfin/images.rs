@@ -0,0 +1,60 @@
+use snafu::ResultExt;
+
+use super::Jellyfin;
+use crate::backends::BackendError;
+use crate::backends::error::ConnectionSnafu;
+use crate::models::image::{ImageKind, ImageRequest};
+use crate::models::media::ItemId;
+
+/// The largest height images are downloaded at, the server scales them down.
+const MAX_IMAGE_HEIGHT: u32 = 720;
+
+impl Jellyfin {
+    pub(super) async fn fetch_primary_image(
+        &self,
+        item_id: ItemId,
+    ) -> Result<Vec<u8>, BackendError> {
+        let endpoint = format!("/Items/{item_id}/Images/Primary");
+        let params = [("maxHeight", MAX_IMAGE_HEIGHT)];
+
+        self.fetch_image_data(&endpoint, &params).await
+    }
+
+    pub(super) async fn fetch_image(
+        &self,
+        request: ImageRequest,
+    ) -> Result<Vec<u8>, BackendError> {
+        let image_type = match request.kind {
+            ImageKind::Primary => "Primary",
+            ImageKind::Thumb => "Thumb",
+            ImageKind::Backdrop => "Backdrop",
+        };
+        let endpoint = format!("/Items/{}/Images/{image_type}", request.item_id);
+        // The server scales the image to cover the size, keeping its aspect ratio.
+        let params = [
+            ("fillWidth", request.width),
+            ("fillHeight", request.height),
+            ("quality", request.quality as u32),
+        ];
+
+        self.fetch_image_data(&endpoint, &params).await
+    }
+
+    async fn fetch_image_data(
+        &self,
+        endpoint: &str,
+        params: &[(&str, u32)],
+    ) -> Result<Vec<u8>, BackendError> {
+        let response = self
+            .client
+            .get(endpoint)
+            .query(params)
+            .send()
+            .await
+            .context(ConnectionSnafu)?
+            .error_for_status()?;
+        let data = response.bytes().await.context(ConnectionSnafu)?;
+
+        Ok(data.to_vec())
+    }
+}
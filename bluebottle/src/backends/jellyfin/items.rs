@@ -0,0 +1,367 @@
+use std::collections::HashMap;
+
+use super::{Jellyfin, send_json, send_json_cached};
+use crate::backends::BackendError;
+use crate::models::media::{ItemKind, ItemPage, ItemSummary, Library, LibraryKind};
+use crate::models::query::{
+    FilterOptions,
+    ItemFilter,
+    ItemQuery,
+    LibraryViewOptions,
+    SortBy,
+    SortOrder,
+};
+
+static USER_VIEWS_ENDPOINT: &str = "/UserViews";
+pub(super) static ITEMS_ENDPOINT: &str = "/Items";
+static ITEM_FILTERS_ENDPOINT: &str = "/Items/Filters";
+static STUDIOS_ENDPOINT: &str = "/Studios";
+
+/// The item types that are displayed within a library grid.
+static LIBRARY_ITEM_TYPES: &str = "Movie,Series,BoxSet,MusicAlbum";
+
+impl Jellyfin {
+    pub(super) async fn fetch_libraries(&self) -> Result<Vec<Library>, BackendError> {
+        let request = self.client.get(USER_VIEWS_ENDPOINT);
+        let payload: ItemsBody = send_json_cached(&self.client, request).await?;
+
+        let libraries = payload
+            .items
+            .into_iter()
+            .map(|item| Library {
+                id: item.id,
+                name: item.name,
+                kind: library_kind(item.collection_type.as_deref()),
+            })
+            .collect();
+
+        Ok(libraries)
+    }
+
+    pub(super) async fn fetch_items(
+        &self,
+        query: ItemQuery,
+    ) -> Result<ItemPage, BackendError> {
+        let params = item_query_params(&query);
+        let request = self.client.get(ITEMS_ENDPOINT).query(&params);
+        let payload: ItemsBody = send_json(request).await?;
+
+        Ok(ItemPage {
+            start_index: payload.start_index.unwrap_or(query.start_index),
+            total_items: payload.total_record_count,
+            items: payload.items.into_iter().map(ItemSummary::from).collect(),
+        })
+    }
+
+    pub(super) async fn fetch_filter_options(
+        &self,
+        library_id: String,
+    ) -> Result<FilterOptions, BackendError> {
+        let params = [
+            ("ParentId", library_id.as_str()),
+            ("IncludeItemTypes", LIBRARY_ITEM_TYPES),
+        ];
+
+        let request = self.client.get(ITEM_FILTERS_ENDPOINT).query(&params);
+        let filters: FiltersBody = send_json_cached(&self.client, request).await?;
+
+        let request = self.client.get(STUDIOS_ENDPOINT).query(&params);
+        let studios: ItemsBody = send_json_cached(&self.client, request).await?;
+
+        Ok(FilterOptions {
+            genres: filters.genres,
+            years: filters.years,
+            official_ratings: filters.official_ratings,
+            studios: studios.items.into_iter().map(|item| item.name).collect(),
+        })
+    }
+}
+
+/// Translate the item query into the Jellyfin `/Items` query parameters.
+fn item_query_params(query: &ItemQuery) -> Vec<(&'static str, String)> {
+    let mut params = vec![
+        ("ParentId", query.library_id.clone()),
+        ("StartIndex", query.start_index.to_string()),
+        ("Limit", query.limit.to_string()),
+        ("Recursive", "true".to_string()),
+        ("IncludeItemTypes", LIBRARY_ITEM_TYPES.to_string()),
+        ("EnableTotalRecordCount", "true".to_string()),
+    ];
+    extend_with_options(&mut params, &query.options);
+    params
+}
+
+fn extend_with_options(
+    params: &mut Vec<(&'static str, String)>,
+    options: &LibraryViewOptions,
+) {
+    let sort_by = match options.sort_by {
+        SortBy::Name => "SortName",
+        SortBy::DateAdded => "DateCreated,SortName",
+        SortBy::ReleaseDate => "PremiereDate,ProductionYear,SortName",
+        SortBy::Rating => "CommunityRating,SortName",
+        SortBy::Runtime => "Runtime,SortName",
+        // Jellyfin cannot seed its random order, so the pages of a library which
+        // has not synced yet may overlap.
+        SortBy::Random => "Random",
+    };
+    let sort_order = match options.sort_order {
+        SortOrder::Ascending => "Ascending",
+        SortOrder::Descending => "Descending",
+    };
+    params.push(("SortBy", sort_by.to_string()));
+    params.push(("SortOrder", sort_order.to_string()));
+
+    let mut genres = Vec::new();
+    let mut years = Vec::new();
+    let mut flags = Vec::new();
+    let mut official_ratings = Vec::new();
+    let mut studios = Vec::new();
+
+    for filter in options.filters.iter() {
+        match filter {
+            ItemFilter::Genre(genre) => genres.push(genre.as_str()),
+            ItemFilter::Years { from, to } => {
+                years.extend((*from..=*to).map(|year| year.to_string()))
+            },
+            ItemFilter::Played => flags.push("IsPlayed"),
+            ItemFilter::Unplayed => flags.push("IsUnplayed"),
+            ItemFilter::Favourite => flags.push("IsFavorite"),
+            ItemFilter::OfficialRating(rating) => official_ratings.push(rating.as_str()),
+            ItemFilter::Studio(studio) => studios.push(studio.as_str()),
+        }
+    }
+
+    // Jellyfin uses `|` to delimit values which may contain commas themselves.
+    if !genres.is_empty() {
+        params.push(("Genres", genres.join("|")));
+    }
+    if !years.is_empty() {
+        params.push(("Years", years.join(",")));
+    }
+    if !flags.is_empty() {
+        params.push(("Filters", flags.join(",")));
+    }
+    if !official_ratings.is_empty() {
+        params.push(("OfficialRatings", official_ratings.join("|")));
+    }
+    if !studios.is_empty() {
+        params.push(("Studios", studios.join("|")));
+    }
+}
+
+#[derive(serde_derive::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct FiltersBody {
+    #[serde(default)]
+    genres: Vec<String>,
+    #[serde(default)]
+    years: Vec<u32>,
+    #[serde(default)]
+    official_ratings: Vec<String>,
+}
+
+#[derive(serde_derive::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub(super) struct ItemsBody {
+    pub(super) items: Vec<BaseItem>,
+    #[serde(default)]
+    pub(super) total_record_count: usize,
+    pub(super) start_index: Option<usize>,
+}
+
+#[derive(serde_derive::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub(super) struct BaseItem {
+    pub(super) id: String,
+    #[serde(default)]
+    pub(super) name: String,
+    #[serde(rename = "Type", default)]
+    pub(super) kind: String,
+    pub(super) production_year: Option<u32>,
+    pub(super) collection_type: Option<String>,
+    pub(super) parent_id: Option<String>,
+    pub(super) series_id: Option<String>,
+    pub(super) index_number: Option<u32>,
+    pub(super) parent_index_number: Option<u32>,
+    pub(super) sort_name: Option<String>,
+    pub(super) original_title: Option<String>,
+    #[serde(default)]
+    pub(super) people: Vec<NamedItem>,
+    #[serde(default)]
+    pub(super) genres: Vec<String>,
+    #[serde(default)]
+    pub(super) studios: Vec<NamedItem>,
+    pub(super) official_rating: Option<String>,
+    pub(super) community_rating: Option<f32>,
+    pub(super) run_time_ticks: Option<u64>,
+    pub(super) premiere_date: Option<String>,
+    pub(super) date_created: Option<String>,
+    pub(super) date_last_saved: Option<String>,
+    pub(super) user_data: Option<UserData>,
+    pub(super) album: Option<String>,
+    pub(super) album_id: Option<String>,
+    pub(super) album_artist: Option<String>,
+    #[serde(default)]
+    pub(super) artists: Vec<String>,
+    /// The loudness correction of audio items in dB.
+    pub(super) normalization_gain: Option<f32>,
+    #[serde(default)]
+    pub(super) media_streams: Vec<super::playback::MediaStream>,
+    pub(super) path: Option<String>,
+    #[serde(default)]
+    pub(super) image_tags: HashMap<String, String>,
+    #[serde(default)]
+    pub(super) image_blur_hashes: ImageBlurHashes,
+}
+
+impl BaseItem {
+    /// Returns the BlurHash of the item's primary image.
+    pub(super) fn primary_blurhash(&mut self) -> Option<String> {
+        let hashes = &mut self.image_blur_hashes.primary;
+        match self.image_tags.get("Primary") {
+            Some(tag) => hashes.remove(tag),
+            None => hashes.drain().map(|(_, hash)| hash).next(),
+        }
+    }
+}
+
+#[derive(Default, serde_derive::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+/// The BlurHashes of an item's images, keyed by their image tag.
+pub(super) struct ImageBlurHashes {
+    #[serde(default)]
+    pub(super) primary: HashMap<String, String>,
+}
+
+#[derive(serde_derive::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub(super) struct NamedItem {
+    pub(super) name: String,
+}
+
+#[derive(serde_derive::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub(super) struct UserData {
+    #[serde(default)]
+    pub(super) played: bool,
+    #[serde(default)]
+    pub(super) is_favorite: bool,
+    #[serde(default)]
+    pub(super) playback_position_ticks: u64,
+    pub(super) rating: Option<f32>,
+    pub(super) last_played_date: Option<String>,
+}
+
+impl From<BaseItem> for ItemSummary {
+    fn from(mut item: BaseItem) -> Self {
+        ItemSummary {
+            blurhash: item.primary_blurhash(),
+            kind: item_kind(&item.kind),
+            id: item.id,
+            name: item.name,
+            production_year: item.production_year,
+        }
+    }
+}
+
+pub(super) fn item_kind(kind: &str) -> ItemKind {
+    match kind {
+        "Movie" => ItemKind::Movie,
+        "Series" => ItemKind::Series,
+        "Season" => ItemKind::Season,
+        "Episode" => ItemKind::Episode,
+        "BoxSet" => ItemKind::BoxSet,
+        "MusicAlbum" => ItemKind::MusicAlbum,
+        "MusicArtist" => ItemKind::MusicArtist,
+        "Audio" => ItemKind::Audio,
+        "Person" => ItemKind::Person,
+        "Folder" | "CollectionFolder" => ItemKind::Folder,
+        _ => ItemKind::Other,
+    }
+}
+
+fn library_kind(collection_type: Option<&str>) -> LibraryKind {
+    match collection_type {
+        Some("movies") => LibraryKind::Movies,
+        Some("tvshows") => LibraryKind::TvShows,
+        Some("music") => LibraryKind::Music,
+        _ => LibraryKind::Mixed,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn param<'a>(params: &'a [(&'static str, String)], key: &str) -> Option<&'a str> {
+        params
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    #[test]
+    fn test_default_options_params() {
+        let mut params = Vec::new();
+        extend_with_options(&mut params, &LibraryViewOptions::default());
+        assert_eq!(param(&params, "SortBy"), Some("SortName"));
+        assert_eq!(param(&params, "SortOrder"), Some("Ascending"));
+        assert_eq!(param(&params, "Genres"), None);
+        assert_eq!(param(&params, "Filters"), None);
+    }
+
+    #[test]
+    fn test_filter_params() {
+        let mut options = LibraryViewOptions {
+            sort_by: SortBy::Rating,
+            sort_order: SortOrder::Descending,
+            filters: Vec::new(),
+        };
+        options.add_filter(ItemFilter::Genre("Action".into()));
+        options.add_filter(ItemFilter::Genre("Sci-Fi".into()));
+        options.add_filter(ItemFilter::Years {
+            from: 1998,
+            to: 2000,
+        });
+        options.add_filter(ItemFilter::Played);
+        options.add_filter(ItemFilter::Favourite);
+        options.add_filter(ItemFilter::OfficialRating("PG-13".into()));
+        options.add_filter(ItemFilter::Studio("Studio Ghibli, Inc.".into()));
+
+        let mut params = Vec::new();
+        extend_with_options(&mut params, &options);
+        assert_eq!(param(&params, "SortBy"), Some("CommunityRating,SortName"));
+        assert_eq!(param(&params, "SortOrder"), Some("Descending"));
+        assert_eq!(param(&params, "Genres"), Some("Action|Sci-Fi"));
+        assert_eq!(param(&params, "Years"), Some("1998,1999,2000"));
+        assert_eq!(param(&params, "Filters"), Some("IsPlayed,IsFavorite"));
+        assert_eq!(param(&params, "OfficialRatings"), Some("PG-13"));
+        assert_eq!(param(&params, "Studios"), Some("Studio Ghibli, Inc."));
+    }
+
+    #[test]
+    fn test_conflicting_filters_replaced() {
+        let mut options = LibraryViewOptions::default();
+        options.add_filter(ItemFilter::Played);
+        options.add_filter(ItemFilter::Unplayed);
+        options.add_filter(ItemFilter::Years {
+            from: 1990,
+            to: 1999,
+        });
+        options.add_filter(ItemFilter::Years {
+            from: 2000,
+            to: 2009,
+        });
+        assert_eq!(
+            options.filters,
+            vec![
+                ItemFilter::Unplayed,
+                ItemFilter::Years {
+                    from: 2000,
+                    to: 2009
+                }
+            ],
+        );
+    }
+}
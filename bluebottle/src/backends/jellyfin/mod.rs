@@ -1,33 +1,259 @@
 use serde_json::Value;
 use snafu::ResultExt;
+use tokio::sync::mpsc;
 
+use crate::backends::error::ConnectionSnafu;
 use crate::backends::http::HttpClient;
-use crate::backends::{Backend, BackendInit};
+use crate::backends::{Backend, BackendError, BackendFuture, BackendId, BackendInit};
+use crate::models::image::ImageRequest;
+use crate::models::interaction::{Interaction, UserData};
+use crate::models::live::LiveEvent;
+use crate::models::media::{ItemId, ItemKind, ItemPage, ItemSummary, Library};
+use crate::models::music::AudioTrack;
+use crate::models::playback::{PlaybackEvent, PlaybackRequest, PlaybackStream};
+use crate::models::profile::DeviceProfile;
+use crate::models::query::{FilterOptions, ItemQuery};
+use crate::models::subtitles::RemoteSubtitle;
+use crate::models::sync::{SyncPage, SyncRequest};
+use crate::models::tracks::ItemTracks;
+use crate::storage::secrets::{self, SecretRef};
 
 mod auth;
+mod images;
+mod items;
+mod music;
+mod playback;
+mod search;
+mod socket;
+mod subtitles;
+mod sync;
+mod user_data;
+
+/// The name the access token is stored under in the secret store.
+static ACCESS_TOKEN_SECRET: &str = "access_token";
 
 #[derive(serde_derive::Serialize, serde_derive::Deserialize)]
 /// The context for the Jellyfin backend.
 struct Context {
     server_url: url::Url,
-    access_token: String,
+    /// The access token in plaintext, only held by contexts saved before the
+    /// secret store existed and by new contexts before they are persisted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    access_token: Option<String>,
+    /// The access token in the secret store.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    access_token_ref: Option<SecretRef>,
+    /// Identifies this install to the server, contexts saved before this was
+    /// stored derive it from the access token.
+    #[serde(default)]
+    device_id: String,
 }
 
 /// A backend client for the Jellkfin media library.
 pub struct Jellyfin {
     client: HttpClient,
+    /// The access token, required by URLs handed to external players.
+    access_token: String,
+    device_id: String,
 }
 
 impl BackendInit for Jellyfin {
-    fn from_context(context: Value) -> Result<Self, snafu::Whatever> {
+    fn from_context(
+        backend_id: BackendId,
+        context: Value,
+    ) -> Result<Self, snafu::Whatever> {
         let context: Context = serde_json::from_value(context)
             .whatever_context("deserialize persisted backend context")?;
 
+        let access_token = match (context.access_token_ref, context.access_token) {
+            (Some(reference), _) => secrets::load(&reference)
+                .whatever_context("load access token from the secret store")?,
+            (None, Some(access_token)) => access_token,
+            (None, None) => snafu::whatever!("backend context has no access token"),
+        };
+        let device_id = if context.device_id.is_empty() {
+            auth::legacy_device_id(&access_token)
+        } else {
+            context.device_id
+        };
+
         let mut client = HttpClient::new(context.server_url);
-        client.add_token_auth(&context.access_token);
+        client.add_token_auth(&auth::authorization(&device_id, Some(&access_token)));
+        client.enable_content_cache(backend_id);
 
-        Ok(Jellyfin { client })
+        Ok(Jellyfin {
+            client,
+            access_token,
+            device_id,
+        })
+    }
+
+    fn secure_context(
+        backend_id: BackendId,
+        context: &Value,
+    ) -> Result<Option<Value>, snafu::Whatever> {
+        let mut context: Context = serde_json::from_value(context.clone())
+            .whatever_context("deserialize persisted backend context")?;
+        let Some(access_token) = context.access_token.take() else {
+            return Ok(None);
+        };
+
+        // The device ID is derived from the token, which will no longer be at hand.
+        if context.device_id.is_empty() {
+            context.device_id = auth::legacy_device_id(&access_token);
+        }
+        context.access_token_ref = Some(secrets::store(
+            backend_id,
+            ACCESS_TOKEN_SECRET,
+            &access_token,
+        )?);
+
+        serde_json::to_value(&context)
+            .map(Some)
+            .whatever_context("serialize backend context")
     }
 }
 
-impl Backend for Jellyfin {}
+impl Backend for Jellyfin {
+    fn libraries(&self) -> BackendFuture<'_, Vec<Library>> {
+        Box::pin(self.fetch_libraries())
+    }
+
+    fn query_items(&self, query: ItemQuery) -> BackendFuture<'_, ItemPage> {
+        Box::pin(self.fetch_items(query))
+    }
+
+    fn filter_options(&self, library_id: ItemId) -> BackendFuture<'_, FilterOptions> {
+        Box::pin(self.fetch_filter_options(library_id))
+    }
+
+    fn search(&self, term: String, limit: usize) -> BackendFuture<'_, Vec<ItemSummary>> {
+        Box::pin(self.fetch_search(term, limit))
+    }
+
+    fn sync_items(&self, request: SyncRequest) -> BackendFuture<'_, SyncPage> {
+        Box::pin(self.fetch_sync_page(request))
+    }
+
+    fn playback_stream(
+        &self,
+        request: PlaybackRequest,
+        profile: DeviceProfile,
+    ) -> BackendFuture<'_, PlaybackStream> {
+        Box::pin(self.fetch_playback_stream(request, profile))
+    }
+
+    fn measure_bitrate(&self) -> BackendFuture<'_, u64> {
+        Box::pin(self.run_bitrate_test())
+    }
+
+    fn item_tracks(&self, item_id: ItemId) -> BackendFuture<'_, ItemTracks> {
+        Box::pin(self.fetch_item_tracks(item_id))
+    }
+
+    fn search_subtitles(
+        &self,
+        item_id: ItemId,
+        language: String,
+    ) -> BackendFuture<'_, Vec<RemoteSubtitle>> {
+        Box::pin(self.search_remote_subtitles(item_id, language))
+    }
+
+    fn download_subtitle(
+        &self,
+        item_id: ItemId,
+        subtitle_id: String,
+    ) -> BackendFuture<'_, ()> {
+        Box::pin(self.download_remote_subtitle(item_id, subtitle_id))
+    }
+
+    fn subtitle_data(&self, subtitle_id: String) -> BackendFuture<'_, Vec<u8>> {
+        Box::pin(self.fetch_remote_subtitle(subtitle_id))
+    }
+
+    fn refresh_item(&self, item_id: ItemId) -> BackendFuture<'_, ()> {
+        Box::pin(self.send_refresh(item_id))
+    }
+
+    fn audio_tracks(
+        &self,
+        item_id: ItemId,
+        kind: ItemKind,
+    ) -> BackendFuture<'_, Vec<AudioTrack>> {
+        Box::pin(self.fetch_audio_tracks(item_id, kind))
+    }
+
+    fn audio_data(&self, item_id: ItemId) -> BackendFuture<'_, Vec<u8>> {
+        Box::pin(self.fetch_audio_data(item_id))
+    }
+
+    fn primary_image(&self, item_id: ItemId) -> BackendFuture<'_, Vec<u8>> {
+        Box::pin(self.fetch_primary_image(item_id))
+    }
+
+    fn image(&self, request: ImageRequest) -> BackendFuture<'_, Vec<u8>> {
+        Box::pin(self.fetch_image(request))
+    }
+
+    fn report_playback(&self, event: PlaybackEvent) -> BackendFuture<'_, ()> {
+        Box::pin(self.send_playback_report(event))
+    }
+
+    fn report_capabilities(&self, profile: DeviceProfile) -> BackendFuture<'_, ()> {
+        Box::pin(self.send_capabilities(profile))
+    }
+
+    fn live_updates(&self, output: mpsc::Sender<LiveEvent>) -> BackendFuture<'_, ()> {
+        Box::pin(self.run_socket(output))
+    }
+
+    fn user_data(&self, item_id: ItemId) -> BackendFuture<'_, UserData> {
+        Box::pin(self.fetch_user_data(item_id))
+    }
+
+    fn apply_interaction(
+        &self,
+        interaction: Interaction,
+        created_at: i64,
+    ) -> BackendFuture<'_, ()> {
+        Box::pin(self.send_interaction(interaction, created_at))
+    }
+}
+
+/// Send the request and deserialize the JSON response body.
+async fn send_json<T>(request: reqwest::RequestBuilder) -> Result<T, BackendError>
+where
+    T: serde::de::DeserializeOwned,
+{
+    request
+        .send()
+        .await
+        .context(ConnectionSnafu)?
+        .error_for_status()?
+        .json()
+        .await
+        .map_err(|_| BackendError::InvalidResponse)
+}
+
+/// Send the request through the content cache and deserialize the JSON response
+/// body.
+async fn send_json_cached<T>(
+    client: &HttpClient,
+    request: reqwest::RequestBuilder,
+) -> Result<T, BackendError>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let body = client.send_cached(request).await?;
+    serde_json::from_slice(&body).map_err(|_| BackendError::InvalidResponse)
+}
+
+/// Send the request, ignoring any response body.
+async fn send_empty(request: reqwest::RequestBuilder) -> Result<(), BackendError> {
+    request
+        .send()
+        .await
+        .context(ConnectionSnafu)?
+        .error_for_status()?;
+    Ok(())
+}
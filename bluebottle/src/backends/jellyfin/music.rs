@@ -0,0 +1,175 @@
+use snafu::ResultExt;
+
+use super::items::{BaseItem, ITEMS_ENDPOINT, ItemsBody};
+use super::playback::ticks_to_duration;
+use super::{Jellyfin, send_json};
+use crate::backends::BackendError;
+use crate::backends::error::ConnectionSnafu;
+use crate::models::media::{ItemId, ItemKind};
+use crate::models::music::AudioTrack;
+
+/// The containers the music player can decode as `container|codec`, anything
+/// else is transcoded by the server.
+static SUPPORTED_CONTAINERS: &str =
+    "flac,mp3,wav,ogg|vorbis,oga|vorbis,m4a|aac,m4a|alac,mp4|aac,mp4|alac";
+static TRANSCODING_CONTAINER: &str = "flac";
+
+impl Jellyfin {
+    pub(super) async fn fetch_audio_tracks(
+        &self,
+        item_id: ItemId,
+        kind: ItemKind,
+    ) -> Result<Vec<AudioTrack>, BackendError> {
+        let params = audio_track_params(item_id, kind);
+        let request = self.client.get(ITEMS_ENDPOINT).query(&params);
+        let payload: ItemsBody = send_json(request).await?;
+
+        Ok(payload.items.into_iter().map(AudioTrack::from).collect())
+    }
+
+    pub(super) async fn fetch_audio_data(
+        &self,
+        item_id: ItemId,
+    ) -> Result<Vec<u8>, BackendError> {
+        // The universal endpoint direct streams the file when it is supported,
+        // otherwise it is transcoded.
+        let endpoint = format!("/Audio/{item_id}/universal");
+        let params = [
+            ("Container", SUPPORTED_CONTAINERS),
+            ("TranscodingContainer", TRANSCODING_CONTAINER),
+            ("AudioCodec", TRANSCODING_CONTAINER),
+            ("TranscodingProtocol", "http"),
+            ("DeviceId", self.device_id.as_str()),
+        ];
+
+        let response = self
+            .client
+            .get(&endpoint)
+            .query(&params)
+            .send()
+            .await
+            .context(ConnectionSnafu)?
+            .error_for_status()?;
+        let data = response.bytes().await.context(ConnectionSnafu)?;
+
+        Ok(data.to_vec())
+    }
+}
+
+/// The `/Items` query parameters listing the tracks of an item in play order.
+fn audio_track_params(item_id: ItemId, kind: ItemKind) -> Vec<(&'static str, String)> {
+    let mut params = vec![
+        ("IncludeItemTypes", "Audio".to_string()),
+        ("Recursive", "true".to_string()),
+        ("EnableImages", "false".to_string()),
+    ];
+
+    match kind {
+        ItemKind::Audio => params.push(("Ids", item_id)),
+        ItemKind::MusicArtist => {
+            params.push(("ArtistIds", item_id));
+            params.push((
+                "SortBy",
+                "ProductionYear,Album,ParentIndexNumber,IndexNumber,SortName"
+                    .to_string(),
+            ));
+        },
+        _ => {
+            params.push(("ParentId", item_id));
+            params.push((
+                "SortBy",
+                "ParentIndexNumber,IndexNumber,SortName".to_string(),
+            ));
+        },
+    }
+
+    params
+}
+
+impl From<BaseItem> for AudioTrack {
+    fn from(item: BaseItem) -> Self {
+        let artist = if item.artists.is_empty() {
+            item.album_artist
+        } else {
+            Some(item.artists.join(", "))
+        };
+
+        AudioTrack {
+            item_id: item.id,
+            name: item.name,
+            artist,
+            album: item.album,
+            album_id: item.album_id,
+            runtime: item.run_time_ticks.map(ticks_to_duration),
+            normalization_gain: item.normalization_gain,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_audio_tracks() {
+        let body = r#"{
+            "Items": [
+                {
+                    "Id": "t1",
+                    "Name": "Intro",
+                    "Type": "Audio",
+                    "Album": "Debut",
+                    "AlbumId": "a1",
+                    "AlbumArtist": "Band",
+                    "Artists": ["Band", "Guest"],
+                    "RunTimeTicks": 1800000000,
+                    "NormalizationGain": -7.5
+                },
+                {
+                    "Id": "t2",
+                    "Name": "Outro",
+                    "Type": "Audio",
+                    "AlbumArtist": "Band"
+                }
+            ],
+            "TotalRecordCount": 2
+        }"#;
+        let payload: ItemsBody = serde_json::from_str(body).unwrap();
+        let tracks: Vec<AudioTrack> =
+            payload.items.into_iter().map(AudioTrack::from).collect();
+
+        assert_eq!(tracks[0].artist.as_deref(), Some("Band, Guest"));
+        assert_eq!(tracks[0].album.as_deref(), Some("Debut"));
+        assert_eq!(tracks[0].album_id.as_deref(), Some("a1"));
+        assert_eq!(tracks[0].runtime, Some(Duration::from_secs(180)));
+        assert_eq!(tracks[0].normalization_gain, Some(-7.5));
+        assert_eq!(tracks[1].artist.as_deref(), Some("Band"));
+        assert_eq!(tracks[1].runtime, None);
+    }
+
+    #[test]
+    fn test_audio_track_params() {
+        let param = |params: &[(&'static str, String)], key: &str| {
+            params
+                .iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, v)| v.clone())
+        };
+
+        let params = audio_track_params("album".into(), ItemKind::MusicAlbum);
+        assert_eq!(param(&params, "ParentId").as_deref(), Some("album"));
+        assert_eq!(
+            param(&params, "SortBy").as_deref(),
+            Some("ParentIndexNumber,IndexNumber,SortName"),
+        );
+
+        let params = audio_track_params("artist".into(), ItemKind::MusicArtist);
+        assert_eq!(param(&params, "ArtistIds").as_deref(), Some("artist"));
+        assert_eq!(param(&params, "ParentId"), None);
+
+        let params = audio_track_params("track".into(), ItemKind::Audio);
+        assert_eq!(param(&params, "Ids").as_deref(), Some("track"));
+    }
+}
@@ -0,0 +1,742 @@
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use serde_json::json;
+use snafu::ResultExt;
+
+use super::items::{ITEMS_ENDPOINT, ItemsBody};
+use super::{Jellyfin, send_empty, send_json};
+use crate::backends::BackendError;
+use crate::backends::error::ConnectionSnafu;
+use crate::models::media::ItemId;
+use crate::models::playback::{
+    PlayMethod,
+    PlaybackEvent,
+    PlaybackReport,
+    PlaybackRequest,
+    PlaybackStream,
+};
+use crate::models::profile::DeviceProfile;
+use crate::models::tracks::{ItemTracks, MediaTrack, TrackSelection};
+
+static PLAYING_ENDPOINT: &str = "/Sessions/Playing";
+static PROGRESS_ENDPOINT: &str = "/Sessions/Playing/Progress";
+static STOPPED_ENDPOINT: &str = "/Sessions/Playing/Stopped";
+static CAPABILITIES_ENDPOINT: &str = "/Sessions/Capabilities/Full";
+static BITRATE_TEST_ENDPOINT: &str = "/Playback/BitrateTest";
+
+/// The sizes in bytes of the bitrate test downloads, which grow until one takes
+/// long enough to measure.
+const BITRATE_TEST_SIZES: &[u64] = &[500_000, 1_000_000, 3_000_000, 10_000_000];
+/// How long a bitrate test download needs to take to be trusted.
+const BITRATE_TEST_DURATION: Duration = Duration::from_secs(1);
+
+/// The general commands the server may send to control playback remotely.
+static SUPPORTED_COMMANDS: &[&str] = &[
+    "SetAudioStreamIndex",
+    "SetSubtitleStreamIndex",
+    "SetVolume",
+    "VolumeUp",
+    "VolumeDown",
+    "Mute",
+    "Unmute",
+    "ToggleMute",
+    "DisplayMessage",
+];
+
+impl Jellyfin {
+    pub(super) async fn fetch_playback_stream(
+        &self,
+        request: PlaybackRequest,
+        profile: DeviceProfile,
+    ) -> Result<PlaybackStream, BackendError> {
+        let endpoint = format!("/Items/{}/PlaybackInfo", request.item_id);
+        let mut payload = json!({
+            "StartTimeTicks": duration_to_ticks(request.start_position),
+            "AutoOpenLiveStream": true,
+            "EnableDirectPlay": true,
+            "EnableDirectStream": true,
+            "EnableTranscoding": true,
+            "AllowVideoStreamCopy": true,
+            "AllowAudioStreamCopy": true,
+            "DeviceProfile": device_profile(&profile),
+        });
+        if let Some(max_bitrate) = profile.max_bitrate {
+            payload["MaxStreamingBitrate"] = json!(max_bitrate);
+        }
+        if let Some(tracks) = request.tracks {
+            payload["AudioStreamIndex"] = json!(tracks.audio_stream_index);
+            // Jellyfin uses `-1` for subtitles which are turned off.
+            payload["SubtitleStreamIndex"] =
+                json!(tracks.subtitle_stream_index.map_or(-1, i64::from));
+        }
+
+        let body: PlaybackInfoBody =
+            send_json(self.client.post(&endpoint).json(&payload)).await?;
+        let play_session_id = body.play_session_id.clone();
+
+        let (selected, streams) = select_source(&request.item_id, body, request.tracks)?;
+        let (media_source_id, play_method, mut url) = match selected {
+            SelectedSource::Direct { media_source_id } => {
+                let endpoint = format!("/Videos/{}/stream", request.item_id);
+                let mut url = self.client.url(&endpoint);
+                url.query_pairs_mut()
+                    .append_pair("static", "true")
+                    .append_pair("MediaSourceId", &media_source_id);
+                if let Some(play_session_id) = play_session_id.as_deref() {
+                    url.query_pairs_mut()
+                        .append_pair("PlaySessionId", play_session_id);
+                }
+                (media_source_id, PlayMethod::DirectPlay, url)
+            },
+            SelectedSource::Stream {
+                media_source_id,
+                play_method,
+                transcoding_url,
+            } => (
+                media_source_id,
+                play_method,
+                self.client.url(&transcoding_url),
+            ),
+        };
+
+        self.append_api_key(&mut url);
+        let subtitle_files = streams
+            .subtitle_files
+            .iter()
+            .map(|delivery_url| {
+                let mut url = self.client.url(delivery_url);
+                self.append_api_key(&mut url);
+                url
+            })
+            .collect();
+
+        Ok(PlaybackStream {
+            item_id: request.item_id,
+            url,
+            media_source_id,
+            play_session_id,
+            play_method,
+            audio_streams: streams.audio,
+            subtitle_streams: streams.subtitle,
+            subtitle_files,
+            tracks: request.tracks,
+        })
+    }
+
+    /// The player fetches streams itself, so it cannot use our auth header.
+    fn append_api_key(&self, url: &mut url::Url) {
+        let has_api_key = url
+            .query_pairs()
+            .any(|(key, _)| key.eq_ignore_ascii_case("api_key") || key == "ApiKey");
+        if !has_api_key {
+            url.query_pairs_mut()
+                .append_pair("api_key", &self.access_token);
+        }
+    }
+
+    pub(super) async fn fetch_item_tracks(
+        &self,
+        item_id: ItemId,
+    ) -> Result<ItemTracks, BackendError> {
+        let params = [
+            ("Ids", item_id.as_str()),
+            ("Fields", "MediaStreams,Path"),
+            ("EnableImages", "false"),
+        ];
+        let request = self.client.get(ITEMS_ENDPOINT).query(&params);
+        let payload: ItemsBody = send_json(request).await?;
+
+        let Some(item) = payload.items.into_iter().next() else {
+            return Err(BackendError::NotPlayable {
+                reason: format!("item {item_id} does not exist"),
+            });
+        };
+
+        Ok(item_tracks(item.series_id, item.path, &item.media_streams))
+    }
+
+    pub(super) async fn send_playback_report(
+        &self,
+        event: PlaybackEvent,
+    ) -> Result<(), BackendError> {
+        let (endpoint, report) = match &event {
+            PlaybackEvent::Started(report) => (PLAYING_ENDPOINT, report),
+            PlaybackEvent::Progress(report) => (PROGRESS_ENDPOINT, report),
+            PlaybackEvent::Stopped(report) => (STOPPED_ENDPOINT, report),
+        };
+
+        let payload = PlaybackProgressInfo::from(report);
+        send_empty(self.client.post(endpoint).json(&payload)).await
+    }
+
+    pub(super) async fn run_bitrate_test(&self) -> Result<u64, BackendError> {
+        let mut bitrate = 0;
+        for size in BITRATE_TEST_SIZES {
+            let started_at = Instant::now();
+            let response = self
+                .client
+                .get(BITRATE_TEST_ENDPOINT)
+                .query(&[("size", size)])
+                .send()
+                .await
+                .context(ConnectionSnafu)?
+                .error_for_status()?;
+            let data = response.bytes().await.context(ConnectionSnafu)?;
+
+            let elapsed = started_at.elapsed();
+            bitrate = bitrate_of(data.len(), elapsed);
+            if elapsed >= BITRATE_TEST_DURATION {
+                break;
+            }
+        }
+        Ok(bitrate)
+    }
+
+    pub(super) async fn send_capabilities(
+        &self,
+        profile: DeviceProfile,
+    ) -> Result<(), BackendError> {
+        let payload = json!({
+            "PlayableMediaTypes": ["Video", "Audio"],
+            "SupportedCommands": SUPPORTED_COMMANDS,
+            "SupportsMediaControl": true,
+            "SupportsPersistentIdentifier": true,
+            "DeviceProfile": device_profile(&profile),
+        });
+        send_empty(self.client.post(CAPABILITIES_ENDPOINT).json(&payload)).await
+    }
+}
+
+/// Describes the profile in Jellyfin's format, the server direct plays what the
+/// profile allows and transcodes to HLS otherwise.
+fn device_profile(profile: &DeviceProfile) -> serde_json::Value {
+    let mut video = json!({"Type": "Video"});
+    if !profile.containers.is_empty() {
+        video["Container"] = json!(profile.containers.join(","));
+    }
+    if !profile.video_codecs.is_empty() {
+        video["VideoCodec"] = json!(profile.video_codecs.join(","));
+    }
+    if !profile.audio_codecs.is_empty() {
+        video["AudioCodec"] = json!(profile.audio_codecs.join(","));
+    }
+
+    let codec_profiles = match profile.max_height {
+        Some(max_height) => json!([{
+            "Type": "Video",
+            "Conditions": [{
+                "Condition": "LessThanEqual",
+                "Property": "Height",
+                "Value": max_height.to_string(),
+                "IsRequired": false,
+            }],
+        }]),
+        None => json!([]),
+    };
+
+    let mut payload = json!({
+        "Name": profile.name,
+        "DirectPlayProfiles": [
+            video,
+            {"Type": "Audio"},
+        ],
+        "TranscodingProfiles": [{
+            "Container": "ts",
+            "Type": "Video",
+            "VideoCodec": profile.transcode_video_codecs.join(","),
+            "AudioCodec": profile.transcode_audio_codecs.join(","),
+            "Protocol": "hls",
+            "Context": "Streaming",
+            "MaxAudioChannels": profile.max_audio_channels.to_string(),
+        }],
+        "CodecProfiles": codec_profiles,
+        "SubtitleProfiles": [
+            {"Format": "srt", "Method": "Embed"},
+            {"Format": "ass", "Method": "Embed"},
+            {"Format": "ssa", "Method": "Embed"},
+            {"Format": "pgssub", "Method": "Embed"},
+            {"Format": "srt", "Method": "External"},
+            {"Format": "ass", "Method": "External"},
+            {"Format": "ssa", "Method": "External"},
+            {"Format": "vtt", "Method": "External"},
+        ],
+    });
+    if let Some(max_bitrate) = profile.max_bitrate {
+        payload["MaxStreamingBitrate"] = json!(max_bitrate);
+        payload["MaxStaticBitrate"] = json!(max_bitrate);
+    }
+    payload
+}
+
+#[derive(Debug, PartialEq)]
+enum SelectedSource {
+    /// The original file can be played directly.
+    Direct { media_source_id: String },
+    /// The server remuxes or transcodes the media.
+    Stream {
+        media_source_id: String,
+        play_method: PlayMethod,
+        transcoding_url: String,
+    },
+}
+
+#[derive(Debug, Default, PartialEq)]
+/// The indices of the streams within the media source, in the order the player
+/// numbers its tracks.
+struct StreamIndices {
+    audio: Vec<u32>,
+    subtitle: Vec<u32>,
+    /// The URLs of the external subtitle files the player loads, their streams
+    /// follow the embedded ones in `subtitle`.
+    subtitle_files: Vec<String>,
+}
+
+impl StreamIndices {
+    /// Returns the indices of the streams embedded in the original file.
+    fn embedded(source: &MediaSource) -> Self {
+        let mut indices = Self::default();
+        for stream in source.media_streams.iter().filter(|s| !s.is_external) {
+            match stream.kind.as_str() {
+                "Audio" => indices.audio.push(stream.index),
+                "Subtitle" => indices.subtitle.push(stream.index),
+                _ => {},
+            }
+        }
+        indices.with_external_subtitles(source)
+    }
+
+    /// Returns the indices of the streams a transcode includes, which is only the
+    /// selected or default audio stream, embedded subtitles are burned in if
+    /// required.
+    fn transcoded(source: &MediaSource, tracks: Option<TrackSelection>) -> Self {
+        let audio = tracks
+            .and_then(|tracks| tracks.audio_stream_index)
+            .or(source.default_audio_stream_index);
+        let indices = Self {
+            audio: audio.into_iter().collect(),
+            ..Self::default()
+        };
+        indices.with_external_subtitles(source)
+    }
+
+    /// Add the external subtitles the server delivers as separate files, i.e.
+    /// downloaded subtitles or sidecar files.
+    fn with_external_subtitles(mut self, source: &MediaSource) -> Self {
+        let external = source
+            .media_streams
+            .iter()
+            .filter(|stream| stream.is_external && stream.kind == "Subtitle");
+        for stream in external {
+            if let Some(delivery_url) = stream.delivery_url.as_ref() {
+                self.subtitle.push(stream.index);
+                self.subtitle_files.push(delivery_url.clone());
+            }
+        }
+        self
+    }
+}
+
+/// Returns the embedded audio streams and the subtitle streams which can be
+/// selected, subtitles may be embedded or stored in external files.
+fn item_tracks(
+    series_id: Option<String>,
+    media_path: Option<String>,
+    streams: &[MediaStream],
+) -> ItemTracks {
+    let mut tracks = ItemTracks {
+        series_id,
+        media_path: media_path.map(PathBuf::from),
+        ..ItemTracks::default()
+    };
+    let selectable = streams
+        .iter()
+        .filter(|stream| !stream.is_external || stream.kind == "Subtitle");
+    for stream in selectable {
+        let track = MediaTrack {
+            index: stream.index,
+            language: stream.language.clone(),
+            title: stream
+                .display_title
+                .clone()
+                .or_else(|| stream.language.clone())
+                .unwrap_or_else(|| format!("Track {}", stream.index)),
+            is_default: stream.is_default,
+            is_forced: stream.is_forced,
+        };
+        match stream.kind.as_str() {
+            "Audio" => tracks.audio.push(track),
+            "Subtitle" => tracks.subtitles.push(track),
+            _ => {},
+        }
+    }
+    tracks
+}
+
+/// Select the media source to play, preferring direct play.
+fn select_source(
+    item_id: &ItemId,
+    body: PlaybackInfoBody,
+    tracks: Option<TrackSelection>,
+) -> Result<(SelectedSource, StreamIndices), BackendError> {
+    if let Some(error_code) = body.error_code {
+        return Err(BackendError::NotPlayable { reason: error_code });
+    }
+
+    let Some(source) = body.media_sources.into_iter().next() else {
+        return Err(BackendError::NotPlayable {
+            reason: format!("no media sources for item {item_id}"),
+        });
+    };
+
+    if source.supports_direct_play {
+        let streams = StreamIndices::embedded(&source);
+        let selected = SelectedSource::Direct {
+            media_source_id: source.id,
+        };
+        return Ok((selected, streams));
+    }
+
+    let streams = StreamIndices::transcoded(&source, tracks);
+    match source.transcoding_url {
+        Some(transcoding_url) => {
+            let play_method = if source.supports_direct_stream {
+                PlayMethod::DirectStream
+            } else {
+                PlayMethod::Transcode
+            };
+            let selected = SelectedSource::Stream {
+                media_source_id: source.id,
+                play_method,
+                transcoding_url,
+            };
+            Ok((selected, streams))
+        },
+        None => Err(BackendError::NotPlayable {
+            reason: "server cannot direct play or transcode the item".to_string(),
+        }),
+    }
+}
+
+/// Returns the bitrate in bits per second of a download.
+fn bitrate_of(bytes: usize, elapsed: Duration) -> u64 {
+    let seconds = elapsed.as_secs_f64().max(0.001);
+    (bytes as f64 * 8.0 / seconds) as u64
+}
+
+/// Convert a duration to Jellyfin ticks, which are 100ns.
+pub(super) fn duration_to_ticks(duration: Duration) -> u64 {
+    (duration.as_nanos() / 100) as u64
+}
+
+/// Convert Jellyfin ticks, which are 100ns, to a duration.
+pub(super) fn ticks_to_duration(ticks: u64) -> Duration {
+    Duration::from_nanos(ticks.saturating_mul(100))
+}
+
+fn play_method_str(play_method: PlayMethod) -> &'static str {
+    match play_method {
+        PlayMethod::DirectPlay => "DirectPlay",
+        PlayMethod::DirectStream => "DirectStream",
+        PlayMethod::Transcode => "Transcode",
+    }
+}
+
+#[derive(serde_derive::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct PlaybackInfoBody {
+    #[serde(default)]
+    media_sources: Vec<MediaSource>,
+    play_session_id: Option<String>,
+    error_code: Option<String>,
+}
+
+#[derive(serde_derive::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct MediaSource {
+    id: String,
+    #[serde(default)]
+    supports_direct_play: bool,
+    #[serde(default)]
+    supports_direct_stream: bool,
+    transcoding_url: Option<String>,
+    #[serde(default)]
+    media_streams: Vec<MediaStream>,
+    default_audio_stream_index: Option<u32>,
+}
+
+#[derive(serde_derive::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub(super) struct MediaStream {
+    index: u32,
+    #[serde(rename = "Type")]
+    kind: String,
+    #[serde(default)]
+    is_external: bool,
+    language: Option<String>,
+    display_title: Option<String>,
+    #[serde(default)]
+    is_default: bool,
+    #[serde(default)]
+    is_forced: bool,
+    /// Where the server delivers an external stream from, if it is not embedded.
+    delivery_url: Option<String>,
+}
+
+#[derive(serde_derive::Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct PlaybackProgressInfo<'a> {
+    item_id: &'a str,
+    media_source_id: &'a str,
+    play_session_id: Option<&'a str>,
+    position_ticks: u64,
+    is_paused: bool,
+    play_method: &'static str,
+    audio_stream_index: Option<u32>,
+    subtitle_stream_index: i64,
+    can_seek: bool,
+}
+
+impl<'a> From<&'a PlaybackReport> for PlaybackProgressInfo<'a> {
+    fn from(report: &'a PlaybackReport) -> Self {
+        Self {
+            item_id: &report.item_id,
+            media_source_id: &report.media_source_id,
+            play_session_id: report.play_session_id.as_deref(),
+            position_ticks: duration_to_ticks(report.position),
+            is_paused: report.paused,
+            play_method: play_method_str(report.play_method),
+            audio_stream_index: report.audio_stream_index,
+            // Jellyfin uses `-1` for subtitles which are turned off.
+            subtitle_stream_index: report.subtitle_stream_index.map_or(-1, i64::from),
+            can_seek: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::profile::Quality;
+
+    fn parse(body: &str) -> PlaybackInfoBody {
+        serde_json::from_str(body).unwrap()
+    }
+
+    #[test]
+    fn test_select_direct_play() {
+        let body = parse(
+            r#"{
+                "MediaSources": [{
+                    "Id": "source-1",
+                    "SupportsDirectPlay": true,
+                    "SupportsDirectStream": true,
+                    "TranscodingUrl": "/videos/abc/master.m3u8"
+                }],
+                "PlaySessionId": "session-1"
+            }"#,
+        );
+
+        let (selected, _) = select_source(&"abc".to_string(), body, None).unwrap();
+        assert_eq!(
+            selected,
+            SelectedSource::Direct {
+                media_source_id: "source-1".into()
+            },
+        );
+    }
+
+    #[test]
+    fn test_select_transcode() {
+        let body = parse(
+            r#"{
+                "MediaSources": [{
+                    "Id": "source-1",
+                    "SupportsDirectPlay": false,
+                    "SupportsDirectStream": false,
+                    "TranscodingUrl": "/videos/abc/master.m3u8?ApiKey=token"
+                }]
+            }"#,
+        );
+
+        let (selected, _) = select_source(&"abc".to_string(), body, None).unwrap();
+        assert_eq!(
+            selected,
+            SelectedSource::Stream {
+                media_source_id: "source-1".into(),
+                play_method: PlayMethod::Transcode,
+                transcoding_url: "/videos/abc/master.m3u8?ApiKey=token".into(),
+            },
+        );
+    }
+
+    #[test]
+    fn test_select_not_playable() {
+        let body = parse(r#"{"MediaSources": [], "ErrorCode": "NoCompatibleStream"}"#);
+        let err = select_source(&"abc".to_string(), body, None).unwrap_err();
+        assert!(
+            matches!(err, BackendError::NotPlayable { reason } if reason == "NoCompatibleStream")
+        );
+
+        let body = parse(r#"{"MediaSources": [{"Id": "source-1"}]}"#);
+        let err = select_source(&"abc".to_string(), body, None).unwrap_err();
+        assert!(matches!(err, BackendError::NotPlayable { .. }));
+    }
+
+    #[test]
+    fn test_progress_payload() {
+        let report = PlaybackReport {
+            item_id: "abc".into(),
+            media_source_id: "source-1".into(),
+            play_session_id: Some("session-1".into()),
+            play_method: PlayMethod::DirectPlay,
+            position: Duration::from_millis(1500),
+            paused: true,
+            audio_stream_index: Some(1),
+            subtitle_stream_index: None,
+        };
+
+        let payload = serde_json::to_value(PlaybackProgressInfo::from(&report)).unwrap();
+        assert_eq!(payload["PositionTicks"], 15_000_000);
+        assert_eq!(payload["PlayMethod"], "DirectPlay");
+        assert_eq!(payload["IsPaused"], true);
+        assert_eq!(payload["PlaySessionId"], "session-1");
+        assert_eq!(payload["AudioStreamIndex"], 1);
+        assert_eq!(payload["SubtitleStreamIndex"], -1);
+    }
+
+    #[test]
+    fn test_direct_play_stream_indices() {
+        let body = parse(
+            r#"{
+                "MediaSources": [{
+                    "Id": "source-1",
+                    "SupportsDirectPlay": true,
+                    "DefaultAudioStreamIndex": 1,
+                    "MediaStreams": [
+                        {"Index": 0, "Type": "Video"},
+                        {"Index": 1, "Type": "Audio"},
+                        {"Index": 2, "Type": "Audio"},
+                        {"Index": 3, "Type": "Subtitle"},
+                        {"Index": 4, "Type": "Subtitle", "IsExternal": true},
+                        {
+                            "Index": 5,
+                            "Type": "Subtitle",
+                            "IsExternal": true,
+                            "DeliveryUrl": "/Videos/abc/source-1/Subtitles/5/0/Stream.srt"
+                        }
+                    ]
+                }]
+            }"#,
+        );
+
+        let (_, streams) = select_source(&"abc".to_string(), body, None).unwrap();
+        assert_eq!(
+            streams,
+            StreamIndices {
+                audio: vec![1, 2],
+                subtitle: vec![3, 5],
+                subtitle_files: vec![
+                    "/Videos/abc/source-1/Subtitles/5/0/Stream.srt".into()
+                ],
+            },
+        );
+    }
+
+    #[test]
+    fn test_transcode_selected_audio() {
+        let body = parse(
+            r#"{
+                "MediaSources": [{
+                    "Id": "source-1",
+                    "DefaultAudioStreamIndex": 1,
+                    "TranscodingUrl": "/videos/abc/master.m3u8"
+                }]
+            }"#,
+        );
+        let tracks = TrackSelection {
+            audio_stream_index: Some(2),
+            subtitle_stream_index: Some(3),
+        };
+
+        let (_, streams) =
+            select_source(&"abc".to_string(), body, Some(tracks)).unwrap();
+        assert_eq!(
+            streams,
+            StreamIndices {
+                audio: vec![2],
+                ..StreamIndices::default()
+            },
+        );
+    }
+
+    #[test]
+    fn test_device_profile() {
+        let profile = device_profile(&DeviceProfile::mpv());
+        assert_eq!(profile["DirectPlayProfiles"][0], json!({"Type": "Video"}));
+        assert_eq!(profile["CodecProfiles"], json!([]));
+        assert!(profile.get("MaxStreamingBitrate").is_none());
+
+        let profile = device_profile(&DeviceProfile::mpv().with_quality(Quality::Mbps8));
+        assert_eq!(profile["MaxStreamingBitrate"], 8_000_000);
+        assert_eq!(profile["MaxStaticBitrate"], 8_000_000);
+        let condition = &profile["CodecProfiles"][0]["Conditions"][0];
+        assert_eq!(condition["Property"], "Height");
+        assert_eq!(condition["Value"], "720");
+    }
+
+    #[test]
+    fn test_bitrate_of() {
+        assert_eq!(bitrate_of(1_000_000, Duration::from_secs(2)), 4_000_000);
+        assert_eq!(bitrate_of(1_000, Duration::ZERO), 8_000_000);
+    }
+
+    #[test]
+    fn test_item_tracks() {
+        let streams: Vec<MediaStream> = serde_json::from_str(
+            r#"[
+                {"Index": 0, "Type": "Video"},
+                {"Index": 1, "Type": "Audio", "Language": "jpn", "DisplayTitle": "Japanese - AAC - Stereo", "IsDefault": true},
+                {"Index": 2, "Type": "Audio"},
+                {"Index": 3, "Type": "Subtitle", "Language": "eng", "DisplayTitle": "English - Forced", "IsForced": true},
+                {"Index": 4, "Type": "Subtitle", "Language": "eng", "IsExternal": true},
+                {"Index": 5, "Type": "Audio", "IsExternal": true}
+            ]"#,
+        )
+        .unwrap();
+
+        let tracks = item_tracks(
+            Some("series-1".into()),
+            Some("/media/show/episode.mkv".into()),
+            &streams,
+        );
+        assert_eq!(tracks.series_id.as_deref(), Some("series-1"));
+        assert_eq!(
+            tracks.media_path,
+            Some(PathBuf::from("/media/show/episode.mkv")),
+        );
+        assert_eq!(tracks.audio.len(), 2);
+        assert_eq!(tracks.audio[0].title, "Japanese - AAC - Stereo");
+        assert!(tracks.audio[0].is_default);
+        assert_eq!(tracks.audio[1].title, "Track 2");
+        assert_eq!(
+            tracks.subtitles,
+            vec![
+                MediaTrack {
+                    index: 3,
+                    language: Some("eng".into()),
+                    title: "English - Forced".into(),
+                    is_default: false,
+                    is_forced: true,
+                },
+                MediaTrack {
+                    index: 4,
+                    language: Some("eng".into()),
+                    title: "eng".into(),
+                    is_default: false,
+                    is_forced: false,
+                },
+            ],
+        );
+    }
+}
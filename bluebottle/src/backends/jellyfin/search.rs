@@ -0,0 +1,102 @@
+use super::{Jellyfin, send_json};
+use crate::backends::BackendError;
+use crate::models::media::ItemSummary;
+
+static SEARCH_HINTS_ENDPOINT: &str = "/Search/Hints";
+
+/// The item types included in search results.
+static SEARCH_ITEM_TYPES: &str =
+    "Movie,BoxSet,Series,Episode,Person,MusicAlbum,MusicArtist,Audio";
+
+impl Jellyfin {
+    pub(super) async fn fetch_search(
+        &self,
+        term: String,
+        limit: usize,
+    ) -> Result<Vec<ItemSummary>, BackendError> {
+        let params = [
+            ("SearchTerm", term),
+            ("Limit", limit.to_string()),
+            ("IncludeItemTypes", SEARCH_ITEM_TYPES.to_string()),
+            ("IncludePeople", "true".to_string()),
+            ("IncludeMedia", "true".to_string()),
+            ("IncludeArtists", "true".to_string()),
+        ];
+
+        let request = self.client.get(SEARCH_HINTS_ENDPOINT).query(&params);
+        let payload: SearchHintsBody = send_json(request).await?;
+
+        let items = payload
+            .search_hints
+            .into_iter()
+            .filter_map(SearchHint::into_summary)
+            .collect();
+
+        Ok(items)
+    }
+}
+
+#[derive(serde_derive::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SearchHintsBody {
+    #[serde(default)]
+    search_hints: Vec<SearchHint>,
+}
+
+#[derive(serde_derive::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SearchHint {
+    id: Option<String>,
+    /// Older servers only return the deprecated `ItemId` field.
+    item_id: Option<String>,
+    #[serde(default)]
+    name: String,
+    #[serde(rename = "Type", default)]
+    kind: String,
+    production_year: Option<u32>,
+    blur_hash: Option<String>,
+}
+
+impl SearchHint {
+    fn into_summary(self) -> Option<ItemSummary> {
+        Some(ItemSummary {
+            id: self.id.or(self.item_id)?,
+            kind: super::items::item_kind(&self.kind),
+            name: self.name,
+            production_year: self.production_year,
+            blurhash: self.blur_hash,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::media::ItemKind;
+
+    #[test]
+    fn test_parse_search_hints() {
+        let body = r#"{
+            "SearchHints": [
+                {"Id": "a1", "ItemId": "a1", "Name": "Alien", "Type": "Movie", "ProductionYear": 1979},
+                {"ItemId": "b2", "Name": "Sigourney Weaver", "Type": "Person"},
+                {"Name": "Missing Id", "Type": "Movie"}
+            ],
+            "TotalRecordCount": 3
+        }"#;
+
+        let payload: SearchHintsBody = serde_json::from_str(body).unwrap();
+        let items: Vec<_> = payload
+            .search_hints
+            .into_iter()
+            .filter_map(SearchHint::into_summary)
+            .collect();
+
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].id, "a1");
+        assert_eq!(items[0].kind, ItemKind::Movie);
+        assert_eq!(items[0].production_year, Some(1979));
+        assert_eq!(items[1].id, "b2");
+        assert_eq!(items[1].kind, ItemKind::Person);
+    }
+}
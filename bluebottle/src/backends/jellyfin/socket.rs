@@ -0,0 +1,383 @@
+//! The Jellyfin `/socket` WebSocket, pushing changes made on other devices and
+//! remote control commands.
+
+use std::time::Duration;
+
+use iced::futures::{SinkExt, StreamExt};
+use serde_json::Value;
+use snafu::ResultExt;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use tokio_tungstenite::tungstenite::Message;
+
+use super::Jellyfin;
+use super::items::UserData as UserDataBody;
+use super::playback::ticks_to_duration;
+use crate::backends::BackendError;
+use crate::backends::error::WebSocketSnafu;
+use crate::models::interaction::UserData;
+use crate::models::live::{LiveEvent, RemoteCommand};
+
+static SOCKET_ENDPOINT: &str = "/socket";
+static KEEP_ALIVE_MESSAGE: &str = r#"{"MessageType":"KeepAlive"}"#;
+/// How often to send keep alive messages until the server says otherwise.
+const DEFAULT_KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(30);
+
+impl Jellyfin {
+    pub(super) async fn run_socket(
+        &self,
+        output: mpsc::Sender<LiveEvent>,
+    ) -> Result<(), BackendError> {
+        let mut url = self.client.url(SOCKET_ENDPOINT);
+        let scheme = if url.scheme() == "https" { "wss" } else { "ws" };
+        url.set_scheme(scheme).expect("ws scheme should be valid");
+        url.query_pairs_mut()
+            .append_pair("api_key", &self.access_token)
+            .append_pair("deviceId", &self.device_id);
+
+        let (mut socket, _) = tokio_tungstenite::connect_async(url.as_str())
+            .await
+            .context(WebSocketSnafu)?;
+        tracing::info!("connected to server socket");
+
+        let mut keep_alive_interval = DEFAULT_KEEP_ALIVE_INTERVAL;
+        let mut next_keep_alive = Instant::now() + keep_alive_interval;
+
+        loop {
+            let message =
+                match tokio::time::timeout_at(next_keep_alive, socket.next()).await {
+                    Err(_elapsed) => {
+                        socket
+                            .send(Message::text(KEEP_ALIVE_MESSAGE))
+                            .await
+                            .context(WebSocketSnafu)?;
+                        next_keep_alive = Instant::now() + keep_alive_interval;
+                        continue;
+                    },
+                    Ok(None) => return Ok(()),
+                    Ok(Some(message)) => message.context(WebSocketSnafu)?,
+                };
+
+            let text = match message {
+                Message::Text(text) => text,
+                Message::Close(_) => return Ok(()),
+                // Pings are answered by the socket itself.
+                _ => continue,
+            };
+
+            match parse_message(&text) {
+                Some(SocketMessage::ForceKeepAlive(timeout)) => {
+                    // Send keep alives twice per timeout so one can be late.
+                    keep_alive_interval = (timeout / 2).max(Duration::from_secs(1));
+                    next_keep_alive = Instant::now();
+                },
+                Some(SocketMessage::Event(event)) => {
+                    // Nobody is listening for events any more.
+                    if let Err(_closed) = output.send(event).await {
+                        return Ok(());
+                    }
+                },
+                None => {},
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum SocketMessage {
+    /// The server closes the socket if no message is sent within the timeout.
+    ForceKeepAlive(Duration),
+    Event(LiveEvent),
+}
+
+/// Parse a message from the server, returning `None` for messages which are not
+/// handled.
+fn parse_message(text: &str) -> Option<SocketMessage> {
+    let message: Value = serde_json::from_str(text).ok()?;
+    let data = message.get("Data").unwrap_or(&Value::Null);
+
+    let event = match message.get("MessageType")?.as_str()? {
+        "ForceKeepAlive" => {
+            let secs = data.as_u64()?;
+            return Some(SocketMessage::ForceKeepAlive(Duration::from_secs(secs)));
+        },
+        "UserDataChanged" => {
+            let body: UserDataChangedBody = serde_json::from_value(data.clone()).ok()?;
+            let items = body
+                .user_data_list
+                .into_iter()
+                .map(|entry| (entry.item_id, UserData::from(entry.user_data)))
+                .collect();
+            LiveEvent::UserDataChanged(items)
+        },
+        "LibraryChanged" => {
+            let body: LibraryChangedBody = serde_json::from_value(data.clone()).ok()?;
+            LiveEvent::LibraryChanged {
+                added: body.items_added,
+                updated: body.items_updated,
+                removed: body.items_removed,
+            }
+        },
+        "Play" => {
+            let body: PlayBody = serde_json::from_value(data.clone()).ok()?;
+            LiveEvent::Remote(RemoteCommand::Play {
+                item_ids: body.item_ids,
+                start_position: ticks_to_duration(body.start_position_ticks),
+            })
+        },
+        "Playstate" => {
+            let body: PlaystateBody = serde_json::from_value(data.clone()).ok()?;
+            let command = match body.command.as_str() {
+                "Pause" => RemoteCommand::Pause,
+                "Unpause" => RemoteCommand::Unpause,
+                "PlayPause" => RemoteCommand::PlayPause,
+                "Stop" => RemoteCommand::Stop,
+                "Seek" => {
+                    RemoteCommand::Seek(ticks_to_duration(body.seek_position_ticks))
+                },
+                _ => return None,
+            };
+            LiveEvent::Remote(command)
+        },
+        "GeneralCommand" => {
+            let body: GeneralCommandBody = serde_json::from_value(data.clone()).ok()?;
+            LiveEvent::Remote(parse_general_command(body)?)
+        },
+        _ => return None,
+    };
+
+    Some(SocketMessage::Event(event))
+}
+
+fn parse_general_command(body: GeneralCommandBody) -> Option<RemoteCommand> {
+    // Arguments are always sent as strings.
+    let argument = |name: &str| body.arguments.get(name).and_then(Value::as_str);
+
+    let command = match body.name.as_str() {
+        "SetAudioStreamIndex" => {
+            RemoteCommand::SetAudioStreamIndex(argument("Index")?.parse().ok()?)
+        },
+        "SetSubtitleStreamIndex" => {
+            // `-1` turns subtitles off.
+            let index: i64 = argument("Index")?.parse().ok()?;
+            RemoteCommand::SetSubtitleStreamIndex(u32::try_from(index).ok())
+        },
+        "SetVolume" => {
+            let volume: u32 = argument("Volume")?.parse().ok()?;
+            RemoteCommand::SetVolume(volume.min(100) as u8)
+        },
+        "VolumeUp" => RemoteCommand::VolumeUp,
+        "VolumeDown" => RemoteCommand::VolumeDown,
+        "Mute" => RemoteCommand::Mute,
+        "Unmute" => RemoteCommand::Unmute,
+        "ToggleMute" => RemoteCommand::ToggleMute,
+        "DisplayMessage" => RemoteCommand::DisplayMessage {
+            header: argument("Header").unwrap_or_default().to_string(),
+            text: argument("Text").unwrap_or_default().to_string(),
+        },
+        _ => return None,
+    };
+
+    Some(command)
+}
+
+#[derive(serde_derive::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct UserDataChangedBody {
+    #[serde(default)]
+    user_data_list: Vec<UserDataEntry>,
+}
+
+#[derive(serde_derive::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct UserDataEntry {
+    item_id: String,
+    #[serde(flatten)]
+    user_data: UserDataBody,
+}
+
+#[derive(serde_derive::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct LibraryChangedBody {
+    #[serde(default)]
+    items_added: Vec<String>,
+    #[serde(default)]
+    items_updated: Vec<String>,
+    #[serde(default)]
+    items_removed: Vec<String>,
+}
+
+#[derive(serde_derive::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct PlayBody {
+    #[serde(default)]
+    item_ids: Vec<String>,
+    #[serde(default)]
+    start_position_ticks: u64,
+}
+
+#[derive(serde_derive::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct PlaystateBody {
+    command: String,
+    #[serde(default)]
+    seek_position_ticks: u64,
+}
+
+#[derive(serde_derive::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct GeneralCommandBody {
+    name: String,
+    #[serde(default)]
+    arguments: serde_json::Map<String, Value>,
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::backends::{BackendId, BackendInit};
+
+    #[test]
+    fn test_parse_messages() {
+        let message = parse_message(r#"{"MessageType": "ForceKeepAlive", "Data": 60}"#);
+        assert_eq!(
+            message,
+            Some(SocketMessage::ForceKeepAlive(Duration::from_secs(60)))
+        );
+
+        let message = parse_message(
+            r#"{
+                "MessageType": "UserDataChanged",
+                "Data": {
+                    "UserId": "user",
+                    "UserDataList": [{
+                        "ItemId": "abc",
+                        "PlaybackPositionTicks": 600000000,
+                        "Played": false,
+                        "IsFavorite": true,
+                        "LastPlayedDate": "2024-01-02T03:04:05.1234567Z"
+                    }]
+                }
+            }"#,
+        );
+        let user_data = UserData {
+            played: false,
+            position: Duration::from_secs(60),
+            favourite: true,
+            rating: None,
+            last_played_at: Some(1_704_164_645_123),
+        };
+        assert_eq!(
+            message,
+            Some(SocketMessage::Event(LiveEvent::UserDataChanged(vec![(
+                "abc".into(),
+                user_data
+            )]))),
+        );
+
+        let message = parse_message(
+            r#"{"MessageType": "LibraryChanged", "Data": {"ItemsAdded": ["abc"], "ItemsRemoved": ["def"]}}"#,
+        );
+        assert_eq!(
+            message,
+            Some(SocketMessage::Event(LiveEvent::LibraryChanged {
+                added: vec!["abc".into()],
+                updated: Vec::new(),
+                removed: vec!["def".into()],
+            })),
+        );
+
+        assert_eq!(parse_message(r#"{"MessageType": "KeepAlive"}"#), None);
+        assert_eq!(parse_message("not json"), None);
+    }
+
+    #[test]
+    fn test_parse_remote_commands() {
+        let remote = |text: &str| match parse_message(text) {
+            Some(SocketMessage::Event(LiveEvent::Remote(command))) => Some(command),
+            _ => None,
+        };
+
+        assert_eq!(
+            remote(
+                r#"{"MessageType": "Play", "Data": {"ItemIds": ["abc"], "StartPositionTicks": 50000000, "PlayCommand": "PlayNow"}}"#
+            ),
+            Some(RemoteCommand::Play {
+                item_ids: vec!["abc".into()],
+                start_position: Duration::from_secs(5),
+            }),
+        );
+        assert_eq!(
+            remote(
+                r#"{"MessageType": "Playstate", "Data": {"Command": "Seek", "SeekPositionTicks": 900000000}}"#
+            ),
+            Some(RemoteCommand::Seek(Duration::from_secs(90))),
+        );
+        assert_eq!(
+            remote(r#"{"MessageType": "Playstate", "Data": {"Command": "Pause"}}"#),
+            Some(RemoteCommand::Pause),
+        );
+        assert_eq!(
+            remote(
+                r#"{"MessageType": "GeneralCommand", "Data": {"Name": "SetSubtitleStreamIndex", "Arguments": {"Index": "-1"}}}"#
+            ),
+            Some(RemoteCommand::SetSubtitleStreamIndex(None)),
+        );
+        assert_eq!(
+            remote(
+                r#"{"MessageType": "GeneralCommand", "Data": {"Name": "SetAudioStreamIndex", "Arguments": {"Index": "2"}}}"#
+            ),
+            Some(RemoteCommand::SetAudioStreamIndex(2)),
+        );
+        assert_eq!(
+            remote(r#"{"MessageType": "GeneralCommand", "Data": {"Name": "GoHome"}}"#),
+            None,
+        );
+    }
+
+    #[tokio::test]
+    async fn test_socket_against_local_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        // A stand-in for the Jellyfin server, which expects a keep alive before
+        // pushing an update and closing the socket.
+        let server = async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+
+            let force_keep_alive = r#"{"MessageType": "ForceKeepAlive", "Data": 2}"#;
+            socket.send(Message::text(force_keep_alive)).await.unwrap();
+
+            let message = socket.next().await.unwrap().unwrap();
+            assert_eq!(message.to_text().unwrap(), KEEP_ALIVE_MESSAGE);
+
+            let pause = r#"{"MessageType": "Playstate", "Data": {"Command": "Pause"}}"#;
+            socket.send(Message::text(pause)).await.unwrap();
+            socket.close(None).await.unwrap();
+        };
+
+        let backend = Jellyfin::from_context(
+            BackendId::nil(),
+            json!({
+                "server_url": format!("http://{address}"),
+                "access_token": "token",
+                "device_id": "device",
+            }),
+        )
+        .unwrap();
+
+        let (tx, mut rx) = mpsc::channel(8);
+        let (result, _) = iced::futures::join!(backend.run_socket(tx), server);
+        result.unwrap();
+
+        assert_eq!(
+            rx.recv().await,
+            Some(LiveEvent::Remote(RemoteCommand::Pause))
+        );
+        assert_eq!(rx.recv().await, None);
+    }
+}
@@ -0,0 +1,137 @@
+use snafu::ResultExt;
+
+use super::{Jellyfin, send_empty, send_json};
+use crate::backends::BackendError;
+use crate::backends::error::ConnectionSnafu;
+use crate::models::media::ItemId;
+use crate::models::subtitles::RemoteSubtitle;
+
+impl Jellyfin {
+    pub(super) async fn search_remote_subtitles(
+        &self,
+        item_id: ItemId,
+        language: String,
+    ) -> Result<Vec<RemoteSubtitle>, BackendError> {
+        let endpoint = format!("/Items/{item_id}/RemoteSearch/Subtitles/{language}");
+        let body: Vec<RemoteSubtitleInfo> =
+            send_json(self.client.get(&endpoint)).await?;
+        Ok(body.into_iter().map(RemoteSubtitle::from).collect())
+    }
+
+    pub(super) async fn download_remote_subtitle(
+        &self,
+        item_id: ItemId,
+        subtitle_id: String,
+    ) -> Result<(), BackendError> {
+        let endpoint = format!("/Items/{item_id}/RemoteSearch/Subtitles/{subtitle_id}");
+        send_empty(self.client.post(&endpoint)).await
+    }
+
+    pub(super) async fn fetch_remote_subtitle(
+        &self,
+        subtitle_id: String,
+    ) -> Result<Vec<u8>, BackendError> {
+        let endpoint = format!("/Providers/Subtitles/Subtitles/{subtitle_id}");
+
+        let response = self
+            .client
+            .get(&endpoint)
+            .send()
+            .await
+            .context(ConnectionSnafu)?
+            .error_for_status()?;
+        let data = response.bytes().await.context(ConnectionSnafu)?;
+
+        Ok(data.to_vec())
+    }
+
+    pub(super) async fn send_refresh(
+        &self,
+        item_id: ItemId,
+    ) -> Result<(), BackendError> {
+        let endpoint = format!("/Items/{item_id}/Refresh");
+        let params = [
+            ("metadataRefreshMode", "Default"),
+            ("imageRefreshMode", "None"),
+        ];
+        send_empty(self.client.post(&endpoint).query(&params)).await
+    }
+}
+
+#[derive(serde_derive::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct RemoteSubtitleInfo {
+    id: String,
+    name: Option<String>,
+    provider_name: Option<String>,
+    #[serde(rename = "ThreeLetterISOLanguageName")]
+    language: Option<String>,
+    format: Option<String>,
+    download_count: Option<u32>,
+    #[serde(default)]
+    is_hash_match: bool,
+    #[serde(default)]
+    forced: bool,
+    #[serde(default)]
+    hearing_impaired: bool,
+}
+
+impl From<RemoteSubtitleInfo> for RemoteSubtitle {
+    fn from(info: RemoteSubtitleInfo) -> Self {
+        Self {
+            name: info.name.unwrap_or_else(|| info.id.clone()),
+            id: info.id,
+            provider: info.provider_name,
+            language: info.language,
+            format: info.format.map(|format| format.to_ascii_lowercase()),
+            download_count: info.download_count,
+            is_hash_match: info.is_hash_match,
+            is_forced: info.forced,
+            is_hearing_impaired: info.hearing_impaired,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remote_subtitle() {
+        let body: Vec<RemoteSubtitleInfo> = serde_json::from_str(
+            r#"[
+                {
+                    "Id": "srt-eng-1",
+                    "Name": "Show.S01E01.1080p.WEB",
+                    "ProviderName": "Open Subtitles",
+                    "ThreeLetterISOLanguageName": "eng",
+                    "Format": "SRT",
+                    "DownloadCount": 120,
+                    "IsHashMatch": true,
+                    "HearingImpaired": true
+                },
+                {"Id": "srt-eng-2"}
+            ]"#,
+        )
+        .unwrap();
+
+        let subtitles: Vec<RemoteSubtitle> =
+            body.into_iter().map(RemoteSubtitle::from).collect();
+        assert_eq!(
+            subtitles[0],
+            RemoteSubtitle {
+                id: "srt-eng-1".into(),
+                name: "Show.S01E01.1080p.WEB".into(),
+                provider: Some("Open Subtitles".into()),
+                language: Some("eng".into()),
+                format: Some("srt".into()),
+                download_count: Some(120),
+                is_hash_match: true,
+                is_forced: false,
+                is_hearing_impaired: true,
+            },
+        );
+        assert_eq!(subtitles[1].name, "srt-eng-2");
+        assert!(subtitles[1].language.is_none());
+    }
+}
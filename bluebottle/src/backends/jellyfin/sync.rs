@@ -0,0 +1,274 @@
+use super::items::{BaseItem, ITEMS_ENDPOINT, ItemsBody};
+use super::{Jellyfin, send_json};
+use crate::backends::BackendError;
+use crate::models::media::ItemSummary;
+use crate::models::sync::{ItemMetadata, SyncPage, SyncRequest};
+
+/// The item types stored locally.
+static SYNC_ITEM_TYPES: &str =
+    "Movie,BoxSet,Series,Season,Episode,MusicAlbum,MusicArtist,Audio";
+/// The additional fields required to build [ItemMetadata].
+static SYNC_FIELDS: &str = "SortName,OriginalTitle,People,Genres,Studios,\
+    OfficialRating,DateCreated,DateLastSaved,PremiereDate,ParentId";
+/// The number of ticks within a second, Jellyfin ticks are 100ns.
+const TICKS_PER_SECOND: u64 = 10_000_000;
+
+impl Jellyfin {
+    pub(super) async fn fetch_sync_page(
+        &self,
+        request: SyncRequest,
+    ) -> Result<SyncPage, BackendError> {
+        let params = sync_query_params(&request);
+        let request = self.client.get(ITEMS_ENDPOINT).query(&params);
+        let payload: ItemsBody = send_json(request).await?;
+
+        Ok(SyncPage {
+            total_items: payload.total_record_count,
+            items: payload.items.into_iter().map(ItemMetadata::from).collect(),
+        })
+    }
+}
+
+fn sync_query_params(request: &SyncRequest) -> Vec<(&'static str, String)> {
+    let mut params = vec![
+        ("ParentId", request.library_id.clone()),
+        ("Recursive", "true".to_string()),
+        ("IncludeItemTypes", SYNC_ITEM_TYPES.to_string()),
+        ("Fields", SYNC_FIELDS.to_string()),
+        // Newly added items are created last, so sorting by creation date keeps
+        // the pages stable while the library changes during a sync.
+        ("SortBy", "DateCreated,SortName".to_string()),
+        ("SortOrder", "Ascending".to_string()),
+        ("StartIndex", request.start_index.to_string()),
+        ("Limit", request.limit.to_string()),
+        ("EnableTotalRecordCount", "true".to_string()),
+        // Only the BlurHash of the primary image is stored.
+        ("EnableImageTypes", "Primary".to_string()),
+        ("ImageTypeLimit", "1".to_string()),
+    ];
+
+    if let Some(changed_since) = request.changed_since.as_ref() {
+        params.push(("MinDateLastSaved", changed_since.clone()));
+    }
+
+    params
+}
+
+impl From<BaseItem> for ItemMetadata {
+    fn from(mut item: BaseItem) -> Self {
+        let blurhash = item.primary_blurhash();
+        let user_data = item.user_data.as_ref();
+        let played = user_data.is_some_and(|data| data.played);
+        let favourite = user_data.is_some_and(|data| data.is_favorite);
+        let playback_position_ticks = user_data
+            .map(|data| data.playback_position_ticks)
+            .unwrap_or(0);
+        let last_played_at = user_data
+            .and_then(|data| data.last_played_date.as_deref())
+            .and_then(parse_timestamp_millis);
+
+        let original_name = item
+            .original_title
+            .filter(|title| !title.is_empty() && *title != item.name);
+
+        ItemMetadata {
+            sort_name: item.sort_name.unwrap_or_else(|| item.name.to_lowercase()),
+            original_name,
+            parent_id: item.parent_id,
+            series_id: item.series_id,
+            season_number: item.parent_index_number,
+            episode_number: item.index_number,
+            people: item.people.into_iter().map(|person| person.name).collect(),
+            genres: item.genres,
+            studios: item.studios.into_iter().map(|studio| studio.name).collect(),
+            official_rating: item.official_rating,
+            community_rating: item.community_rating,
+            runtime_secs: item.run_time_ticks.map(|ticks| ticks / TICKS_PER_SECOND),
+            premiere_date: item.premiere_date,
+            date_added: item.date_created,
+            date_last_saved: item.date_last_saved,
+            played,
+            playback_position_secs: playback_position_ticks / TICKS_PER_SECOND,
+            last_played_at,
+            favourite,
+            summary: ItemSummary {
+                blurhash,
+                kind: super::items::item_kind(&item.kind),
+                id: item.id,
+                name: item.name,
+                production_year: item.production_year,
+            },
+        }
+    }
+}
+
+/// Parse a Jellyfin UTC timestamp, i.e. `2024-01-02T03:04:05.1234567Z`, into
+/// milliseconds since the unix epoch.
+pub(super) fn parse_timestamp_millis(timestamp: &str) -> Option<i64> {
+    let timestamp = timestamp
+        .strip_suffix('Z')
+        .or_else(|| timestamp.strip_suffix("+00:00"))
+        .unwrap_or(timestamp);
+    let (date, time) = timestamp.split_once('T')?;
+
+    let mut date_parts = date.splitn(3, '-').map(str::parse::<i64>);
+    let year = date_parts.next()?.ok()?;
+    let month = date_parts.next()?.ok()?;
+    let day = date_parts.next()?.ok()?;
+
+    let (time, fraction) = time.split_once('.').unwrap_or((time, ""));
+    let mut time_parts = time.splitn(3, ':').map(str::parse::<i64>);
+    let hours = time_parts.next()?.ok()?;
+    let minutes = time_parts.next()?.ok()?;
+    let seconds = time_parts.next()?.ok()?;
+    let millis = fraction
+        .chars()
+        .chain(std::iter::repeat('0'))
+        .take(3)
+        .collect::<String>()
+        .parse::<i64>()
+        .ok()?;
+
+    // Days since the epoch of a proleptic Gregorian date.
+    // See <https://howardhinnant.github.io/date_algorithms.html#days_from_civil>.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era =
+        year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+
+    let seconds = days * 86400 + hours * 3600 + minutes * 60 + seconds;
+    Some(seconds * 1000 + millis)
+}
+
+/// Format milliseconds since the unix epoch as a Jellyfin UTC timestamp.
+pub(super) fn format_timestamp_millis(timestamp: i64) -> String {
+    let millis = timestamp.rem_euclid(1000);
+    let seconds = timestamp.div_euclid(1000);
+    let days = seconds.div_euclid(86400);
+    let seconds = seconds.rem_euclid(86400);
+
+    // The proleptic Gregorian date of the days since the epoch.
+    // See <https://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524
+        - day_of_era / 146096)
+        / 365;
+    let day_of_year =
+        day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{millis:03}Z",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::media::ItemKind;
+
+    #[test]
+    fn test_parse_sync_item() {
+        let body = r#"{
+            "Items": [{
+                "Id": "abc",
+                "Name": "Spirited Away",
+                "OriginalTitle": "千と千尋の神隠し",
+                "SortName": "spirited away",
+                "Type": "Movie",
+                "ProductionYear": 2001,
+                "ParentId": "lib",
+                "People": [{"Name": "Rumi Hiiragi", "Type": "Actor"}],
+                "Genres": ["Animation", "Fantasy"],
+                "Studios": [{"Name": "Studio Ghibli", "Id": "1"}],
+                "CommunityRating": 8.5,
+                "RunTimeTicks": 74850000000,
+                "DateLastSaved": "2024-01-02T03:04:05.0000000Z",
+                "UserData": {"Played": true, "IsFavorite": false},
+                "ImageTags": {"Primary": "tag-2"},
+                "ImageBlurHashes": {
+                    "Primary": {"tag-1": "LKO2?U%2Tw=w", "tag-2": "LEHV6nWB2yk8"},
+                    "Backdrop": {"tag-3": "L6PZfSi_.AyE"}
+                }
+            }],
+            "TotalRecordCount": 1
+        }"#;
+
+        let payload: ItemsBody = serde_json::from_str(body).unwrap();
+        let item = ItemMetadata::from(payload.items.into_iter().next().unwrap());
+
+        assert_eq!(item.summary.kind, ItemKind::Movie);
+        assert_eq!(item.summary.blurhash.as_deref(), Some("LEHV6nWB2yk8"));
+        assert_eq!(item.original_name.as_deref(), Some("千と千尋の神隠し"));
+        assert_eq!(item.people, vec!["Rumi Hiiragi".to_string()]);
+        assert_eq!(item.studios, vec!["Studio Ghibli".to_string()]);
+        assert_eq!(item.runtime_secs, Some(7485));
+        assert!(item.played);
+        assert!(!item.favourite);
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(
+            parse_timestamp_millis("1970-01-01T00:00:00.0000000Z"),
+            Some(0)
+        );
+        assert_eq!(
+            parse_timestamp_millis("2024-01-02T03:04:05.1234567Z"),
+            Some(1_704_164_645_123),
+        );
+        assert_eq!(
+            parse_timestamp_millis("2000-03-01T00:00:00Z"),
+            Some(951_868_800_000)
+        );
+        assert_eq!(parse_timestamp_millis("not a timestamp"), None);
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp_millis(0), "1970-01-01T00:00:00.000Z");
+        assert_eq!(
+            format_timestamp_millis(1_704_164_645_123),
+            "2024-01-02T03:04:05.123Z"
+        );
+        assert_eq!(
+            format_timestamp_millis(951_868_800_000),
+            "2000-03-01T00:00:00.000Z"
+        );
+
+        let timestamp = format_timestamp_millis(1_760_000_000_000);
+        assert_eq!(parse_timestamp_millis(&timestamp), Some(1_760_000_000_000));
+    }
+
+    #[test]
+    fn test_incremental_sync_params() {
+        let request = SyncRequest {
+            library_id: "lib".into(),
+            changed_since: Some("2024-01-02T03:04:05.0000000Z".into()),
+            start_index: 200,
+            limit: 100,
+        };
+
+        let params = sync_query_params(&request);
+        assert!(
+            params
+                .contains(&("MinDateLastSaved", "2024-01-02T03:04:05.0000000Z".into()))
+        );
+        assert!(params.contains(&("StartIndex", "200".into())));
+    }
+}
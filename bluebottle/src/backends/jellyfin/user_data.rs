@@ -0,0 +1,130 @@
+use std::time::Duration;
+
+use serde_json::{Value, json};
+
+use super::items::UserData as UserDataBody;
+use super::playback::duration_to_ticks;
+use super::sync::{format_timestamp_millis, parse_timestamp_millis};
+use super::{Jellyfin, send_empty, send_json};
+use crate::backends::BackendError;
+use crate::models::interaction::{Interaction, UserData};
+
+impl Jellyfin {
+    pub(super) async fn fetch_user_data(
+        &self,
+        item_id: String,
+    ) -> Result<UserData, BackendError> {
+        let endpoint = format!("/UserItems/{item_id}/UserData");
+        let body: UserDataBody = send_json(self.client.get(&endpoint)).await?;
+        Ok(UserData::from(body))
+    }
+
+    pub(super) async fn send_interaction(
+        &self,
+        interaction: Interaction,
+        created_at: i64,
+    ) -> Result<(), BackendError> {
+        let endpoint = format!("/UserItems/{}/UserData", interaction.item_id());
+        let payload = user_data_update(&interaction, created_at);
+        send_empty(self.client.post(&endpoint).json(&payload)).await
+    }
+}
+
+/// Build the partial `UpdateUserItemDataDto` setting the state changed by the
+/// interaction, fields which are omitted are left unchanged by the server.
+fn user_data_update(interaction: &Interaction, created_at: i64) -> Value {
+    let last_played_date = format_timestamp_millis(created_at);
+    match interaction {
+        Interaction::Played { played: true, .. } => json!({
+            "Played": true,
+            "PlaybackPositionTicks": 0,
+            "LastPlayedDate": last_played_date,
+        }),
+        Interaction::Played { played: false, .. } => json!({
+            "Played": false,
+            "PlaybackPositionTicks": 0,
+        }),
+        Interaction::Progress { position, .. } => json!({
+            "PlaybackPositionTicks": duration_to_ticks(*position),
+            "LastPlayedDate": last_played_date,
+        }),
+        Interaction::Favourite { favourite, .. } => json!({
+            "IsFavorite": favourite,
+        }),
+        Interaction::Rating { rating, .. } => json!({
+            "Rating": rating,
+        }),
+    }
+}
+
+impl From<UserDataBody> for UserData {
+    fn from(body: UserDataBody) -> Self {
+        UserData {
+            played: body.played,
+            position: Duration::from_nanos(body.playback_position_ticks * 100),
+            favourite: body.is_favorite,
+            rating: body.rating,
+            last_played_at: body
+                .last_played_date
+                .as_deref()
+                .and_then(parse_timestamp_millis),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_data_update() {
+        let created_at = 1_704_164_645_123;
+
+        let interaction = Interaction::Progress {
+            item_id: "abc".into(),
+            position: Duration::from_secs(90),
+        };
+        assert_eq!(
+            user_data_update(&interaction, created_at),
+            json!({
+                "PlaybackPositionTicks": 900_000_000u64,
+                "LastPlayedDate": "2024-01-02T03:04:05.123Z",
+            }),
+        );
+
+        let interaction = Interaction::Played {
+            item_id: "abc".into(),
+            played: false,
+        };
+        assert_eq!(
+            user_data_update(&interaction, created_at),
+            json!({"Played": false, "PlaybackPositionTicks": 0}),
+        );
+
+        let interaction = Interaction::Favourite {
+            item_id: "abc".into(),
+            favourite: true,
+        };
+        assert_eq!(
+            user_data_update(&interaction, created_at),
+            json!({"IsFavorite": true}),
+        );
+    }
+
+    #[test]
+    fn test_parse_user_data() {
+        let body = r#"{
+            "PlaybackPositionTicks": 25330000000,
+            "Played": false,
+            "IsFavorite": true,
+            "LastPlayedDate": "2024-01-02T03:04:05.1234567Z"
+        }"#;
+
+        let body: UserDataBody = serde_json::from_str(body).unwrap();
+        let user_data = UserData::from(body);
+        assert_eq!(user_data.position, Duration::from_secs(2533));
+        assert!(!user_data.played);
+        assert!(user_data.favourite);
+        assert_eq!(user_data.last_played_at, Some(1_704_164_645_123));
+    }
+}
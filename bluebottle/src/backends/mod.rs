@@ -1,20 +1,154 @@
 use std::fmt::{Display, Formatter};
+use std::pin::Pin;
 use std::str::FromStr;
 
 use rusqlite::ToSql;
 use rusqlite::types::{FromSql, FromSqlError};
 use serde_json::Value;
+use tokio::sync::mpsc;
 
+pub use self::error::BackendError;
+use crate::models::image::ImageRequest;
+use crate::models::interaction::{Interaction, UserData};
+use crate::models::live::LiveEvent;
+use crate::models::media::{ItemId, ItemKind, ItemPage, ItemSummary, Library};
+use crate::models::music::AudioTrack;
+use crate::models::playback::{PlaybackEvent, PlaybackRequest, PlaybackStream};
+use crate::models::profile::DeviceProfile;
+use crate::models::query::{FilterOptions, ItemQuery};
+use crate::models::subtitles::RemoteSubtitle;
+use crate::models::sync::{SyncPage, SyncRequest};
+use crate::models::tracks::ItemTracks;
+
+mod error;
 mod http;
 pub mod jellyfin;
+pub mod registry;
+
+/// A boxed future returned by [Backend] operations.
+pub type BackendFuture<'a, T> =
+    Pin<Box<dyn Future<Output = Result<T, BackendError>> + Send + 'a>>;
 
 /// The backend provides access to media information required by the Bluebottle UI.
-pub trait Backend {}
+pub trait Backend: Send + Sync {
+    /// Returns the top level media libraries available to the user.
+    fn libraries(&self) -> BackendFuture<'_, Vec<Library>>;
+
+    /// Returns a single page of items matching the query.
+    fn query_items(&self, query: ItemQuery) -> BackendFuture<'_, ItemPage>;
+
+    /// Returns the values the items of a library can be filtered by.
+    fn filter_options(&self, library_id: ItemId) -> BackendFuture<'_, FilterOptions>;
+
+    /// Search all libraries for items matching the term, returning at most `limit`
+    /// items.
+    fn search(&self, term: String, limit: usize) -> BackendFuture<'_, Vec<ItemSummary>>;
+
+    /// Returns a page of item metadata to be stored locally, optionally only
+    /// including items which changed since the last sync.
+    fn sync_items(&self, request: SyncRequest) -> BackendFuture<'_, SyncPage>;
+
+    /// Resolve a stream the player can open, letting the backend decide whether
+    /// the item can be played directly or must be transcoded to fit the profile.
+    fn playback_stream(
+        &self,
+        request: PlaybackRequest,
+        profile: DeviceProfile,
+    ) -> BackendFuture<'_, PlaybackStream>;
+
+    /// Measure the bitrate the backend can stream to us at, in bits per second.
+    fn measure_bitrate(&self) -> BackendFuture<'_, u64>;
+
+    /// Returns the audio and subtitle streams of a video item which can be
+    /// selected for playback.
+    fn item_tracks(&self, item_id: ItemId) -> BackendFuture<'_, ItemTracks>;
+
+    /// Search the backend's subtitle providers for subtitles of a video item in
+    /// the language, given as a three letter code.
+    fn search_subtitles(
+        &self,
+        item_id: ItemId,
+        language: String,
+    ) -> BackendFuture<'_, Vec<RemoteSubtitle>>;
+
+    /// Download subtitles found by [Backend::search_subtitles] into the backend's
+    /// library, adding them to the item's subtitle streams.
+    fn download_subtitle(
+        &self,
+        item_id: ItemId,
+        subtitle_id: String,
+    ) -> BackendFuture<'_, ()>;
+
+    /// Download the file of subtitles found by [Backend::search_subtitles],
+    /// used to store them next to media files on this machine.
+    fn subtitle_data(&self, subtitle_id: String) -> BackendFuture<'_, Vec<u8>>;
+
+    /// Ask the backend to rescan an item's files, i.e. to pick up new sidecar
+    /// subtitles.
+    fn refresh_item(&self, item_id: ItemId) -> BackendFuture<'_, ()>;
+
+    /// Returns the audio tracks of an audio item, album or artist in the order
+    /// they should be played.
+    fn audio_tracks(
+        &self,
+        item_id: ItemId,
+        kind: ItemKind,
+    ) -> BackendFuture<'_, Vec<AudioTrack>>;
+
+    /// Download an audio track in a format the music player can decode.
+    fn audio_data(&self, item_id: ItemId) -> BackendFuture<'_, Vec<u8>>;
+
+    /// Download the item's primary image, i.e. its poster or album cover.
+    fn primary_image(&self, item_id: ItemId) -> BackendFuture<'_, Vec<u8>>;
+
+    /// Download an item's artwork, scaled by the backend to roughly the size it
+    /// is displayed at.
+    fn image(&self, request: ImageRequest) -> BackendFuture<'_, Vec<u8>>;
+
+    /// Report a change in playback state, keeping resume positions and played
+    /// state up to date.
+    fn report_playback(&self, event: PlaybackEvent) -> BackendFuture<'_, ()>;
+
+    /// Announce what the client can play and be remotely controlled with, so the
+    /// backend lists it as an active session.
+    fn report_capabilities(&self, profile: DeviceProfile) -> BackendFuture<'_, ()>;
+
+    /// Push updates from the backend into `output` until the connection closes or
+    /// `output` is dropped.
+    ///
+    /// Returns an error if the connection could not be made or failed, the caller
+    /// is responsible for reconnecting.
+    fn live_updates(&self, output: mpsc::Sender<LiveEvent>) -> BackendFuture<'_, ()>;
+
+    /// Returns the user's current state of the item.
+    fn user_data(&self, item_id: ItemId) -> BackendFuture<'_, UserData>;
+
+    /// Apply an interaction the user made at `created_at`, in milliseconds since
+    /// the unix epoch.
+    ///
+    /// Applying the same interaction more than once must have no additional
+    /// effect, as interactions are retried until they succeed.
+    fn apply_interaction(
+        &self,
+        interaction: Interaction,
+        created_at: i64,
+    ) -> BackendFuture<'_, ()>;
+}
 
 /// The backend trait for initialising the backend from a persisted state.
 pub trait BackendInit: Sized {
     /// Load the backend from some persisted context state.
-    fn from_context(context: Value) -> Result<Self, snafu::Whatever>;
+    fn from_context(
+        backend_id: BackendId,
+        context: Value,
+    ) -> Result<Self, snafu::Whatever>;
+
+    /// Move any secrets held in plaintext by the context into the secret store,
+    /// returning the context to persist in its place if anything was moved.
+    fn secure_context(
+        backend_id: BackendId,
+        context: &Value,
+    ) -> Result<Option<Value>, snafu::Whatever>;
 }
 
 /// A unique identifier assigned to the backend.
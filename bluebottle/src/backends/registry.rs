@@ -0,0 +1,99 @@
+//! Holds onto every backend the user has configured.
+
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use super::jellyfin::Jellyfin;
+use super::{Backend, BackendId, BackendInit, BackendInitState, BackendKind};
+use crate::storage;
+
+static BACKENDS: RwLock<Vec<(BackendId, Arc<dyn Backend>)>> = RwLock::new(Vec::new());
+
+/// Load all persisted backends from the durable state.
+///
+/// Backends which fail to initialise are logged and skipped.
+pub fn load_from_state() {
+    let states = storage::with_durable_state(|state| {
+        state
+            .read_all_backend_init_state()
+            .inspect_err(
+                |err| tracing::error!(error = %err, "failed to read backend state"),
+            )
+            .unwrap_or_default()
+    });
+
+    for mut state in states {
+        let backend_id = state.id;
+        if let Err(err) = secure_context(&mut state) {
+            tracing::warn!(backend_id = %backend_id, error = %err, "failed to move backend secrets into the secret store");
+        }
+
+        match create_backend(state) {
+            Ok(backend) => register(backend_id, backend),
+            Err(err) => {
+                tracing::error!(backend_id = %backend_id, error = %err, "failed to load backend");
+            },
+        }
+    }
+}
+
+/// Register a new backend, replacing any existing backend with the same ID.
+pub fn register(backend_id: BackendId, backend: Arc<dyn Backend>) {
+    let mut backends = BACKENDS.write();
+    backends.retain(|(id, _)| *id != backend_id);
+    backends.push((backend_id, backend));
+}
+
+/// Returns the backend with the given ID if it exists.
+pub fn get(backend_id: BackendId) -> Option<Arc<dyn Backend>> {
+    BACKENDS
+        .read()
+        .iter()
+        .find(|(id, _)| *id == backend_id)
+        .map(|(_, backend)| backend.clone())
+}
+
+/// Returns the first registered backend if one exists.
+pub fn first() -> Option<(BackendId, Arc<dyn Backend>)> {
+    BACKENDS.read().first().cloned()
+}
+
+/// Returns all registered backends.
+pub fn all() -> Vec<(BackendId, Arc<dyn Backend>)> {
+    BACKENDS.read().clone()
+}
+
+/// Move the plaintext secrets of contexts saved before the secret store existed
+/// into it, persisting the context which references them instead.
+fn secure_context(state: &mut BackendInitState) -> Result<(), snafu::Whatever> {
+    let context = match state.kind {
+        BackendKind::Jellyfin => Jellyfin::secure_context(state.id, &state.context)?,
+    };
+    let Some(context) = context else {
+        return Ok(());
+    };
+
+    let backend_id = state.id;
+    let persisted = context.clone();
+    let result = storage::with_durable_state(move |durable| {
+        durable
+            .update_backend_context(backend_id, &persisted)
+            .map_err(|err| err.to_string())
+    });
+    if let Err(err) = result {
+        snafu::whatever!("persist secured backend context: {err}");
+    }
+    tracing::info!(backend_id = %state.id, "moved backend secrets into the secret store");
+    state.context = context;
+    Ok(())
+}
+
+fn create_backend(state: BackendInitState) -> Result<Arc<dyn Backend>, snafu::Whatever> {
+    match state.kind {
+        BackendKind::Jellyfin => {
+            let backend = Jellyfin::from_context(state.id, state.context)?;
+            Ok(Arc::new(backend))
+        },
+    }
+}
@@ -0,0 +1,153 @@
+//! The backlog worker, replaying user interactions to their backend.
+//!
+//! Interactions are applied in the order they were made, stopping at the first
+//! one that fails to apply until the backend can be reached again. Interactions
+//! set absolute values, so an event applied again after the app exits early has
+//! no additional effect.
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::time::Duration;
+
+use iced::Subscription;
+use iced::futures::Stream;
+use iced::futures::channel::mpsc;
+use tokio::time::Instant;
+
+use crate::backends::{BackendError, BackendId, registry};
+use crate::storage::interaction_backlog::{self, InteractionEvent};
+
+/// How long to wait between checks of the backlog when nothing is recorded.
+const IDLE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// The delay before the first retry of a backend that could not be reached.
+const MIN_BACKOFF: Duration = Duration::from_secs(5);
+/// The longest delay between retries of a backend that could not be reached.
+const MAX_BACKOFF: Duration = Duration::from_secs(10 * 60);
+/// The number of interactions read from the backlog at once.
+const BATCH_SIZE: usize = 50;
+
+/// Runs the backlog worker, it never produces a message.
+pub fn subscription() -> Subscription<Infallible> {
+    Subscription::run(replay_stream)
+}
+
+fn replay_stream() -> impl Stream<Item = Infallible> {
+    iced::stream::channel(1, async |_output: mpsc::Sender<Infallible>| {
+        let mut backoff = HashMap::<BackendId, Backoff>::new();
+
+        loop {
+            let pending = interaction_backlog::pending_backends().await;
+            backoff.retain(|backend_id, _| pending.contains(backend_id));
+
+            for backend_id in pending {
+                if backoff
+                    .get(&backend_id)
+                    .is_some_and(|backoff| backoff.retry_at > Instant::now())
+                {
+                    continue;
+                }
+
+                match replay_backend(backend_id).await {
+                    Ok(()) => {
+                        backoff.remove(&backend_id);
+                    },
+                    Err(err) => {
+                        let backoff = backoff.entry(backend_id).or_default();
+                        backoff.failed();
+                        tracing::info!(
+                            backend_id = %backend_id,
+                            error = %err,
+                            retry_in = ?backoff.delay,
+                            "backend unavailable, interactions will be retried",
+                        );
+                    },
+                }
+            }
+
+            let wake_at = backoff
+                .values()
+                .map(|backoff| backoff.retry_at)
+                .min()
+                .unwrap_or_else(|| Instant::now() + IDLE_INTERVAL);
+            let _ =
+                tokio::time::timeout_at(wake_at, interaction_backlog::recorded()).await;
+        }
+    })
+}
+
+/// Replay the backlog of the backend until it is empty.
+///
+/// Returns the error which stopped the replay if it should be retried later.
+async fn replay_backend(backend_id: BackendId) -> Result<(), BackendError> {
+    loop {
+        let events = interaction_backlog::pending(backend_id, BATCH_SIZE).await;
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        let Some(backend) = registry::get(backend_id) else {
+            tracing::warn!(backend_id = %backend_id, "backend no longer exists, discarding interactions");
+            for event in events {
+                if !remove(&event).await {
+                    return Ok(());
+                }
+            }
+            continue;
+        };
+
+        for event in events {
+            let result = backend
+                .apply_interaction(event.interaction.clone(), event.created_at)
+                .await;
+            match result {
+                Ok(()) => {},
+                Err(err) if err.is_retryable() => return Err(err),
+                Err(err) => {
+                    tracing::warn!(
+                        event_id = %event.event_id,
+                        error = %err,
+                        "backend rejected interaction, discarding it",
+                    );
+                },
+            }
+
+            // Stop rather than replaying the same event forever.
+            if !remove(&event).await {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Remove the event from the backlog, returning `false` if it failed.
+async fn remove(event: &InteractionEvent) -> bool {
+    match interaction_backlog::remove(event.event_id).await {
+        Ok(()) => true,
+        Err(err) => {
+            tracing::error!(event_id = %event.event_id, error = %err, "failed to remove interaction from backlog");
+            false
+        },
+    }
+}
+
+/// Exponential backoff between retries of a backend.
+struct Backoff {
+    delay: Duration,
+    retry_at: Instant,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            delay: Duration::ZERO,
+            retry_at: Instant::now(),
+        }
+    }
+}
+
+impl Backoff {
+    fn failed(&mut self) {
+        self.delay = (self.delay * 2).clamp(MIN_BACKOFF, MAX_BACKOFF);
+        self.retry_at = Instant::now() + self.delay;
+    }
+}
@@ -0,0 +1,471 @@
+//! The details of a video item shown before it is played, offering to resume it,
+//! picking its audio and subtitle tracks and quality, and finding more subtitles.
+//!
+//! Favouriting and rating the item are recorded in the
+//! [interaction backlog](interaction_backlog), so they are applied once the
+//! backend is reachable.
+
+use std::time::Duration;
+
+use bluebottle_ui::{button, color, pill, pill_box, text};
+use iced::widget::{column, container};
+use iced::{Element, border, task};
+
+use super::subtitle_search::{SubtitleSearch, SubtitleSearchMsg};
+use crate::backends::{BackendId, registry};
+use crate::models::interaction::Interaction;
+use crate::models::media::ItemId;
+use crate::models::profile::{Quality, QualitySetting};
+use crate::models::tracks::{ItemTracks, MediaTrack, TrackPreferences, TrackSelection};
+use crate::playback::{self, quality};
+use crate::storage::settings::SettingChange;
+use crate::storage::{
+    interaction_backlog,
+    library_items,
+    stream_quality,
+    track_preferences,
+};
+use crate::view::View;
+
+/// The labels of the star ratings an item can be given, each star worth two
+/// points of the backend's rating out of 10.
+const RATING_STARS: [&str; 5] = ["★", "★★", "★★★", "★★★★", "★★★★★"];
+
+/// The language subtitles are searched for if the user has no preference.
+const DEFAULT_SUBTITLE_LANGUAGE: &str = "eng";
+
+pub struct ItemDetail {
+    backend_id: BackendId,
+    item_id: ItemId,
+    title: String,
+    /// The position playback can resume from, along with its button label.
+    resume: Option<(Duration, String)>,
+    /// The selectable tracks, `None` while loading or if they could not be loaded.
+    tracks: Option<ItemTracks>,
+    loading: bool,
+    /// The preferences the tracks were selected with.
+    preferences: TrackPreferences,
+    selection: TrackSelection,
+    /// Whether the user changed the selected tracks.
+    picked: bool,
+    subtitle_search: Option<SubtitleSearch>,
+    /// The quality picked for this playback, `None` to use the quality setting.
+    quality: Option<Quality>,
+    /// The label of the quality setting.
+    default_quality: String,
+    favourite: bool,
+    /// The user's rating out of 10, if they rated the item.
+    rating: Option<f32>,
+}
+
+#[derive(Clone)]
+pub enum ItemDetailMsg {
+    TracksLoaded(Result<ItemTracks, String>),
+    SelectAudio(u32),
+    /// Select the subtitle stream, or turn subtitles off.
+    SelectSubtitle(Option<u32>),
+    /// Stream at the quality, or the quality setting if `None`.
+    SelectQuality(Option<Quality>),
+    FindSubtitles,
+    SubtitleSearch(SubtitleSearchMsg),
+    /// The item's favourite state and rating.
+    UserDataLoaded(Result<(bool, Option<f32>), String>),
+    SetFavourite(bool),
+    /// Rate the item out of 10, or clear the rating.
+    SetRating(Option<f32>),
+    InteractionRecorded(Result<(), String>),
+    /// Play the item from the position, handled by the parent screen.
+    Play(Duration),
+    /// Close the details, handled by the parent screen.
+    Dismiss,
+}
+
+impl View<ItemDetailMsg> for ItemDetail {
+    fn update(&mut self, message: ItemDetailMsg) -> task::Task<ItemDetailMsg> {
+        match message {
+            ItemDetailMsg::TracksLoaded(Ok(tracks)) => {
+                self.preferences = track_preferences::resolve(
+                    self.backend_id,
+                    tracks.series_id.as_ref(),
+                );
+                // Keep the user's choice when the tracks are reloaded.
+                if !self.picked {
+                    self.selection = self.preferences.select(&tracks);
+                }
+                self.tracks = Some(tracks);
+                self.loading = false;
+            },
+            ItemDetailMsg::TracksLoaded(Err(err)) => {
+                self.loading = false;
+                tracing::warn!(item_id = %self.item_id, error = %err, "failed to load item tracks");
+            },
+            ItemDetailMsg::SelectAudio(index) => {
+                self.selection.audio_stream_index = Some(index);
+                self.picked = true;
+            },
+            ItemDetailMsg::SelectSubtitle(index) => {
+                self.selection.subtitle_stream_index = index;
+                self.picked = true;
+            },
+            ItemDetailMsg::SelectQuality(quality) => self.quality = quality,
+            ItemDetailMsg::FindSubtitles => {
+                let Some(tracks) = self.tracks.as_ref() else {
+                    return task::Task::none();
+                };
+                let language = self
+                    .preferences
+                    .subtitle_language
+                    .clone()
+                    .unwrap_or_else(|| DEFAULT_SUBTITLE_LANGUAGE.to_string());
+                let (search, task) = SubtitleSearch::new(
+                    self.backend_id,
+                    self.item_id.clone(),
+                    tracks.media_path.clone(),
+                    language,
+                );
+                self.subtitle_search = Some(search);
+                return task.map(ItemDetailMsg::SubtitleSearch);
+            },
+            ItemDetailMsg::SubtitleSearch(message) => {
+                if let Some(search) = self.subtitle_search.as_mut() {
+                    return search.update(message).map(ItemDetailMsg::SubtitleSearch);
+                }
+            },
+            ItemDetailMsg::UserDataLoaded(Ok((favourite, rating))) => {
+                self.favourite = favourite;
+                self.rating = rating;
+            },
+            ItemDetailMsg::UserDataLoaded(Err(err)) => {
+                tracing::warn!(item_id = %self.item_id, error = %err, "failed to load item user data");
+            },
+            ItemDetailMsg::SetFavourite(favourite) => {
+                self.favourite = favourite;
+                let (backend_id, item_id) = (self.backend_id, self.item_id.clone());
+                let fut = async move {
+                    library_items::set_favourite(backend_id, item_id.clone(), favourite)
+                        .await?;
+                    let interaction = Interaction::Favourite { item_id, favourite };
+                    interaction_backlog::record(backend_id, interaction).await
+                };
+                return task::Task::perform(fut, ItemDetailMsg::InteractionRecorded);
+            },
+            ItemDetailMsg::SetRating(rating) => {
+                self.rating = rating;
+                let interaction = Interaction::Rating {
+                    item_id: self.item_id.clone(),
+                    rating,
+                };
+                let fut = interaction_backlog::record(self.backend_id, interaction);
+                return task::Task::perform(fut, ItemDetailMsg::InteractionRecorded);
+            },
+            ItemDetailMsg::InteractionRecorded(Ok(())) => {},
+            ItemDetailMsg::InteractionRecorded(Err(err)) => {
+                tracing::error!(item_id = %self.item_id, error = %err, "failed to add interaction to backlog");
+            },
+            ItemDetailMsg::Play(_) | ItemDetailMsg::Dismiss => {},
+        }
+
+        task::Task::none()
+    }
+
+    fn view(&self) -> Element<'_, ItemDetailMsg> {
+        let mut content = column![text::subheading(self.title.as_str())].spacing(8);
+
+        match self.tracks.as_ref() {
+            Some(tracks) => {
+                if tracks.audio.len() > 1 {
+                    let pills = tracks.audio.iter().map(|track| {
+                        let selected =
+                            self.selection.audio_stream_index == Some(track.index);
+                        track_pill(
+                            track,
+                            selected,
+                            ItemDetailMsg::SelectAudio(track.index),
+                        )
+                    });
+                    content = content.push(pill_box::pill_box("Audio", pills));
+                }
+
+                if !tracks.subtitles.is_empty() {
+                    let off = if self.selection.subtitle_stream_index.is_none() {
+                        pill::small("Off", Some("check")).into()
+                    } else {
+                        pill::small("Off", None)
+                            .on_press(ItemDetailMsg::SelectSubtitle(None))
+                            .into()
+                    };
+                    let pills = tracks.subtitles.iter().map(|track| {
+                        let selected =
+                            self.selection.subtitle_stream_index == Some(track.index);
+                        let message = ItemDetailMsg::SelectSubtitle(Some(track.index));
+                        track_pill(track, selected, message)
+                    });
+                    content = content.push(pill_box::pill_box(
+                        "Subtitles",
+                        std::iter::once(off).chain(pills),
+                    ));
+                }
+            },
+            None if self.loading => {
+                content = content.push(text::label("Loading tracks..."));
+            },
+            None => {},
+        }
+
+        let (favourite_label, favourite_message) = if self.favourite {
+            ("Remove from favourites", ItemDetailMsg::SetFavourite(false))
+        } else {
+            ("Add to favourites", ItemDetailMsg::SetFavourite(true))
+        };
+        content = content.push(button::standard(
+            favourite_label,
+            Some("favorite"),
+            false,
+            favourite_message,
+        ));
+
+        let stars = self.rating.map(|rating| (rating / 2.0).round() as usize);
+        let clear = if stars.is_none() {
+            pill::small("None", Some("check")).into()
+        } else {
+            pill::small("None", None)
+                .on_press(ItemDetailMsg::SetRating(None))
+                .into()
+        };
+        let ratings = RATING_STARS.into_iter().zip(1..).map(|(label, count)| {
+            if stars == Some(count) {
+                pill::small(label, Some("check")).into()
+            } else {
+                let rating = count as f32 * 2.0;
+                pill::small(label, None)
+                    .on_press(ItemDetailMsg::SetRating(Some(rating)))
+                    .into()
+            }
+        });
+        content = content.push(pill_box::pill_box(
+            "Your rating",
+            std::iter::once(clear).chain(ratings),
+        ));
+
+        let default = if self.quality.is_none() {
+            pill::small(&self.default_quality, Some("check")).into()
+        } else {
+            pill::small(&self.default_quality, None)
+                .on_press(ItemDetailMsg::SelectQuality(None))
+                .into()
+        };
+        let qualities = Quality::ALL.into_iter().map(|quality| {
+            if self.quality == Some(quality) {
+                pill::small(quality.label(), Some("check")).into()
+            } else {
+                pill::small(quality.label(), None)
+                    .on_press(ItemDetailMsg::SelectQuality(Some(quality)))
+                    .into()
+            }
+        });
+        content = content.push(pill_box::pill_box(
+            "Quality",
+            std::iter::once(default).chain(qualities),
+        ));
+
+        match self.subtitle_search.as_ref() {
+            Some(search) => {
+                content = content.push(search.view().map(ItemDetailMsg::SubtitleSearch));
+            },
+            None if self.tracks.is_some() => {
+                content = content.push(button::standard(
+                    "Find subtitles",
+                    Some("subtitles"),
+                    false,
+                    ItemDetailMsg::FindSubtitles,
+                ));
+            },
+            None => {},
+        }
+
+        if let Some((position, label)) = self.resume.as_ref() {
+            content = content.push(button::standard(
+                label,
+                Some("play_arrow"),
+                false,
+                ItemDetailMsg::Play(*position),
+            ));
+        }
+        let (play_label, play_icon) = match self.resume {
+            Some(_) => ("Start over", "replay"),
+            None => ("Play", "play_arrow"),
+        };
+        content = content
+            .push(button::standard(
+                play_label,
+                Some(play_icon),
+                false,
+                ItemDetailMsg::Play(Duration::ZERO),
+            ))
+            .push(button::standard(
+                "Cancel",
+                None,
+                false,
+                ItemDetailMsg::Dismiss,
+            ));
+
+        container(content)
+            .padding(24)
+            .max_width(560)
+            .style(|_| container::Style {
+                background: Some(color::secondary().into()),
+                border: border::rounded(8),
+                ..Default::default()
+            })
+            .into()
+    }
+}
+
+impl ItemDetail {
+    /// Open the details of an item, loading its tracks from the backend.
+    pub fn open(
+        backend_id: BackendId,
+        item_id: ItemId,
+        title: String,
+        resume_position: Option<Duration>,
+    ) -> (Self, task::Task<ItemDetailMsg>) {
+        let resume = resume_position.map(|position| {
+            let label = format!("Resume from {}", playback::format_position(position));
+            (position, label)
+        });
+        let detail = Self {
+            backend_id,
+            item_id: item_id.clone(),
+            title,
+            resume,
+            tracks: None,
+            loading: true,
+            preferences: TrackPreferences::default(),
+            selection: TrackSelection::default(),
+            picked: false,
+            subtitle_search: None,
+            quality: None,
+            default_quality: default_quality_label(backend_id),
+            favourite: false,
+            rating: None,
+        };
+        let task = task::Task::batch([detail.load_tracks(), detail.load_user_data()]);
+        (detail, task)
+    }
+
+    /// Reload the tracks if the backend changed the item, i.e. after it stored
+    /// downloaded subtitles.
+    pub fn item_updated(
+        &self,
+        backend_id: BackendId,
+        item_ids: &[ItemId],
+    ) -> task::Task<ItemDetailMsg> {
+        if backend_id != self.backend_id || !item_ids.contains(&self.item_id) {
+            return task::Task::none();
+        }
+        self.load_tracks()
+    }
+
+    fn load_tracks(&self) -> task::Task<ItemDetailMsg> {
+        let Some(backend) = registry::get(self.backend_id) else {
+            return task::Task::done(ItemDetailMsg::TracksLoaded(Err(
+                "backend no longer exists".to_string(),
+            )));
+        };
+
+        let item_id = self.item_id.clone();
+        let fut = async move {
+            backend
+                .item_tracks(item_id)
+                .await
+                .map_err(|err| err.to_string())
+        };
+        task::Task::perform(fut, ItemDetailMsg::TracksLoaded)
+    }
+
+    /// Load the item's favourite state and rating from the backend, falling back
+    /// to the synced favourite state if the backend is unreachable.
+    fn load_user_data(&self) -> task::Task<ItemDetailMsg> {
+        let backend = registry::get(self.backend_id);
+        let (backend_id, item_id) = (self.backend_id, self.item_id.clone());
+        let fut = async move {
+            if let Some(backend) = backend {
+                match backend.user_data(item_id.clone()).await {
+                    Ok(user_data) => return Ok((user_data.favourite, user_data.rating)),
+                    Err(err) => {
+                        tracing::debug!(error = %err, "failed to load user data from backend");
+                    },
+                }
+            }
+
+            match library_items::favourite(backend_id, item_id).await? {
+                Some(favourite) => Ok((favourite, None)),
+                None => Err("item has not been synced".to_string()),
+            }
+        };
+        task::Task::perform(fut, ItemDetailMsg::UserDataLoaded)
+    }
+
+    /// Refresh the label of the default quality if the quality setting changed
+    /// elsewhere, i.e. from the settings screen.
+    pub fn setting_changed(&mut self, change: &SettingChange) {
+        if stream_quality::STREAM_QUALITY.is_changed(change) {
+            self.default_quality = default_quality_label(self.backend_id);
+        }
+    }
+
+    pub fn item_id(&self) -> &ItemId {
+        &self.item_id
+    }
+
+    /// Returns the quality picked for this playback, `None` to use the quality
+    /// setting.
+    pub fn quality(&self) -> Option<Quality> {
+        self.quality
+    }
+
+    /// Returns the selected tracks, `None` if they are not known.
+    pub fn selection(&self) -> Option<TrackSelection> {
+        self.tracks.as_ref().map(|_| self.selection)
+    }
+
+    /// Remember the tracks the user picked for the rest of the series.
+    pub fn remember_choice(&self) {
+        let Some(tracks) = self.tracks.as_ref() else {
+            return;
+        };
+        let Some(series_id) = tracks.series_id.as_deref() else {
+            return;
+        };
+
+        if self.picked {
+            let preferences = self.preferences.remember(tracks, self.selection);
+            track_preferences::save_series(self.backend_id, series_id, &preferences);
+        }
+    }
+}
+
+/// Returns the label of the backend's quality setting, including the quality
+/// picked automatically once the bitrate was measured.
+fn default_quality_label(backend_id: BackendId) -> String {
+    match stream_quality::load(backend_id) {
+        QualitySetting::Auto => match quality::auto_quality(backend_id) {
+            Some(quality) => format!("Auto ({})", quality.label()),
+            None => "Auto".to_string(),
+        },
+        QualitySetting::Fixed(quality) => format!("Default ({})", quality.label()),
+    }
+}
+
+/// A pill selecting the track, or a marked pill if it is already selected.
+fn track_pill(
+    track: &MediaTrack,
+    selected: bool,
+    message: ItemDetailMsg,
+) -> Element<'_, ItemDetailMsg> {
+    if selected {
+        pill::small(&track.title, Some("check")).into()
+    } else {
+        pill::small(&track.title, None).on_press(message).into()
+    }
+}
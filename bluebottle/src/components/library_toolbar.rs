@@ -0,0 +1,226 @@
+//! Sorting and filtering controls for a library view.
+
+use bluebottle_ui::{button, pill, pill_box, scrollable};
+use iced::widget::{column, row, space};
+use iced::{Center, Element, Length, task};
+
+use crate::models::query::{
+    FilterOptions,
+    ItemFilter,
+    LibraryViewOptions,
+    SortBy,
+    SortOrder,
+};
+use crate::view;
+
+#[derive(Default)]
+pub struct LibraryToolbar {
+    options: LibraryViewOptions,
+    available: FilterOptions,
+    /// Display labels of the active filters, in the same order as the filters.
+    filter_labels: Vec<String>,
+    /// The decades that can be filtered by, along with their display label.
+    decades: Vec<(u32, String)>,
+    filter_panel_open: bool,
+}
+
+#[derive(Clone)]
+pub enum LibraryToolbarMsg {
+    SortBy(SortBy),
+    ToggleFilterPanel,
+    AddFilter(ItemFilter),
+    RemoveFilter(ItemFilter),
+    ClearFilters,
+}
+
+impl LibraryToolbarMsg {
+    /// Returns whether the message changes which items are displayed, or their order.
+    pub fn changes_query(&self) -> bool {
+        !matches!(self, LibraryToolbarMsg::ToggleFilterPanel)
+    }
+}
+
+impl view::View<LibraryToolbarMsg> for LibraryToolbar {
+    fn update(&mut self, message: LibraryToolbarMsg) -> task::Task<LibraryToolbarMsg> {
+        match message {
+            LibraryToolbarMsg::SortBy(sort_by) => {
+                if self.options.sort_by == sort_by {
+                    self.options.sort_order = self.options.sort_order.reversed();
+                } else {
+                    self.options.sort_by = sort_by;
+                }
+            },
+            LibraryToolbarMsg::ToggleFilterPanel => {
+                self.filter_panel_open = !self.filter_panel_open;
+            },
+            LibraryToolbarMsg::AddFilter(filter) => {
+                self.options.add_filter(filter);
+            },
+            LibraryToolbarMsg::RemoveFilter(filter) => {
+                self.options.remove_filter(&filter);
+            },
+            LibraryToolbarMsg::ClearFilters => {
+                self.options.filters.clear();
+            },
+        }
+
+        self.refresh_filter_labels();
+
+        task::Task::none()
+    }
+
+    fn view(&self) -> Element<'_, LibraryToolbarMsg> {
+        let sort_pills = SortBy::ALL
+            .into_iter()
+            .map(|sort_by| self.sort_pill(sort_by));
+
+        let controls = row![
+            row(sort_pills).spacing(4).align_y(Center),
+            space().width(Length::Fill),
+            button::standard(
+                "Filter",
+                Some("filter_list"),
+                false,
+                LibraryToolbarMsg::ToggleFilterPanel
+            ),
+        ]
+        .align_y(Center);
+
+        let mut content = column![controls].spacing(8);
+
+        if !self.options.filters.is_empty() {
+            content = content.push(self.active_filters());
+        }
+
+        if self.filter_panel_open {
+            content = content.push(self.filter_panel());
+        }
+
+        content.padding([8, 16]).into()
+    }
+}
+
+impl LibraryToolbar {
+    /// Returns the current view options.
+    pub fn options(&self) -> &LibraryViewOptions {
+        &self.options
+    }
+
+    /// Replace the current view options, i.e. when switching library.
+    pub fn set_options(&mut self, options: LibraryViewOptions) {
+        self.options = options;
+        self.refresh_filter_labels();
+    }
+
+    /// Set the values which are available to filter by.
+    pub fn set_available(&mut self, available: FilterOptions) {
+        let mut decades: Vec<u32> =
+            available.years.iter().map(|year| year / 10 * 10).collect();
+        decades.sort_unstable_by(|a, b| b.cmp(a));
+        decades.dedup();
+
+        self.decades = decades
+            .into_iter()
+            .map(|decade| (decade, format!("{decade}s")))
+            .collect();
+        self.available = available;
+    }
+
+    fn refresh_filter_labels(&mut self) {
+        self.filter_labels = self
+            .options
+            .filters
+            .iter()
+            .map(|filter| filter.to_string())
+            .collect();
+    }
+
+    fn sort_pill(&self, sort_by: SortBy) -> Element<'_, LibraryToolbarMsg> {
+        let icon =
+            (self.options.sort_by == sort_by).then_some(match self.options.sort_order {
+                SortOrder::Ascending => "arrow_upward",
+                SortOrder::Descending => "arrow_downward",
+            });
+
+        pill::small(sort_by.label(), icon)
+            .on_press(LibraryToolbarMsg::SortBy(sort_by))
+            .into()
+    }
+
+    fn active_filters(&self) -> Element<'_, LibraryToolbarMsg> {
+        let chips = self
+            .options
+            .filters
+            .iter()
+            .zip(self.filter_labels.iter())
+            .map(|(filter, label)| {
+                pill::small(label, Some("close"))
+                    .on_press(LibraryToolbarMsg::RemoveFilter(filter.clone()))
+                    .into()
+            })
+            .chain([pill::small("Clear all", Some("delete"))
+                .on_press(LibraryToolbarMsg::ClearFilters)
+                .into()]);
+
+        pill_box::pill_box("Active Filters", chips)
+    }
+
+    fn filter_panel(&self) -> Element<'_, LibraryToolbarMsg> {
+        let status = [
+            ("Played", ItemFilter::Played),
+            ("Unplayed", ItemFilter::Unplayed),
+            ("Favourites", ItemFilter::Favourite),
+        ]
+        .into_iter()
+        .map(|(label, filter)| self.filter_pill(label, filter));
+
+        let genres = self
+            .available
+            .genres
+            .iter()
+            .map(|genre| self.filter_pill(genre, ItemFilter::Genre(genre.clone())));
+
+        let decades = self.decades.iter().map(|(decade, label)| {
+            let filter = ItemFilter::Years {
+                from: *decade,
+                to: decade + 9,
+            };
+            self.filter_pill(label, filter)
+        });
+
+        let ratings = self.available.official_ratings.iter().map(|rating| {
+            self.filter_pill(rating, ItemFilter::OfficialRating(rating.clone()))
+        });
+
+        let studios =
+            self.available.studios.iter().map(|studio| {
+                self.filter_pill(studio, ItemFilter::Studio(studio.clone()))
+            });
+
+        let panel = column![
+            pill_box::pill_box("Status", status),
+            pill_box::pill_box("Genres", genres),
+            pill_box::pill_box("Decades", decades),
+            pill_box::pill_box("Ratings", ratings),
+            pill_box::pill_box("Studios", studios),
+        ]
+        .spacing(12);
+
+        scrollable::scrollable(panel).height(240).into()
+    }
+
+    /// A pill which adds the filter, or a marked pill if the filter is already active.
+    fn filter_pill<'a>(
+        &self,
+        label: &'a str,
+        filter: ItemFilter,
+    ) -> Element<'a, LibraryToolbarMsg> {
+        if self.options.filters.contains(&filter) {
+            pill::small(label, Some("check")).into()
+        } else {
+            pill::small(label, None)
+                .on_press(LibraryToolbarMsg::AddFilter(filter))
+                .into()
+        }
+    }
+}
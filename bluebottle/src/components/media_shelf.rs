@@ -0,0 +1,175 @@
+//! A titled row of item cards, paged with a carousel navigator.
+
+use bluebottle_ui::image::PosterSize;
+use bluebottle_ui::{card, carousel_navigator, image, text};
+use iced::widget::{column, row, space};
+use iced::{Center, Element, Length, task};
+
+use crate::backends::BackendId;
+use crate::images::{self, ImageKey};
+use crate::models::media::{ItemId, ItemSummary};
+use crate::view;
+
+/// The number of cards displayed per page.
+const ITEMS_PER_PAGE: usize = 6;
+const POSTER_SIZE: PosterSize = PosterSize::Small;
+
+pub struct MediaShelf {
+    title: &'static str,
+    /// The backend the items belong to.
+    backend_id: Option<BackendId>,
+    items: Vec<ShelfEntry>,
+    /// The current page, starting from `1`.
+    page: u32,
+}
+
+#[derive(Clone)]
+pub enum MediaShelfMsg {
+    Back,
+    Forward,
+    Open(ItemId),
+    /// The artwork of an item has loaded.
+    ImageLoaded,
+}
+
+impl view::View<MediaShelfMsg> for MediaShelf {
+    fn update(&mut self, message: MediaShelfMsg) -> task::Task<MediaShelfMsg> {
+        match message {
+            MediaShelfMsg::Back => {
+                self.page = self.page.saturating_sub(1).max(1);
+                return self.request_images();
+            },
+            MediaShelfMsg::Forward => {
+                self.page = (self.page + 1).min(self.total_pages());
+                return self.request_images();
+            },
+            // Handled by the parent screen.
+            MediaShelfMsg::Open(_) => {},
+            MediaShelfMsg::ImageLoaded => {},
+        }
+
+        task::Task::none()
+    }
+
+    fn view(&self) -> Element<'_, MediaShelfMsg> {
+        if self.items.is_empty() {
+            return space().into();
+        }
+
+        let header = row![
+            text::subheading(self.title),
+            space().width(Length::Fill),
+            carousel_navigator::navigator(
+                self.page,
+                self.total_pages(),
+                MediaShelfMsg::Back,
+                MediaShelfMsg::Forward,
+            ),
+        ]
+        .align_y(Center);
+
+        let cards = self.visible_items().map(|entry| {
+            let handle = self.image_key(entry).as_ref().and_then(images::get);
+            let placeholder =
+                entry.item.blurhash.as_deref().and_then(images::placeholder);
+            let artwork: Element<'_, MediaShelfMsg> = match (placeholder, handle) {
+                (Some(placeholder), handle) => {
+                    image::poster_artwork(placeholder, handle, POSTER_SIZE).into()
+                },
+                (None, Some(handle)) => image::poster(handle, POSTER_SIZE).into(),
+                (None, None) => image::poster_skeleton(POSTER_SIZE),
+            };
+            card::card(
+                &entry.item.name,
+                &entry.subtext,
+                artwork,
+                space(),
+                MediaShelfMsg::Open(entry.item.id.clone()),
+            )
+            .into()
+        });
+
+        column![header, row(cards).spacing(8)]
+            .spacing(8)
+            .padding([8, 16])
+            .into()
+    }
+}
+
+impl MediaShelf {
+    /// Create a new, empty shelf with the given title.
+    pub fn new(title: &'static str) -> Self {
+        Self {
+            title,
+            backend_id: None,
+            items: Vec::new(),
+            page: 1,
+        }
+    }
+
+    /// Replace the items of the shelf, keeping the current page if it still exists.
+    pub fn set_items(
+        &mut self,
+        backend_id: BackendId,
+        items: Vec<ItemSummary>,
+    ) -> task::Task<MediaShelfMsg> {
+        self.backend_id = Some(backend_id);
+        self.items = items.into_iter().map(ShelfEntry::from).collect();
+        self.page = self.page.min(self.total_pages());
+        self.request_images()
+    }
+
+    /// Returns the item with the given ID, if it is on the shelf.
+    pub fn find(&self, item_id: &str) -> Option<&ItemSummary> {
+        self.items
+            .iter()
+            .map(|entry| &entry.item)
+            .find(|item| item.id == item_id)
+    }
+
+    fn total_pages(&self) -> u32 {
+        self.items.len().div_ceil(ITEMS_PER_PAGE).max(1) as u32
+    }
+
+    fn visible_items(&self) -> impl Iterator<Item = &ShelfEntry> {
+        let start = (self.page as usize - 1) * ITEMS_PER_PAGE;
+        self.items.iter().skip(start).take(ITEMS_PER_PAGE)
+    }
+
+    fn image_key(&self, entry: &ShelfEntry) -> Option<ImageKey> {
+        let backend_id = self.backend_id?;
+        Some(ImageKey::primary(
+            backend_id,
+            entry.item.id.clone(),
+            POSTER_SIZE.dimensions(),
+        ))
+    }
+
+    /// Load the artwork of the items on the current page.
+    fn request_images(&self) -> task::Task<MediaShelfMsg> {
+        let tasks: Vec<_> = self
+            .visible_items()
+            .filter_map(|entry| self.image_key(entry))
+            .filter(images::needs_load)
+            .map(|key| {
+                task::Task::perform(images::load(key), |_| MediaShelfMsg::ImageLoaded)
+            })
+            .collect();
+        task::Task::batch(tasks)
+    }
+}
+
+struct ShelfEntry {
+    item: ItemSummary,
+    subtext: String,
+}
+
+impl From<ItemSummary> for ShelfEntry {
+    fn from(item: ItemSummary) -> Self {
+        let subtext = item
+            .production_year
+            .map(|year| year.to_string())
+            .unwrap_or_default();
+        Self { item, subtext }
+    }
+}
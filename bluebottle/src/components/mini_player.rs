@@ -0,0 +1,192 @@
+//! The music player controls, shown along the bottom of the window while a
+//! queue is loaded.
+
+use std::time::Duration;
+
+use bluebottle_ui::{bar, button, color, icon, text};
+use iced::widget::{column, row, slider, space};
+use iced::{Center, Element, Length, task};
+
+use crate::models::music::RepeatMode;
+use crate::music::{self, PlayerCommand, PlayerState, PlayerStatus};
+use crate::storage::music_settings;
+use crate::{playback, view};
+
+#[derive(Default)]
+pub struct MiniPlayer {
+    state: PlayerState,
+    /// The volume shown while the slider is being dragged.
+    volume: f32,
+    /// The position in seconds the seek slider is being dragged to.
+    seeking: Option<f32>,
+    /// The artist and album of the current track.
+    subtitle: String,
+    /// The position and duration, i.e. `1:02 / 3:45`.
+    position_label: String,
+}
+
+#[derive(Clone)]
+pub enum MiniPlayerMsg {
+    State(Box<PlayerState>),
+    TogglePause,
+    Next,
+    Previous,
+    ToggleShuffle,
+    CycleRepeat,
+    Stop,
+    Seek(f32),
+    SeekReleased,
+    Volume(f32),
+    VolumeReleased,
+}
+
+impl view::View<MiniPlayerMsg> for MiniPlayer {
+    fn update(&mut self, message: MiniPlayerMsg) -> task::Task<MiniPlayerMsg> {
+        match message {
+            MiniPlayerMsg::State(state) => self.set_state(*state),
+            MiniPlayerMsg::TogglePause => music::send(PlayerCommand::TogglePause),
+            MiniPlayerMsg::Next => music::send(PlayerCommand::Next),
+            MiniPlayerMsg::Previous => music::send(PlayerCommand::Previous),
+            MiniPlayerMsg::ToggleShuffle => {
+                music::send(PlayerCommand::SetShuffle(!self.state.shuffle));
+            },
+            MiniPlayerMsg::CycleRepeat => {
+                music::send(PlayerCommand::SetRepeat(self.state.repeat.cycle()));
+            },
+            MiniPlayerMsg::Stop => music::send(PlayerCommand::Stop),
+            MiniPlayerMsg::Seek(position) => self.seeking = Some(position),
+            MiniPlayerMsg::SeekReleased => {
+                if let Some(position) = self.seeking.take() {
+                    let position = Duration::from_secs_f32(position);
+                    music::send(PlayerCommand::Seek(position));
+                }
+            },
+            MiniPlayerMsg::Volume(volume) => {
+                self.volume = volume;
+                music::send(PlayerCommand::SetVolume(volume));
+            },
+            MiniPlayerMsg::VolumeReleased => {
+                let mut settings = music_settings::load();
+                settings.volume = self.volume;
+                music_settings::save(&settings);
+            },
+        }
+
+        task::Task::none()
+    }
+
+    fn view(&self) -> Element<'_, MiniPlayerMsg> {
+        let Some(entry) = self.state.current.as_ref() else {
+            return space().into();
+        };
+
+        let mut details = column![
+            text::subheading(entry.track.name.as_str()),
+            text::paragraph(self.subtitle.as_str()).size(12),
+        ];
+        if let Some(error) = self.state.error.as_deref() {
+            details = details.push(text::label(error).color(color::ERROR));
+        }
+
+        let play_icon = match self.state.status {
+            PlayerStatus::Playing | PlayerStatus::Loading => "pause",
+            PlayerStatus::Paused | PlayerStatus::Stopped => "play_arrow",
+        };
+        let repeat_icon = match self.state.repeat {
+            RepeatMode::One => "repeat_one",
+            RepeatMode::Off | RepeatMode::All => "repeat",
+        };
+
+        let controls = row![
+            button::toggle_icon(
+                "shuffle",
+                "shuffle",
+                self.state.shuffle,
+                MiniPlayerMsg::ToggleShuffle,
+            ),
+            skip_button(
+                "skip_previous",
+                self.state.has_previous,
+                MiniPlayerMsg::Previous
+            ),
+            button::icon(play_icon, false, MiniPlayerMsg::TogglePause),
+            skip_button("skip_next", self.state.has_next, MiniPlayerMsg::Next),
+            button::toggle_icon(
+                repeat_icon,
+                repeat_icon,
+                self.state.repeat != RepeatMode::Off,
+                MiniPlayerMsg::CycleRepeat,
+            ),
+        ]
+        .spacing(8)
+        .align_y(Center);
+
+        let mut progress = row![text::label(self.position_label.as_str())]
+            .spacing(8)
+            .align_y(Center);
+        if let Some(duration) = self.state.duration {
+            let position = self.seeking.unwrap_or(self.state.position.as_secs_f32());
+            progress = progress.push(
+                slider(0.0..=duration.as_secs_f32(), position, MiniPlayerMsg::Seek)
+                    .on_release(MiniPlayerMsg::SeekReleased)
+                    .width(320),
+            );
+        }
+
+        let center = column![controls, progress].spacing(4).align_x(Center);
+
+        let volume = row![
+            icon::filled("volume_up").color(color::text_secondary()),
+            slider(0.0..=1.0, self.volume, MiniPlayerMsg::Volume)
+                .step(0.01)
+                .on_release(MiniPlayerMsg::VolumeReleased)
+                .width(120),
+            button::icon("stop", false, MiniPlayerMsg::Stop),
+        ]
+        .spacing(8)
+        .align_y(Center);
+
+        bar::bottom(details.width(Length::Fill), center, volume)
+    }
+}
+
+impl MiniPlayer {
+    fn set_state(&mut self, state: PlayerState) {
+        if state.current != self.state.current {
+            self.subtitle = state
+                .current
+                .as_ref()
+                .map(|entry| {
+                    [entry.track.artist.as_deref(), entry.track.album.as_deref()]
+                        .into_iter()
+                        .flatten()
+                        .collect::<Vec<_>>()
+                        .join(" - ")
+                })
+                .unwrap_or_default();
+        }
+
+        let position = playback::format_position(state.position);
+        self.position_label = match state.duration {
+            Some(duration) => {
+                format!("{position} / {}", playback::format_position(duration))
+            },
+            None => position,
+        };
+        self.volume = state.volume;
+        self.state = state;
+    }
+}
+
+/// A skip button, disabled when there is nothing to skip to.
+fn skip_button(
+    icon: &str,
+    enabled: bool,
+    message: MiniPlayerMsg,
+) -> Element<'_, MiniPlayerMsg> {
+    if enabled {
+        button::icon(icon, false, message).into()
+    } else {
+        button::disabled(None, Some(icon))
+    }
+}
@@ -1 +1,6 @@
+pub mod item_detail;
 pub mod jellyfin_onboard;
+pub mod library_toolbar;
+pub mod media_shelf;
+pub mod mini_player;
+pub mod subtitle_search;
@@ -0,0 +1,274 @@
+//! Searches the backend's subtitle providers for an item and downloads the chosen
+//! subtitles, storing them as sidecar files when the item's media is on this
+//! machine.
+
+use std::path::PathBuf;
+
+use bluebottle_ui::{button, input, scrollable, spinner, text};
+use iced::widget::{column, row};
+use iced::{Alignment, Element, Length, task};
+
+use crate::backends::{BackendId, registry};
+use crate::models::media::ItemId;
+use crate::models::subtitles::RemoteSubtitle;
+use crate::sidecar::{self, Sidecar};
+use crate::view::View;
+
+/// The format downloaded subtitles are stored as if the provider does not say.
+const DEFAULT_FORMAT: &str = "srt";
+
+pub struct SubtitleSearch {
+    backend_id: BackendId,
+    item_id: ItemId,
+    /// The item's media file, if it can be read on this machine.
+    media_path: Option<PathBuf>,
+    language: String,
+    results: Vec<RemoteSubtitle>,
+    searching: bool,
+    /// The ID of the subtitles being downloaded.
+    downloading: Option<String>,
+    sidecars: Vec<Sidecar>,
+    /// The outcome of the last search or download.
+    status: Option<String>,
+}
+
+#[derive(Clone)]
+pub enum SubtitleSearchMsg {
+    LanguageChanged(String),
+    Search,
+    ResultsLoaded(Result<Vec<RemoteSubtitle>, String>),
+    SidecarsLoaded(Vec<Sidecar>),
+    Download(RemoteSubtitle),
+    /// The subtitles were downloaded, along with a description of where to.
+    Downloaded(Result<String, String>),
+}
+
+impl View<SubtitleSearchMsg> for SubtitleSearch {
+    fn update(&mut self, message: SubtitleSearchMsg) -> task::Task<SubtitleSearchMsg> {
+        match message {
+            SubtitleSearchMsg::LanguageChanged(language) => self.language = language,
+            SubtitleSearchMsg::Search => return self.search(),
+            SubtitleSearchMsg::ResultsLoaded(result) => {
+                self.searching = false;
+                match result {
+                    Ok(results) => {
+                        self.status = results
+                            .is_empty()
+                            .then(|| "No subtitles found.".to_string());
+                        self.results = results;
+                    },
+                    Err(err) => {
+                        tracing::warn!(item_id = %self.item_id, error = %err, "failed to search subtitles");
+                        self.status = Some(format!("Search failed: {err}"));
+                    },
+                }
+            },
+            SubtitleSearchMsg::SidecarsLoaded(sidecars) => self.sidecars = sidecars,
+            SubtitleSearchMsg::Download(subtitle) => return self.download(subtitle),
+            SubtitleSearchMsg::Downloaded(result) => {
+                self.downloading = None;
+                match result {
+                    Ok(status) => {
+                        self.status = Some(status);
+                        return self.load_sidecars();
+                    },
+                    Err(err) => {
+                        tracing::warn!(item_id = %self.item_id, error = %err, "failed to download subtitles");
+                        self.status = Some(format!("Download failed: {err}"));
+                    },
+                }
+            },
+        }
+
+        task::Task::none()
+    }
+
+    fn view(&self) -> Element<'_, SubtitleSearchMsg> {
+        let search_button = if self.searching {
+            button::disabled(Some("Search"), Some("search"))
+        } else {
+            button::standard("Search", Some("search"), false, SubtitleSearchMsg::Search)
+                .into()
+        };
+        let search = row![
+            input::text_input(
+                "Language, i.e. eng",
+                &self.language,
+                SubtitleSearchMsg::LanguageChanged,
+            )
+            .on_submit(SubtitleSearchMsg::Search),
+            search_button,
+        ]
+        .spacing(8)
+        .align_y(Alignment::Center);
+
+        let mut content = column![text::subheading("Find Subtitles"), search].spacing(8);
+
+        if !self.sidecars.is_empty() {
+            let files = self.sidecars.iter().map(|sidecar| {
+                let language = sidecar.language.as_deref().unwrap_or("unknown");
+                row![
+                    text::paragraph(sidecar.file_name()).width(Length::Fill),
+                    text::label(format!("{language} · {}", sidecar.format)),
+                ]
+                .spacing(8)
+                .into()
+            });
+            content = content
+                .push(text::label("Next to the media file"))
+                .push(column(files).spacing(4));
+        }
+
+        if self.searching {
+            content = content.push(spinner::linear());
+        }
+        if let Some(status) = self.status.as_deref() {
+            content = content.push(text::paragraph(status));
+        }
+
+        let results = self.results.iter().map(|subtitle| {
+            let download: Element<'_, SubtitleSearchMsg> =
+                if self.downloading.as_ref() == Some(&subtitle.id) {
+                    spinner::linear().width(48).into()
+                } else if self.downloading.is_some() {
+                    button::disabled(None, Some("download"))
+                } else {
+                    button::icon(
+                        "download",
+                        false,
+                        SubtitleSearchMsg::Download(subtitle.clone()),
+                    )
+                    .into()
+                };
+
+            row![
+                column![
+                    text::paragraph(subtitle.name.as_str()),
+                    text::label(subtitle.details()),
+                ]
+                .spacing(2)
+                .width(Length::Fill),
+                download,
+            ]
+            .spacing(8)
+            .align_y(Alignment::Center)
+            .into()
+        });
+        if !self.results.is_empty() {
+            let results = column(results).spacing(8).padding([0, 8]);
+            content = content.push(scrollable::scrollable(results).height(240));
+        }
+        content.into()
+    }
+}
+
+impl SubtitleSearch {
+    /// Create the panel for an item, listing the sidecar subtitles next to its
+    /// media file if it is on this machine.
+    pub fn new(
+        backend_id: BackendId,
+        item_id: ItemId,
+        media_path: Option<PathBuf>,
+        language: String,
+    ) -> (Self, task::Task<SubtitleSearchMsg>) {
+        let media_path = media_path.filter(|path| sidecar::is_local(path));
+        let search = Self {
+            backend_id,
+            item_id,
+            media_path,
+            language,
+            results: Vec::new(),
+            searching: false,
+            downloading: None,
+            sidecars: Vec::new(),
+            status: None,
+        };
+        let task = search.load_sidecars();
+        (search, task)
+    }
+
+    fn search(&mut self) -> task::Task<SubtitleSearchMsg> {
+        let language = self.language.trim().to_ascii_lowercase();
+        if language.is_empty() || self.searching {
+            return task::Task::none();
+        }
+        let Some(backend) = registry::get(self.backend_id) else {
+            return task::Task::none();
+        };
+
+        self.searching = true;
+        self.status = None;
+        self.results.clear();
+
+        let item_id = self.item_id.clone();
+        let fut = async move {
+            backend
+                .search_subtitles(item_id, language)
+                .await
+                .map_err(|err| err.to_string())
+        };
+        task::Task::perform(fut, SubtitleSearchMsg::ResultsLoaded)
+    }
+
+    /// Download the subtitles, writing them next to the media file if it is on
+    /// this machine, otherwise letting the backend store them.
+    fn download(&mut self, subtitle: RemoteSubtitle) -> task::Task<SubtitleSearchMsg> {
+        if self.downloading.is_some() {
+            return task::Task::none();
+        }
+        let Some(backend) = registry::get(self.backend_id) else {
+            return task::Task::none();
+        };
+
+        self.downloading = Some(subtitle.id.clone());
+        self.status = None;
+
+        let item_id = self.item_id.clone();
+        let media_path = self.media_path.clone();
+        let fut = async move {
+            let Some(media_path) = media_path else {
+                backend
+                    .download_subtitle(item_id, subtitle.id)
+                    .await
+                    .map_err(|err| err.to_string())?;
+                return Ok(format!("Downloaded {}.", subtitle.name));
+            };
+
+            let data = backend
+                .subtitle_data(subtitle.id)
+                .await
+                .map_err(|err| err.to_string())?;
+            let sidecar = tokio::task::spawn_blocking(move || {
+                let format = subtitle.format.as_deref().unwrap_or(DEFAULT_FORMAT);
+                sidecar::write(&media_path, subtitle.language.as_deref(), format, &data)
+            })
+            .await
+            .map_err(|err| err.to_string())?
+            .map_err(|err| err.to_string())?;
+
+            // The backend picks up the new file once it rescans the item.
+            backend
+                .refresh_item(item_id)
+                .await
+                .map_err(|err| err.to_string())?;
+            Ok::<_, String>(format!("Saved {}.", sidecar.file_name()))
+        };
+        task::Task::perform(fut, SubtitleSearchMsg::Downloaded)
+    }
+
+    fn load_sidecars(&self) -> task::Task<SubtitleSearchMsg> {
+        let Some(media_path) = self.media_path.clone() else {
+            return task::Task::none();
+        };
+
+        let fut = async move {
+            tokio::task::spawn_blocking(move || sidecar::find(&media_path))
+                .await
+                .map_err(|err| err.to_string())
+                .and_then(|result| result.map_err(|err| err.to_string()))
+                .inspect_err(|err| tracing::warn!(error = %err, "failed to find sidecar subtitles"))
+                .unwrap_or_default()
+        };
+        task::Task::perform(fut, SubtitleSearchMsg::SidecarsLoaded)
+    }
+}
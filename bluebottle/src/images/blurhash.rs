@@ -0,0 +1,136 @@
+//! A decoder for [BlurHash](https://blurha.sh) placeholders.
+//!
+//! A BlurHash encodes the first few cosine components of an image as a short
+//! base83 string, which decodes into a blurred approximation of the image.
+
+use std::f32::consts::PI;
+
+/// The characters of the base83 alphabet, in order of value.
+const BASE83: &[u8; 83] =
+    b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz#$%*+,-.:;=?@[]^_{|}~";
+
+/// Decode the hash into `width` by `height` RGBA pixels, returning `None` if
+/// the hash is malformed.
+pub fn decode(hash: &str, width: u32, height: u32) -> Option<Vec<u8>> {
+    let hash = hash.as_bytes();
+    if hash.len() < 6 || width == 0 || height == 0 {
+        return None;
+    }
+
+    let size_flag = decode_base83(&hash[..1])?;
+    let num_x = (size_flag % 9 + 1) as usize;
+    let num_y = (size_flag / 9 + 1) as usize;
+    if hash.len() != 4 + 2 * num_x * num_y {
+        return None;
+    }
+
+    let quantised_max = decode_base83(&hash[1..2])?;
+    let max_value = (quantised_max + 1) as f32 / 166.0;
+
+    let mut colours = Vec::with_capacity(num_x * num_y);
+    colours.push(decode_dc(decode_base83(&hash[2..6])?));
+    for component in hash[6..].chunks_exact(2) {
+        colours.push(decode_ac(decode_base83(component)?, max_value));
+    }
+
+    let (width, height) = (width as usize, height as usize);
+    let cosines_x = cosines(width, num_x);
+    let cosines_y = cosines(height, num_y);
+
+    let mut pixels = Vec::with_capacity(width * height * 4);
+    for y in 0..height {
+        for x in 0..width {
+            let mut pixel = [0.0; 3];
+            for j in 0..num_y {
+                for i in 0..num_x {
+                    let basis = cosines_x[x * num_x + i] * cosines_y[y * num_y + j];
+                    let colour = colours[j * num_x + i];
+                    for (channel, value) in pixel.iter_mut().zip(colour) {
+                        *channel += value * basis;
+                    }
+                }
+            }
+            pixels.extend(pixel.map(linear_to_srgb));
+            pixels.push(u8::MAX);
+        }
+    }
+
+    Some(pixels)
+}
+
+/// Returns `cos(PI * position * component / size)` for every position and
+/// component, indexed by `position * components + component`.
+fn cosines(size: usize, components: usize) -> Vec<f32> {
+    (0..size)
+        .flat_map(|position| {
+            (0..components).map(move |component| {
+                (PI * position as f32 * component as f32 / size as f32).cos()
+            })
+        })
+        .collect()
+}
+
+fn decode_base83(characters: &[u8]) -> Option<u32> {
+    characters.iter().try_fold(0, |value, character| {
+        let digit = BASE83.iter().position(|c| c == character)?;
+        Some(value * 83 + digit as u32)
+    })
+}
+
+fn decode_dc(value: u32) -> [f32; 3] {
+    [value >> 16, (value >> 8) & 0xFF, value & 0xFF].map(srgb_to_linear)
+}
+
+fn decode_ac(value: u32, max_value: f32) -> [f32; 3] {
+    [value / (19 * 19), (value / 19) % 19, value % 19].map(|quantised| {
+        let normalised = (quantised as f32 - 9.0) / 9.0;
+        normalised.signum() * normalised.powi(2) * max_value
+    })
+}
+
+fn srgb_to_linear(value: u32) -> f32 {
+    let value = value as f32 / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> u8 {
+    let value = value.clamp(0.0, 1.0);
+    let srgb = if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+    (srgb * 255.0 + 0.5) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        let pixels = decode("LEHV6nWB2yk8pyo0adR*.7kCMdnj", 4, 3).unwrap();
+        #[rustfmt::skip]
+        let expected = [
+            135, 164, 178, 255, 162, 173, 177, 255, 182, 180, 172, 255, 160, 172, 175, 255,
+            124, 155, 170, 255, 148, 149, 155, 255, 165, 145, 135, 255, 147, 152, 155, 255,
+            125, 145, 155, 255, 145, 135, 133, 255, 164, 131, 105, 255, 148, 140, 135, 255,
+        ];
+        assert_eq!(pixels.len(), expected.len());
+        for (actual, expected) in pixels.iter().zip(expected) {
+            assert!(actual.abs_diff(expected) <= 1, "{pixels:?}");
+        }
+    }
+
+    #[test]
+    fn test_decode_malformed() {
+        assert!(decode("", 4, 3).is_none());
+        assert!(decode("LEHV6nWB2yk8pyo0adR*.7kCMdn", 4, 3).is_none());
+        assert!(decode("LEHV6nWB2yk8pyo0adR*.7kCMd\"j", 4, 3).is_none());
+        assert!(decode("LEHV6nWB2yk8pyo0adR*.7kCMdnj", 0, 3).is_none());
+    }
+}
@@ -0,0 +1,307 @@
+//! The image service, loading item artwork for display.
+//!
+//! Images are fetched from the backend sized for where they are displayed and
+//! kept in the asset cache, then decoded and resized to the exact size on a pool
+//! of blocking workers. Decoded images are kept in memory, and concurrent loads
+//! of the same image share a single fetch.
+//!
+//! While an image loads, its BlurHash is displayed in its place.
+
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+
+use bluebottle_ui::image::Handle;
+use iced::futures::FutureExt;
+use iced::futures::future::{BoxFuture, Shared};
+use image::imageops::FilterType;
+use lru::LruCache;
+use parking_lot::Mutex;
+use tokio::sync::Semaphore;
+
+use crate::backends::{BackendId, registry};
+use crate::models::image::{ImageKind, ImageRequest};
+use crate::models::media::ItemId;
+use crate::storage::asset_cache;
+
+mod blurhash;
+
+/// The number of decoded images kept in memory.
+const MEMORY_CAPACITY: NonZeroUsize = NonZeroUsize::new(512).unwrap();
+/// The number of decoded placeholders kept in memory.
+const PLACEHOLDER_CAPACITY: NonZeroUsize = NonZeroUsize::new(1024).unwrap();
+/// The width and height placeholders are decoded at, they are stretched to
+/// fill the space of the image.
+const PLACEHOLDER_SIZE: u32 = 32;
+/// The JPEG quality images are requested at.
+const IMAGE_QUALITY: u8 = 90;
+/// The most images decoded at once.
+const MAX_DECODE_WORKERS: usize = 4;
+/// How long to wait before fetching an image again after the backend could not
+/// be reached.
+const RETRY_DELAY: Duration = Duration::from_secs(60);
+
+static MEMORY: LazyLock<Mutex<LruCache<ImageKey, MemoryEntry>>> =
+    LazyLock::new(|| Mutex::new(LruCache::new(MEMORY_CAPACITY)));
+static PLACEHOLDERS: LazyLock<Mutex<LruCache<String, Option<Handle>>>> =
+    LazyLock::new(|| Mutex::new(LruCache::new(PLACEHOLDER_CAPACITY)));
+static INFLIGHT: LazyLock<Mutex<HashMap<ImageKey, SharedLoad>>> =
+    LazyLock::new(Default::default);
+static DECODE_WORKERS: LazyLock<Semaphore> = LazyLock::new(|| {
+    let workers = std::thread::available_parallelism()
+        .map(NonZeroUsize::get)
+        .unwrap_or(1)
+        .min(MAX_DECODE_WORKERS);
+    Semaphore::new(workers)
+});
+
+type SharedLoad = Shared<BoxFuture<'static, Option<Handle>>>;
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+/// Identifies an item's artwork at the size it is displayed.
+pub struct ImageKey {
+    pub backend_id: BackendId,
+    pub item_id: ItemId,
+    pub kind: ImageKind,
+    /// The width the image is displayed at in pixels.
+    pub width: u32,
+    /// The height the image is displayed at in pixels.
+    pub height: u32,
+}
+
+impl ImageKey {
+    /// The key of the item's primary image displayed at `(width, height)`.
+    pub fn primary(
+        backend_id: BackendId,
+        item_id: ItemId,
+        (width, height): (u32, u32),
+    ) -> Self {
+        Self {
+            backend_id,
+            item_id,
+            kind: ImageKind::Primary,
+            width,
+            height,
+        }
+    }
+
+    /// Returns the path of the image in the asset cache.
+    fn cache_path(&self) -> String {
+        format!(
+            "images/{}/{}/{}/{}x{}",
+            self.backend_id,
+            self.item_id,
+            self.kind.as_str(),
+            self.width,
+            self.height,
+        )
+    }
+}
+
+#[derive(Clone)]
+enum MemoryEntry {
+    Ready(Handle),
+    /// The item has no artwork of the kind, or it could not be decoded.
+    Missing,
+    /// The backend could not be reached at the given time.
+    Failed(Instant),
+}
+
+/// Returns the decoded image, if it has been loaded.
+pub fn get(key: &ImageKey) -> Option<Handle> {
+    match MEMORY.lock().get(key)? {
+        MemoryEntry::Ready(handle) => Some(handle.clone()),
+        MemoryEntry::Missing | MemoryEntry::Failed(_) => None,
+    }
+}
+
+/// Returns the BlurHash decoded into a placeholder image, or `None` if the hash
+/// is malformed.
+///
+/// Placeholders are cached so the same hash always gives the same handle.
+pub fn placeholder(hash: &str) -> Option<Handle> {
+    let mut placeholders = PLACEHOLDERS.lock();
+    if let Some(handle) = placeholders.get(hash) {
+        return handle.clone();
+    }
+
+    let handle = blurhash::decode(hash, PLACEHOLDER_SIZE, PLACEHOLDER_SIZE)
+        .map(|pixels| Handle::from_rgba(PLACEHOLDER_SIZE, PLACEHOLDER_SIZE, pixels));
+    placeholders.put(hash.to_string(), handle.clone());
+    handle
+}
+
+/// Returns whether the image should be loaded, i.e. it is not already loaded,
+/// being loaded or known to be missing.
+pub fn needs_load(key: &ImageKey) -> bool {
+    if INFLIGHT.lock().contains_key(key) {
+        return false;
+    }
+    match MEMORY.lock().peek(key) {
+        None => true,
+        Some(MemoryEntry::Ready(_) | MemoryEntry::Missing) => false,
+        Some(MemoryEntry::Failed(failed_at)) => failed_at.elapsed() >= RETRY_DELAY,
+    }
+}
+
+/// Load the image, returning `None` if it is missing or could not be fetched.
+///
+/// Images are read from memory, the asset cache or the backend, in that order.
+pub async fn load(key: ImageKey) -> Option<Handle> {
+    if let Some(handle) = get(&key) {
+        return Some(handle);
+    }
+
+    let load = INFLIGHT
+        .lock()
+        .entry(key.clone())
+        .or_insert_with(|| load_uncached(key).boxed().shared())
+        .clone();
+    load.await
+}
+
+async fn load_uncached(key: ImageKey) -> Option<Handle> {
+    let entry = fetch_and_decode(&key).await;
+    let handle = match &entry {
+        MemoryEntry::Ready(handle) => Some(handle.clone()),
+        MemoryEntry::Missing | MemoryEntry::Failed(_) => None,
+    };
+
+    // Stored before the load is removed, so later loads never miss both.
+    MEMORY.lock().put(key.clone(), entry);
+    INFLIGHT.lock().remove(&key);
+    handle
+}
+
+async fn fetch_and_decode(key: &ImageKey) -> MemoryEntry {
+    let path = key.cache_path();
+    let cached = {
+        let path = path.clone();
+        tokio::task::spawn_blocking(move || asset_cache::try_get(&path))
+            .await
+            .ok()
+            .flatten()
+    };
+
+    let data = match cached {
+        Some(data) => data,
+        None => match fetch(key).await {
+            Ok(data) => {
+                let cached = data.clone();
+                let _ = tokio::task::spawn_blocking(move || {
+                    asset_cache::insert(&path, &cached)
+                })
+                .await;
+                data
+            },
+            Err(entry) => return entry,
+        },
+    };
+
+    let (width, height) = (key.width, key.height);
+    let Ok(_permit) = DECODE_WORKERS.acquire().await else {
+        return MemoryEntry::Missing;
+    };
+    match tokio::task::spawn_blocking(move || decode(&data, width, height)).await {
+        Ok(Ok(handle)) => MemoryEntry::Ready(handle),
+        Ok(Err(err)) => {
+            tracing::warn!(item_id = %key.item_id, error = %err, "failed to decode image");
+            MemoryEntry::Missing
+        },
+        Err(err) => {
+            tracing::error!(item_id = %key.item_id, error = %err, "image decode panicked");
+            MemoryEntry::Missing
+        },
+    }
+}
+
+/// Fetch the image from its backend, returning the entry to remember if it
+/// could not be fetched.
+async fn fetch(key: &ImageKey) -> Result<Vec<u8>, MemoryEntry> {
+    let Some(backend) = registry::get(key.backend_id) else {
+        return Err(MemoryEntry::Missing);
+    };
+
+    let request = ImageRequest {
+        item_id: key.item_id.clone(),
+        kind: key.kind,
+        width: key.width,
+        height: key.height,
+        quality: IMAGE_QUALITY,
+    };
+    backend.image(request).await.map_err(|err| {
+        if err.is_retryable() {
+            tracing::debug!(item_id = %key.item_id, error = %err, "failed to fetch image");
+            MemoryEntry::Failed(Instant::now())
+        } else {
+            MemoryEntry::Missing
+        }
+    })
+}
+
+/// Decode the image, scaling and cropping it to cover exactly `width` by `height`.
+fn decode(data: &[u8], width: u32, height: u32) -> Result<Handle, image::ImageError> {
+    let image = image::load_from_memory(data)?;
+    let image = if image.width() == width && image.height() == height {
+        image
+    } else {
+        image.resize_to_fill(width, height, FilterType::Triangle)
+    };
+    let pixels = image.into_rgba8();
+    Ok(Handle::from_rgba(width, height, pixels.into_raw()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn encoded_png(width: u32, height: u32) -> Vec<u8> {
+        let image =
+            image::RgbaImage::from_pixel(width, height, image::Rgba([255, 0, 0, 255]));
+        let mut data = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut data), image::ImageFormat::Png)
+            .unwrap();
+        data
+    }
+
+    #[test]
+    fn test_decode_resizes_to_cover() {
+        let handle = decode(&encoded_png(300, 200), 150, 150).unwrap();
+        match handle {
+            Handle::Rgba {
+                width,
+                height,
+                pixels,
+                ..
+            } => {
+                assert_eq!((width, height), (150, 150));
+                assert_eq!(pixels.len(), 150 * 150 * 4);
+            },
+            _ => panic!("expected decoded pixels"),
+        }
+
+        assert!(decode(b"not an image", 150, 150).is_err());
+    }
+
+    #[test]
+    fn test_placeholder_cached() {
+        let hash = "LEHV6nWB2yk8pyo0adR*.7kCMdnj";
+        let handle = placeholder(hash).unwrap();
+        assert_eq!(placeholder(hash).unwrap().id(), handle.id());
+        assert!(placeholder("malformed").is_none());
+    }
+
+    #[test]
+    fn test_cache_path() {
+        let backend_id = BackendId::nil();
+        let key = ImageKey::primary(backend_id, "item".to_string(), (152, 224));
+        assert_eq!(
+            key.cache_path(),
+            format!("images/{backend_id}/item/primary/152x224"),
+        );
+    }
+}
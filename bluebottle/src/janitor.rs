@@ -0,0 +1,102 @@
+//! The cache janitor, keeping the asset cache within the limits the user set.
+//!
+//! On startup the cache index is reconciled with the cache directory, then the
+//! cache is cleaned straight away, periodically after that, and whenever the
+//! cache settings change.
+
+use std::time::Duration;
+
+use iced::Subscription;
+use iced::futures::channel::mpsc;
+use iced::futures::{SinkExt, Stream};
+use tokio::sync::broadcast;
+
+use crate::storage::asset_cache::{self, AssetCacheStats};
+use crate::storage::{cache_settings, settings};
+
+/// How long to wait between clean ups of the asset cache.
+const CLEAN_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone)]
+/// An update from the cache janitor.
+pub enum JanitorEvent {
+    /// The asset cache was cleaned, carrying its size afterwards.
+    Cleaned(AssetCacheStats),
+}
+
+/// Runs the cache janitor.
+pub fn subscription() -> Subscription<JanitorEvent> {
+    Subscription::run(clean_stream)
+}
+
+fn clean_stream() -> impl Stream<Item = JanitorEvent> {
+    iced::stream::channel(1, async |mut output: mpsc::Sender<JanitorEvent>| {
+        let mut changes = settings::subscribe();
+
+        match tokio::task::spawn_blocking(asset_cache::reconcile).await {
+            Ok(Ok(())) => {},
+            Ok(Err(err)) => {
+                tracing::warn!(error = %err, "failed to reconcile asset cache index")
+            },
+            Err(err) => tracing::error!(error = %err, "asset cache reconcile panicked"),
+        }
+
+        loop {
+            if let Some(stats) = clean().await
+                && output.send(JanitorEvent::Cleaned(stats)).await.is_err()
+            {
+                return;
+            }
+
+            let _ = tokio::time::timeout(
+                CLEAN_INTERVAL,
+                cache_settings_changed(&mut changes),
+            )
+            .await;
+        }
+    })
+}
+
+/// Clean the asset cache, returning its size afterwards.
+async fn clean() -> Option<AssetCacheStats> {
+    let result = tokio::task::spawn_blocking(|| {
+        let report = asset_cache::clean(&cache_settings::load())?;
+        if report.removed > 0 {
+            tracing::info!(
+                removed = report.removed,
+                bytes_freed = report.bytes_freed,
+                "cleaned asset cache",
+            );
+        }
+        asset_cache::stats()
+    })
+    .await;
+
+    match result {
+        Ok(Ok(stats)) => Some(stats),
+        Ok(Err(err)) => {
+            tracing::warn!(error = %err, "failed to clean asset cache");
+            None
+        },
+        Err(err) => {
+            tracing::error!(error = %err, "asset cache clean up panicked");
+            None
+        },
+    }
+}
+
+/// Wait until the cache settings are saved.
+async fn cache_settings_changed(
+    changes: &mut broadcast::Receiver<settings::SettingChange>,
+) {
+    loop {
+        match changes.recv().await {
+            Ok(change) if cache_settings::CACHE_SETTINGS.is_changed(&change) => return,
+            Ok(_) => {},
+            // A missed change may have been to the cache settings.
+            Err(broadcast::error::RecvError::Lagged(_)) => return,
+            // Settings are no longer announced, fall back to the interval.
+            Err(broadcast::error::RecvError::Closed) => std::future::pending().await,
+        }
+    }
+}
@@ -0,0 +1,162 @@
+//! Live updates pushed by each backend, keeping the local copy in step with
+//! changes made on other devices and handling remote control commands.
+//!
+//! Each backend keeps a single connection open, reconnecting with backoff when it
+//! drops.
+
+use std::time::Duration;
+
+use iced::Subscription;
+use iced::futures::channel::mpsc;
+use iced::futures::{SinkExt, Stream};
+use tokio::time::Instant;
+
+use crate::backends::{BackendId, registry};
+use crate::models::live::{LiveEvent, RemoteCommand};
+use crate::models::media::ItemId;
+use crate::storage::{content_cache, playback_progress};
+use crate::{playback, sync};
+
+/// The delay before the first reconnect attempt.
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+/// The longest delay between reconnect attempts.
+const MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+/// Connections lasting longer than this reset the backoff.
+const STABLE_CONNECTION: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone)]
+/// A live update which the UI needs to act on.
+pub enum LiveUpdate {
+    /// The local user state of the backend's items changed.
+    StateChanged(BackendId),
+    /// The backend changed the items, i.e. their metadata or files.
+    ItemsUpdated {
+        backend_id: BackendId,
+        item_ids: Vec<ItemId>,
+    },
+    /// Another client asked us to play an item.
+    Play {
+        backend_id: BackendId,
+        item_id: ItemId,
+        start_position: Duration,
+    },
+}
+
+/// Listens for live updates from every registered backend.
+pub fn subscription() -> Subscription<LiveUpdate> {
+    let subscriptions = registry::all()
+        .into_iter()
+        .map(|(backend_id, _)| Subscription::run_with(backend_id, live_stream));
+    Subscription::batch(subscriptions)
+}
+
+fn live_stream(backend_id: &BackendId) -> impl Stream<Item = LiveUpdate> + use<> {
+    let backend_id = *backend_id;
+    iced::stream::channel(32, async move |mut output: mpsc::Sender<LiveUpdate>| {
+        let mut backoff = MIN_BACKOFF;
+
+        loop {
+            match registry::get(backend_id) {
+                Some(backend) => {
+                    let connected_at = Instant::now();
+                    let (events_tx, mut events) = tokio::sync::mpsc::channel(32);
+
+                    let handle_events = async {
+                        while let Some(event) = events.recv().await {
+                            handle_event(backend_id, event, &mut output).await;
+                        }
+                    };
+                    let (result, ()) = iced::futures::join!(
+                        backend.live_updates(events_tx),
+                        handle_events
+                    );
+
+                    if connected_at.elapsed() >= STABLE_CONNECTION {
+                        backoff = MIN_BACKOFF;
+                    }
+                    match result {
+                        Ok(()) => {
+                            tracing::info!(backend_id = %backend_id, "live updates closed")
+                        },
+                        Err(err) => {
+                            tracing::warn!(backend_id = %backend_id, error = %err, retry_in = ?backoff, "live updates failed")
+                        },
+                    }
+                },
+                None => {
+                    tracing::warn!(backend_id = %backend_id, "backend no longer exists")
+                },
+            }
+
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    })
+}
+
+async fn handle_event(
+    backend_id: BackendId,
+    event: LiveEvent,
+    output: &mut mpsc::Sender<LiveUpdate>,
+) {
+    match event {
+        LiveEvent::UserDataChanged(items) => {
+            let mut changed = false;
+            for (item_id, user_data) in items {
+                match playback_progress::overwrite(backend_id, item_id, user_data).await
+                {
+                    Ok(()) => changed = true,
+                    Err(err) => {
+                        tracing::warn!(error = %err, "failed to update local user data")
+                    },
+                }
+            }
+
+            if changed {
+                let _ = output.send(LiveUpdate::StateChanged(backend_id)).await;
+            }
+        },
+        LiveEvent::LibraryChanged {
+            added,
+            updated,
+            removed,
+        } => {
+            tracing::debug!(
+                backend_id = %backend_id,
+                added = added.len(),
+                updated = updated.len(),
+                removed = removed.len(),
+                "backend libraries changed",
+            );
+            content_cache::purge_backend(backend_id).await;
+            sync::request_sync(backend_id);
+
+            if !updated.is_empty() {
+                let update = LiveUpdate::ItemsUpdated {
+                    backend_id,
+                    item_ids: updated,
+                };
+                let _ = output.send(update).await;
+            }
+        },
+        LiveEvent::Remote(RemoteCommand::Play {
+            item_ids,
+            start_position,
+        }) => {
+            let Some(item_id) = item_ids.into_iter().next() else {
+                return;
+            };
+            let update = LiveUpdate::Play {
+                backend_id,
+                item_id,
+                start_position,
+            };
+            let _ = output.send(update).await;
+        },
+        LiveEvent::Remote(command) => {
+            if !playback::send_command(command) {
+                tracing::debug!("ignoring remote command, nothing is playing");
+            }
+        },
+    }
+}
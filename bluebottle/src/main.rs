@@ -1,17 +1,34 @@
 use std::path::PathBuf;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use snafu::ResultExt;
 
+use crate::backends::BackendId;
+
 mod app;
 mod backends;
+mod backlog;
 mod components;
+mod images;
+mod janitor;
+mod live;
 mod models;
+mod mpris;
+mod music;
 mod navigator;
+mod playback;
 mod screen;
+mod sidecar;
 mod storage;
+mod sync;
 mod view;
 
+/// The environment variable holding the passphrase encrypting backend secrets
+/// when there is no desktop keyring.
+///
+/// Without a keyring, secrets can only be stored and read when this is set.
+const SECRETS_PASSPHRASE_ENV: &str = "BLUEBOTTLE_SECRETS_PASSPHRASE";
+
 #[derive(Debug, Parser)]
 struct Args {
     #[arg(long)]
@@ -22,6 +39,32 @@ struct Args {
     ///
     /// If this is not set, it will use the conventional OS paths.
     storage_path: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+/// Commands run instead of the app.
+enum Command {
+    /// Export the cached content of a backend to an archive, which can be
+    /// imported to seed another install.
+    ExportCache {
+        /// The path to write the archive to.
+        output: PathBuf,
+        #[arg(long)]
+        /// The backend to export, required if more than one backend is configured.
+        backend_id: Option<BackendId>,
+    },
+    /// Import an archive written by `export-cache`, replacing the cached content
+    /// of the backend.
+    ImportCache {
+        /// The path of the archive to import.
+        input: PathBuf,
+        #[arg(long)]
+        /// The backend to import the content for, required if more than one
+        /// backend is configured.
+        backend_id: Option<BackendId>,
+    },
 }
 
 #[snafu::report]
@@ -42,16 +85,106 @@ fn main() -> Result<(), snafu::Whatever> {
         unsafe { std::env::set_var("WGPU_POWER_PREF", "low") };
     }
 
+    // The passphrase of the encrypted secrets file is only read from the
+    // environment, as arguments are visible to every user of the system. It is
+    // removed so it is not passed on to the player.
+    let secrets_passphrase = std::env::var(SECRETS_PASSPHRASE_ENV).ok();
+    unsafe { std::env::remove_var(SECRETS_PASSPHRASE_ENV) };
+
     tracing_subscriber::fmt::init();
 
     storage::init_storage(args.storage_path)
         .whatever_context("failed to init app storage")?;
+    if let Some(passphrase) = secrets_passphrase {
+        storage::secrets::set_passphrase(passphrase);
+    }
+
+    if let Some(command) = args.command {
+        let result = run_command(command);
+        storage::shutdown_storage();
+        return result;
+    }
 
     tracing::info!("starting Bluebottle");
 
     app::run_app()?;
+    music::shutdown();
+    // Last, so the state saved while shutting down is written.
+    storage::shutdown_storage();
 
     tracing::info!("system exit complete");
 
     Ok(())
 }
+
+/// Run a command against the app storage.
+fn run_command(command: Command) -> Result<(), snafu::Whatever> {
+    match command {
+        Command::ExportCache { output, backend_id } => {
+            let backend_id = match backend_id {
+                Some(backend_id) => backend_id,
+                None => only_backend_id()?,
+            };
+            let summary = storage::cache_archive::export(backend_id, &output)
+                .whatever_context("failed to export cache")?;
+            tracing::info!(
+                backend_id = %summary.backend_id,
+                rows = summary.rows,
+                assets = summary.assets,
+                path = %output.display(),
+                "exported cache",
+            );
+        },
+        Command::ImportCache { input, backend_id } => {
+            // Checked first, content imported for any other backend is never read.
+            let backend_id = match backend_id {
+                Some(backend_id) if configured_backend_ids()?.contains(&backend_id) => {
+                    backend_id
+                },
+                Some(backend_id) => {
+                    snafu::whatever!("backend ({backend_id}) is not configured")
+                },
+                None => only_backend_id()?,
+            };
+            let summary = storage::cache_archive::import(&input, backend_id)
+                .whatever_context("failed to import cache")?;
+            tracing::info!(
+                backend_id = %summary.backend_id,
+                rows = summary.rows,
+                assets = summary.assets,
+                "imported cache",
+            );
+        },
+    }
+
+    Ok(())
+}
+
+/// Returns the ID of the only configured backend.
+fn only_backend_id() -> Result<BackendId, snafu::Whatever> {
+    match configured_backend_ids()?.as_slice() {
+        [backend_id] => Ok(*backend_id),
+        [] => snafu::whatever!("no backends are configured"),
+        backend_ids => {
+            let backend_ids: Vec<_> =
+                backend_ids.iter().map(ToString::to_string).collect();
+            snafu::whatever!(
+                "multiple backends are configured, pass --backend-id with one of: {}",
+                backend_ids.join(", "),
+            )
+        },
+    }
+}
+
+fn configured_backend_ids() -> Result<Vec<BackendId>, snafu::Whatever> {
+    let result = storage::with_durable_state(|state| {
+        state
+            .read_all_backend_init_state()
+            .map(|states| states.into_iter().map(|state| state.id).collect())
+            .map_err(|err| err.to_string())
+    });
+    match result {
+        Ok(backend_ids) => Ok(backend_ids),
+        Err(err) => snafu::whatever!("failed to read backends: {err}"),
+    }
+}
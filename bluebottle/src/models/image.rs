@@ -0,0 +1,38 @@
+use super::media::ItemId;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+/// The artwork of an item.
+pub enum ImageKind {
+    /// The poster of a movie or series, or the cover of an album.
+    Primary,
+    /// A landscape still, i.e. of an episode.
+    Thumb,
+    /// A wide background image.
+    Backdrop,
+}
+
+impl ImageKind {
+    /// Returns the image kind as a static string.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Primary => "primary",
+            Self::Thumb => "thumb",
+            Self::Backdrop => "backdrop",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+/// Requests an item's artwork sized for where it is displayed.
+///
+/// The size and quality are hints, backends may return a larger image.
+pub struct ImageRequest {
+    pub item_id: ItemId,
+    pub kind: ImageKind,
+    /// The width the image is displayed at in pixels.
+    pub width: u32,
+    /// The height the image is displayed at in pixels.
+    pub height: u32,
+    /// The JPEG quality of the image, from `0` to `100`.
+    pub quality: u8,
+}
@@ -0,0 +1,51 @@
+use std::time::Duration;
+
+use super::media::ItemId;
+
+#[derive(
+    Debug, Clone, PartialEq, serde_derive::Serialize, serde_derive::Deserialize,
+)]
+#[serde(tag = "type", rename_all = "snake_case")]
+/// A change the user made to their own state of an item.
+///
+/// Interactions set absolute values rather than toggling them, so applying the
+/// same interaction more than once has no additional effect. This is what makes
+/// retrying them safe, Jellyfin has no way to deduplicate requests.
+pub enum Interaction {
+    /// The item was marked as played or unplayed.
+    Played { item_id: ItemId, played: bool },
+    /// Playback of the item stopped at the position.
+    Progress { item_id: ItemId, position: Duration },
+    /// The item was added to or removed from the user's favourites.
+    Favourite { item_id: ItemId, favourite: bool },
+    /// The user rated the item out of 10, or cleared their rating.
+    Rating {
+        item_id: ItemId,
+        rating: Option<f32>,
+    },
+}
+
+impl Interaction {
+    /// Returns the item the interaction applies to.
+    pub fn item_id(&self) -> &str {
+        match self {
+            Self::Played { item_id, .. } => item_id,
+            Self::Progress { item_id, .. } => item_id,
+            Self::Favourite { item_id, .. } => item_id,
+            Self::Rating { item_id, .. } => item_id,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// The user's state of an item as held by the backend.
+pub struct UserData {
+    pub played: bool,
+    /// The position playback can be resumed from.
+    pub position: Duration,
+    pub favourite: bool,
+    /// The user's rating of the item out of 10, if they rated it.
+    pub rating: Option<f32>,
+    /// When the item was last played, in milliseconds since the unix epoch.
+    pub last_played_at: Option<i64>,
+}
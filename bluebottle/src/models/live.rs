@@ -0,0 +1,52 @@
+use std::time::Duration;
+
+use super::interaction::UserData;
+use super::media::ItemId;
+
+#[derive(Debug, Clone, PartialEq)]
+/// An update pushed by the backend while connected.
+pub enum LiveEvent {
+    /// The user's state of the items changed, i.e. they were played on another
+    /// device.
+    UserDataChanged(Vec<(ItemId, UserData)>),
+    /// Items were added, changed or removed from the libraries.
+    LibraryChanged {
+        added: Vec<ItemId>,
+        updated: Vec<ItemId>,
+        removed: Vec<ItemId>,
+    },
+    /// Another client asked us to do something.
+    Remote(RemoteCommand),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A command sent by another client to control playback.
+pub enum RemoteCommand {
+    /// Play the items, starting from the first.
+    Play {
+        item_ids: Vec<ItemId>,
+        start_position: Duration,
+    },
+    Pause,
+    Unpause,
+    PlayPause,
+    Stop,
+    /// Seek to the absolute position.
+    Seek(Duration),
+    /// Select the audio stream with the backend's index.
+    SetAudioStreamIndex(u32),
+    /// Select the subtitle stream with the backend's index, or turn subtitles off.
+    SetSubtitleStreamIndex(Option<u32>),
+    /// Set the volume as a percentage.
+    SetVolume(u8),
+    VolumeUp,
+    VolumeDown,
+    Mute,
+    Unmute,
+    ToggleMute,
+    /// Show a message over the video.
+    DisplayMessage {
+        header: String,
+        text: String,
+    },
+}
@@ -1,3 +1,127 @@
+/// A unique identifier assigned to a media item by its backend.
+pub type ItemId = String;
+
+#[derive(
+    Debug,
+    Copy,
+    Clone,
+    Eq,
+    PartialEq,
+    Hash,
+    serde_derive::Serialize,
+    serde_derive::Deserialize,
+)]
+/// The type of media an item represents.
+pub enum ItemKind {
+    Movie,
+    Series,
+    Season,
+    Episode,
+    BoxSet,
+    MusicAlbum,
+    MusicArtist,
+    Audio,
+    Person,
+    Folder,
+    /// Any item type Bluebottle does not know how to handle yet.
+    Other,
+}
+
+impl ItemKind {
+    /// Returns the item kind as a static string.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ItemKind::Movie => "movie",
+            ItemKind::Series => "series",
+            ItemKind::Season => "season",
+            ItemKind::Episode => "episode",
+            ItemKind::BoxSet => "box_set",
+            ItemKind::MusicAlbum => "music_album",
+            ItemKind::MusicArtist => "music_artist",
+            ItemKind::Audio => "audio",
+            ItemKind::Person => "person",
+            ItemKind::Folder => "folder",
+            ItemKind::Other => "other",
+        }
+    }
+
+    /// Parse the item kind from the string returned by [ItemKind::as_str].
+    ///
+    /// Unknown kinds are treated as [ItemKind::Other].
+    pub fn from_str_lossy(s: &str) -> Self {
+        match s {
+            "movie" => ItemKind::Movie,
+            "series" => ItemKind::Series,
+            "season" => ItemKind::Season,
+            "episode" => ItemKind::Episode,
+            "box_set" => ItemKind::BoxSet,
+            "music_album" => ItemKind::MusicAlbum,
+            "music_artist" => ItemKind::MusicArtist,
+            "audio" => ItemKind::Audio,
+            "person" => ItemKind::Person,
+            "folder" => ItemKind::Folder,
+            _ => ItemKind::Other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde_derive::Serialize, serde_derive::Deserialize)]
+/// A lightweight summary of a media item, containing just enough information
+/// to display it within a listing.
+pub struct ItemSummary {
+    /// The unique ID of the item.
+    pub id: ItemId,
+    /// The type of the item.
+    pub kind: ItemKind,
+    /// The display name of the item.
+    pub name: String,
+    /// The year the item was released.
+    pub production_year: Option<u32>,
+    /// The BlurHash of the item's primary image, displayed while it loads.
+    #[serde(default)]
+    pub blurhash: Option<String>,
+}
+
+#[derive(Debug, Clone, serde_derive::Serialize, serde_derive::Deserialize)]
+/// A single page of items returned by a query.
+pub struct ItemPage {
+    /// The index of the first item in the page.
+    pub start_index: usize,
+    /// The total number of items matching the query, across all pages.
+    pub total_items: usize,
+    /// The items within the page.
+    pub items: Vec<ItemSummary>,
+}
+
+#[derive(
+    Debug,
+    Copy,
+    Clone,
+    Eq,
+    PartialEq,
+    Hash,
+    serde_derive::Serialize,
+    serde_derive::Deserialize,
+)]
+/// The type of content a library holds.
+pub enum LibraryKind {
+    Movies,
+    TvShows,
+    Music,
+    Mixed,
+}
+
+#[derive(Debug, Clone, serde_derive::Serialize, serde_derive::Deserialize)]
+/// A top level library of media provided by a backend.
+pub struct Library {
+    /// The unique ID of the library.
+    pub id: ItemId,
+    /// The display name of the library.
+    pub name: String,
+    /// The type of content within the library.
+    pub kind: LibraryKind,
+}
+
 #[derive(Debug, serde_derive::Serialize, serde_derive::Deserialize)]
 /// A collection of series or movies.
 pub struct Collection {}
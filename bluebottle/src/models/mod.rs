@@ -1 +1,13 @@
+pub mod image;
+pub mod interaction;
+pub mod live;
 pub mod media;
+pub mod music;
+pub mod playback;
+pub mod profile;
+pub mod query;
+pub mod search;
+pub mod settings;
+pub mod subtitles;
+pub mod sync;
+pub mod tracks;
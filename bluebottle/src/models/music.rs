@@ -0,0 +1,189 @@
+use std::time::Duration;
+
+use super::media::ItemId;
+
+#[derive(
+    Debug, Clone, PartialEq, serde_derive::Serialize, serde_derive::Deserialize,
+)]
+/// A single audio track which can be added to the play queue.
+pub struct AudioTrack {
+    pub item_id: ItemId,
+    /// The display name of the track.
+    pub name: String,
+    /// The artists of the track, joined for display.
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    /// The album the track belongs to, which usually holds the artwork.
+    #[serde(default)]
+    pub album_id: Option<ItemId>,
+    pub runtime: Option<Duration>,
+    /// The loudness correction computed by the backend in dB, used when the file
+    /// has no ReplayGain tags.
+    pub normalization_gain: Option<f32>,
+}
+
+#[derive(
+    Debug,
+    Default,
+    Copy,
+    Clone,
+    Eq,
+    PartialEq,
+    serde_derive::Serialize,
+    serde_derive::Deserialize,
+)]
+/// What happens once the current track finishes.
+pub enum RepeatMode {
+    #[default]
+    /// Stop after the last track in the queue.
+    Off,
+    /// Start the queue again after the last track.
+    All,
+    /// Repeat the current track.
+    One,
+}
+
+impl RepeatMode {
+    /// Returns the next mode, used to cycle through the modes with one button.
+    pub fn cycle(self) -> Self {
+        match self {
+            Self::Off => Self::All,
+            Self::All => Self::One,
+            Self::One => Self::Off,
+        }
+    }
+}
+
+#[derive(
+    Debug,
+    Default,
+    Copy,
+    Clone,
+    Eq,
+    PartialEq,
+    serde_derive::Serialize,
+    serde_derive::Deserialize,
+)]
+/// Which ReplayGain values are used to even out the loudness of tracks.
+pub enum ReplayGainMode {
+    Off,
+    #[default]
+    /// Each track is played at the same loudness.
+    Track,
+    /// Albums are played at the same loudness, keeping the differences between
+    /// their tracks.
+    Album,
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+/// The ReplayGain tags of a track, gains are in dB and peaks are linear.
+pub struct ReplayGain {
+    pub track_gain: Option<f32>,
+    pub track_peak: Option<f32>,
+    pub album_gain: Option<f32>,
+    pub album_peak: Option<f32>,
+}
+
+impl ReplayGain {
+    /// Returns the linear gain to apply to the samples.
+    ///
+    /// Album values fall back to the track values and the other way around, if
+    /// the track has no tags the backend's `normalization_gain` is used. The gain
+    /// is limited so the peak does not clip.
+    pub fn linear_gain(
+        &self,
+        mode: ReplayGainMode,
+        normalization_gain: Option<f32>,
+    ) -> f32 {
+        let (gain, peak) = match mode {
+            ReplayGainMode::Off => return 1.0,
+            ReplayGainMode::Track => (
+                self.track_gain.or(self.album_gain),
+                self.track_peak.or(self.album_peak),
+            ),
+            ReplayGainMode::Album => (
+                self.album_gain.or(self.track_gain),
+                self.album_peak.or(self.track_peak),
+            ),
+        };
+
+        let Some(gain) = gain.or(normalization_gain) else {
+            return 1.0;
+        };
+
+        let linear = 10f32.powf(gain / 20.0);
+        match peak.filter(|peak| *peak > 0.0) {
+            Some(peak) => linear.min(1.0 / peak),
+            None => linear,
+        }
+    }
+}
+
+/// Parse a ReplayGain tag value, i.e. `-6.52 dB` or `0.988312`.
+pub fn parse_replay_gain_value(value: &str) -> Option<f32> {
+    let value = value.trim();
+    let value = value
+        .strip_suffix("dB")
+        .or_else(|| value.strip_suffix("db"))
+        .unwrap_or(value);
+    value
+        .trim()
+        .parse()
+        .ok()
+        .filter(|value: &f32| value.is_finite())
+}
+
+#[derive(
+    Debug, Clone, PartialEq, serde_derive::Serialize, serde_derive::Deserialize,
+)]
+/// The settings of the music player.
+pub struct MusicSettings {
+    pub replay_gain: ReplayGainMode,
+    /// The playback volume, between `0.0` and `1.0`.
+    pub volume: f32,
+}
+
+impl Default for MusicSettings {
+    fn default() -> Self {
+        Self {
+            replay_gain: ReplayGainMode::default(),
+            volume: 1.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_replay_gain_value() {
+        assert_eq!(parse_replay_gain_value("-6.52 dB"), Some(-6.52));
+        assert_eq!(parse_replay_gain_value("+1.5dB"), Some(1.5));
+        assert_eq!(parse_replay_gain_value(" 0.988312 "), Some(0.988312));
+        assert_eq!(parse_replay_gain_value("loud"), None);
+    }
+
+    #[test]
+    fn test_linear_gain() {
+        let tags = ReplayGain {
+            track_gain: Some(-6.0),
+            track_peak: Some(0.5),
+            album_gain: Some(6.0),
+            album_peak: Some(0.9),
+        };
+        let gain = tags.linear_gain(ReplayGainMode::Track, None);
+        assert!((gain - 0.501).abs() < 0.001, "{gain}");
+        assert_eq!(tags.linear_gain(ReplayGainMode::Off, None), 1.0);
+
+        // +6dB would clip the album peak, so it is limited.
+        let gain = tags.linear_gain(ReplayGainMode::Album, None);
+        assert!((gain - 1.0 / 0.9).abs() < 0.001, "{gain}");
+
+        // Untagged tracks fall back to the backend's gain.
+        let untagged = ReplayGain::default();
+        let gain = untagged.linear_gain(ReplayGainMode::Album, Some(-20.0));
+        assert!((gain - 0.1).abs() < 0.001, "{gain}");
+        assert_eq!(untagged.linear_gain(ReplayGainMode::Track, None), 1.0);
+    }
+}
@@ -0,0 +1,269 @@
+use std::time::Duration;
+
+use super::media::ItemId;
+use super::profile::Quality;
+use super::tracks::TrackSelection;
+
+#[derive(Debug, Clone)]
+/// A request to play an item.
+pub struct PlaybackRequest {
+    /// The item to play.
+    pub item_id: ItemId,
+    /// The position to start playback from.
+    pub start_position: Duration,
+    /// The audio and subtitle streams to play, otherwise the backend decides.
+    pub tracks: Option<TrackSelection>,
+    /// The quality to stream at, otherwise the user's quality setting.
+    pub quality: Option<Quality>,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+/// How the media is delivered to the player.
+pub enum PlayMethod {
+    /// The original file is played as is.
+    DirectPlay,
+    /// The original streams are remuxed into a container the player supports.
+    DirectStream,
+    /// The media is transcoded by the backend.
+    Transcode,
+}
+
+#[derive(Debug, Clone)]
+/// A stream resolved by the backend which can be handed to a player.
+pub struct PlaybackStream {
+    /// The item being played.
+    pub item_id: ItemId,
+    /// The URL the player should open, this includes any required authentication.
+    pub url: url::Url,
+    /// The backend's ID of the media source selected for playback.
+    pub media_source_id: String,
+    /// The backend's ID of the playback session, if it tracks sessions.
+    pub play_session_id: Option<String>,
+    pub play_method: PlayMethod,
+    /// The backend's indices of the audio streams, in the order the player
+    /// numbers its audio tracks.
+    pub audio_streams: Vec<u32>,
+    /// The backend's indices of the subtitle streams, in the order the player
+    /// numbers its subtitle tracks.
+    pub subtitle_streams: Vec<u32>,
+    /// The external subtitle files the player should load, which it numbers
+    /// after the embedded subtitle tracks.
+    pub subtitle_files: Vec<url::Url>,
+    /// The audio and subtitle streams the player should start with.
+    pub tracks: Option<TrackSelection>,
+}
+
+impl PlaybackStream {
+    /// Returns the backend's index of the player's audio track.
+    ///
+    /// Player track IDs start from `1`.
+    pub fn audio_stream_index(&self, track_id: u64) -> Option<u32> {
+        stream_index(&self.audio_streams, track_id)
+    }
+
+    /// Returns the backend's index of the player's subtitle track.
+    ///
+    /// Player track IDs start from `1`.
+    pub fn subtitle_stream_index(&self, track_id: u64) -> Option<u32> {
+        stream_index(&self.subtitle_streams, track_id)
+    }
+
+    /// Returns the player's track ID of the backend's audio stream.
+    pub fn audio_track_id(&self, stream_index: u32) -> Option<u64> {
+        track_id(&self.audio_streams, stream_index)
+    }
+
+    /// Returns the player's track ID of the backend's subtitle stream.
+    pub fn subtitle_track_id(&self, stream_index: u32) -> Option<u64> {
+        track_id(&self.subtitle_streams, stream_index)
+    }
+}
+
+fn track_id(streams: &[u32], stream_index: u32) -> Option<u64> {
+    let position = streams.iter().position(|index| *index == stream_index)?;
+    Some(position as u64 + 1)
+}
+
+fn stream_index(streams: &[u32], track_id: u64) -> Option<u32> {
+    let position = usize::try_from(track_id.checked_sub(1)?).ok()?;
+    streams.get(position).copied()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// The state of playback reported to the backend.
+pub struct PlaybackReport {
+    pub item_id: ItemId,
+    pub media_source_id: String,
+    pub play_session_id: Option<String>,
+    pub play_method: PlayMethod,
+    /// The current playback position.
+    pub position: Duration,
+    pub paused: bool,
+    /// The backend's index of the selected audio stream.
+    pub audio_stream_index: Option<u32>,
+    /// The backend's index of the selected subtitle stream, `None` if subtitles
+    /// are off.
+    pub subtitle_stream_index: Option<u32>,
+}
+
+impl PlaybackReport {
+    /// Create a report for the start of the given stream.
+    pub fn new(stream: &PlaybackStream, position: Duration) -> Self {
+        Self {
+            item_id: stream.item_id.clone(),
+            media_source_id: stream.media_source_id.clone(),
+            play_session_id: stream.play_session_id.clone(),
+            play_method: stream.play_method,
+            position,
+            paused: false,
+            audio_stream_index: stream
+                .tracks
+                .and_then(|tracks| tracks.audio_stream_index),
+            subtitle_stream_index: stream
+                .tracks
+                .and_then(|tracks| tracks.subtitle_stream_index),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A change in playback state reported to the backend.
+pub enum PlaybackEvent {
+    /// Playback has started.
+    Started(PlaybackReport),
+    /// Playback is ongoing, sent periodically and whenever playback is paused or
+    /// resumed.
+    Progress(PlaybackReport),
+    /// Playback has stopped, the position is used as the resume point.
+    Stopped(PlaybackReport),
+}
+
+#[derive(
+    Debug, Clone, PartialEq, serde_derive::Serialize, serde_derive::Deserialize,
+)]
+/// The external player used to play video.
+pub struct PlayerSettings {
+    /// The player executable, this must be compatible with mpv's command line
+    /// options and JSON IPC protocol.
+    pub command: String,
+    /// Additional arguments passed to the player.
+    pub args: Vec<String>,
+}
+
+impl Default for PlayerSettings {
+    fn default() -> Self {
+        Self {
+            command: "mpv".to_string(),
+            args: Vec::new(),
+        }
+    }
+}
+
+#[derive(
+    Debug, Copy, Clone, PartialEq, serde_derive::Serialize, serde_derive::Deserialize,
+)]
+/// Decides when an item counts as started or finished, the defaults match
+/// Jellyfin's server defaults.
+pub struct ProgressThresholds {
+    /// Positions before this percentage of the runtime are not resumed.
+    pub min_resume_percent: f32,
+    /// Positions after this percentage of the runtime mark the item as played.
+    pub max_resume_percent: f32,
+    /// Items shorter than this are never resumed, only marked as played.
+    pub min_resume_duration: Duration,
+}
+
+impl Default for ProgressThresholds {
+    fn default() -> Self {
+        Self {
+            min_resume_percent: 5.0,
+            max_resume_percent: 90.0,
+            min_resume_duration: Duration::from_secs(300),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+/// How far the user has got through an item.
+pub enum WatchProgress {
+    Unplayed,
+    /// The item was started and can be resumed from the position.
+    InProgress(Duration),
+    Played,
+}
+
+impl ProgressThresholds {
+    /// Classify the playback position of an item.
+    ///
+    /// Without a runtime, any position is treated as resumable.
+    pub fn classify(
+        &self,
+        position: Duration,
+        runtime: Option<Duration>,
+    ) -> WatchProgress {
+        let Some(runtime) = runtime.filter(|runtime| !runtime.is_zero()) else {
+            return if position.is_zero() {
+                WatchProgress::Unplayed
+            } else {
+                WatchProgress::InProgress(position)
+            };
+        };
+
+        let percent = position.as_secs_f32() / runtime.as_secs_f32() * 100.0;
+        if percent >= self.max_resume_percent {
+            WatchProgress::Played
+        } else if runtime < self.min_resume_duration || percent < self.min_resume_percent
+        {
+            // Short items are never resumed, only finishing them counts.
+            WatchProgress::Unplayed
+        } else {
+            WatchProgress::InProgress(position)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_progress() {
+        let thresholds = ProgressThresholds::default();
+        let runtime = Some(Duration::from_secs(100 * 60));
+        let minutes = |minutes: u64| Duration::from_secs(minutes * 60);
+
+        assert_eq!(
+            thresholds.classify(minutes(2), runtime),
+            WatchProgress::Unplayed
+        );
+        assert_eq!(
+            thresholds.classify(minutes(42), runtime),
+            WatchProgress::InProgress(minutes(42)),
+        );
+        assert_eq!(
+            thresholds.classify(minutes(95), runtime),
+            WatchProgress::Played
+        );
+
+        // Short items are never resumed.
+        let short = Some(minutes(4));
+        assert_eq!(
+            thresholds.classify(minutes(2), short),
+            WatchProgress::Unplayed
+        );
+        assert_eq!(
+            thresholds.classify(minutes(4), short),
+            WatchProgress::Played
+        );
+
+        // Unknown runtimes can always be resumed.
+        assert_eq!(
+            thresholds.classify(Duration::ZERO, None),
+            WatchProgress::Unplayed
+        );
+        assert_eq!(
+            thresholds.classify(minutes(42), None),
+            WatchProgress::InProgress(minutes(42)),
+        );
+    }
+}
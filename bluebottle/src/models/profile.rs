@@ -0,0 +1,199 @@
+/// The bitrate a connection must sustain to stream the original files, which is
+/// only expected of local networks.
+const ORIGINAL_MIN_BITRATE: u64 = 60_000_000;
+/// The share of the measured bitrate a stream may use, leaving headroom for
+/// bitrate spikes and other traffic.
+const BITRATE_HEADROOM: f64 = 0.8;
+
+#[derive(
+    Debug,
+    Default,
+    Copy,
+    Clone,
+    Eq,
+    PartialEq,
+    serde_derive::Serialize,
+    serde_derive::Deserialize,
+)]
+/// A limit on the bitrate and resolution of streamed video.
+pub enum Quality {
+    #[default]
+    /// The original files, as long as the player supports them.
+    Original,
+    Mbps20,
+    Mbps10,
+    Mbps8,
+    Mbps4,
+    Mbps3,
+    Mbps1_5,
+}
+
+impl Quality {
+    pub const ALL: [Self; 7] = [
+        Self::Original,
+        Self::Mbps20,
+        Self::Mbps10,
+        Self::Mbps8,
+        Self::Mbps4,
+        Self::Mbps3,
+        Self::Mbps1_5,
+    ];
+
+    /// Returns the display label of the quality.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Original => "Original",
+            Self::Mbps20 => "1080p · 20 Mbps",
+            Self::Mbps10 => "1080p · 10 Mbps",
+            Self::Mbps8 => "720p · 8 Mbps",
+            Self::Mbps4 => "720p · 4 Mbps",
+            Self::Mbps3 => "480p · 3 Mbps",
+            Self::Mbps1_5 => "360p · 1.5 Mbps",
+        }
+    }
+
+    /// Returns the maximum bitrate in bits per second, `None` if unlimited.
+    pub fn max_bitrate(&self) -> Option<u64> {
+        match self {
+            Self::Original => None,
+            Self::Mbps20 => Some(20_000_000),
+            Self::Mbps10 => Some(10_000_000),
+            Self::Mbps8 => Some(8_000_000),
+            Self::Mbps4 => Some(4_000_000),
+            Self::Mbps3 => Some(3_000_000),
+            Self::Mbps1_5 => Some(1_500_000),
+        }
+    }
+
+    /// Returns the maximum video height in pixels, `None` if unlimited.
+    pub fn max_height(&self) -> Option<u32> {
+        match self {
+            Self::Original => None,
+            Self::Mbps20 | Self::Mbps10 => Some(1080),
+            Self::Mbps8 | Self::Mbps4 => Some(720),
+            Self::Mbps3 => Some(480),
+            Self::Mbps1_5 => Some(360),
+        }
+    }
+
+    /// Returns the best quality a connection with the measured bitrate can
+    /// stream, the original files on local networks and a bitrate cap otherwise.
+    pub fn for_bitrate(measured_bitrate: u64) -> Self {
+        if measured_bitrate >= ORIGINAL_MIN_BITRATE {
+            return Self::Original;
+        }
+
+        let usable = (measured_bitrate as f64 * BITRATE_HEADROOM) as u64;
+        Self::ALL
+            .into_iter()
+            .find(|quality| quality.max_bitrate().is_some_and(|max| max <= usable))
+            .unwrap_or(Self::Mbps1_5)
+    }
+}
+
+#[derive(
+    Debug,
+    Default,
+    Copy,
+    Clone,
+    Eq,
+    PartialEq,
+    serde_derive::Serialize,
+    serde_derive::Deserialize,
+)]
+/// The quality video is streamed at unless another is picked for an item.
+pub enum QualitySetting {
+    #[default]
+    /// Pick the quality from a bitrate test against the backend.
+    Auto,
+    Fixed(Quality),
+}
+
+impl QualitySetting {
+    /// Returns the display label of the setting.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Auto => "Auto",
+            Self::Fixed(quality) => quality.label(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Describes the media a player can direct play, the backend transcodes anything
+/// else.
+pub struct DeviceProfile {
+    pub name: &'static str,
+    /// The containers which can be played, empty if any can.
+    pub containers: Vec<&'static str>,
+    /// The video codecs which can be played, empty if any can.
+    pub video_codecs: Vec<&'static str>,
+    /// The audio codecs which can be played, empty if any can.
+    pub audio_codecs: Vec<&'static str>,
+    /// The video codecs transcodes are encoded with, in order of preference.
+    pub transcode_video_codecs: Vec<&'static str>,
+    /// The audio codecs transcodes are encoded with, in order of preference.
+    pub transcode_audio_codecs: Vec<&'static str>,
+    pub max_audio_channels: u32,
+    /// The maximum video height in pixels, `None` if unlimited.
+    pub max_height: Option<u32>,
+    /// The maximum stream bitrate in bits per second, `None` if unlimited.
+    pub max_bitrate: Option<u64>,
+}
+
+impl DeviceProfile {
+    /// The profile of mpv, which plays practically anything through FFmpeg.
+    pub fn mpv() -> Self {
+        Self {
+            name: "Bluebottle",
+            containers: Vec::new(),
+            video_codecs: Vec::new(),
+            audio_codecs: Vec::new(),
+            transcode_video_codecs: vec!["h264", "hevc"],
+            transcode_audio_codecs: vec!["aac", "mp3", "ac3", "eac3", "opus"],
+            max_audio_channels: 6,
+            max_height: None,
+            max_bitrate: None,
+        }
+    }
+
+    /// Limit the profile to the quality, anything above it is transcoded.
+    pub fn with_quality(mut self, quality: Quality) -> Self {
+        self.max_height = min_limit(self.max_height, quality.max_height());
+        self.max_bitrate = min_limit(self.max_bitrate, quality.max_bitrate());
+        self
+    }
+}
+
+fn min_limit<T: Ord>(a: Option<T>, b: Option<T>) -> Option<T> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_for_bitrate() {
+        assert_eq!(Quality::for_bitrate(400_000_000), Quality::Original);
+        assert_eq!(Quality::for_bitrate(30_000_000), Quality::Mbps20);
+        assert_eq!(Quality::for_bitrate(10_000_000), Quality::Mbps8);
+        assert_eq!(Quality::for_bitrate(4_000_000), Quality::Mbps3);
+        assert_eq!(Quality::for_bitrate(500_000), Quality::Mbps1_5);
+    }
+
+    #[test]
+    fn test_with_quality() {
+        let profile = DeviceProfile::mpv().with_quality(Quality::Original);
+        assert_eq!(profile, DeviceProfile::mpv());
+
+        let profile = DeviceProfile::mpv()
+            .with_quality(Quality::Mbps8)
+            .with_quality(Quality::Mbps10);
+        assert_eq!(profile.max_height, Some(720));
+        assert_eq!(profile.max_bitrate, Some(8_000_000));
+    }
+}
@@ -0,0 +1,179 @@
+use std::fmt::{Display, Formatter};
+
+use super::media::ItemId;
+
+#[derive(Debug, Clone, serde_derive::Serialize, serde_derive::Deserialize)]
+/// A query for a page of items within a library.
+pub struct ItemQuery {
+    /// The library to list items from.
+    pub library_id: ItemId,
+    /// The index of the first item to return.
+    pub start_index: usize,
+    /// The maximum number of items to return.
+    pub limit: usize,
+    /// How the items should be sorted and filtered.
+    pub options: LibraryViewOptions,
+    #[serde(default)]
+    /// Seeds the order of a random sort, queries with the same seed return the
+    /// items in the same order so pages of a view never overlap.
+    pub random_seed: u64,
+}
+
+#[derive(
+    Debug, Default, Clone, PartialEq, serde_derive::Serialize, serde_derive::Deserialize,
+)]
+/// The sorting and filtering applied to a library view.
+pub struct LibraryViewOptions {
+    /// The field to sort by.
+    pub sort_by: SortBy,
+    /// The direction of the sort.
+    pub sort_order: SortOrder,
+    /// The filters items must match, all filters must match for an item to be
+    /// included.
+    pub filters: Vec<ItemFilter>,
+}
+
+impl LibraryViewOptions {
+    /// Add a new filter, replacing any filters that conflict with it.
+    pub fn add_filter(&mut self, filter: ItemFilter) {
+        self.filters
+            .retain(|existing| existing != &filter && !existing.conflicts_with(&filter));
+        self.filters.push(filter);
+    }
+
+    /// Remove an existing filter.
+    pub fn remove_filter(&mut self, filter: &ItemFilter) {
+        self.filters.retain(|existing| existing != filter);
+    }
+}
+
+#[derive(
+    Debug,
+    Default,
+    Copy,
+    Clone,
+    Eq,
+    PartialEq,
+    Hash,
+    serde_derive::Serialize,
+    serde_derive::Deserialize,
+)]
+/// The field items are sorted by.
+pub enum SortBy {
+    #[default]
+    Name,
+    DateAdded,
+    ReleaseDate,
+    Rating,
+    Runtime,
+    Random,
+}
+
+impl SortBy {
+    /// All sort options in display order.
+    pub const ALL: [SortBy; 6] = [
+        SortBy::Name,
+        SortBy::DateAdded,
+        SortBy::ReleaseDate,
+        SortBy::Rating,
+        SortBy::Runtime,
+        SortBy::Random,
+    ];
+
+    /// Returns the display label of the sort option.
+    pub fn label(&self) -> &'static str {
+        match self {
+            SortBy::Name => "Name",
+            SortBy::DateAdded => "Date Added",
+            SortBy::ReleaseDate => "Release Date",
+            SortBy::Rating => "Rating",
+            SortBy::Runtime => "Runtime",
+            SortBy::Random => "Random",
+        }
+    }
+}
+
+#[derive(
+    Debug,
+    Default,
+    Copy,
+    Clone,
+    Eq,
+    PartialEq,
+    Hash,
+    serde_derive::Serialize,
+    serde_derive::Deserialize,
+)]
+/// The direction items are sorted in.
+pub enum SortOrder {
+    #[default]
+    Ascending,
+    Descending,
+}
+
+impl SortOrder {
+    /// Returns the opposite sort order.
+    pub fn reversed(self) -> Self {
+        match self {
+            SortOrder::Ascending => SortOrder::Descending,
+            SortOrder::Descending => SortOrder::Ascending,
+        }
+    }
+}
+
+#[derive(
+    Debug, Clone, Eq, PartialEq, Hash, serde_derive::Serialize, serde_derive::Deserialize,
+)]
+/// A single filter applied to the items of a library.
+pub enum ItemFilter {
+    /// The item must have the given genre.
+    Genre(String),
+    /// The item must have been released within the inclusive range of years.
+    Years { from: u32, to: u32 },
+    /// The item must have been played.
+    Played,
+    /// The item must not have been played.
+    Unplayed,
+    /// The item must be marked as a favourite.
+    Favourite,
+    /// The item must have the given official (parental) rating.
+    OfficialRating(String),
+    /// The item must have been produced by the given studio.
+    Studio(String),
+}
+
+impl ItemFilter {
+    /// Returns whether both filters can never match at the same time.
+    fn conflicts_with(&self, other: &ItemFilter) -> bool {
+        matches!(
+            (self, other),
+            (ItemFilter::Played, ItemFilter::Unplayed)
+                | (ItemFilter::Unplayed, ItemFilter::Played)
+                | (ItemFilter::Years { .. }, ItemFilter::Years { .. })
+        )
+    }
+}
+
+impl Display for ItemFilter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ItemFilter::Genre(genre) => f.write_str(genre),
+            ItemFilter::Years { from, to } if from == to => write!(f, "{from}"),
+            ItemFilter::Years { from, to } => write!(f, "{from} - {to}"),
+            ItemFilter::Played => f.write_str("Played"),
+            ItemFilter::Unplayed => f.write_str("Unplayed"),
+            ItemFilter::Favourite => f.write_str("Favourites"),
+            ItemFilter::OfficialRating(rating) => f.write_str(rating),
+            ItemFilter::Studio(studio) => f.write_str(studio),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, serde_derive::Serialize, serde_derive::Deserialize)]
+/// The values available to filter a library by.
+pub struct FilterOptions {
+    pub genres: Vec<String>,
+    pub years: Vec<u32>,
+    pub official_ratings: Vec<String>,
+    pub studios: Vec<String>,
+}
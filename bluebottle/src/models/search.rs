@@ -0,0 +1,191 @@
+use super::media::{ItemKind, ItemSummary};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+/// The groups search results are displayed under.
+pub enum SearchGroup {
+    Movies,
+    Series,
+    Episodes,
+    People,
+    Music,
+}
+
+impl SearchGroup {
+    /// All groups in display order.
+    pub const ALL: [SearchGroup; 5] = [
+        SearchGroup::Movies,
+        SearchGroup::Series,
+        SearchGroup::Episodes,
+        SearchGroup::People,
+        SearchGroup::Music,
+    ];
+
+    /// Returns the group an item of the given kind belongs to, if any.
+    pub fn of(kind: ItemKind) -> Option<Self> {
+        match kind {
+            ItemKind::Movie | ItemKind::BoxSet => Some(SearchGroup::Movies),
+            ItemKind::Series | ItemKind::Season => Some(SearchGroup::Series),
+            ItemKind::Episode => Some(SearchGroup::Episodes),
+            ItemKind::Person => Some(SearchGroup::People),
+            ItemKind::MusicAlbum | ItemKind::MusicArtist | ItemKind::Audio => {
+                Some(SearchGroup::Music)
+            },
+            ItemKind::Folder | ItemKind::Other => None,
+        }
+    }
+
+    /// Returns the display label of the group.
+    pub fn label(&self) -> &'static str {
+        match self {
+            SearchGroup::Movies => "Movies",
+            SearchGroup::Series => "TV Shows",
+            SearchGroup::Episodes => "Episodes",
+            SearchGroup::People => "People",
+            SearchGroup::Music => "Music",
+        }
+    }
+
+    /// Returns the name of the icon displayed next to results in the group.
+    pub fn icon(&self) -> &'static str {
+        match self {
+            SearchGroup::Movies => "movie",
+            SearchGroup::Series => "tv",
+            SearchGroup::Episodes => "play_circle",
+            SearchGroup::People => "person",
+            SearchGroup::Music => "library_music",
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+/// The results of a search, grouped by [SearchGroup].
+///
+/// Results can be addressed by a single index which runs across the groups in
+/// display order, this is used for keyboard navigation of the results.
+pub struct SearchResults {
+    groups: Vec<(SearchGroup, Vec<ItemSummary>)>,
+}
+
+impl SearchResults {
+    /// Add items to the results, items which do not belong to any group or are
+    /// already present are ignored.
+    pub fn extend(&mut self, items: impl IntoIterator<Item = ItemSummary>) {
+        for item in items {
+            let Some(group) = SearchGroup::of(item.kind) else {
+                continue;
+            };
+
+            if self.contains(&item.id) {
+                continue;
+            }
+
+            match self
+                .groups
+                .iter_mut()
+                .find(|(existing, _)| *existing == group)
+            {
+                Some((_, entries)) => entries.push(item),
+                None => self.groups.push((group, vec![item])),
+            }
+        }
+
+        self.groups
+            .sort_by_key(|(group, _)| SearchGroup::ALL.iter().position(|g| g == group));
+    }
+
+    /// Returns whether an item with the given ID is within the results.
+    pub fn contains(&self, item_id: &str) -> bool {
+        self.groups
+            .iter()
+            .any(|(_, items)| items.iter().any(|item| item.id == item_id))
+    }
+
+    /// Remove all results.
+    pub fn clear(&mut self) {
+        self.groups.clear();
+    }
+
+    /// Returns the total number of results across all groups.
+    pub fn len(&self) -> usize {
+        self.groups.iter().map(|(_, items)| items.len()).sum()
+    }
+
+    /// Returns whether there are no results.
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// Returns the result at the given index, counting across all groups.
+    pub fn get(&self, mut index: usize) -> Option<&ItemSummary> {
+        for (_, items) in self.groups.iter() {
+            if index < items.len() {
+                return items.get(index);
+            }
+            index -= items.len();
+        }
+        None
+    }
+
+    /// Returns the non-empty groups in display order.
+    pub fn groups(&self) -> impl Iterator<Item = (SearchGroup, &[ItemSummary])> {
+        self.groups
+            .iter()
+            .map(|(group, items)| (*group, items.as_slice()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(name: &str, kind: ItemKind) -> ItemSummary {
+        ItemSummary {
+            id: name.to_string(),
+            kind,
+            name: name.to_string(),
+            production_year: None,
+            blurhash: None,
+        }
+    }
+
+    #[test]
+    fn test_results_grouped_in_display_order() {
+        let mut results = SearchResults::default();
+        results.extend([
+            item("artist", ItemKind::MusicArtist),
+            item("movie-1", ItemKind::Movie),
+            item("folder", ItemKind::Folder),
+            item("episode", ItemKind::Episode),
+            item("movie-2", ItemKind::Movie),
+            item("movie-1", ItemKind::Movie),
+        ]);
+
+        let groups: Vec<_> = results
+            .groups()
+            .map(|(group, items)| (group, items.len()))
+            .collect();
+        assert_eq!(
+            groups,
+            vec![
+                (SearchGroup::Movies, 2),
+                (SearchGroup::Episodes, 1),
+                (SearchGroup::Music, 1),
+            ],
+        );
+
+        assert_eq!(results.len(), 4);
+        assert_eq!(
+            results.get(1).map(|item| item.name.as_str()),
+            Some("movie-2")
+        );
+        assert_eq!(
+            results.get(2).map(|item| item.name.as_str()),
+            Some("episode")
+        );
+        assert_eq!(
+            results.get(3).map(|item| item.name.as_str()),
+            Some("artist")
+        );
+        assert!(results.get(4).is_none());
+    }
+}
@@ -0,0 +1,225 @@
+use std::time::Duration;
+
+const MIB: u64 = 1024 * 1024;
+const DAY: Duration = Duration::from_secs(24 * 3600);
+
+#[derive(
+    Debug,
+    Default,
+    Copy,
+    Clone,
+    Eq,
+    PartialEq,
+    serde_derive::Serialize,
+    serde_derive::Deserialize,
+)]
+/// Whether the app is drawn light on dark or dark on light.
+pub enum ThemeMode {
+    #[default]
+    Dark,
+    Light,
+}
+
+impl ThemeMode {
+    pub const ALL: [Self; 2] = [Self::Dark, Self::Light];
+
+    /// Returns the display label of the mode.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Dark => "Dark",
+            Self::Light => "Light",
+        }
+    }
+}
+
+#[derive(
+    Debug,
+    Default,
+    Copy,
+    Clone,
+    Eq,
+    PartialEq,
+    serde_derive::Serialize,
+    serde_derive::Deserialize,
+)]
+/// The colour highlighting selected and interactive elements.
+pub enum AccentColor {
+    #[default]
+    Indigo,
+    Sky,
+    Teal,
+    Violet,
+    Pink,
+}
+
+impl AccentColor {
+    pub const ALL: [Self; 5] = [
+        Self::Indigo,
+        Self::Sky,
+        Self::Teal,
+        Self::Violet,
+        Self::Pink,
+    ];
+
+    /// Returns the display label of the accent.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Indigo => "Indigo",
+            Self::Sky => "Sky",
+            Self::Teal => "Teal",
+            Self::Violet => "Violet",
+            Self::Pink => "Pink",
+        }
+    }
+}
+
+#[derive(
+    Debug,
+    Default,
+    Copy,
+    Clone,
+    Eq,
+    PartialEq,
+    serde_derive::Serialize,
+    serde_derive::Deserialize,
+)]
+/// How much of a library fits on screen at once.
+pub enum Density {
+    /// Smaller posters, fitting more items on screen.
+    Compact,
+    #[default]
+    Comfortable,
+    /// Larger posters, for big screens viewed from a distance.
+    Spacious,
+}
+
+impl Density {
+    pub const ALL: [Self; 3] = [Self::Compact, Self::Comfortable, Self::Spacious];
+
+    /// Returns the display label of the density.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Compact => "Compact",
+            Self::Comfortable => "Comfortable",
+            Self::Spacious => "Spacious",
+        }
+    }
+}
+
+#[derive(
+    Debug,
+    Default,
+    Copy,
+    Clone,
+    Eq,
+    PartialEq,
+    serde_derive::Serialize,
+    serde_derive::Deserialize,
+)]
+/// The look of the app.
+pub struct Appearance {
+    pub theme: ThemeMode,
+    pub accent: AccentColor,
+    pub density: Density,
+}
+
+#[derive(
+    Debug, Copy, Clone, Eq, PartialEq, serde_derive::Serialize, serde_derive::Deserialize,
+)]
+/// Limits how much the app keeps cached on disk.
+pub struct CacheSettings {
+    /// The size the asset cache, i.e. posters, is pruned down to in bytes.
+    pub asset_cache_limit: u64,
+    /// How long backend responses are cached for unless the backend says
+    /// otherwise.
+    pub content_ttl: Duration,
+    /// How long an asset is kept for since it was last used, this is last so
+    /// settings saved before it was added still load.
+    #[serde(default = "default_asset_max_age")]
+    pub asset_max_age: Duration,
+}
+
+impl CacheSettings {
+    /// The asset cache limits offered to the user.
+    pub const ASSET_CACHE_LIMITS: [u64; 5] =
+        [256 * MIB, 512 * MIB, 1024 * MIB, 2048 * MIB, 5120 * MIB];
+    /// The asset max ages offered to the user.
+    pub const ASSET_MAX_AGES: [Duration; 4] = [
+        Duration::from_secs(7 * 24 * 3600),
+        Duration::from_secs(30 * 24 * 3600),
+        Duration::from_secs(90 * 24 * 3600),
+        Duration::from_secs(365 * 24 * 3600),
+    ];
+    /// The content cache TTLs offered to the user.
+    pub const CONTENT_TTLS: [Duration; 4] = [
+        DAY,
+        Duration::from_secs(3 * 24 * 3600),
+        Duration::from_secs(7 * 24 * 3600),
+        Duration::from_secs(30 * 24 * 3600),
+    ];
+}
+
+impl Default for CacheSettings {
+    fn default() -> Self {
+        Self {
+            asset_cache_limit: 1024 * MIB,
+            content_ttl: Duration::from_secs(7 * 24 * 3600),
+            asset_max_age: default_asset_max_age(),
+        }
+    }
+}
+
+fn default_asset_max_age() -> Duration {
+    Duration::from_secs(90 * 24 * 3600)
+}
+
+/// Returns a size in bytes as a human readable label, i.e. `1.5 GB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if size.fract() < 0.05 || size >= 100.0 {
+        format!("{size:.0} {}", UNITS[unit])
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+/// Returns a TTL as a human readable label, i.e. `7 days`.
+pub fn format_ttl(ttl: Duration) -> String {
+    let days = ttl.as_secs() / DAY.as_secs();
+    match days {
+        0 => format!("{} hours", ttl.as_secs() / 3600),
+        1 => "1 day".to_string(),
+        days => format!("{days} days"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(2048), "2 KB");
+        assert_eq!(format_size(256 * MIB), "256 MB");
+        assert_eq!(format_size(1536 * MIB), "1.5 GB");
+        assert_eq!(format_size(5120 * MIB), "5 GB");
+    }
+
+    #[test]
+    fn test_format_ttl() {
+        assert_eq!(format_ttl(Duration::from_secs(7200)), "2 hours");
+        assert_eq!(format_ttl(DAY), "1 day");
+        assert_eq!(format_ttl(CacheSettings::default().content_ttl), "7 days");
+    }
+}
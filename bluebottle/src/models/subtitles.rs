@@ -0,0 +1,74 @@
+#[derive(Debug, Clone, PartialEq)]
+/// Subtitles found for an item by one of the backend's subtitle providers.
+pub struct RemoteSubtitle {
+    /// The backend's ID of the subtitles, used to download them.
+    pub id: String,
+    /// The display name of the subtitles, usually the release they were made for.
+    pub name: String,
+    /// The provider the subtitles were found with, i.e. `Open Subtitles`.
+    pub provider: Option<String>,
+    /// The language code of the subtitles, i.e. `eng`.
+    pub language: Option<String>,
+    /// The file format of the subtitles, i.e. `srt`.
+    pub format: Option<String>,
+    pub download_count: Option<u32>,
+    /// Whether the subtitles were made for the exact file of the item.
+    pub is_hash_match: bool,
+    pub is_forced: bool,
+    pub is_hearing_impaired: bool,
+}
+
+impl RemoteSubtitle {
+    /// Returns a short description of the subtitles to show alongside the name.
+    pub fn details(&self) -> String {
+        let mut details = Vec::new();
+        details.extend(self.language.as_deref());
+        details.extend(self.format.as_deref());
+        details.extend(self.provider.as_deref());
+        if self.is_hash_match {
+            details.push("exact match");
+        }
+        if self.is_forced {
+            details.push("forced");
+        }
+        if self.is_hearing_impaired {
+            details.push("SDH");
+        }
+
+        let mut details = details.join(" · ");
+        if let Some(count) = self.download_count {
+            details.push_str(&format!(" · {count} downloads"));
+        }
+        details
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_details() {
+        let mut subtitle = RemoteSubtitle {
+            id: "1".into(),
+            name: "Show.S01E01.1080p.WEB".into(),
+            provider: Some("Open Subtitles".into()),
+            language: Some("eng".into()),
+            format: Some("srt".into()),
+            download_count: Some(120),
+            is_hash_match: true,
+            is_forced: false,
+            is_hearing_impaired: true,
+        };
+        assert_eq!(
+            subtitle.details(),
+            "eng · srt · Open Subtitles · exact match · SDH · 120 downloads",
+        );
+
+        subtitle.provider = None;
+        subtitle.download_count = None;
+        subtitle.is_hash_match = false;
+        subtitle.is_hearing_impaired = false;
+        assert_eq!(subtitle.details(), "eng · srt");
+    }
+}
@@ -0,0 +1,67 @@
+use super::media::{ItemId, ItemSummary};
+
+#[derive(Debug, Clone)]
+/// A request for a page of items to store locally.
+pub struct SyncRequest {
+    /// The library to sync.
+    pub library_id: ItemId,
+    /// Only return items saved on or after this backend timestamp, or every item
+    /// if `None`.
+    pub changed_since: Option<String>,
+    /// The index of the first item to return.
+    pub start_index: usize,
+    /// The maximum number of items to return.
+    pub limit: usize,
+}
+
+#[derive(Debug, Clone)]
+/// A single page of items returned by a [SyncRequest].
+pub struct SyncPage {
+    /// The total number of items matching the request, across all pages.
+    pub total_items: usize,
+    /// The items within the page.
+    pub items: Vec<ItemMetadata>,
+}
+
+#[derive(Debug, Clone)]
+/// The metadata of an item which is stored locally, this holds everything required
+/// to list, sort, filter and search items without the network.
+pub struct ItemMetadata {
+    pub summary: ItemSummary,
+    /// The immediate parent of the item, i.e. the series of a season.
+    pub parent_id: Option<ItemId>,
+    /// The series an episode belongs to.
+    pub series_id: Option<ItemId>,
+    /// The season number of an episode.
+    pub season_number: Option<u32>,
+    /// The episode number within its season.
+    pub episode_number: Option<u32>,
+    /// The name used when sorting by name.
+    pub sort_name: String,
+    /// The name of the item in its original language, if it differs.
+    pub original_name: Option<String>,
+    /// The names of the cast and crew.
+    pub people: Vec<String>,
+    pub genres: Vec<String>,
+    pub studios: Vec<String>,
+    pub official_rating: Option<String>,
+    pub community_rating: Option<f32>,
+    /// The runtime of the item in seconds.
+    pub runtime_secs: Option<u64>,
+    /// When the item was first released, as a backend timestamp.
+    pub premiere_date: Option<String>,
+    /// When the item was added to the library, as a backend timestamp.
+    pub date_added: Option<String>,
+    /// When the item was last changed, as a backend timestamp.
+    ///
+    /// Backend timestamps are ISO 8601 formatted and can be compared as strings.
+    pub date_last_saved: Option<String>,
+    /// Whether the user has played the item.
+    pub played: bool,
+    /// The position playback stopped at, in seconds.
+    pub playback_position_secs: u64,
+    /// When the user last played the item, in milliseconds.
+    pub last_played_at: Option<i64>,
+    /// Whether the user has marked the item as a favourite.
+    pub favourite: bool,
+}
@@ -0,0 +1,321 @@
+use std::path::PathBuf;
+
+use super::media::ItemId;
+
+#[derive(Debug, Clone, PartialEq)]
+/// An audio or subtitle stream within an item's media.
+pub struct MediaTrack {
+    /// The backend's index of the stream.
+    pub index: u32,
+    /// The language code of the stream, i.e. `jpn`.
+    pub language: Option<String>,
+    /// The display name of the stream.
+    pub title: String,
+    pub is_default: bool,
+    /// Whether the subtitles only cover foreign dialogue and signs.
+    pub is_forced: bool,
+}
+
+impl MediaTrack {
+    fn has_language(&self, language: &str) -> bool {
+        self.language
+            .as_deref()
+            .is_some_and(|own| own.eq_ignore_ascii_case(language))
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+/// The audio and subtitle streams of an item which can be selected.
+pub struct ItemTracks {
+    /// The series the item belongs to, which track preferences are remembered for.
+    pub series_id: Option<ItemId>,
+    /// The path of the item's media file as the backend knows it, which can be
+    /// read when the library is stored on this machine.
+    pub media_path: Option<PathBuf>,
+    pub audio: Vec<MediaTrack>,
+    pub subtitles: Vec<MediaTrack>,
+}
+
+impl ItemTracks {
+    pub fn audio_track(&self, index: u32) -> Option<&MediaTrack> {
+        self.audio.iter().find(|track| track.index == index)
+    }
+
+    pub fn subtitle_track(&self, index: u32) -> Option<&MediaTrack> {
+        self.subtitles.iter().find(|track| track.index == index)
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+/// The streams to play an item with.
+pub struct TrackSelection {
+    /// The backend's index of the audio stream, the default stream if `None`.
+    pub audio_stream_index: Option<u32>,
+    /// The backend's index of the subtitle stream, subtitles are off if `None`.
+    pub subtitle_stream_index: Option<u32>,
+}
+
+#[derive(
+    Debug,
+    Default,
+    Copy,
+    Clone,
+    Eq,
+    PartialEq,
+    serde_derive::Serialize,
+    serde_derive::Deserialize,
+)]
+/// When subtitles are turned on.
+pub enum SubtitleMode {
+    Off,
+    /// Only forced subtitles, covering foreign dialogue and signs.
+    OnlyForced,
+    /// Always show subtitles.
+    Always,
+    #[default]
+    /// Show subtitles when the audio is not in the preferred subtitle language,
+    /// otherwise only forced subtitles.
+    Smart,
+}
+
+impl SubtitleMode {
+    pub const ALL: [Self; 4] = [Self::Off, Self::OnlyForced, Self::Always, Self::Smart];
+
+    /// Returns the display label of the mode.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Off => "Off",
+            Self::OnlyForced => "Forced only",
+            Self::Always => "Always",
+            Self::Smart => "Smart",
+        }
+    }
+}
+
+#[derive(
+    Debug, Default, Clone, PartialEq, serde_derive::Serialize, serde_derive::Deserialize,
+)]
+/// Which audio and subtitle streams are selected by default.
+pub struct TrackPreferences {
+    /// The preferred audio language code, otherwise the default stream is played.
+    pub audio_language: Option<String>,
+    pub subtitle_mode: SubtitleMode,
+    /// The preferred subtitle language code.
+    pub subtitle_language: Option<String>,
+}
+
+impl TrackPreferences {
+    /// Select the streams of an item matching the preferences.
+    pub fn select(&self, tracks: &ItemTracks) -> TrackSelection {
+        let audio = self
+            .audio_language
+            .as_deref()
+            .and_then(|language| {
+                tracks
+                    .audio
+                    .iter()
+                    .find(|track| track.has_language(language))
+            })
+            .or_else(|| tracks.audio.iter().find(|track| track.is_default))
+            .or_else(|| tracks.audio.first());
+        let audio_language = audio.and_then(|track| track.language.as_deref());
+        let subtitle_language = self.subtitle_language.as_deref();
+
+        let subtitle = match self.subtitle_mode {
+            SubtitleMode::Off => None,
+            SubtitleMode::OnlyForced => {
+                forced_subtitle(tracks, subtitle_language.or(audio_language))
+            },
+            SubtitleMode::Always => full_subtitle(tracks, subtitle_language)
+                .or_else(|| tracks.subtitles.iter().find(|track| track.is_default))
+                .or_else(|| tracks.subtitles.iter().find(|track| !track.is_forced))
+                .or_else(|| tracks.subtitles.first()),
+            SubtitleMode::Smart => {
+                let is_foreign = subtitle_language.zip(audio_language).is_some_and(
+                    |(subtitle, audio)| !subtitle.eq_ignore_ascii_case(audio),
+                );
+                if is_foreign {
+                    full_subtitle(tracks, subtitle_language)
+                } else {
+                    forced_subtitle(tracks, subtitle_language.or(audio_language))
+                }
+            },
+        };
+
+        TrackSelection {
+            audio_stream_index: audio.map(|track| track.index),
+            subtitle_stream_index: subtitle.map(|track| track.index),
+        }
+    }
+
+    /// Returns the preferences which select the same languages as a selection
+    /// the user made, used to remember the choice for the rest of a series.
+    pub fn remember(&self, tracks: &ItemTracks, selection: TrackSelection) -> Self {
+        let audio = selection
+            .audio_stream_index
+            .and_then(|index| tracks.audio_track(index));
+        let subtitle = selection
+            .subtitle_stream_index
+            .and_then(|index| tracks.subtitle_track(index));
+
+        let subtitle_mode = match subtitle {
+            None => SubtitleMode::Off,
+            Some(track) if track.is_forced => SubtitleMode::OnlyForced,
+            Some(_) => SubtitleMode::Always,
+        };
+
+        Self {
+            audio_language: audio
+                .and_then(|track| track.language.clone())
+                .or_else(|| self.audio_language.clone()),
+            subtitle_mode,
+            subtitle_language: subtitle
+                .and_then(|track| track.language.clone())
+                .or_else(|| self.subtitle_language.clone()),
+        }
+    }
+}
+
+/// Returns the full subtitles in the language, preferring those which are not
+/// forced.
+fn full_subtitle<'a>(
+    tracks: &'a ItemTracks,
+    language: Option<&str>,
+) -> Option<&'a MediaTrack> {
+    let language = language?;
+    let mut matching = tracks
+        .subtitles
+        .iter()
+        .filter(|track| track.has_language(language));
+    let first = matching.clone().next();
+    matching.find(|track| !track.is_forced).or(first)
+}
+
+/// Returns the forced subtitles in the language, or any forced subtitles if the
+/// language is unknown.
+fn forced_subtitle<'a>(
+    tracks: &'a ItemTracks,
+    language: Option<&str>,
+) -> Option<&'a MediaTrack> {
+    let mut forced = tracks.subtitles.iter().filter(|track| track.is_forced);
+    match language {
+        Some(language) => forced.find(|track| track.has_language(language)),
+        None => forced.next(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(
+        index: u32,
+        language: &str,
+        is_default: bool,
+        is_forced: bool,
+    ) -> MediaTrack {
+        MediaTrack {
+            index,
+            language: Some(language.to_string()),
+            title: language.to_string(),
+            is_default,
+            is_forced,
+        }
+    }
+
+    /// An anime episode with Japanese and English audio, and full and forced
+    /// English subtitles.
+    fn tracks() -> ItemTracks {
+        ItemTracks {
+            series_id: Some("series-1".into()),
+            media_path: None,
+            audio: vec![track(1, "jpn", true, false), track(2, "eng", false, false)],
+            subtitles: vec![
+                track(3, "eng", false, true),
+                track(4, "eng", false, false),
+                track(5, "spa", false, false),
+            ],
+        }
+    }
+
+    fn select(
+        audio_language: Option<&str>,
+        subtitle_mode: SubtitleMode,
+        subtitle_language: Option<&str>,
+    ) -> (Option<u32>, Option<u32>) {
+        let preferences = TrackPreferences {
+            audio_language: audio_language.map(String::from),
+            subtitle_mode,
+            subtitle_language: subtitle_language.map(String::from),
+        };
+        let selection = preferences.select(&tracks());
+        (
+            selection.audio_stream_index,
+            selection.subtitle_stream_index,
+        )
+    }
+
+    #[test]
+    fn test_select_audio() {
+        assert_eq!(select(None, SubtitleMode::Off, None), (Some(1), None));
+        assert_eq!(
+            select(Some("ENG"), SubtitleMode::Off, None),
+            (Some(2), None)
+        );
+        assert_eq!(
+            select(Some("fra"), SubtitleMode::Off, None),
+            (Some(1), None)
+        );
+    }
+
+    #[test]
+    fn test_select_subtitles() {
+        let eng = Some("eng");
+        assert_eq!(select(None, SubtitleMode::Always, eng), (Some(1), Some(4)));
+        assert_eq!(select(None, SubtitleMode::Always, None), (Some(1), Some(4)));
+        assert_eq!(
+            select(eng, SubtitleMode::OnlyForced, None),
+            (Some(2), Some(3))
+        );
+        assert_eq!(
+            select(None, SubtitleMode::OnlyForced, None),
+            (Some(1), None)
+        );
+
+        // Japanese audio needs full subtitles, English audio only forced ones.
+        assert_eq!(select(None, SubtitleMode::Smart, eng), (Some(1), Some(4)));
+        assert_eq!(select(eng, SubtitleMode::Smart, eng), (Some(2), Some(3)));
+        assert_eq!(
+            select(None, SubtitleMode::Smart, Some("deu")),
+            (Some(1), None)
+        );
+    }
+
+    #[test]
+    fn test_remember() {
+        let defaults = TrackPreferences {
+            audio_language: Some("eng".into()),
+            subtitle_mode: SubtitleMode::Smart,
+            subtitle_language: Some("eng".into()),
+        };
+
+        let selection = TrackSelection {
+            audio_stream_index: Some(1),
+            subtitle_stream_index: Some(5),
+        };
+        let remembered = defaults.remember(&tracks(), selection);
+        assert_eq!(remembered.audio_language.as_deref(), Some("jpn"));
+        assert_eq!(remembered.subtitle_mode, SubtitleMode::Always);
+        assert_eq!(remembered.subtitle_language.as_deref(), Some("spa"));
+        assert_eq!(remembered.select(&tracks()), selection);
+
+        let selection = TrackSelection {
+            audio_stream_index: Some(2),
+            subtitle_stream_index: None,
+        };
+        let remembered = defaults.remember(&tracks(), selection);
+        assert_eq!(remembered.subtitle_mode, SubtitleMode::Off);
+        assert_eq!(remembered.subtitle_language.as_deref(), Some("eng"));
+        assert_eq!(remembered.select(&tracks()), selection);
+    }
+}
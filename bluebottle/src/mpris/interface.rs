@@ -0,0 +1,523 @@
+//! The `org.mpris.MediaPlayer2` D-Bus interfaces.
+//!
+//! Each interface reads the shared [MediaState] and forwards the methods called
+//! by clients as [MprisCommand]s, the state is then updated once the player acts
+//! on them.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use parking_lot::RwLock;
+use tokio::sync::mpsc;
+use zbus::object_server::SignalEmitter;
+use zbus::zvariant::{ObjectPath, OwnedObjectPath, OwnedValue};
+use zbus::{Connection, connection, fdo, interface};
+
+use super::state::{self, MediaState, Source};
+use crate::models::music::RepeatMode;
+use crate::music::queue::EntryId;
+
+/// The object path all MPRIS interfaces are served at.
+pub const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+
+#[derive(Debug, Clone, PartialEq)]
+/// A command sent by an MPRIS client.
+pub enum MprisCommand {
+    Raise,
+    Quit,
+    Play,
+    Pause,
+    PlayPause,
+    Stop,
+    Next,
+    Previous,
+    /// Seek to the absolute position.
+    Seek(Duration),
+    /// Play the queue entry.
+    GoTo(EntryId),
+    SetShuffle(bool),
+    SetRepeat(RepeatMode),
+    SetVolume(f64),
+}
+
+#[derive(Clone)]
+/// The state shared by every interface.
+pub struct Shared {
+    state: Arc<RwLock<MediaState>>,
+    commands: mpsc::UnboundedSender<MprisCommand>,
+}
+
+impl Shared {
+    pub fn new(commands: mpsc::UnboundedSender<MprisCommand>) -> Self {
+        Self {
+            state: Arc::default(),
+            commands,
+        }
+    }
+
+    /// The player currently exposed to clients.
+    pub fn source(&self) -> Option<Source> {
+        self.state.read().source
+    }
+
+    fn send(&self, command: MprisCommand) {
+        let _ = self.commands.send(command);
+    }
+}
+
+/// Serve the interfaces on the connection being built.
+pub fn serve<'a>(
+    builder: connection::Builder<'a>,
+    shared: &Shared,
+) -> zbus::Result<connection::Builder<'a>> {
+    builder
+        .serve_at(OBJECT_PATH, Root(shared.clone()))?
+        .serve_at(OBJECT_PATH, Player(shared.clone()))?
+        .serve_at(OBJECT_PATH, TrackList(shared.clone()))
+}
+
+/// Replace the state, notifying clients of the properties which changed.
+pub async fn update(
+    connection: &Connection,
+    shared: &Shared,
+    state: MediaState,
+) -> zbus::Result<()> {
+    let (changes, position, tracks, track_id) = {
+        let mut current = shared.state.write();
+        let changes = state.changes(&current);
+        *current = state;
+        let tracks = current
+            .tracks
+            .iter()
+            .map(|track| track.id.clone())
+            .collect::<Vec<_>>();
+        (changes, current.position(), tracks, current.track_id())
+    };
+
+    let server = connection.object_server();
+    let player_ref = server.interface::<_, Player>(OBJECT_PATH).await?;
+    let emitter = player_ref.signal_emitter();
+    let player = player_ref.get().await;
+    if changes.status {
+        player.playback_status_changed(emitter).await?;
+    }
+    if changes.metadata {
+        player.metadata_changed(emitter).await?;
+    }
+    if changes.loop_status {
+        player.loop_status_changed(emitter).await?;
+    }
+    if changes.shuffle {
+        player.shuffle_changed(emitter).await?;
+    }
+    if changes.volume {
+        player.volume_changed(emitter).await?;
+    }
+    if changes.capabilities {
+        player.can_go_next_changed(emitter).await?;
+        player.can_go_previous_changed(emitter).await?;
+        player.can_play_changed(emitter).await?;
+        player.can_pause_changed(emitter).await?;
+        player.can_seek_changed(emitter).await?;
+    }
+    if changes.seeked {
+        Player::seeked(emitter, state::micros(position)).await?;
+    }
+    drop(player);
+
+    if changes.tracks {
+        let track_list_ref = server.interface::<_, TrackList>(OBJECT_PATH).await?;
+        let emitter = track_list_ref.signal_emitter();
+        TrackList::track_list_replaced(emitter, tracks, track_id).await?;
+        track_list_ref
+            .get()
+            .await
+            .tracks_invalidate(emitter)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// The `org.mpris.MediaPlayer2` interface.
+pub struct Root(Shared);
+
+#[interface(name = "org.mpris.MediaPlayer2")]
+impl Root {
+    fn raise(&self) {
+        self.0.send(MprisCommand::Raise);
+    }
+
+    fn quit(&self) {
+        self.0.send(MprisCommand::Quit);
+    }
+
+    #[zbus(property)]
+    fn can_quit(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_raise(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn has_track_list(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn identity(&self) -> &str {
+        "Bluebottle"
+    }
+
+    #[zbus(property)]
+    fn desktop_entry(&self) -> &str {
+        "bluebottle"
+    }
+
+    #[zbus(property)]
+    fn supported_uri_schemes(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    #[zbus(property)]
+    fn supported_mime_types(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// The `org.mpris.MediaPlayer2.Player` interface.
+pub struct Player(Shared);
+
+#[interface(name = "org.mpris.MediaPlayer2.Player")]
+impl Player {
+    fn next(&self) {
+        self.0.send(MprisCommand::Next);
+    }
+
+    fn previous(&self) {
+        self.0.send(MprisCommand::Previous);
+    }
+
+    fn pause(&self) {
+        self.0.send(MprisCommand::Pause);
+    }
+
+    fn play_pause(&self) {
+        self.0.send(MprisCommand::PlayPause);
+    }
+
+    fn stop(&self) {
+        self.0.send(MprisCommand::Stop);
+    }
+
+    fn play(&self) {
+        self.0.send(MprisCommand::Play);
+    }
+
+    /// Seek relative to the current position, seeking past the end skips to the
+    /// next track.
+    fn seek(&self, offset: i64) {
+        let (position, length) = {
+            let state = self.0.state.read();
+            (state.position(), state.length())
+        };
+        let offset_abs = Duration::from_micros(offset.unsigned_abs());
+        let target = if offset < 0 {
+            position.saturating_sub(offset_abs)
+        } else {
+            position + offset_abs
+        };
+
+        match length {
+            Some(length) if target >= length => self.0.send(MprisCommand::Next),
+            _ => self.0.send(MprisCommand::Seek(target)),
+        }
+    }
+
+    /// Seek to the position, ignored if the track has changed since.
+    fn set_position(&self, track_id: ObjectPath<'_>, position: i64) {
+        let length = {
+            let state = self.0.state.read();
+            if state.track_id().as_str() != track_id.as_str() {
+                return;
+            }
+            state.length()
+        };
+
+        let Ok(position) = u64::try_from(position).map(Duration::from_micros) else {
+            return;
+        };
+        if length.is_some_and(|length| position > length) {
+            return;
+        }
+        self.0.send(MprisCommand::Seek(position));
+    }
+
+    fn open_uri(&self, _uri: &str) -> fdo::Result<()> {
+        Err(fdo::Error::NotSupported(
+            "opening URIs is not supported".into(),
+        ))
+    }
+
+    #[zbus(signal)]
+    async fn seeked(emitter: &SignalEmitter<'_>, position: i64) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn playback_status(&self) -> &str {
+        self.0.state.read().status.as_str()
+    }
+
+    #[zbus(property)]
+    fn loop_status(&self) -> &str {
+        self.0.state.read().loop_status()
+    }
+
+    #[zbus(property)]
+    fn set_loop_status(&mut self, loop_status: &str) -> fdo::Result<()> {
+        let repeat = match loop_status {
+            "None" => RepeatMode::Off,
+            "Track" => RepeatMode::One,
+            "Playlist" => RepeatMode::All,
+            _ => {
+                return Err(fdo::Error::InvalidArgs(format!(
+                    "unknown loop status {loop_status:?}"
+                )));
+            },
+        };
+        self.0.send(MprisCommand::SetRepeat(repeat));
+        Ok(())
+    }
+
+    #[zbus(property)]
+    fn rate(&self) -> f64 {
+        1.0
+    }
+
+    /// Only the normal rate is supported, so changes are ignored.
+    #[zbus(property)]
+    fn set_rate(&mut self, _rate: f64) {}
+
+    #[zbus(property)]
+    fn shuffle(&self) -> bool {
+        self.0.state.read().shuffle
+    }
+
+    #[zbus(property)]
+    fn set_shuffle(&mut self, shuffle: bool) {
+        self.0.send(MprisCommand::SetShuffle(shuffle));
+    }
+
+    #[zbus(property)]
+    fn metadata(&self) -> HashMap<String, OwnedValue> {
+        self.0.state.read().metadata()
+    }
+
+    #[zbus(property)]
+    fn volume(&self) -> f64 {
+        self.0.state.read().volume
+    }
+
+    #[zbus(property)]
+    fn set_volume(&mut self, volume: f64) {
+        self.0.send(MprisCommand::SetVolume(volume.clamp(0.0, 1.0)));
+    }
+
+    /// Clients extrapolate the position themselves, so changes are not signalled.
+    #[zbus(property(emits_changed_signal = "false"))]
+    fn position(&self) -> i64 {
+        state::micros(self.0.state.read().position())
+    }
+
+    #[zbus(property)]
+    fn minimum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn maximum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn can_go_next(&self) -> bool {
+        self.0.state.read().can_go_next
+    }
+
+    #[zbus(property)]
+    fn can_go_previous(&self) -> bool {
+        self.0.state.read().can_go_previous
+    }
+
+    #[zbus(property)]
+    fn can_play(&self) -> bool {
+        self.0.state.read().has_track()
+    }
+
+    #[zbus(property)]
+    fn can_pause(&self) -> bool {
+        self.0.state.read().has_track()
+    }
+
+    #[zbus(property)]
+    fn can_seek(&self) -> bool {
+        self.0.state.read().has_track()
+    }
+
+    #[zbus(property)]
+    fn can_control(&self) -> bool {
+        true
+    }
+}
+
+/// The `org.mpris.MediaPlayer2.TrackList` interface, listing the music queue.
+pub struct TrackList(Shared);
+
+#[interface(name = "org.mpris.MediaPlayer2.TrackList")]
+impl TrackList {
+    fn get_tracks_metadata(
+        &self,
+        track_ids: Vec<OwnedObjectPath>,
+    ) -> Vec<HashMap<String, OwnedValue>> {
+        let state = self.0.state.read();
+        track_ids
+            .iter()
+            .filter_map(|track_id| {
+                state.tracks.iter().find(|track| &track.id == track_id)
+            })
+            .map(|track| track.metadata())
+            .collect()
+    }
+
+    /// The queue is only edited within the app.
+    fn add_track(
+        &self,
+        _uri: &str,
+        _after_track: ObjectPath<'_>,
+        _set_as_current: bool,
+    ) {
+    }
+
+    /// The queue is only edited within the app.
+    fn remove_track(&self, _track_id: ObjectPath<'_>) {}
+
+    fn go_to(&self, track_id: ObjectPath<'_>) {
+        if let Some(entry_id) = state::entry_id(track_id.as_str()) {
+            self.0.send(MprisCommand::GoTo(entry_id));
+        }
+    }
+
+    #[zbus(signal)]
+    async fn track_list_replaced(
+        emitter: &SignalEmitter<'_>,
+        tracks: Vec<OwnedObjectPath>,
+        current_track: OwnedObjectPath,
+    ) -> zbus::Result<()>;
+
+    #[zbus(property(emits_changed_signal = "invalidates"))]
+    fn tracks(&self) -> Vec<OwnedObjectPath> {
+        let state = self.0.state.read();
+        state.tracks.iter().map(|track| track.id.clone()).collect()
+    }
+
+    #[zbus(property)]
+    fn can_edit_tracks(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zbus::proxy::CacheProperties;
+
+    use super::*;
+    use crate::mpris::state::{Status, Track};
+
+    /// Serve the interfaces over a peer to peer connection, returning the server
+    /// and client connections.
+    async fn connect(shared: &Shared) -> (Connection, Connection) {
+        let (server, client) = tokio::net::UnixStream::pair().unwrap();
+        let server = serve(
+            connection::Builder::unix_stream(server)
+                .server(zbus::Guid::generate())
+                .unwrap()
+                .p2p(),
+            shared,
+        )
+        .unwrap();
+        let client = connection::Builder::unix_stream(client).p2p();
+        tokio::try_join!(server.build(), client.build()).unwrap()
+    }
+
+    async fn proxy<'a>(client: &Connection, interface: &'a str) -> zbus::Proxy<'a> {
+        zbus::proxy::Builder::new(client)
+            .destination("org.mpris.MediaPlayer2.bluebottle")
+            .unwrap()
+            .path(OBJECT_PATH)
+            .unwrap()
+            .interface(interface)
+            .unwrap()
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_player_interface() {
+        let (commands_tx, mut commands) = mpsc::unbounded_channel();
+        let shared = Shared::new(commands_tx);
+        let (server, client) = connect(&shared).await;
+
+        let player = proxy(&client, "org.mpris.MediaPlayer2.Player").await;
+        let status: String = player.get_property("PlaybackStatus").await.unwrap();
+        assert_eq!(status, "Stopped");
+        assert!(!player.get_property::<bool>("CanPlay").await.unwrap());
+
+        let track = Track {
+            id: OwnedObjectPath::try_from("/bluebottle/track/1").unwrap(),
+            title: "One".to_string(),
+            artist: None,
+            album: None,
+            length: Some(Duration::from_secs(180)),
+            art_url: None,
+        };
+        let state = MediaState {
+            source: Some(Source::Music),
+            status: Status::Paused,
+            current: Some(track.clone()),
+            tracks: vec![track],
+            position: Duration::from_secs(30),
+            ..MediaState::default()
+        };
+        update(&server, &shared, state).await.unwrap();
+
+        let status: String = player.get_property("PlaybackStatus").await.unwrap();
+        assert_eq!(status, "Paused");
+        let position: i64 = player.get_property("Position").await.unwrap();
+        assert_eq!(position, 30_000_000);
+        let metadata: HashMap<String, OwnedValue> =
+            player.get_property("Metadata").await.unwrap();
+        let title: String = metadata["xesam:title"].clone().try_into().unwrap();
+        assert_eq!(title, "One");
+
+        player.call_method("PlayPause", &()).await.unwrap();
+        assert_eq!(commands.recv().await, Some(MprisCommand::PlayPause));
+
+        player.call_method("Seek", &(-60_000_000i64)).await.unwrap();
+        assert_eq!(
+            commands.recv().await,
+            Some(MprisCommand::Seek(Duration::ZERO))
+        );
+        player.call_method("Seek", &(600_000_000i64)).await.unwrap();
+        assert_eq!(commands.recv().await, Some(MprisCommand::Next));
+
+        let track_list = proxy(&client, "org.mpris.MediaPlayer2.TrackList").await;
+        let track_id = ObjectPath::try_from("/bluebottle/track/1").unwrap();
+        track_list.call_method("GoTo", &track_id).await.unwrap();
+        assert_eq!(commands.recv().await, Some(MprisCommand::GoTo(1)));
+    }
+}
@@ -0,0 +1,222 @@
+//! MPRIS support, letting the desktop's media controls show and control the music
+//! or video being played.
+//!
+//! The `org.mpris.MediaPlayer2` service reflects the video being played if any,
+//! otherwise the music player. Artwork is downloaded into the asset cache, so it
+//! can be handed to clients as a file.
+
+use std::collections::HashMap;
+
+use iced::Subscription;
+use iced::futures::channel::mpsc;
+use iced::futures::{SinkExt, Stream};
+use tokio::sync::{mpsc as tokio_mpsc, watch};
+use zbus::{Connection, connection};
+
+use self::interface::{MprisCommand, Shared};
+use self::state::{MediaState, Source};
+use crate::backends::{BackendId, registry};
+use crate::models::live::RemoteCommand;
+use crate::models::media::ItemId;
+use crate::music::{self, PlayerCommand, PlayerState};
+use crate::playback::{self, NowPlaying};
+use crate::storage::asset_cache;
+
+mod interface;
+mod state;
+
+/// The well known name the service is registered under.
+static BUS_NAME: &str = "org.mpris.MediaPlayer2.bluebottle";
+
+#[derive(Debug, Clone)]
+/// A request from the desktop which the UI needs to act on.
+pub enum MprisEvent {
+    /// Bring the window to the front.
+    Raise,
+    Quit,
+}
+
+/// Serves the MPRIS interfaces on the session bus while the app runs.
+///
+/// Nothing is served if there is no session bus.
+pub fn subscription() -> Subscription<MprisEvent> {
+    Subscription::run(mpris_stream)
+}
+
+fn mpris_stream() -> impl Stream<Item = MprisEvent> {
+    iced::stream::channel(4, async |mut output: mpsc::Sender<MprisEvent>| {
+        let (commands_tx, mut commands) = tokio_mpsc::unbounded_channel();
+        let shared = Shared::new(commands_tx);
+        let connection = match connect(&shared).await {
+            Ok(connection) => connection,
+            Err(err) => {
+                tracing::warn!(error = %err, "failed to register MPRIS service");
+                return;
+            },
+        };
+
+        let mut music = music::state();
+        let mut video = playback::now_playing();
+        let (artwork_tx, mut artwork_ready) = tokio_mpsc::unbounded_channel();
+        let mut artwork = Artwork::new(artwork_tx);
+
+        loop {
+            let state = media_state(&mut music, &mut video, &mut artwork);
+            if let Err(err) = interface::update(&connection, &shared, state).await {
+                tracing::warn!(error = %err, "failed to update MPRIS state");
+            }
+
+            tokio::select! {
+                result = music.changed() => {
+                    if result.is_err() {
+                        return;
+                    }
+                },
+                result = video.changed() => {
+                    if result.is_err() {
+                        return;
+                    }
+                },
+                Some(key) = artwork_ready.recv() => artwork.ready(&key),
+                Some(command) = commands.recv() => {
+                    let event = match command {
+                        MprisCommand::Raise => MprisEvent::Raise,
+                        MprisCommand::Quit => MprisEvent::Quit,
+                        command => {
+                            dispatch(shared.source(), command);
+                            continue;
+                        },
+                    };
+                    if output.send(event).await.is_err() {
+                        return;
+                    }
+                },
+            }
+        }
+    })
+}
+
+async fn connect(shared: &Shared) -> zbus::Result<Connection> {
+    let builder = connection::Builder::session()?.name(BUS_NAME)?;
+    interface::serve(builder, shared)?.build().await
+}
+
+/// The state of the video being played, otherwise the music player.
+fn media_state(
+    music: &mut watch::Receiver<PlayerState>,
+    video: &mut watch::Receiver<Option<NowPlaying>>,
+    artwork: &mut Artwork,
+) -> MediaState {
+    if let Some(now_playing) = video.borrow_and_update().as_ref() {
+        let art_url = artwork.url(now_playing.backend_id, &now_playing.item_id);
+        return MediaState::from_video(now_playing, art_url);
+    }
+
+    let music = music.borrow_and_update();
+    if music.current.is_none() {
+        return MediaState::default();
+    }
+    MediaState::from_music(&music, |entry| {
+        // Tracks rarely have their own artwork, unlike their album.
+        let item_id = entry
+            .track
+            .album_id
+            .as_ref()
+            .unwrap_or(&entry.track.item_id);
+        artwork.url(entry.backend_id, item_id)
+    })
+}
+
+/// Forward a command to the player exposed to clients.
+fn dispatch(source: Option<Source>, command: MprisCommand) {
+    match source {
+        Some(Source::Video) => {
+            let command = match command {
+                MprisCommand::Play => RemoteCommand::Unpause,
+                MprisCommand::Pause => RemoteCommand::Pause,
+                MprisCommand::PlayPause => RemoteCommand::PlayPause,
+                MprisCommand::Stop => RemoteCommand::Stop,
+                MprisCommand::Seek(position) => RemoteCommand::Seek(position),
+                MprisCommand::SetVolume(volume) => {
+                    RemoteCommand::SetVolume((volume * 100.0).round() as u8)
+                },
+                _ => return,
+            };
+            playback::send_command(command);
+        },
+        Some(Source::Music) => {
+            let command = match command {
+                MprisCommand::Play => PlayerCommand::Play,
+                MprisCommand::Pause => PlayerCommand::Pause,
+                MprisCommand::PlayPause => PlayerCommand::TogglePause,
+                MprisCommand::Stop => PlayerCommand::Stop,
+                MprisCommand::Next => PlayerCommand::Next,
+                MprisCommand::Previous => PlayerCommand::Previous,
+                MprisCommand::Seek(position) => PlayerCommand::Seek(position),
+                MprisCommand::GoTo(entry_id) => PlayerCommand::Jump(entry_id),
+                MprisCommand::SetShuffle(shuffle) => PlayerCommand::SetShuffle(shuffle),
+                MprisCommand::SetRepeat(repeat) => PlayerCommand::SetRepeat(repeat),
+                MprisCommand::SetVolume(volume) => {
+                    PlayerCommand::SetVolume(volume as f32)
+                },
+                MprisCommand::Raise | MprisCommand::Quit => return,
+            };
+            music::send(command);
+        },
+        None => {},
+    }
+}
+
+/// Resolves the artwork of items to files within the asset cache.
+struct Artwork {
+    /// The resolved URLs, `None` while downloading or if the download failed.
+    urls: HashMap<String, Option<String>>,
+    ready: tokio_mpsc::UnboundedSender<String>,
+}
+
+impl Artwork {
+    fn new(ready: tokio_mpsc::UnboundedSender<String>) -> Self {
+        Self {
+            urls: HashMap::new(),
+            ready,
+        }
+    }
+
+    /// Returns the URL of the item's cached artwork.
+    ///
+    /// Missing artwork is downloaded in the background, the cache key is sent
+    /// once it is ready.
+    fn url(&mut self, backend_id: BackendId, item_id: &ItemId) -> Option<String> {
+        let key = format!("{backend_id}/{item_id}/primary");
+        if let Some(url) = self.urls.get(&key) {
+            return url.clone();
+        }
+
+        if let Some(path) = asset_cache::try_get_file(&key) {
+            let url = url::Url::from_file_path(path).ok().map(String::from);
+            self.urls.insert(key, url.clone());
+            return url;
+        }
+
+        // Downloads are only attempted once, as not every item has artwork.
+        self.urls.insert(key.clone(), None);
+        let backend = registry::get(backend_id)?;
+        let item_id = item_id.clone();
+        let ready = self.ready.clone();
+        tokio::spawn(async move {
+            match backend.primary_image(item_id).await {
+                Ok(data) => {
+                    asset_cache::insert(&key, &data);
+                    let _ = ready.send(key);
+                },
+                Err(err) => tracing::debug!(error = %err, "failed to fetch artwork"),
+            }
+        });
+        None
+    }
+
+    /// Resolve the artwork again now it has been downloaded.
+    fn ready(&mut self, key: &str) {
+        self.urls.remove(key);
+    }
+}
@@ -0,0 +1,422 @@
+//! The playback state exposed over MPRIS, built from either the music player or
+//! the external video player.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use zbus::zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value};
+
+use crate::models::music::RepeatMode;
+use crate::music::queue::{EntryId, QueueEntry};
+use crate::music::{PlayerState, PlayerStatus};
+use crate::playback::NowPlaying;
+
+/// The track ID used when nothing is loaded.
+pub const NO_TRACK: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+static MUSIC_TRACK_PREFIX: &str = "/bluebottle/track/";
+static VIDEO_TRACK: &str = "/bluebottle/video";
+/// How far the position may drift from where it is expected to be before it is
+/// treated as a seek, as positions are only published periodically.
+const SEEK_TOLERANCE: Duration = Duration::from_millis(1500);
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+/// The player the state was built from.
+pub enum Source {
+    Music,
+    Video,
+}
+
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub enum Status {
+    Playing,
+    Paused,
+    #[default]
+    Stopped,
+}
+
+impl Status {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Playing => "Playing",
+            Self::Paused => "Paused",
+            Self::Stopped => "Stopped",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A track as described to MPRIS clients.
+pub struct Track {
+    pub id: OwnedObjectPath,
+    pub title: String,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub length: Option<Duration>,
+    /// A `file://` URL of the cached artwork.
+    pub art_url: Option<String>,
+}
+
+impl Track {
+    /// The MPRIS metadata of the track.
+    pub fn metadata(&self) -> HashMap<String, OwnedValue> {
+        let mut metadata = HashMap::new();
+        insert(&mut metadata, "mpris:trackid", Value::from(self.id.clone()));
+        insert(
+            &mut metadata,
+            "xesam:title",
+            Value::new(self.title.as_str()),
+        );
+        if let Some(artist) = self.artist.as_deref() {
+            insert(&mut metadata, "xesam:artist", Value::new(vec![artist]));
+        }
+        if let Some(album) = self.album.as_deref() {
+            insert(&mut metadata, "xesam:album", Value::new(album));
+        }
+        if let Some(length) = self.length {
+            insert(&mut metadata, "mpris:length", Value::new(micros(length)));
+        }
+        if let Some(art_url) = self.art_url.as_deref() {
+            insert(&mut metadata, "mpris:artUrl", Value::new(art_url));
+        }
+        metadata
+    }
+}
+
+#[derive(Debug, Clone)]
+/// The state of whichever player is active.
+pub struct MediaState {
+    pub source: Option<Source>,
+    pub status: Status,
+    pub current: Option<Track>,
+    /// The queued tracks in the order they are played.
+    pub tracks: Vec<Track>,
+    /// The position when the state was built.
+    pub position: Duration,
+    pub position_at: Instant,
+    pub shuffle: bool,
+    pub repeat: RepeatMode,
+    pub volume: f64,
+    pub can_go_next: bool,
+    pub can_go_previous: bool,
+}
+
+impl Default for MediaState {
+    fn default() -> Self {
+        Self {
+            source: None,
+            status: Status::Stopped,
+            current: None,
+            tracks: Vec::new(),
+            position: Duration::ZERO,
+            position_at: Instant::now(),
+            shuffle: false,
+            repeat: RepeatMode::Off,
+            volume: 1.0,
+            can_go_next: false,
+            can_go_previous: false,
+        }
+    }
+}
+
+impl MediaState {
+    /// Build the state of the music player, `art_url` resolves the artwork of
+    /// each entry.
+    pub fn from_music(
+        state: &PlayerState,
+        mut art_url: impl FnMut(&QueueEntry) -> Option<String>,
+    ) -> Self {
+        let status = match state.status {
+            PlayerStatus::Playing | PlayerStatus::Loading => Status::Playing,
+            PlayerStatus::Paused => Status::Paused,
+            PlayerStatus::Stopped => Status::Stopped,
+        };
+        let current = state.current.as_ref().map(|entry| {
+            let mut track = music_track(entry, art_url(entry));
+            track.length = state.duration.or(track.length);
+            track
+        });
+
+        Self {
+            source: Some(Source::Music),
+            status,
+            current,
+            tracks: state
+                .queue
+                .iter()
+                .map(|entry| music_track(entry, art_url(entry)))
+                .collect(),
+            position: state.position,
+            position_at: Instant::now(),
+            shuffle: state.shuffle,
+            repeat: state.repeat,
+            volume: state.volume as f64,
+            can_go_next: state.has_next,
+            can_go_previous: state.has_previous,
+        }
+    }
+
+    /// Build the state of the video being played.
+    pub fn from_video(now_playing: &NowPlaying, art_url: Option<String>) -> Self {
+        let status = if now_playing.paused {
+            Status::Paused
+        } else {
+            Status::Playing
+        };
+        let track = Track {
+            id: object_path(VIDEO_TRACK.to_string()),
+            title: now_playing.title.clone(),
+            artist: None,
+            album: None,
+            length: None,
+            art_url,
+        };
+
+        Self {
+            source: Some(Source::Video),
+            status,
+            tracks: vec![track.clone()],
+            current: Some(track),
+            position: now_playing.position,
+            position_at: now_playing.reported_at,
+            ..Self::default()
+        }
+    }
+
+    /// The current position, extrapolated while playing.
+    pub fn position(&self) -> Duration {
+        let position = match self.status {
+            Status::Playing => self.position + self.position_at.elapsed(),
+            Status::Paused | Status::Stopped => self.position,
+        };
+        match self.length() {
+            Some(length) => position.min(length),
+            None => position,
+        }
+    }
+
+    /// The length of the current track, if known.
+    pub fn length(&self) -> Option<Duration> {
+        self.current.as_ref().and_then(|track| track.length)
+    }
+
+    /// The ID of the current track.
+    pub fn track_id(&self) -> OwnedObjectPath {
+        match &self.current {
+            Some(track) => track.id.clone(),
+            None => object_path(NO_TRACK.to_string()),
+        }
+    }
+
+    /// The metadata of the current track.
+    pub fn metadata(&self) -> HashMap<String, OwnedValue> {
+        match &self.current {
+            Some(track) => track.metadata(),
+            None => {
+                let mut metadata = HashMap::new();
+                let no_track = ObjectPath::from_static_str_unchecked(NO_TRACK);
+                insert(&mut metadata, "mpris:trackid", Value::new(no_track));
+                metadata
+            },
+        }
+    }
+
+    pub fn loop_status(&self) -> &'static str {
+        match self.repeat {
+            RepeatMode::Off => "None",
+            RepeatMode::One => "Track",
+            RepeatMode::All => "Playlist",
+        }
+    }
+
+    pub fn has_track(&self) -> bool {
+        self.current.is_some()
+    }
+
+    /// Returns which properties differ from the previous state.
+    pub fn changes(&self, previous: &MediaState) -> Changes {
+        let same_track = self.track_id() == previous.track_id();
+        let drift = self.position().abs_diff(previous.position());
+
+        Changes {
+            status: self.status != previous.status,
+            metadata: self.current != previous.current,
+            loop_status: self.repeat != previous.repeat,
+            shuffle: self.shuffle != previous.shuffle,
+            volume: self.volume != previous.volume,
+            capabilities: self.can_go_next != previous.can_go_next
+                || self.can_go_previous != previous.can_go_previous
+                || self.has_track() != previous.has_track(),
+            tracks: self
+                .tracks
+                .iter()
+                .map(|track| &track.id)
+                .ne(previous.tracks.iter().map(|track| &track.id)),
+            seeked: same_track && self.has_track() && drift > SEEK_TOLERANCE,
+        }
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+/// The properties which changed between two states.
+pub struct Changes {
+    pub status: bool,
+    pub metadata: bool,
+    pub loop_status: bool,
+    pub shuffle: bool,
+    pub volume: bool,
+    /// Whether any of the `Can*` properties changed.
+    pub capabilities: bool,
+    pub tracks: bool,
+    /// The position jumped, rather than advancing with playback.
+    pub seeked: bool,
+}
+
+/// Returns the queue entry of a track ID.
+pub fn entry_id(track_id: &str) -> Option<EntryId> {
+    track_id.strip_prefix(MUSIC_TRACK_PREFIX)?.parse().ok()
+}
+
+/// The position in microseconds, as used by MPRIS.
+pub fn micros(position: Duration) -> i64 {
+    position.as_micros().try_into().unwrap_or(i64::MAX)
+}
+
+fn music_track(entry: &QueueEntry, art_url: Option<String>) -> Track {
+    Track {
+        id: object_path(format!("{MUSIC_TRACK_PREFIX}{}", entry.id)),
+        title: entry.track.name.clone(),
+        artist: entry.track.artist.clone(),
+        album: entry.track.album.clone(),
+        length: entry.track.runtime,
+        art_url,
+    }
+}
+
+fn object_path(path: String) -> OwnedObjectPath {
+    OwnedObjectPath::try_from(path).expect("track paths are valid object paths")
+}
+
+fn insert(metadata: &mut HashMap<String, OwnedValue>, key: &str, value: Value<'_>) {
+    // Only values holding file descriptors can fail to convert.
+    if let Ok(value) = OwnedValue::try_from(value) {
+        metadata.insert(key.to_string(), value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::models::music::AudioTrack;
+
+    fn entry(id: EntryId, name: &str) -> QueueEntry {
+        QueueEntry {
+            id,
+            backend_id: uuid::Uuid::nil(),
+            track: AudioTrack {
+                item_id: format!("item-{id}"),
+                name: name.to_string(),
+                artist: Some("Artist".to_string()),
+                album: Some("Album".to_string()),
+                album_id: Some("album".to_string()),
+                runtime: Some(Duration::from_secs(180)),
+                normalization_gain: None,
+            },
+        }
+    }
+
+    fn player_state() -> PlayerState {
+        let queue: Arc<[QueueEntry]> = vec![entry(1, "One"), entry(2, "Two")].into();
+        PlayerState {
+            current: Some(queue[0].clone()),
+            queue,
+            status: PlayerStatus::Paused,
+            position: Duration::from_secs(30),
+            duration: Some(Duration::from_secs(181)),
+            volume: 0.5,
+            has_next: true,
+            ..PlayerState::default()
+        }
+    }
+
+    #[test]
+    fn test_from_music() {
+        let state = MediaState::from_music(&player_state(), |entry| {
+            Some(format!("file:///cache/{}", entry.id))
+        });
+        assert_eq!(state.source, Some(Source::Music));
+        assert_eq!(state.status, Status::Paused);
+        assert_eq!(state.position(), Duration::from_secs(30));
+        assert_eq!(state.length(), Some(Duration::from_secs(181)));
+        assert_eq!(state.track_id().as_str(), "/bluebottle/track/1");
+        assert_eq!(state.tracks.len(), 2);
+        assert_eq!(state.tracks[1].id.as_str(), "/bluebottle/track/2");
+        assert!(state.can_go_next);
+        assert!(!state.can_go_previous);
+
+        let metadata = state.metadata();
+        let title: String = metadata["xesam:title"].clone().try_into().unwrap();
+        assert_eq!(title, "One");
+        let length: i64 = metadata["mpris:length"].clone().try_into().unwrap();
+        assert_eq!(length, 181_000_000);
+        let art_url: String = metadata["mpris:artUrl"].clone().try_into().unwrap();
+        assert_eq!(art_url, "file:///cache/1");
+    }
+
+    #[test]
+    fn test_from_video() {
+        let now_playing = NowPlaying {
+            backend_id: uuid::Uuid::nil(),
+            item_id: "movie".to_string(),
+            title: "A Movie".to_string(),
+            position: Duration::from_secs(60),
+            reported_at: Instant::now() - Duration::from_secs(5),
+            paused: false,
+        };
+        let state = MediaState::from_video(&now_playing, None);
+        assert_eq!(state.source, Some(Source::Video));
+        assert_eq!(state.status, Status::Playing);
+        assert_eq!(state.track_id().as_str(), VIDEO_TRACK);
+        assert!(state.position() >= Duration::from_secs(65));
+        assert!(!state.can_go_next);
+    }
+
+    #[test]
+    fn test_no_track_metadata() {
+        let state = MediaState::default();
+        assert_eq!(state.track_id().as_str(), NO_TRACK);
+        let metadata = state.metadata();
+        assert_eq!(metadata.len(), 1);
+        assert!(metadata.contains_key("mpris:trackid"));
+    }
+
+    #[test]
+    fn test_changes() {
+        let previous = MediaState::from_music(&player_state(), |_| None);
+        assert_eq!(previous.changes(&previous), Changes::default());
+
+        let mut state = player_state();
+        state.position = Duration::from_secs(90);
+        state.shuffle = true;
+        let current = MediaState::from_music(&state, |_| None);
+        let changes = current.changes(&previous);
+        assert!(changes.seeked);
+        assert!(changes.shuffle);
+        assert!(!changes.metadata);
+        assert!(!changes.tracks);
+
+        state.current = Some(state.queue[1].clone());
+        let current = MediaState::from_music(&state, |_| None);
+        let changes = current.changes(&previous);
+        assert!(changes.metadata);
+        assert!(!changes.seeked);
+    }
+
+    #[test]
+    fn test_entry_id() {
+        assert_eq!(entry_id("/bluebottle/track/42"), Some(42));
+        assert_eq!(entry_id(VIDEO_TRACK), None);
+        assert_eq!(entry_id(NO_TRACK), None);
+    }
+}
@@ -0,0 +1,294 @@
+//! Plays music on the default output device through cpal.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use parking_lot::Mutex;
+use snafu::{OptionExt, ResultExt};
+
+use super::decoder::AudioSpec;
+use super::sink::AudioSink;
+
+/// How much audio is buffered ahead of the output device.
+const BUFFER_DURATION: Duration = Duration::from_millis(250);
+
+#[derive(Debug, snafu::Snafu)]
+pub enum SinkError {
+    #[snafu(display("no audio output device available"))]
+    NoDevice,
+    #[snafu(display("failed to read output device config: {}", source))]
+    Config {
+        source: cpal::DefaultStreamConfigError,
+    },
+    #[snafu(display("failed to open audio output: {}", source))]
+    Build { source: cpal::BuildStreamError },
+    #[snafu(display("failed to start audio output: {}", source))]
+    Play { source: cpal::PlayStreamError },
+}
+
+struct SharedBuffer {
+    samples: VecDeque<f32>,
+    paused: bool,
+    volume: f32,
+}
+
+/// Plays samples on the default output device.
+///
+/// The device is opened at the track's sample rate when possible so the audio
+/// is left untouched, otherwise the samples are resampled to the device's
+/// default rate.
+pub struct CpalSink {
+    shared: Arc<Mutex<SharedBuffer>>,
+    /// The number of samples the buffer holds at most.
+    capacity: usize,
+    spec: AudioSpec,
+    resampler: Option<Resampler>,
+    /// Resampled frames waiting to be moved into the buffer.
+    pending: Vec<f32>,
+    // The stream stops once dropped.
+    _stream: cpal::Stream,
+}
+
+impl CpalSink {
+    pub fn open(spec: AudioSpec) -> Result<Self, SinkError> {
+        let device = cpal::default_host()
+            .default_output_device()
+            .context(NoDeviceSnafu)?;
+
+        let supports_spec = device
+            .supported_output_configs()
+            .map(|mut configs| {
+                configs.any(|config| {
+                    config.channels() == spec.channels
+                        && config.sample_format() == cpal::SampleFormat::F32
+                        && (config.min_sample_rate().0..=config.max_sample_rate().0)
+                            .contains(&spec.sample_rate)
+                })
+            })
+            .unwrap_or(false);
+
+        let output_spec = if supports_spec {
+            spec
+        } else {
+            let config = device.default_output_config().context(ConfigSnafu)?;
+            AudioSpec {
+                sample_rate: config.sample_rate().0,
+                channels: config.channels(),
+            }
+        };
+
+        let resampler = (output_spec != spec).then(|| {
+            tracing::info!(track = ?spec, output = ?output_spec, "resampling audio for output device");
+            Resampler::new(spec, output_spec)
+        });
+
+        let capacity = (output_spec.sample_rate as f64 * BUFFER_DURATION.as_secs_f64())
+            as usize
+            * output_spec.channels as usize;
+        let shared = Arc::new(Mutex::new(SharedBuffer {
+            samples: VecDeque::with_capacity(capacity),
+            paused: false,
+            volume: 1.0,
+        }));
+
+        let config = cpal::StreamConfig {
+            channels: output_spec.channels,
+            sample_rate: cpal::SampleRate(output_spec.sample_rate),
+            buffer_size: cpal::BufferSize::Default,
+        };
+        let callback_shared = shared.clone();
+        let stream = device
+            .build_output_stream(
+                &config,
+                move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
+                    let mut shared = callback_shared.lock();
+                    if shared.paused {
+                        data.fill(0.0);
+                        return;
+                    }
+
+                    let volume = shared.volume;
+                    for sample in data.iter_mut() {
+                        // Underruns are filled with silence.
+                        *sample = shared.samples.pop_front().unwrap_or(0.0) * volume;
+                    }
+                },
+                |err| tracing::error!(error = %err, "audio output failed"),
+                None,
+            )
+            .context(BuildSnafu)?;
+        stream.play().context(PlaySnafu)?;
+
+        Ok(Self {
+            shared,
+            capacity,
+            spec,
+            resampler,
+            pending: Vec::new(),
+            _stream: stream,
+        })
+    }
+
+    /// Move as many pending samples into the shared buffer as will fit.
+    fn flush_pending(&mut self, shared: &mut SharedBuffer) {
+        let room = self.capacity.saturating_sub(shared.samples.len());
+        let count = room.min(self.pending.len());
+        shared.samples.extend(self.pending.drain(..count));
+    }
+}
+
+impl AudioSink for CpalSink {
+    fn write(&mut self, samples: &[f32]) -> usize {
+        let channels = self.spec.channels as usize;
+        let shared = self.shared.clone();
+        let mut shared = shared.lock();
+
+        // Taken while writing so the pending samples can be flushed alongside it.
+        let Some(mut resampler) = self.resampler.take() else {
+            let room = self.capacity.saturating_sub(shared.samples.len());
+            let count = room.min(samples.len()) / channels * channels;
+            shared.samples.extend(&samples[..count]);
+            return count;
+        };
+
+        self.flush_pending(&mut shared);
+        let written = if self.pending.is_empty() {
+            resampler.process(samples, &mut self.pending);
+            self.flush_pending(&mut shared);
+            samples.len() / channels * channels
+        } else {
+            0
+        };
+
+        self.resampler = Some(resampler);
+        written
+    }
+
+    fn buffered_frames(&self) -> u64 {
+        let shared = self.shared.lock();
+        let output_channels = self
+            .resampler
+            .as_ref()
+            .map(|resampler| resampler.output.channels)
+            .unwrap_or(self.spec.channels) as usize;
+        let frames = (shared.samples.len() + self.pending.len()) / output_channels;
+
+        // Convert back into frames of the track.
+        match &self.resampler {
+            Some(resampler) => (frames as f64 / resampler.ratio) as u64,
+            None => frames as u64,
+        }
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        self.shared.lock().paused = paused;
+    }
+
+    fn set_volume(&mut self, volume: f32) {
+        self.shared.lock().volume = volume.clamp(0.0, 1.0);
+    }
+
+    fn clear(&mut self) {
+        self.shared.lock().samples.clear();
+        self.pending.clear();
+        if let Some(resampler) = self.resampler.as_mut() {
+            resampler.reset();
+        }
+    }
+}
+
+/// A linear resampler, also mapping the channels to the output layout.
+///
+/// This is only used when the device cannot play the track's sample rate.
+struct Resampler {
+    input: AudioSpec,
+    output: AudioSpec,
+    /// Output frames produced per input frame.
+    ratio: f64,
+    /// The position of the next output frame, relative to `previous`.
+    position: f64,
+    /// The last input frame, mapped to the output channels.
+    previous: Vec<f32>,
+}
+
+impl Resampler {
+    fn new(input: AudioSpec, output: AudioSpec) -> Self {
+        Self {
+            input,
+            output,
+            ratio: output.sample_rate as f64 / input.sample_rate as f64,
+            position: 0.0,
+            previous: vec![0.0; output.channels as usize],
+        }
+    }
+
+    fn reset(&mut self) {
+        self.position = 0.0;
+        self.previous.fill(0.0);
+    }
+
+    fn process(&mut self, samples: &[f32], output: &mut Vec<f32>) {
+        let step = 1.0 / self.ratio;
+        let input_channels = self.input.channels as usize;
+        let output_channels = self.output.channels as usize;
+
+        let mut current = vec![0.0; output_channels];
+        for frame in samples.chunks_exact(input_channels) {
+            map_channels(frame, &mut current);
+
+            // Emit every output frame which falls between the two input frames.
+            while self.position < 1.0 {
+                let t = self.position as f32;
+                output.extend(
+                    self.previous
+                        .iter()
+                        .zip(&current)
+                        .map(|(a, b)| a + (b - a) * t),
+                );
+                self.position += step;
+            }
+
+            self.position -= 1.0;
+            self.previous.copy_from_slice(&current);
+        }
+    }
+}
+
+/// Map a frame onto a different number of channels, mono is duplicated and
+/// extra channels are dropped.
+fn map_channels(input: &[f32], output: &mut [f32]) {
+    for (index, sample) in output.iter_mut().enumerate() {
+        *sample = match input.len() {
+            1 => input[0],
+            len => input.get(index).copied().unwrap_or(input[index % len]),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resampler() {
+        let input = AudioSpec {
+            sample_rate: 4,
+            channels: 1,
+        };
+        let output = AudioSpec {
+            sample_rate: 8,
+            channels: 2,
+        };
+        let mut resampler = Resampler::new(input, output);
+
+        let mut samples = Vec::new();
+        resampler.process(&[1.0, 1.0, 0.0, 0.0], &mut samples);
+        assert_eq!(samples.len(), 16);
+        // Mono is duplicated across both channels.
+        assert!(samples.chunks(2).all(|frame| frame[0] == frame[1]));
+        let left: Vec<f32> = samples.iter().step_by(2).copied().collect();
+        assert_eq!(left, vec![0.0, 0.5, 1.0, 1.0, 1.0, 0.5, 0.0, 0.0]);
+    }
+}
@@ -0,0 +1,286 @@
+//! Decodes tracks into interleaved `f32` samples with symphonia.
+
+use std::io::Cursor;
+use std::sync::Arc;
+use std::time::Duration;
+
+use snafu::{OptionExt, ResultExt};
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{CODEC_TYPE_NULL, Decoder, DecoderOptions};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::{FormatOptions, FormatReader, SeekMode, SeekTo};
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::{MetadataOptions, MetadataRevision, StandardTagKey};
+use symphonia::core::probe::Hint;
+use symphonia::core::units::{Time, TimeBase};
+
+use crate::models::music::{ReplayGain, parse_replay_gain_value};
+
+#[derive(Debug, snafu::Snafu)]
+pub enum DecodeError {
+    #[snafu(display("unsupported audio format: {}", source))]
+    Unsupported { source: SymphoniaError },
+    #[snafu(display("file has no audio track"))]
+    NoTrack,
+    #[snafu(display("failed to decode audio: {}", source))]
+    Decode { source: SymphoniaError },
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+/// The layout of the decoded samples.
+pub struct AudioSpec {
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+/// Decodes a single track held in memory.
+pub struct TrackDecoder {
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    time_base: Option<TimeBase>,
+    spec: AudioSpec,
+    duration: Option<Duration>,
+    replay_gain: ReplayGain,
+    buffer: Option<SampleBuffer<f32>>,
+}
+
+impl TrackDecoder {
+    /// Probe the format of the data and prepare to decode its first audio track.
+    pub fn new(data: Arc<[u8]>) -> Result<Self, DecodeError> {
+        let source =
+            MediaSourceStream::new(Box::new(Cursor::new(data)), Default::default());
+        let format_options = FormatOptions {
+            // Trims the encoder delay and padding so albums play without gaps.
+            enable_gapless: true,
+            ..Default::default()
+        };
+
+        let mut probed = symphonia::default::get_probe()
+            .format(
+                &Hint::new(),
+                source,
+                &format_options,
+                &MetadataOptions::default(),
+            )
+            .context(UnsupportedSnafu)?;
+
+        // Tags are found either before the container, i.e. ID3, or within it.
+        let mut replay_gain = ReplayGain::default();
+        if let Some(revision) = probed.metadata.get().as_ref().and_then(|m| m.current())
+        {
+            read_replay_gain(revision, &mut replay_gain);
+        }
+        if let Some(revision) = probed.format.metadata().current() {
+            read_replay_gain(revision, &mut replay_gain);
+        }
+
+        let format = probed.format;
+        let track = format
+            .tracks()
+            .iter()
+            .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+            .context(NoTrackSnafu)?;
+
+        let params = &track.codec_params;
+        let spec = AudioSpec {
+            sample_rate: params.sample_rate.context(NoTrackSnafu)?,
+            channels: params
+                .channels
+                .map(|channels| channels.count() as u16)
+                .unwrap_or(2),
+        };
+        let duration = params.n_frames.map(|frames| {
+            Duration::from_secs_f64(frames as f64 / spec.sample_rate as f64)
+        });
+        let track_id = track.id;
+        let time_base = params.time_base;
+
+        let decoder = symphonia::default::get_codecs()
+            .make(params, &DecoderOptions::default())
+            .context(UnsupportedSnafu)?;
+
+        Ok(Self {
+            format,
+            decoder,
+            track_id,
+            time_base,
+            spec,
+            duration,
+            replay_gain,
+            buffer: None,
+        })
+    }
+
+    pub fn spec(&self) -> AudioSpec {
+        self.spec
+    }
+
+    pub fn duration(&self) -> Option<Duration> {
+        self.duration
+    }
+
+    pub fn replay_gain(&self) -> ReplayGain {
+        self.replay_gain
+    }
+
+    /// Decode the next chunk of interleaved samples, returning `None` at the end
+    /// of the track.
+    ///
+    /// Corrupt packets are skipped rather than ending the track.
+    pub fn next_chunk(&mut self) -> Result<Option<&[f32]>, DecodeError> {
+        loop {
+            let packet = match self.format.next_packet() {
+                Ok(packet) => packet,
+                Err(SymphoniaError::IoError(err))
+                    if err.kind() == std::io::ErrorKind::UnexpectedEof =>
+                {
+                    return Ok(None);
+                },
+                Err(err) => return Err(err).context(DecodeSnafu),
+            };
+
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+
+            let decoded = match self.decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                Err(SymphoniaError::DecodeError(err)) => {
+                    tracing::debug!(error = %err, "skipping corrupt audio packet");
+                    continue;
+                },
+                Err(err) => return Err(err).context(DecodeSnafu),
+            };
+
+            if decoded.frames() == 0 {
+                continue;
+            }
+
+            let spec = *decoded.spec();
+            let capacity = decoded.capacity() as u64;
+            let required = decoded.capacity() * spec.channels.count();
+            if self
+                .buffer
+                .as_ref()
+                .is_some_and(|buffer| buffer.capacity() < required)
+            {
+                self.buffer = None;
+            }
+            let buffer = self
+                .buffer
+                .get_or_insert_with(|| SampleBuffer::new(capacity, spec));
+            buffer.copy_interleaved_ref(decoded);
+
+            return Ok(Some(buffer.samples()));
+        }
+    }
+
+    /// Seek to the position, returning the position actually reached.
+    pub fn seek(&mut self, position: Duration) -> Result<Duration, DecodeError> {
+        let time = Time::new(position.as_secs(), position.subsec_nanos() as f64 / 1e9);
+        let seeked = self
+            .format
+            .seek(
+                SeekMode::Accurate,
+                SeekTo::Time {
+                    time,
+                    track_id: Some(self.track_id),
+                },
+            )
+            .context(DecodeSnafu)?;
+        self.decoder.reset();
+
+        let actual = match self.time_base {
+            Some(time_base) => {
+                let time = time_base.calc_time(seeked.actual_ts);
+                Duration::from_secs(time.seconds) + Duration::from_secs_f64(time.frac)
+            },
+            None => position,
+        };
+        Ok(actual)
+    }
+}
+
+fn read_replay_gain(revision: &MetadataRevision, replay_gain: &mut ReplayGain) {
+    for tag in revision.tags() {
+        let Some(key) = tag.std_key else {
+            continue;
+        };
+
+        let field = match key {
+            StandardTagKey::ReplayGainTrackGain => &mut replay_gain.track_gain,
+            StandardTagKey::ReplayGainTrackPeak => &mut replay_gain.track_peak,
+            StandardTagKey::ReplayGainAlbumGain => &mut replay_gain.album_gain,
+            StandardTagKey::ReplayGainAlbumPeak => &mut replay_gain.album_peak,
+            _ => continue,
+        };
+        if let Some(value) = parse_replay_gain_value(&tag.value.to_string()) {
+            *field = Some(value);
+        }
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+
+    /// Encode a WAV file of a constant sample value.
+    pub(crate) fn wav(spec: AudioSpec, frames: usize, value: i16) -> Arc<[u8]> {
+        let channels = spec.channels as usize;
+        let data_len = (frames * channels * 2) as u32;
+
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(b"RIFF");
+        buffer.extend_from_slice(&(36 + data_len).to_le_bytes());
+        buffer.extend_from_slice(b"WAVEfmt ");
+        buffer.extend_from_slice(&16u32.to_le_bytes());
+        buffer.extend_from_slice(&1u16.to_le_bytes());
+        buffer.extend_from_slice(&spec.channels.to_le_bytes());
+        buffer.extend_from_slice(&spec.sample_rate.to_le_bytes());
+        buffer
+            .extend_from_slice(&(spec.sample_rate * channels as u32 * 2).to_le_bytes());
+        buffer.extend_from_slice(&(channels as u16 * 2).to_le_bytes());
+        buffer.extend_from_slice(&16u16.to_le_bytes());
+        buffer.extend_from_slice(b"data");
+        buffer.extend_from_slice(&data_len.to_le_bytes());
+        for _ in 0..frames * channels {
+            buffer.extend_from_slice(&value.to_le_bytes());
+        }
+        buffer.into()
+    }
+
+    #[test]
+    fn test_decode_wav() {
+        let spec = AudioSpec {
+            sample_rate: 8_000,
+            channels: 2,
+        };
+        let mut decoder = TrackDecoder::new(wav(spec, 8_000, i16::MAX / 2)).unwrap();
+        assert_eq!(decoder.spec(), spec);
+        assert_eq!(decoder.duration(), Some(Duration::from_secs(1)));
+
+        let mut samples = 0;
+        while let Some(chunk) = decoder.next_chunk().unwrap() {
+            assert!(chunk.iter().all(|sample| (*sample - 0.5).abs() < 0.001));
+            samples += chunk.len();
+        }
+        assert_eq!(samples, 16_000);
+
+        // Seeking may land on an earlier frame, which is reported back.
+        let position = decoder.seek(Duration::from_millis(500)).unwrap();
+        assert!(position <= Duration::from_millis(500));
+        let mut samples = 0;
+        while let Some(chunk) = decoder.next_chunk().unwrap() {
+            samples += chunk.len();
+        }
+        let remaining = (Duration::from_secs(1) - position).as_secs_f64() * 16_000.0;
+        assert_eq!(samples, remaining.round() as usize);
+    }
+
+    #[test]
+    fn test_invalid_data() {
+        let result = TrackDecoder::new(Arc::from(&b"not audio"[..]));
+        assert!(matches!(result, Err(DecodeError::Unsupported { .. })));
+    }
+}
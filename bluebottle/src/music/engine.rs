@@ -0,0 +1,1058 @@
+//! The playback engine, running on its own thread so decoding never waits on the
+//! UI.
+//!
+//! The engine decodes the current track into the audio sink and starts decoding
+//! the next track as soon as the current one is fully decoded, so there is no
+//! gap between them. Tracks with a different sample rate or channel layout wait
+//! for the sink to drain before it is reopened.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
+
+use super::decoder::{AudioSpec, TrackDecoder};
+use super::queue::{EntryId, Queue, QueueEntry, SavedQueue};
+use super::sink::{AudioSink, SinkFactory};
+use super::{PlayerState, PlayerStatus};
+use crate::models::music::{RepeatMode, ReplayGainMode};
+
+/// How long to wait for commands while the sink is full.
+const PLAYING_POLL_INTERVAL: Duration = Duration::from_millis(20);
+/// How long to wait for commands while nothing is playing.
+const IDLE_POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// How often the position is published while playing.
+const STATE_INTERVAL: Duration = Duration::from_millis(250);
+/// How often the queue position is saved while playing.
+const PERSIST_INTERVAL: Duration = Duration::from_secs(10);
+/// Going back within this time of the start restarts the previous track rather
+/// than the current one.
+const PREVIOUS_THRESHOLD: Duration = Duration::from_secs(3);
+
+/// Fetches the encoded data of a track, called on a worker thread.
+pub type Loader = Arc<dyn Fn(&QueueEntry) -> Result<Arc<[u8]>, String> + Send + Sync>;
+
+#[derive(Debug)]
+pub enum Command {
+    /// Replace the queue, starting from the current entry at the position.
+    Replace {
+        queue: Queue,
+        position: Duration,
+        play: bool,
+    },
+    Play,
+    Pause,
+    TogglePause,
+    Stop,
+    Next,
+    Previous,
+    Jump(EntryId),
+    Seek(Duration),
+    SetShuffle(bool),
+    SetRepeat(RepeatMode),
+    SetVolume(f32),
+    SetReplayGain(ReplayGainMode),
+    /// The data of an entry was loaded by the [Loader].
+    Loaded {
+        entry: QueueEntry,
+        result: Result<Arc<[u8]>, String>,
+    },
+    Shutdown,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EngineEvent {
+    /// The state shown to the user changed.
+    State(PlayerState),
+    /// A track started playing.
+    Started(QueueEntry),
+    /// A track stopped playing at the position, `finished` if it played to the
+    /// end.
+    Stopped {
+        entry: QueueEntry,
+        position: Duration,
+        finished: bool,
+    },
+    /// The queue or position changed and should be saved.
+    Persist(SavedQueue),
+}
+
+/// A track being decoded into the sink.
+struct Decoding {
+    entry_id: EntryId,
+    decoder: TrackDecoder,
+    gain: f32,
+    /// Decoded samples waiting for room in the sink.
+    pending: Vec<f32>,
+    finished: bool,
+}
+
+/// A track with samples written to the sink.
+struct Segment {
+    entry: QueueEntry,
+    /// The total number of frames written before this track's first frame.
+    start_frame: u64,
+    /// The position of the track's first frame.
+    start_position: Duration,
+    sample_rate: u32,
+    duration: Option<Duration>,
+}
+
+struct OpenSink {
+    spec: AudioSpec,
+    sink: Box<dyn AudioSink>,
+}
+
+/// The engine must be created on the thread it runs on, audio outputs cannot be
+/// moved between threads on every platform.
+pub struct Engine {
+    commands: Receiver<Command>,
+    commands_tx: Sender<Command>,
+    loader: Loader,
+    open_sink: SinkFactory,
+    on_event: Box<dyn FnMut(EngineEvent) + Send>,
+
+    queue: Queue,
+    /// The queue's entries in play order, shared with every published state.
+    ordered: Arc<[QueueEntry]>,
+    status: PlayerStatus,
+    volume: f32,
+    replay_gain: ReplayGainMode,
+    error: Option<String>,
+
+    sink: Option<OpenSink>,
+    /// The total number of frames written to the sink.
+    written_frames: u64,
+    /// The tracks with samples in the sink, the front is the one being heard.
+    segments: VecDeque<Segment>,
+    decoding: Option<Decoding>,
+    /// The entry waiting to be loaded before it can be decoded, and where to
+    /// start it from.
+    awaiting: Option<(EntryId, Duration)>,
+    /// Set once the last track in the queue has been decoded.
+    ended: bool,
+    /// The position shown while nothing is decoded, i.e. a restored queue.
+    idle_position: Duration,
+    /// The entry reported as started but not yet stopped.
+    announced: Option<QueueEntry>,
+
+    loaded: HashMap<EntryId, Arc<[u8]>>,
+    loading: HashSet<EntryId>,
+
+    last_state: Option<PlayerState>,
+    last_state_at: Instant,
+    last_persist_at: Instant,
+}
+
+impl Engine {
+    pub fn new(
+        commands: Receiver<Command>,
+        commands_tx: Sender<Command>,
+        loader: Loader,
+        open_sink: SinkFactory,
+        on_event: Box<dyn FnMut(EngineEvent) + Send>,
+    ) -> Self {
+        Self {
+            commands,
+            commands_tx,
+            loader,
+            open_sink,
+            on_event,
+            queue: Queue::default(),
+            ordered: Arc::default(),
+            status: PlayerStatus::Stopped,
+            volume: 1.0,
+            replay_gain: ReplayGainMode::default(),
+            error: None,
+            sink: None,
+            written_frames: 0,
+            segments: VecDeque::new(),
+            decoding: None,
+            awaiting: None,
+            ended: false,
+            idle_position: Duration::ZERO,
+            announced: None,
+            loaded: HashMap::new(),
+            loading: HashSet::new(),
+            last_state: None,
+            last_state_at: Instant::now(),
+            last_persist_at: Instant::now(),
+        }
+    }
+
+    /// Run the engine until it is shut down or every command sender is dropped.
+    pub fn run(mut self) {
+        loop {
+            let wait = match self.status {
+                PlayerStatus::Playing => PLAYING_POLL_INTERVAL,
+                _ => IDLE_POLL_INTERVAL,
+            };
+
+            let command = match self.commands.recv_timeout(wait) {
+                Ok(command) => Some(command),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => return,
+            };
+            let commands = command
+                .into_iter()
+                .chain(std::iter::from_fn(|| self.commands.try_recv().ok()));
+            for command in commands.collect::<Vec<_>>() {
+                if matches!(command, Command::Shutdown) {
+                    self.persist();
+                    self.stop();
+                    return;
+                }
+                self.handle(command);
+            }
+
+            if self.status == PlayerStatus::Playing {
+                self.pump();
+                self.prefetch();
+
+                if self.last_persist_at.elapsed() >= PERSIST_INTERVAL {
+                    self.persist();
+                }
+            }
+            self.publish_state(false);
+        }
+    }
+
+    fn handle(&mut self, command: Command) {
+        match command {
+            Command::Replace {
+                queue,
+                position,
+                play,
+            } => {
+                self.stop();
+                self.queue = queue;
+                self.refresh_ordered();
+                self.loaded.clear();
+                self.loading.clear();
+                self.idle_position = position;
+                if play {
+                    self.start(position);
+                } else if !self.queue.is_empty() {
+                    self.status = PlayerStatus::Paused;
+                }
+                self.persist();
+            },
+            Command::Play => self.play(),
+            Command::Pause => self.pause(),
+            Command::TogglePause => match self.status {
+                PlayerStatus::Playing => self.pause(),
+                _ => self.play(),
+            },
+            Command::Stop => {
+                self.stop();
+                self.idle_position = Duration::ZERO;
+                self.persist();
+            },
+            Command::Next => {
+                self.stop_announced();
+                match self.queue.skip_next() {
+                    Some(_) => self.start(Duration::ZERO),
+                    None => self.stop(),
+                }
+                self.persist();
+            },
+            Command::Previous => {
+                if self.position() <= PREVIOUS_THRESHOLD {
+                    self.stop_announced();
+                    self.queue.skip_previous();
+                }
+                self.start(Duration::ZERO);
+                self.persist();
+            },
+            Command::Jump(entry_id) => {
+                self.stop_announced();
+                if self.queue.jump_to(entry_id) {
+                    self.start(Duration::ZERO);
+                }
+                self.persist();
+            },
+            Command::Seek(position) => match self.status {
+                PlayerStatus::Stopped => self.idle_position = position,
+                status => {
+                    self.start(position);
+                    if status == PlayerStatus::Paused {
+                        self.pause();
+                    }
+                },
+            },
+            Command::SetShuffle(shuffle) => {
+                let seed = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_nanos() as u64;
+                self.queue.set_shuffle(shuffle, seed);
+                self.refresh_ordered();
+                self.discard_prefetch();
+                self.persist();
+            },
+            Command::SetRepeat(repeat) => {
+                self.queue.set_repeat(repeat);
+                self.discard_prefetch();
+                self.persist();
+            },
+            Command::SetVolume(volume) => {
+                self.volume = volume.clamp(0.0, 1.0);
+                if let Some(open) = self.sink.as_mut() {
+                    open.sink.set_volume(self.volume);
+                }
+            },
+            Command::SetReplayGain(mode) => self.replay_gain = mode,
+            Command::Loaded { entry, result } => self.loaded(entry, result),
+            Command::Shutdown => {},
+        }
+
+        self.publish_state(true);
+    }
+
+    fn play(&mut self) {
+        match self.status {
+            PlayerStatus::Playing => {},
+            PlayerStatus::Paused if !self.segments.is_empty() => {
+                self.status = PlayerStatus::Playing;
+                if let Some(open) = self.sink.as_mut() {
+                    open.sink.set_paused(false);
+                }
+            },
+            _ => self.start(self.idle_position),
+        }
+    }
+
+    fn pause(&mut self) {
+        if self.status != PlayerStatus::Playing {
+            return;
+        }
+
+        self.status = PlayerStatus::Paused;
+        if let Some(open) = self.sink.as_mut() {
+            open.sink.set_paused(true);
+        }
+        self.persist();
+    }
+
+    /// Stop playback, the queue is kept.
+    fn stop(&mut self) {
+        self.stop_announced();
+        self.clear_playback();
+        self.status = PlayerStatus::Stopped;
+        // The device is released until something plays again.
+        self.sink = None;
+    }
+
+    /// Start playing the current entry from the position, dropping anything
+    /// buffered.
+    fn start(&mut self, position: Duration) {
+        self.clear_playback();
+        self.error = None;
+        self.idle_position = position;
+
+        let Some(entry) = self.queue.current().cloned() else {
+            self.status = PlayerStatus::Stopped;
+            return;
+        };
+
+        self.status = PlayerStatus::Playing;
+        if let Some(open) = self.sink.as_mut() {
+            open.sink.set_paused(false);
+        }
+        if self.announced.as_ref().map(|announced| announced.id) != Some(entry.id) {
+            self.stop_announced();
+            self.announced = Some(entry.clone());
+            self.emit(EngineEvent::Started(entry.clone()));
+        }
+
+        self.awaiting = Some((entry.id, position));
+        self.begin_awaiting();
+    }
+
+    fn clear_playback(&mut self) {
+        if let Some(open) = self.sink.as_mut() {
+            open.sink.clear();
+        }
+        self.segments.clear();
+        self.decoding = None;
+        self.awaiting = None;
+        self.ended = false;
+    }
+
+    /// Report the announced entry as stopped at the current position.
+    fn stop_announced(&mut self) {
+        if let Some(entry) = self.announced.take() {
+            let position = self.position();
+            self.emit(EngineEvent::Stopped {
+                entry,
+                position,
+                finished: false,
+            });
+        }
+    }
+
+    /// Report the announced entry as played to the end.
+    fn finish_announced(&mut self, duration: Option<Duration>) {
+        if let Some(entry) = self.announced.take() {
+            let position = duration
+                .or(entry.track.runtime)
+                .unwrap_or_else(|| self.position());
+            self.emit(EngineEvent::Stopped {
+                entry,
+                position,
+                finished: true,
+            });
+        }
+    }
+
+    /// Forget the decoded upcoming track, as the entry after the current one
+    /// changed.
+    fn discard_prefetch(&mut self) {
+        self.ended = false;
+
+        if self.segments.len() > 1 {
+            // The next track is already in the sink, so replay from here.
+            let position = self.position();
+            let paused = self.status == PlayerStatus::Paused;
+            self.start(position);
+            if paused {
+                self.pause();
+            }
+            return;
+        }
+
+        let current = self.queue.current().map(|entry| entry.id);
+        if self
+            .decoding
+            .as_ref()
+            .is_some_and(|decoding| Some(decoding.entry_id) != current)
+        {
+            self.decoding = None;
+        }
+        if self
+            .awaiting
+            .is_some_and(|(entry_id, _)| Some(entry_id) != current)
+        {
+            self.awaiting = None;
+        }
+    }
+
+    /// Start decoding the awaited entry if its data is loaded, otherwise load it.
+    fn begin_awaiting(&mut self) {
+        let Some((entry_id, position)) = self.awaiting else {
+            return;
+        };
+        let entry = self
+            .queue
+            .ordered()
+            .find(|entry| entry.id == entry_id)
+            .cloned();
+        let Some(entry) = entry else {
+            self.awaiting = None;
+            return;
+        };
+
+        let Some(data) = self.loaded.get(&entry_id).cloned() else {
+            self.request_load(&entry);
+            return;
+        };
+        self.awaiting = None;
+
+        let mut decoder = match TrackDecoder::new(data) {
+            Ok(decoder) => decoder,
+            Err(err) => {
+                self.track_failed(&entry, err.to_string());
+                return;
+            },
+        };
+
+        let start_position = if position.is_zero() {
+            position
+        } else {
+            decoder.seek(position).unwrap_or_else(|err| {
+                tracing::warn!(error = %err, "failed to seek track");
+                Duration::ZERO
+            })
+        };
+
+        let gain = decoder
+            .replay_gain()
+            .linear_gain(self.replay_gain, entry.track.normalization_gain);
+        let spec = decoder.spec();
+
+        self.segments.push_back(Segment {
+            start_frame: self.written_frames,
+            start_position,
+            sample_rate: spec.sample_rate,
+            duration: decoder.duration().or(entry.track.runtime),
+            entry,
+        });
+        self.decoding = Some(Decoding {
+            entry_id,
+            decoder,
+            gain,
+            pending: Vec::new(),
+            finished: false,
+        });
+        self.prune_loaded();
+    }
+
+    /// A track could not be loaded or decoded, skip over it.
+    fn track_failed(&mut self, entry: &QueueEntry, error: String) {
+        tracing::error!(item_id = %entry.track.item_id, error = %error, "failed to play track");
+        self.error = Some(format!("Could not play {}: {error}", entry.track.name));
+
+        let is_current =
+            self.queue.current().map(|current| current.id) == Some(entry.id);
+        if !is_current {
+            // The upcoming track failed, so playback ends after the current one.
+            self.ended = true;
+            return;
+        }
+
+        self.stop_announced();
+        let error = self.error.take();
+        if self
+            .queue
+            .skip_next()
+            .is_some_and(|next| next.id != entry.id)
+        {
+            self.start(Duration::ZERO);
+        } else {
+            self.stop();
+        }
+        self.error = error;
+    }
+
+    fn loaded(&mut self, entry: QueueEntry, result: Result<Arc<[u8]>, String>) {
+        self.loading.remove(&entry.id);
+
+        // Entry IDs are reused by a new queue, so loads of the old one are ignored.
+        if !self.queue.ordered().any(|queued| *queued == entry) {
+            return;
+        }
+
+        match result {
+            Ok(data) => {
+                self.loaded.insert(entry.id, data);
+                self.prune_loaded();
+            },
+            Err(err) => {
+                self.track_failed(&entry, err);
+                return;
+            },
+        }
+
+        if self.status != PlayerStatus::Stopped
+            && self
+                .awaiting
+                .is_some_and(|(awaiting, _)| awaiting == entry.id)
+        {
+            self.begin_awaiting();
+        }
+    }
+
+    /// Decode as much as the sink accepts, moving on to the next track once the
+    /// current one is fully decoded.
+    fn pump(&mut self) {
+        loop {
+            self.advance_segments();
+
+            let Some(mut decoding) = self.decoding.take() else {
+                if self.segments.len() == 1 && self.awaiting.is_none() && !self.ended {
+                    self.begin_next();
+                    if self.decoding.is_some() {
+                        continue;
+                    }
+                }
+                break;
+            };
+
+            if !decoding.pending.is_empty() {
+                let spec = decoding.decoder.spec();
+                let Some(sink) = self.sink_for(spec) else {
+                    // Playback stops if the sink could not be opened.
+                    if self.status == PlayerStatus::Playing {
+                        self.decoding = Some(decoding);
+                    }
+                    break;
+                };
+
+                let accepted = sink.write(&decoding.pending);
+                decoding.pending.drain(..accepted);
+                self.written_frames += (accepted / spec.channels as usize) as u64;
+                if !decoding.pending.is_empty() {
+                    // The sink is full.
+                    self.decoding = Some(decoding);
+                    break;
+                }
+            }
+
+            if decoding.finished {
+                continue;
+            }
+
+            match decoding.decoder.next_chunk() {
+                Ok(Some(samples)) => {
+                    let gain = decoding.gain;
+                    decoding
+                        .pending
+                        .extend(samples.iter().map(|sample| sample * gain));
+                },
+                Ok(None) => decoding.finished = true,
+                Err(err) => {
+                    tracing::warn!(error = %err, "track ended early");
+                    decoding.finished = true;
+                },
+            }
+            self.decoding = Some(decoding);
+        }
+
+        self.finish_if_drained();
+    }
+
+    /// Start decoding the entry after the current one, so it follows on without
+    /// a gap.
+    fn begin_next(&mut self) {
+        match self.queue.peek_next().map(|entry| entry.id) {
+            Some(entry_id) => {
+                self.awaiting = Some((entry_id, Duration::ZERO));
+                self.begin_awaiting();
+            },
+            None => self.ended = true,
+        }
+    }
+
+    /// Returns the sink for the spec, reopening it once the previous track has
+    /// finished playing if the spec changed.
+    fn sink_for(&mut self, spec: AudioSpec) -> Option<&mut Box<dyn AudioSink>> {
+        let reopen = match self.sink.as_ref() {
+            Some(open) if open.spec == spec => false,
+            Some(open) if open.sink.buffered_frames() > 0 => return None,
+            _ => true,
+        };
+
+        if reopen {
+            self.sink = None;
+            match (self.open_sink)(spec) {
+                Ok(mut sink) => {
+                    sink.set_volume(self.volume);
+                    self.sink = Some(OpenSink { spec, sink });
+                },
+                Err(err) => {
+                    tracing::error!(error = %err, "failed to open audio output");
+                    self.error = Some(err.to_string());
+                    self.stop();
+                    return None;
+                },
+            }
+        }
+
+        self.sink.as_mut().map(|open| &mut open.sink)
+    }
+
+    /// Move on to the next segment once its first frame has been played.
+    fn advance_segments(&mut self) {
+        let played = self.played_frames();
+        while self
+            .segments
+            .get(1)
+            .is_some_and(|next| next.start_frame <= played)
+        {
+            let finished = self.segments.pop_front();
+            self.finish_announced(finished.and_then(|segment| segment.duration));
+
+            let entry = self.segments[0].entry.clone();
+            self.queue.advance();
+            if self.queue.current().map(|current| current.id) != Some(entry.id) {
+                self.queue.jump_to(entry.id);
+            }
+
+            self.announced = Some(entry.clone());
+            self.emit(EngineEvent::Started(entry));
+            self.persist();
+            self.prune_loaded();
+        }
+    }
+
+    /// Stop once the last track in the queue has finished playing.
+    fn finish_if_drained(&mut self) {
+        if !self.ended
+            || self.decoding.is_some()
+            || self.played_frames() < self.written_frames
+        {
+            return;
+        }
+
+        let duration = self.segments.front().and_then(|segment| segment.duration);
+        self.finish_announced(duration);
+        self.clear_playback();
+        self.status = PlayerStatus::Stopped;
+        self.idle_position = Duration::ZERO;
+        self.sink = None;
+        self.persist();
+    }
+
+    /// Load the next entry ahead of time.
+    fn prefetch(&mut self) {
+        if let Some(next) = self.queue.peek_next().cloned() {
+            self.request_load(&next);
+        }
+    }
+
+    fn request_load(&mut self, entry: &QueueEntry) {
+        if self.loaded.contains_key(&entry.id) || !self.loading.insert(entry.id) {
+            return;
+        }
+
+        let loader = self.loader.clone();
+        let commands = self.commands_tx.clone();
+        let entry = entry.clone();
+        std::thread::spawn(move || {
+            let result = loader(&entry);
+            let _ = commands.send(Command::Loaded { entry, result });
+        });
+    }
+
+    /// Drop the data of tracks which are no longer current or next.
+    fn prune_loaded(&mut self) {
+        let keep: Vec<EntryId> = self
+            .queue
+            .current()
+            .into_iter()
+            .chain(self.queue.peek_next())
+            .map(|entry| entry.id)
+            .collect();
+        self.loaded.retain(|entry_id, _| keep.contains(entry_id));
+    }
+
+    fn refresh_ordered(&mut self) {
+        self.ordered = self.queue.ordered().cloned().collect();
+    }
+
+    fn played_frames(&self) -> u64 {
+        let buffered = self
+            .sink
+            .as_ref()
+            .map(|open| open.sink.buffered_frames())
+            .unwrap_or(0);
+        self.written_frames.saturating_sub(buffered)
+    }
+
+    /// Returns the position within the track being heard.
+    fn position(&self) -> Duration {
+        let Some(segment) = self.segments.front() else {
+            return self.idle_position;
+        };
+
+        let frames = self.played_frames().saturating_sub(segment.start_frame);
+        let position = segment.start_position
+            + Duration::from_secs_f64(frames as f64 / segment.sample_rate as f64);
+        match segment.duration {
+            Some(duration) => position.min(duration),
+            None => position,
+        }
+    }
+
+    fn state(&self) -> PlayerState {
+        let status = match self.status {
+            PlayerStatus::Playing if self.segments.is_empty() => PlayerStatus::Loading,
+            status => status,
+        };
+        let current = self.queue.current().cloned();
+        let duration = self
+            .segments
+            .front()
+            .and_then(|segment| segment.duration)
+            .or_else(|| current.as_ref().and_then(|entry| entry.track.runtime));
+
+        PlayerState {
+            current,
+            queue: self.ordered.clone(),
+            status,
+            position: self.position(),
+            duration,
+            shuffle: self.queue.shuffle(),
+            repeat: self.queue.repeat(),
+            volume: self.volume,
+            has_previous: self.queue.has_previous(),
+            has_next: self.queue.has_next(),
+            error: self.error.clone(),
+        }
+    }
+
+    /// Publish the state if it changed, position changes are only published
+    /// periodically unless `force` is set.
+    fn publish_state(&mut self, force: bool) {
+        let state = self.state();
+        let Some(last) = self.last_state.as_ref() else {
+            return self.send_state(state);
+        };
+        if *last == state {
+            return;
+        }
+
+        let only_position = PlayerState {
+            position: state.position,
+            ..last.clone()
+        } == state;
+        if only_position && !force && self.last_state_at.elapsed() < STATE_INTERVAL {
+            return;
+        }
+
+        self.send_state(state);
+    }
+
+    fn send_state(&mut self, state: PlayerState) {
+        self.last_state = Some(state.clone());
+        self.last_state_at = Instant::now();
+        self.emit(EngineEvent::State(state));
+    }
+
+    fn persist(&mut self) {
+        self.last_persist_at = Instant::now();
+        let saved = SavedQueue {
+            queue: self.queue.clone(),
+            position: self.position(),
+        };
+        self.emit(EngineEvent::Persist(saved));
+    }
+
+    fn emit(&mut self, event: EngineEvent) {
+        (self.on_event)(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use parking_lot::Mutex;
+
+    use super::*;
+    use crate::backends::BackendId;
+    use crate::models::music::AudioTrack;
+    use crate::music::decoder::tests::wav;
+    use crate::music::sink::tests::null_output;
+
+    const SPEC: AudioSpec = AudioSpec {
+        sample_rate: 8_000,
+        channels: 2,
+    };
+
+    fn track(name: &str) -> AudioTrack {
+        AudioTrack {
+            item_id: name.to_string(),
+            name: name.to_string(),
+            artist: None,
+            album: None,
+            album_id: None,
+            runtime: None,
+            normalization_gain: None,
+        }
+    }
+
+    struct Harness {
+        commands: Sender<Command>,
+        events: Arc<Mutex<Vec<EngineEvent>>>,
+        thread: std::thread::JoinHandle<()>,
+    }
+
+    impl Harness {
+        /// Start an engine which loads `{item_id}` from the files.
+        fn start(files: HashMap<String, Arc<[u8]>>, open_sink: SinkFactory) -> Self {
+            let (commands, receiver) = mpsc::channel();
+            let events = Arc::new(Mutex::new(Vec::new()));
+
+            let loader: Loader = Arc::new(move |entry: &QueueEntry| {
+                files
+                    .get(&entry.track.item_id)
+                    .cloned()
+                    .ok_or_else(|| "not found".to_string())
+            });
+            let recorded = events.clone();
+            let commands_tx = commands.clone();
+            let thread = std::thread::spawn(move || {
+                Engine::new(
+                    receiver,
+                    commands_tx,
+                    loader,
+                    open_sink,
+                    Box::new(move |event| recorded.lock().push(event)),
+                )
+                .run()
+            });
+
+            Self {
+                commands,
+                events,
+                thread,
+            }
+        }
+
+        /// Wait for playback to stop, returning the events emitted.
+        fn wait_stopped(&self) -> Vec<EngineEvent> {
+            let deadline = Instant::now() + Duration::from_secs(5);
+            loop {
+                let stopped = self.events.lock().iter().any(|event| {
+                    matches!(event, EngineEvent::State(state) if state.status == PlayerStatus::Stopped)
+                });
+                if stopped {
+                    return self.events.lock().clone();
+                }
+                assert!(Instant::now() < deadline, "playback did not finish");
+                std::thread::sleep(Duration::from_millis(5));
+            }
+        }
+
+        fn shutdown(self) {
+            self.commands.send(Command::Shutdown).unwrap();
+            self.thread.join().unwrap();
+        }
+    }
+
+    fn started(events: &[EngineEvent]) -> Vec<&str> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                EngineEvent::Started(entry) => Some(entry.track.name.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_gapless_queue() {
+        let files = HashMap::from([
+            ("a".to_string(), wav(SPEC, 4_000, 1000)),
+            ("b".to_string(), wav(SPEC, 2_000, 2000)),
+            ("c".to_string(), wav(SPEC, 1_000, 3000)),
+        ]);
+        let (open_sink, output) = null_output();
+        let harness = Harness::start(files, open_sink);
+
+        let queue = Queue::new(
+            BackendId::nil(),
+            vec![track("a"), track("b"), track("c")],
+            0,
+        );
+        harness
+            .commands
+            .send(Command::Replace {
+                queue,
+                position: Duration::ZERO,
+                play: true,
+            })
+            .unwrap();
+
+        let events = harness.wait_stopped();
+        assert_eq!(started(&events), vec!["a", "b", "c"]);
+
+        let finished = events
+            .iter()
+            .filter(|event| matches!(event, EngineEvent::Stopped { finished: true, .. }))
+            .count();
+        assert_eq!(finished, 3);
+
+        // Every track was written back to back into a single sink.
+        let output = output.lock();
+        assert_eq!(output.opened, vec![SPEC]);
+        assert_eq!(output.samples.len(), (4_000 + 2_000 + 1_000) * 2);
+        let boundary = 4_000 * 2;
+        assert!((output.samples[boundary - 1] - 1000.0 / 32768.0).abs() < 1e-4);
+        assert!((output.samples[boundary] - 2000.0 / 32768.0).abs() < 1e-4);
+
+        harness.shutdown();
+    }
+
+    #[test]
+    fn test_spec_change_reopens_sink() {
+        let other_spec = AudioSpec {
+            sample_rate: 16_000,
+            channels: 1,
+        };
+        let files = HashMap::from([
+            ("a".to_string(), wav(SPEC, 1_000, 1000)),
+            ("b".to_string(), wav(other_spec, 1_000, 1000)),
+        ]);
+        let (open_sink, output) = null_output();
+        let harness = Harness::start(files, open_sink);
+
+        let queue = Queue::new(BackendId::nil(), vec![track("a"), track("b")], 0);
+        harness
+            .commands
+            .send(Command::Replace {
+                queue,
+                position: Duration::ZERO,
+                play: true,
+            })
+            .unwrap();
+
+        let events = harness.wait_stopped();
+        assert_eq!(started(&events), vec!["a", "b"]);
+        assert_eq!(output.lock().opened, vec![SPEC, other_spec]);
+
+        harness.shutdown();
+    }
+
+    #[test]
+    fn test_missing_track_skipped() {
+        let files = HashMap::from([("b".to_string(), wav(SPEC, 1_000, 1000))]);
+        let (open_sink, output) = null_output();
+        let harness = Harness::start(files, open_sink);
+
+        let queue = Queue::new(BackendId::nil(), vec![track("a"), track("b")], 0);
+        harness
+            .commands
+            .send(Command::Replace {
+                queue,
+                position: Duration::ZERO,
+                play: true,
+            })
+            .unwrap();
+
+        let events = harness.wait_stopped();
+        assert_eq!(started(&events), vec!["a", "b"]);
+        assert_eq!(output.lock().samples.len(), 2_000);
+
+        harness.shutdown();
+    }
+
+    #[test]
+    fn test_restore_paused() {
+        let (open_sink, output) = null_output();
+        let harness = Harness::start(HashMap::new(), open_sink);
+
+        let queue = Queue::new(BackendId::nil(), vec![track("a")], 0);
+        harness
+            .commands
+            .send(Command::Replace {
+                queue,
+                position: Duration::from_secs(42),
+                play: false,
+            })
+            .unwrap();
+        let events = harness.events.clone();
+        harness.shutdown();
+
+        // Nothing is loaded or played until the user presses play.
+        let events = events.lock();
+        assert!(started(&events).is_empty());
+        assert!(output.lock().opened.is_empty());
+
+        let state = events.iter().rev().find_map(|event| match event {
+            EngineEvent::State(state) => Some(state),
+            _ => None,
+        });
+        let state = state.expect("state should be published");
+        assert_eq!(state.status, PlayerStatus::Paused);
+        assert_eq!(state.position, Duration::from_secs(42));
+        assert_eq!(state.current.as_ref().unwrap().track.name, "a");
+
+        let saved = events.iter().rev().find_map(|event| match event {
+            EngineEvent::Persist(saved) => Some(saved),
+            _ => None,
+        });
+        assert_eq!(saved.unwrap().position, Duration::from_secs(42));
+    }
+}
@@ -0,0 +1,315 @@
+//! The built-in music player.
+//!
+//! Tracks are downloaded from their backend and decoded in process, then played
+//! on the default audio output by the [engine] running on its own thread. The
+//! queue is saved to relaxed storage, so it is restored paused after a restart.
+
+use std::sync::{Arc, OnceLock, mpsc as std_mpsc};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use iced::Subscription;
+use iced::futures::channel::mpsc;
+use iced::futures::{SinkExt, Stream};
+use parking_lot::Mutex;
+use tokio::sync::{mpsc as tokio_mpsc, watch};
+
+use self::engine::{Command, Engine, EngineEvent, Loader};
+use self::queue::{EntryId, Queue, QueueEntry};
+use crate::backends::{BackendId, registry};
+use crate::models::interaction::Interaction;
+use crate::models::media::{ItemId, ItemKind};
+use crate::models::music::RepeatMode;
+use crate::models::playback::{PlayMethod, PlaybackEvent, PlaybackReport};
+use crate::storage::{interaction_backlog, music_queue, music_settings};
+
+#[cfg(feature = "audio-output")]
+mod cpal_sink;
+mod decoder;
+mod engine;
+pub mod queue;
+mod sink;
+
+static PLAYER: OnceLock<MusicPlayer> = OnceLock::new();
+
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub enum PlayerStatus {
+    #[default]
+    Stopped,
+    /// Waiting for the track to download.
+    Loading,
+    Playing,
+    Paused,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+/// The state of the music player shown to the user.
+pub struct PlayerState {
+    /// The entry being played, or which plays next once resumed.
+    pub current: Option<QueueEntry>,
+    /// The queued entries in the order they are played.
+    pub queue: Arc<[QueueEntry]>,
+    pub status: PlayerStatus,
+    pub position: Duration,
+    pub duration: Option<Duration>,
+    pub shuffle: bool,
+    pub repeat: RepeatMode,
+    pub volume: f32,
+    pub has_previous: bool,
+    pub has_next: bool,
+    /// Why the last track could not be played.
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+/// A command controlling the music player.
+pub enum PlayerCommand {
+    Play,
+    TogglePause,
+    Pause,
+    Stop,
+    Next,
+    Previous,
+    Seek(Duration),
+    /// Play the queue entry.
+    Jump(EntryId),
+    SetShuffle(bool),
+    SetRepeat(RepeatMode),
+    SetVolume(f32),
+}
+
+impl From<PlayerCommand> for Command {
+    fn from(command: PlayerCommand) -> Self {
+        match command {
+            PlayerCommand::Play => Command::Play,
+            PlayerCommand::TogglePause => Command::TogglePause,
+            PlayerCommand::Pause => Command::Pause,
+            PlayerCommand::Stop => Command::Stop,
+            PlayerCommand::Next => Command::Next,
+            PlayerCommand::Previous => Command::Previous,
+            PlayerCommand::Seek(position) => Command::Seek(position),
+            PlayerCommand::Jump(entry_id) => Command::Jump(entry_id),
+            PlayerCommand::SetShuffle(shuffle) => Command::SetShuffle(shuffle),
+            PlayerCommand::SetRepeat(repeat) => Command::SetRepeat(repeat),
+            PlayerCommand::SetVolume(volume) => Command::SetVolume(volume),
+        }
+    }
+}
+
+struct MusicPlayer {
+    commands: std_mpsc::Sender<Command>,
+    state: watch::Receiver<PlayerState>,
+    engine: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl MusicPlayer {
+    /// Start the engine, restoring the saved settings and queue.
+    ///
+    /// This must be called within the tokio runtime, which tracks are downloaded
+    /// and reported on.
+    fn start() -> Self {
+        let runtime = tokio::runtime::Handle::current();
+        let (commands_tx, commands) = std_mpsc::channel();
+        let (state_tx, state) = watch::channel(PlayerState::default());
+        let (events_tx, events) = tokio_mpsc::unbounded_channel();
+
+        let loader_runtime = runtime.clone();
+        let loader: Loader = Arc::new(move |entry: &QueueEntry| {
+            let backend = registry::get(entry.backend_id)
+                .ok_or_else(|| "backend no longer exists".to_string())?;
+            let data = loader_runtime
+                .block_on(backend.audio_data(entry.track.item_id.clone()))
+                .map_err(|err| err.to_string())?;
+            Ok(Arc::from(data))
+        });
+
+        let engine_commands_tx = commands_tx.clone();
+        let on_event = Box::new(move |event| match event {
+            EngineEvent::State(state) => {
+                state_tx.send_replace(state);
+            },
+            // Saved from the engine thread, so the queue is saved on shutdown
+            // after the runtime has stopped.
+            EngineEvent::Persist(saved) => music_queue::save(saved),
+            event => {
+                let _ = events_tx.send(event);
+            },
+        });
+        let engine = std::thread::Builder::new()
+            .name("music-engine".to_string())
+            .spawn(move || {
+                Engine::new(
+                    commands,
+                    engine_commands_tx,
+                    loader,
+                    sink::default_output(),
+                    on_event,
+                )
+                .run()
+            })
+            .expect("spawn music engine thread");
+        runtime.spawn(handle_events(events));
+
+        let settings = music_settings::load();
+        let _ = commands_tx.send(Command::SetVolume(settings.volume));
+        let _ = commands_tx.send(Command::SetReplayGain(settings.replay_gain));
+        if let Some(saved) = music_queue::load() {
+            let _ = commands_tx.send(Command::Replace {
+                queue: saved.queue,
+                position: saved.position,
+                play: false,
+            });
+        }
+
+        Self {
+            commands: commands_tx,
+            state,
+            engine: Mutex::new(Some(engine)),
+        }
+    }
+
+    fn send(&self, command: Command) {
+        if self.commands.send(command).is_err() {
+            tracing::error!("music engine is no longer running");
+        }
+    }
+}
+
+/// Returns the player, starting it on first use.
+fn player() -> &'static MusicPlayer {
+    PLAYER.get_or_init(MusicPlayer::start)
+}
+
+/// Replace the queue with the tracks of an audio item, album or artist and
+/// start playing the first.
+pub async fn play_item(
+    backend_id: BackendId,
+    item_id: ItemId,
+    kind: ItemKind,
+) -> Result<(), String> {
+    let backend = registry::get(backend_id).ok_or("backend no longer exists")?;
+    let tracks = backend
+        .audio_tracks(item_id, kind)
+        .await
+        .map_err(|err| err.to_string())?;
+    if tracks.is_empty() {
+        return Err("there are no tracks to play".to_string());
+    }
+
+    player().send(Command::Replace {
+        queue: Queue::new(backend_id, tracks, 0),
+        position: Duration::ZERO,
+        play: true,
+    });
+
+    Ok(())
+}
+
+/// Send a command to the music player.
+///
+/// Commands sent before the player has started are ignored, as nothing can be
+/// playing yet.
+pub fn send(command: PlayerCommand) {
+    if let Some(player) = PLAYER.get() {
+        player.send(command.into());
+    }
+}
+
+/// Stop playback and wait for the queue to be saved.
+pub fn shutdown() {
+    let Some(player) = PLAYER.get() else {
+        return;
+    };
+
+    player.send(Command::Shutdown);
+    if let Some(engine) = player.engine.lock().take()
+        && engine.join().is_err()
+    {
+        tracing::error!("music engine panicked");
+    }
+}
+
+/// Listens for changes to the player's state.
+///
+/// This starts the player, restoring the saved queue.
+pub fn subscription() -> Subscription<PlayerState> {
+    Subscription::run(state_stream)
+}
+
+/// Returns a receiver of the player's state.
+///
+/// This starts the player, restoring the saved queue.
+pub fn state() -> watch::Receiver<PlayerState> {
+    player().state.clone()
+}
+
+fn state_stream() -> impl Stream<Item = PlayerState> {
+    iced::stream::channel(8, async |mut output: mpsc::Sender<PlayerState>| {
+        let mut state = state();
+        loop {
+            let current = state.borrow_and_update().clone();
+            if output.send(current).await.is_err() || state.changed().await.is_err() {
+                return;
+            }
+        }
+    })
+}
+
+/// Report playback to the backends.
+async fn handle_events(mut events: tokio_mpsc::UnboundedReceiver<EngineEvent>) {
+    while let Some(event) = events.recv().await {
+        match event {
+            EngineEvent::State(_) | EngineEvent::Persist(_) => {},
+            EngineEvent::Started(entry) => {
+                let report = playback_report(&entry, Duration::ZERO);
+                report_playback(entry.backend_id, PlaybackEvent::Started(report)).await;
+            },
+            EngineEvent::Stopped {
+                entry,
+                position,
+                finished,
+            } => record_stopped(&entry, position, finished).await,
+        }
+    }
+}
+
+fn playback_report(entry: &QueueEntry, position: Duration) -> PlaybackReport {
+    PlaybackReport {
+        item_id: entry.track.item_id.clone(),
+        // Audio items only have a single source, with the same ID as the item.
+        media_source_id: entry.track.item_id.clone(),
+        play_session_id: None,
+        play_method: PlayMethod::DirectPlay,
+        position,
+        paused: false,
+        audio_stream_index: None,
+        subtitle_stream_index: None,
+    }
+}
+
+/// Add where the track stopped to the interaction backlog, which applies it to
+/// the backend.
+async fn record_stopped(entry: &QueueEntry, position: Duration, finished: bool) {
+    let item_id = entry.track.item_id.clone();
+    let interaction = if finished {
+        Interaction::Played {
+            item_id,
+            played: true,
+        }
+    } else {
+        Interaction::Progress { item_id, position }
+    };
+
+    if let Err(err) = interaction_backlog::record(entry.backend_id, interaction).await {
+        tracing::error!(error = %err, "failed to add music playback to backlog");
+    }
+}
+
+async fn report_playback(backend_id: BackendId, event: PlaybackEvent) {
+    let Some(backend) = registry::get(backend_id) else {
+        return;
+    };
+    if let Err(err) = backend.report_playback(event).await {
+        tracing::warn!(error = %err, "failed to report music playback");
+    }
+}
@@ -0,0 +1,305 @@
+//! The play queue, tracking the order tracks are played in with shuffle and
+//! repeat applied.
+
+use std::time::Duration;
+
+use crate::backends::BackendId;
+use crate::models::music::{AudioTrack, RepeatMode};
+
+/// A unique identifier of an entry within the queue, the same track may be
+/// queued more than once.
+pub type EntryId = u64;
+
+#[derive(
+    Debug, Clone, PartialEq, serde_derive::Serialize, serde_derive::Deserialize,
+)]
+/// A track within the queue.
+pub struct QueueEntry {
+    pub id: EntryId,
+    /// The backend the track is streamed from.
+    pub backend_id: BackendId,
+    pub track: AudioTrack,
+}
+
+#[derive(
+    Debug, Clone, PartialEq, serde_derive::Serialize, serde_derive::Deserialize,
+)]
+/// The queue as saved between restarts.
+pub struct SavedQueue {
+    pub queue: Queue,
+    /// The position within the current entry.
+    pub position: Duration,
+}
+
+#[derive(
+    Debug, Default, Clone, PartialEq, serde_derive::Serialize, serde_derive::Deserialize,
+)]
+/// The tracks queued for playback.
+pub struct Queue {
+    /// The entries in the order they were queued.
+    entries: Vec<QueueEntry>,
+    /// The order entries are played in, as indices into `entries`.
+    order: Vec<usize>,
+    /// The index into `order` of the current entry.
+    position: usize,
+    shuffle: bool,
+    repeat: RepeatMode,
+    next_id: EntryId,
+}
+
+impl Queue {
+    /// Create a queue of the tracks, starting from the track at `start_index`.
+    pub fn new(
+        backend_id: BackendId,
+        tracks: Vec<AudioTrack>,
+        start_index: usize,
+    ) -> Self {
+        let mut queue = Self::default();
+        queue.append(backend_id, tracks);
+        queue.position = start_index.min(queue.order.len().saturating_sub(1));
+        queue
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn shuffle(&self) -> bool {
+        self.shuffle
+    }
+
+    pub fn repeat(&self) -> RepeatMode {
+        self.repeat
+    }
+
+    /// Returns the entry being played.
+    pub fn current(&self) -> Option<&QueueEntry> {
+        self.entry_at(self.position)
+    }
+
+    /// Returns the entries in the order they are played.
+    pub fn ordered(&self) -> impl Iterator<Item = &QueueEntry> {
+        self.order.iter().map(|index| &self.entries[*index])
+    }
+
+    /// Returns `true` if the queue has an entry before the current one.
+    pub fn has_previous(&self) -> bool {
+        self.position > 0 || (self.repeat != RepeatMode::Off && !self.is_empty())
+    }
+
+    /// Returns `true` if the queue has an entry after the current one.
+    pub fn has_next(&self) -> bool {
+        self.position + 1 < self.order.len()
+            || (self.repeat != RepeatMode::Off && !self.is_empty())
+    }
+
+    /// Returns the entry played once the current entry finishes, without
+    /// advancing.
+    pub fn peek_next(&self) -> Option<&QueueEntry> {
+        self.next_position(self.repeat)
+            .and_then(|pos| self.entry_at(pos))
+    }
+
+    /// Advance to the next entry once the current entry finishes.
+    pub fn advance(&mut self) -> Option<&QueueEntry> {
+        self.position = self.next_position(self.repeat)?;
+        self.current()
+    }
+
+    /// Skip to the next entry, repeating a single track is ignored as the user
+    /// asked to move on.
+    pub fn skip_next(&mut self) -> Option<&QueueEntry> {
+        let repeat = match self.repeat {
+            RepeatMode::One => RepeatMode::All,
+            repeat => repeat,
+        };
+        self.position = self.next_position(repeat)?;
+        self.current()
+    }
+
+    /// Go back to the previous entry.
+    pub fn skip_previous(&mut self) -> Option<&QueueEntry> {
+        self.position = match self.position.checked_sub(1) {
+            Some(position) => position,
+            None if self.repeat != RepeatMode::Off => self.order.len().checked_sub(1)?,
+            None => return None,
+        };
+        self.current()
+    }
+
+    /// Make the entry current, returning `false` if it is not in the queue.
+    pub fn jump_to(&mut self, entry_id: EntryId) -> bool {
+        let position = self
+            .order
+            .iter()
+            .position(|index| self.entries[*index].id == entry_id);
+        match position {
+            Some(position) => {
+                self.position = position;
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Add the tracks to the end of the queue.
+    pub fn append(&mut self, backend_id: BackendId, tracks: Vec<AudioTrack>) {
+        for track in tracks {
+            let id = self.next_id;
+            self.next_id += 1;
+
+            self.order.push(self.entries.len());
+            self.entries.push(QueueEntry {
+                id,
+                backend_id,
+                track,
+            });
+        }
+    }
+
+    /// Enable or disable shuffle, the current entry stays current.
+    ///
+    /// When enabled, the entries after the current entry are shuffled with the
+    /// `seed`, entries which were already played are not played again.
+    pub fn set_shuffle(&mut self, shuffle: bool, seed: u64) {
+        self.shuffle = shuffle;
+
+        let current = self.order.get(self.position).copied();
+        if shuffle {
+            let mut rng = SplitMix64(seed);
+            let upcoming =
+                &mut self.order[(self.position + 1).min(self.entries.len())..];
+            // Fisher-Yates.
+            for i in (1..upcoming.len()).rev() {
+                let j = (rng.next_u64() % (i as u64 + 1)) as usize;
+                upcoming.swap(i, j);
+            }
+        } else {
+            self.order = (0..self.entries.len()).collect();
+            self.position = current.unwrap_or(0);
+        }
+    }
+
+    pub fn set_repeat(&mut self, repeat: RepeatMode) {
+        self.repeat = repeat;
+    }
+
+    fn entry_at(&self, position: usize) -> Option<&QueueEntry> {
+        self.order.get(position).map(|index| &self.entries[*index])
+    }
+
+    fn next_position(&self, repeat: RepeatMode) -> Option<usize> {
+        if self.is_empty() {
+            return None;
+        }
+
+        match repeat {
+            RepeatMode::One => Some(self.position),
+            RepeatMode::All => Some((self.position + 1) % self.order.len()),
+            RepeatMode::Off => {
+                Some(self.position + 1).filter(|position| *position < self.order.len())
+            },
+        }
+    }
+}
+
+/// A small, fast generator used to shuffle the queue.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracks(count: usize) -> Vec<AudioTrack> {
+        (0..count)
+            .map(|n| AudioTrack {
+                item_id: format!("track-{n}"),
+                name: format!("Track {n}"),
+                artist: None,
+                album: None,
+                album_id: None,
+                runtime: None,
+                normalization_gain: None,
+            })
+            .collect()
+    }
+
+    fn current_name(queue: &Queue) -> Option<&str> {
+        queue.current().map(|entry| entry.track.name.as_str())
+    }
+
+    #[test]
+    fn test_advance_and_repeat() {
+        let mut queue = Queue::new(BackendId::nil(), tracks(3), 1);
+        assert_eq!(current_name(&queue), Some("Track 1"));
+        assert!(queue.has_previous());
+
+        queue.advance();
+        assert_eq!(current_name(&queue), Some("Track 2"));
+        assert!(!queue.has_next());
+        assert!(queue.peek_next().is_none());
+        assert!(queue.advance().is_none());
+        assert_eq!(current_name(&queue), Some("Track 2"));
+
+        queue.set_repeat(RepeatMode::All);
+        assert_eq!(queue.advance().unwrap().track.name, "Track 0");
+        assert_eq!(queue.skip_previous().unwrap().track.name, "Track 2");
+
+        // Repeating one track keeps playing it until the user skips.
+        queue.set_repeat(RepeatMode::One);
+        assert_eq!(queue.peek_next().unwrap().track.name, "Track 2");
+        assert_eq!(queue.advance().unwrap().track.name, "Track 2");
+        assert_eq!(queue.skip_next().unwrap().track.name, "Track 0");
+
+        queue.set_repeat(RepeatMode::Off);
+        assert!(queue.skip_previous().is_none());
+    }
+
+    #[test]
+    fn test_shuffle() {
+        let mut queue = Queue::new(BackendId::nil(), tracks(20), 5);
+        let current = queue.current().unwrap().id;
+
+        queue.set_shuffle(true, 42);
+        assert_eq!(queue.current().unwrap().id, current);
+        let order: Vec<EntryId> = queue.ordered().map(|entry| entry.id).collect();
+        // Already played entries stay where they were.
+        assert_eq!(&order[..6], &[0, 1, 2, 3, 4, 5]);
+        assert_ne!(&order[6..], &(6..20).collect::<Vec<_>>());
+
+        let mut sorted = order.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..20).collect::<Vec<_>>());
+
+        queue.skip_next();
+        let current = queue.current().unwrap().id;
+        queue.set_shuffle(false, 0);
+        assert_eq!(queue.current().unwrap().id, current);
+        let order: Vec<EntryId> = queue.ordered().map(|entry| entry.id).collect();
+        assert_eq!(order, (0..20).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_jump_and_append() {
+        let mut queue = Queue::new(BackendId::nil(), tracks(2), 0);
+        queue.append(BackendId::nil(), tracks(2));
+
+        // The same track queued twice gets its own entry.
+        let ids: Vec<EntryId> = queue.ordered().map(|entry| entry.id).collect();
+        assert_eq!(ids, vec![0, 1, 2, 3]);
+
+        assert!(queue.jump_to(3));
+        assert_eq!(current_name(&queue), Some("Track 1"));
+        assert!(!queue.jump_to(42));
+    }
+}
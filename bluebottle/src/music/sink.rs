@@ -0,0 +1,188 @@
+//! Audio outputs the decoded samples are written to.
+//!
+//! Samples are played on the default output device through cpal when the
+//! `audio-output` feature is enabled, which needs ALSA on Linux. Without it they
+//! are discarded by a [SilentSink] instead.
+
+#[cfg(not(feature = "audio-output"))]
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "audio-output")]
+pub use super::cpal_sink::SinkError;
+use super::decoder::AudioSpec;
+
+#[cfg(not(feature = "audio-output"))]
+/// The error opening an output, a [SilentSink] always opens.
+pub type SinkError = std::convert::Infallible;
+
+/// An audio output playing interleaved `f32` samples of a fixed [AudioSpec].
+pub trait AudioSink {
+    /// Queue the samples, returning how many were accepted.
+    ///
+    /// Only whole frames are accepted, the rest must be written again once
+    /// there is room.
+    fn write(&mut self, samples: &[f32]) -> usize;
+
+    /// Returns the number of frames queued which have not been played yet.
+    fn buffered_frames(&self) -> u64;
+
+    fn set_paused(&mut self, paused: bool);
+
+    /// Set the output volume, between `0.0` and `1.0`.
+    fn set_volume(&mut self, volume: f32);
+
+    /// Drop any queued samples, i.e. when seeking.
+    fn clear(&mut self);
+}
+
+/// Opens an audio output for the given spec.
+pub type SinkFactory =
+    Box<dyn FnMut(AudioSpec) -> Result<Box<dyn AudioSink>, SinkError> + Send>;
+
+#[cfg(feature = "audio-output")]
+/// Returns a factory opening the default output device.
+pub fn default_output() -> SinkFactory {
+    use super::cpal_sink::CpalSink;
+
+    Box::new(|spec| Ok(Box::new(CpalSink::open(spec)?) as Box<dyn AudioSink>))
+}
+
+#[cfg(not(feature = "audio-output"))]
+/// Returns a factory opening a [SilentSink], as this build has no audio output.
+pub fn default_output() -> SinkFactory {
+    tracing::warn!("built without the audio-output feature, music will not be heard");
+    Box::new(|spec| Ok(Box::new(SilentSink::new(spec)) as Box<dyn AudioSink>))
+}
+
+#[cfg(not(feature = "audio-output"))]
+/// How much audio a [SilentSink] buffers ahead.
+const SILENT_BUFFER_DURATION: Duration = Duration::from_millis(250);
+
+#[cfg(not(feature = "audio-output"))]
+/// A sink which discards the samples, at the pace a device would play them so
+/// tracks still take as long as they last.
+pub struct SilentSink {
+    spec: AudioSpec,
+    /// The frames written and not yet considered played.
+    buffered: u64,
+    /// When the buffered frames were last drained, `None` while paused.
+    drained_at: Option<Instant>,
+}
+
+#[cfg(not(feature = "audio-output"))]
+impl SilentSink {
+    fn new(spec: AudioSpec) -> Self {
+        Self {
+            spec,
+            buffered: 0,
+            drained_at: Some(Instant::now()),
+        }
+    }
+
+    /// Drop the frames a device would have played since the last drain.
+    fn drain(&mut self) {
+        if let Some(drained_at) = self.drained_at {
+            let played = (drained_at.elapsed().as_secs_f64()
+                * self.spec.sample_rate as f64) as u64;
+            if played > 0 {
+                self.buffered = self.buffered.saturating_sub(played);
+                self.drained_at = Some(Instant::now());
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "audio-output"))]
+impl AudioSink for SilentSink {
+    fn write(&mut self, samples: &[f32]) -> usize {
+        self.drain();
+        let capacity =
+            (self.spec.sample_rate as f64 * SILENT_BUFFER_DURATION.as_secs_f64()) as u64;
+        let channels = self.spec.channels as usize;
+        let room = capacity.saturating_sub(self.buffered) as usize;
+        let frames = room.min(samples.len() / channels);
+        self.buffered += frames as u64;
+        frames * channels
+    }
+
+    fn buffered_frames(&self) -> u64 {
+        let played = self.drained_at.map_or(0, |drained_at| {
+            (drained_at.elapsed().as_secs_f64() * self.spec.sample_rate as f64) as u64
+        });
+        self.buffered.saturating_sub(played)
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        self.drain();
+        self.drained_at = match (paused, self.drained_at) {
+            (true, _) => None,
+            (false, Some(drained_at)) => Some(drained_at),
+            (false, None) => Some(Instant::now()),
+        };
+    }
+
+    fn set_volume(&mut self, _volume: f32) {}
+
+    fn clear(&mut self) {
+        self.buffered = 0;
+        if self.drained_at.is_some() {
+            self.drained_at = Some(Instant::now());
+        }
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use std::sync::Arc;
+
+    use parking_lot::Mutex;
+
+    use super::*;
+
+    #[derive(Default)]
+    /// What was written to a [NullSink].
+    pub(crate) struct NullOutput {
+        pub(crate) samples: Vec<f32>,
+        /// The specs of every sink opened, in order.
+        pub(crate) opened: Vec<AudioSpec>,
+        pub(crate) cleared: usize,
+    }
+
+    /// A sink which plays nothing, recording the samples written to it.
+    ///
+    /// Every sample is played as soon as it is written.
+    pub(crate) struct NullSink {
+        output: Arc<Mutex<NullOutput>>,
+    }
+
+    impl AudioSink for NullSink {
+        fn write(&mut self, samples: &[f32]) -> usize {
+            self.output.lock().samples.extend_from_slice(samples);
+            samples.len()
+        }
+
+        fn buffered_frames(&self) -> u64 {
+            0
+        }
+
+        fn set_paused(&mut self, _paused: bool) {}
+
+        fn set_volume(&mut self, _volume: f32) {}
+
+        fn clear(&mut self) {
+            self.output.lock().cleared += 1;
+        }
+    }
+
+    pub(crate) fn null_output() -> (SinkFactory, Arc<Mutex<NullOutput>>) {
+        let output = Arc::new(Mutex::new(NullOutput::default()));
+        let factory_output = output.clone();
+        let factory: SinkFactory = Box::new(move |spec| {
+            factory_output.lock().opened.push(spec);
+            Ok(Box::new(NullSink {
+                output: factory_output.clone(),
+            }) as Box<dyn AudioSink>)
+        });
+        (factory, output)
+    }
+}
@@ -1,9 +1,10 @@
 use std::sync::atomic::{AtomicU32, Ordering};
 
-use crate::storage;
+use crate::storage::settings::Setting;
 
 static SCREEN: AtomicU32 = AtomicU32::new(0);
-static NAVIGATOR_STATE_KEY: &str = "navigator_screen";
+static SAVED_SCREEN: Setting<ActiveScreen> =
+    Setting::relaxed("navigator_screen", ActiveScreen::default);
 
 /// Returns the currently active screen.
 pub fn active() -> ActiveScreen {
@@ -13,38 +14,26 @@ pub fn active() -> ActiveScreen {
 
 /// Attempt to load the last navigation screen from the persisted state.
 pub fn load_from_state() {
-    let screen_bytes = storage::with_relaxed_state(move |state| {
-        state
-            .get_key_value(NAVIGATOR_STATE_KEY)
-            .inspect_err(|err| tracing::error!(error = %err, "failed to fetch navigator key state"))
-            .ok()
-    });
-    dbg!(&screen_bytes);
-
-    let screen: ActiveScreen = screen_bytes
-        .and_then(|bytes| Some(u32::from_le_bytes(bytes.try_into().ok()?)))
-        .and_then(ActiveScreen::from_id)
-        .unwrap_or_default();
-
+    let screen = SAVED_SCREEN.entry().load();
     SCREEN.store(screen as u32, Ordering::Relaxed);
 }
 
 /// Navigate to a new screen.
 pub fn navigate(screen: ActiveScreen) {
     SCREEN.store(screen as u32, Ordering::Relaxed);
-
-    let bytes = (screen as u32).to_le_bytes();
-    dbg!(&bytes);
-    storage::submit_relaxed_state(move |state| {
-        if let Err(err) = state.set_key_value(NAVIGATOR_STATE_KEY, &bytes) {
-            tracing::error!(error = %err, "failed to set navigator state");
-        }
-    });
+    SAVED_SCREEN.entry().save(&screen);
 }
 
 #[repr(u32)]
 #[derive(
-    Default, Copy, Clone, Debug, serde_derive::Serialize, serde_derive::Deserialize,
+    Default,
+    Copy,
+    Clone,
+    Debug,
+    Eq,
+    PartialEq,
+    serde_derive::Serialize,
+    serde_derive::Deserialize,
 )]
 /// What UI screen the app should be displaying.
 pub enum ActiveScreen {
@@ -61,6 +50,8 @@ pub enum ActiveScreen {
     LibrarySelect = 3,
     /// View the app settings.
     Settings = 4,
+    /// Search across all libraries.
+    Search = 5,
 }
 
 impl ActiveScreen {
@@ -71,6 +62,7 @@ impl ActiveScreen {
             2 => Some(ActiveScreen::Setup),
             3 => Some(ActiveScreen::LibrarySelect),
             4 => Some(ActiveScreen::Settings),
+            5 => Some(ActiveScreen::Search),
             _ => None,
         }
     }
@@ -0,0 +1,302 @@
+//! Video playback through an external player.
+//!
+//! The backend resolves a stream for the item, which is handed to the player
+//! configured in the [PlayerSettings]. The player is then controlled over its IPC
+//! socket, and playback progress is reported back to the backend so resume points
+//! and played state stay correct.
+
+use std::sync::{Arc, LazyLock};
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use snafu::{OptionExt, ResultExt};
+use tokio::sync::{mpsc, watch};
+
+use crate::backends::{Backend, BackendError, BackendId, registry};
+use crate::models::interaction::Interaction;
+use crate::models::live::RemoteCommand;
+use crate::models::media::ItemId;
+use crate::models::playback::{
+    PlaybackEvent,
+    PlaybackReport,
+    PlaybackRequest,
+    PlayerSettings,
+    ProgressThresholds,
+    WatchProgress,
+};
+use crate::models::profile::DeviceProfile;
+use crate::models::tracks::TrackSelection;
+use crate::storage::{interaction_backlog, playback_progress, track_preferences};
+
+mod mpv;
+pub mod quality;
+mod session;
+
+/// Forwards remote control commands to the running player, if any.
+static ACTIVE_PLAYER: Mutex<Option<mpsc::Sender<RemoteCommand>>> = Mutex::new(None);
+/// The video being played, if any.
+static NOW_PLAYING: LazyLock<watch::Sender<Option<NowPlaying>>> =
+    LazyLock::new(|| watch::Sender::new(None));
+
+#[derive(Debug, Clone, PartialEq)]
+/// The video being played by the external player.
+pub struct NowPlaying {
+    pub backend_id: BackendId,
+    pub item_id: ItemId,
+    pub title: String,
+    /// The position last reported by the player.
+    pub position: Duration,
+    /// When the position was reported, playback continues in between reports.
+    pub reported_at: Instant,
+    pub paused: bool,
+}
+
+#[derive(Debug, snafu::Snafu)]
+/// An error preventing playback from starting.
+pub enum PlaybackError {
+    #[snafu(display("backend no longer exists"))]
+    MissingBackend,
+    #[snafu(display("{}", source))]
+    Backend { source: BackendError },
+    #[snafu(display("failed to launch player {:?}: {}", command, source))]
+    Launch {
+        command: String,
+        source: std::io::Error,
+    },
+    #[snafu(display("failed to connect to player: {}", source))]
+    Connect { source: std::io::Error },
+    #[snafu(display("player exited before playback started (code: {:?})", code))]
+    PlayerExited { code: Option<i32> },
+}
+
+/// Play an item with the external player, returning once the player exits.
+///
+/// The audio and subtitle tracks are selected with the user's preferences, and the
+/// quality with the user's quality setting, unless the request selects them.
+///
+/// The position playback stops at is recorded locally using the `thresholds`, so
+/// resume points are correct before the next sync, and added to the interaction
+/// backlog which applies it to the backend.
+pub async fn play(
+    backend_id: BackendId,
+    mut request: PlaybackRequest,
+    title: String,
+    settings: PlayerSettings,
+    thresholds: ProgressThresholds,
+) -> Result<(), PlaybackError> {
+    let backend = registry::get(backend_id).context(MissingBackendSnafu)?;
+
+    if request.tracks.is_none() {
+        request.tracks = preferred_tracks(backend_id, &*backend, &request.item_id).await;
+    }
+
+    let quality = match request.quality {
+        Some(quality) => quality,
+        None => quality::resolve(backend_id, &*backend).await,
+    };
+    request.quality = Some(quality);
+    let profile = DeviceProfile::mpv().with_quality(quality);
+
+    let start_position = request.start_position;
+    let stream = backend
+        .playback_stream(request, profile)
+        .await
+        .context(BackendSnafu)?;
+
+    let mut player =
+        mpv::MpvProcess::launch(&settings, &stream, &title, start_position)?;
+    let connection = player.connect().await?;
+
+    let (commands_tx, commands) = mpsc::channel(16);
+    *ACTIVE_PLAYER.lock() = Some(commands_tx.clone());
+
+    let result =
+        session::run(
+            connection,
+            &stream,
+            start_position,
+            session::PROGRESS_INTERVAL,
+            commands,
+            // Each event is handled by a future owning what it needs, so the whole
+            // playback future stays `Send`.
+            |event| {
+                let backend = backend.clone();
+                let title = title.clone();
+                async move {
+                    handle_event(backend_id, backend, title, thresholds, event).await
+                }
+            },
+        )
+        .await;
+
+    {
+        // Another player may have been started by a remote command since.
+        let mut active = ACTIVE_PLAYER.lock();
+        if active
+            .as_ref()
+            .is_some_and(|active| active.same_channel(&commands_tx))
+        {
+            *active = None;
+        }
+    }
+
+    clear_now_playing(&stream.item_id);
+
+    if let Err(err) = result {
+        tracing::warn!(error = %err, "player IPC session failed");
+    }
+
+    player.wait().await;
+
+    Ok(())
+}
+
+/// Playback runs as an iced task, so its future must be `Send`.
+#[allow(dead_code)]
+fn assert_send(
+    backend_id: BackendId,
+    request: PlaybackRequest,
+    title: String,
+    settings: PlayerSettings,
+    thresholds: ProgressThresholds,
+) {
+    fn is_send<T: Send>(_: &T) {}
+
+    is_send(&play(backend_id, request, title, settings, thresholds));
+}
+
+/// Update what is playing and report it to the backend, adding where playback
+/// stopped to the interaction backlog.
+async fn handle_event(
+    backend_id: BackendId,
+    backend: Arc<dyn Backend>,
+    title: String,
+    thresholds: ProgressThresholds,
+    event: PlaybackEvent,
+) {
+    match &event {
+        PlaybackEvent::Started(report) | PlaybackEvent::Progress(report) => {
+            NOW_PLAYING.send_replace(Some(NowPlaying {
+                backend_id,
+                item_id: report.item_id.clone(),
+                title,
+                position: report.position,
+                reported_at: Instant::now(),
+                paused: report.paused,
+            }));
+        },
+        // Where playback stopped is applied to the backend by the interaction
+        // backlog, not reported here as well.
+        PlaybackEvent::Stopped(report) => {
+            clear_now_playing(&report.item_id);
+            record_progress(backend_id, report, thresholds).await;
+            return;
+        },
+    }
+
+    if let Err(err) = backend.report_playback(event).await {
+        tracing::warn!(error = %err, "failed to report playback");
+    }
+}
+
+/// Send a remote control command to the running player.
+///
+/// Returns `false` if nothing is playing.
+pub fn send_command(command: RemoteCommand) -> bool {
+    ACTIVE_PLAYER
+        .lock()
+        .as_ref()
+        .is_some_and(|commands| commands.try_send(command).is_ok())
+}
+
+/// Select the item's tracks matching the user's preferences, leaving the player to
+/// pick its own if the tracks cannot be loaded.
+async fn preferred_tracks(
+    backend_id: BackendId,
+    backend: &dyn Backend,
+    item_id: &ItemId,
+) -> Option<TrackSelection> {
+    match backend.item_tracks(item_id.clone()).await {
+        Ok(tracks) => {
+            let preferences =
+                track_preferences::resolve_async(backend_id, tracks.series_id.as_ref())
+                    .await;
+            Some(preferences.select(&tracks))
+        },
+        Err(err) => {
+            tracing::warn!(item_id = %item_id, error = %err, "failed to load item tracks");
+            None
+        },
+    }
+}
+
+/// Returns a receiver of the video being played.
+pub fn now_playing() -> watch::Receiver<Option<NowPlaying>> {
+    NOW_PLAYING.subscribe()
+}
+
+/// Clear the video being played, unless another item has started since.
+fn clear_now_playing(item_id: &str) {
+    NOW_PLAYING.send_if_modified(|now_playing| {
+        let is_item = now_playing
+            .as_ref()
+            .is_some_and(|now_playing| now_playing.item_id == item_id);
+        if is_item {
+            *now_playing = None;
+        }
+        is_item
+    });
+}
+
+/// Record where playback stopped locally, then add it to the interaction backlog
+/// so it reaches the backend even if it cannot be reached right now.
+async fn record_progress(
+    backend_id: BackendId,
+    report: &PlaybackReport,
+    thresholds: ProgressThresholds,
+) {
+    let item_id = report.item_id.clone();
+    let result = playback_progress::record(
+        backend_id,
+        item_id.clone(),
+        report.position,
+        thresholds,
+    )
+    .await;
+    let progress = match result {
+        Ok(progress) => progress,
+        Err(err) => {
+            tracing::warn!(error = %err, "failed to record playback progress");
+            thresholds.classify(report.position, None)
+        },
+    };
+
+    let interaction = match progress {
+        WatchProgress::Unplayed => Interaction::Progress {
+            item_id,
+            position: Duration::ZERO,
+        },
+        WatchProgress::InProgress(position) => {
+            Interaction::Progress { item_id, position }
+        },
+        WatchProgress::Played => Interaction::Played {
+            item_id,
+            played: true,
+        },
+    };
+
+    if let Err(err) = interaction_backlog::record(backend_id, interaction).await {
+        tracing::error!(error = %err, "failed to add playback progress to backlog");
+    }
+}
+
+/// Formats a playback position, i.e. `42:13` or `1:02:03`.
+pub fn format_position(position: Duration) -> String {
+    let secs = position.as_secs();
+    let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{seconds:02}")
+    } else {
+        format!("{minutes}:{seconds:02}")
+    }
+}
@@ -0,0 +1,169 @@
+//! Launches mpv, or any player compatible with its command line options and JSON
+//! IPC protocol.
+//!
+//! The player starts idle and the stream is loaded over IPC by the
+//! [session](super::session), as stream URLs carry the backend's access token and
+//! the command line of a process can be read by any local user.
+
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+
+use snafu::ResultExt;
+
+use super::{ConnectSnafu, LaunchSnafu, PlaybackError};
+use crate::models::playback::{PlaybackStream, PlayerSettings};
+
+/// How long to wait for the player to open its IPC socket.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often to retry connecting while the player starts.
+const CONNECT_RETRY_INTERVAL: Duration = Duration::from_millis(100);
+/// How long to wait for the player to exit after the IPC connection closes.
+const EXIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[cfg(unix)]
+pub type IpcStream = tokio::net::UnixStream;
+#[cfg(windows)]
+pub type IpcStream = tokio::net::windows::named_pipe::NamedPipeClient;
+
+/// A running player process.
+pub struct MpvProcess {
+    child: Child,
+    ipc_path: PathBuf,
+}
+
+impl MpvProcess {
+    /// Launch the player for the given stream, which is loaded once connected.
+    pub fn launch(
+        settings: &PlayerSettings,
+        stream: &PlaybackStream,
+        title: &str,
+        start_position: Duration,
+    ) -> Result<Self, PlaybackError> {
+        let ipc_path = ipc_path();
+
+        let mut command = Command::new(&settings.command);
+        command
+            .arg(format!("--input-ipc-server={}", ipc_path.display()))
+            .arg(format!("--force-media-title={title}"))
+            // Exits once the stream loaded over IPC ends, as with `--idle=no`.
+            .arg("--idle=once")
+            .arg("--keep-open=no");
+        if !start_position.is_zero() {
+            command.arg(format!("--start={:.3}", start_position.as_secs_f64()));
+        }
+        command.args(track_args(stream));
+        command
+            .args(&settings.args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+
+        tracing::info!(
+            command = %settings.command,
+            item_id = %stream.item_id,
+            play_method = ?stream.play_method,
+            "launching external player",
+        );
+
+        let child = command.spawn().context(LaunchSnafu {
+            command: settings.command.clone(),
+        })?;
+
+        Ok(Self { child, ipc_path })
+    }
+
+    /// Connect to the player's IPC socket, waiting for the player to create it.
+    pub async fn connect(&mut self) -> Result<IpcStream, PlaybackError> {
+        let deadline = tokio::time::Instant::now() + CONNECT_TIMEOUT;
+        loop {
+            match connect_ipc(&self.ipc_path).await {
+                Ok(stream) => return Ok(stream),
+                Err(err) if tokio::time::Instant::now() >= deadline => {
+                    return Err(err).context(ConnectSnafu);
+                },
+                Err(_) => {},
+            }
+
+            if let Ok(Some(status)) = self.child.try_wait() {
+                return Err(PlaybackError::PlayerExited {
+                    code: status.code(),
+                });
+            }
+
+            tokio::time::sleep(CONNECT_RETRY_INTERVAL).await;
+        }
+    }
+
+    /// Wait for the player to exit, killing it if it does not exit in time.
+    pub async fn wait(mut self) {
+        let deadline = tokio::time::Instant::now() + EXIT_TIMEOUT;
+        while tokio::time::Instant::now() < deadline {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            tokio::time::sleep(CONNECT_RETRY_INTERVAL).await;
+        }
+
+        tracing::warn!("external player did not exit, killing it");
+    }
+}
+
+impl Drop for MpvProcess {
+    fn drop(&mut self) {
+        if let Ok(None) = self.child.try_wait() {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+
+        #[cfg(unix)]
+        let _ = std::fs::remove_file(&self.ipc_path);
+    }
+}
+
+/// Returns the options selecting the stream's audio and subtitle tracks, the
+/// player picks its own if none were selected.
+fn track_args(stream: &PlaybackStream) -> Vec<String> {
+    let mut args = Vec::new();
+    let Some(tracks) = stream.tracks else {
+        return args;
+    };
+
+    if let Some(track_id) = tracks
+        .audio_stream_index
+        .and_then(|index| stream.audio_track_id(index))
+    {
+        args.push(format!("--aid={track_id}"));
+    }
+    match tracks.subtitle_stream_index {
+        // Burned into the video when transcoding, so there is no track to select.
+        Some(index) => {
+            if let Some(track_id) = stream.subtitle_track_id(index) {
+                args.push(format!("--sid={track_id}"));
+            }
+        },
+        None => args.push("--sid=no".to_string()),
+    }
+    args
+}
+
+#[cfg(unix)]
+fn ipc_path() -> PathBuf {
+    let name = format!("bluebottle-mpv-{}.sock", uuid::Uuid::now_v7());
+    std::env::temp_dir().join(name)
+}
+
+#[cfg(windows)]
+fn ipc_path() -> PathBuf {
+    PathBuf::from(format!(r"\\.\pipe\bluebottle-mpv-{}", uuid::Uuid::now_v7()))
+}
+
+#[cfg(unix)]
+async fn connect_ipc(path: &std::path::Path) -> std::io::Result<IpcStream> {
+    tokio::net::UnixStream::connect(path).await
+}
+
+#[cfg(windows)]
+async fn connect_ipc(path: &std::path::Path) -> std::io::Result<IpcStream> {
+    tokio::net::windows::named_pipe::ClientOptions::new().open(path)
+}
@@ -0,0 +1,65 @@
+//! Picks the quality video is streamed at, measuring the bitrate of each backend
+//! when the quality is picked automatically.
+
+use std::collections::HashMap;
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+use crate::backends::{Backend, BackendId};
+use crate::models::profile::{Quality, QualitySetting};
+use crate::storage::stream_quality;
+
+/// How long a measured bitrate is used for, the connection may change after.
+const MEASUREMENT_TTL: Duration = Duration::from_secs(30 * 60);
+
+/// The last measured bitrate of each backend, along with when it was measured.
+static MEASUREMENTS: LazyLock<Mutex<HashMap<BackendId, (u64, Instant)>>> =
+    LazyLock::new(Mutex::default);
+
+/// Returns the quality to stream at when none was picked for the item, measuring
+/// the backend's bitrate if the quality is picked automatically.
+///
+/// The original files are streamed if the bitrate cannot be measured, leaving the
+/// backend to enforce its own limits.
+pub async fn resolve(backend_id: BackendId, backend: &dyn Backend) -> Quality {
+    match stream_quality::load_async(backend_id).await {
+        QualitySetting::Fixed(quality) => quality,
+        QualitySetting::Auto => measure(backend_id, backend)
+            .await
+            .map_or(Quality::Original, Quality::for_bitrate),
+    }
+}
+
+/// Returns the quality picked automatically for the backend, if its bitrate was
+/// measured recently.
+pub fn auto_quality(backend_id: BackendId) -> Option<Quality> {
+    recent_bitrate(backend_id).map(Quality::for_bitrate)
+}
+
+async fn measure(backend_id: BackendId, backend: &dyn Backend) -> Option<u64> {
+    if let Some(bitrate) = recent_bitrate(backend_id) {
+        return Some(bitrate);
+    }
+
+    match backend.measure_bitrate().await {
+        Ok(bitrate) => {
+            tracing::info!(backend_id = %backend_id, bitrate, "measured backend bitrate");
+            MEASUREMENTS
+                .lock()
+                .insert(backend_id, (bitrate, Instant::now()));
+            Some(bitrate)
+        },
+        Err(err) => {
+            tracing::warn!(backend_id = %backend_id, error = %err, "failed to measure backend bitrate");
+            None
+        },
+    }
+}
+
+fn recent_bitrate(backend_id: BackendId) -> Option<u64> {
+    let measurements = MEASUREMENTS.lock();
+    let (bitrate, measured_at) = measurements.get(&backend_id)?;
+    (measured_at.elapsed() < MEASUREMENT_TTL).then_some(*bitrate)
+}
@@ -0,0 +1,382 @@
+//! Drives a playback session over mpv's JSON IPC protocol, turning player events
+//! into [PlaybackEvent]s for the backend.
+//!
+//! See <https://mpv.io/manual/stable/#json-ipc> for the protocol.
+
+use std::time::Duration;
+
+use serde_json::{Value, json};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+use crate::models::live::RemoteCommand;
+use crate::models::playback::{PlaybackEvent, PlaybackReport, PlaybackStream};
+
+/// How often progress is reported while playing.
+pub const PROGRESS_INTERVAL: Duration = Duration::from_secs(10);
+
+const TIME_POS_OBSERVER: u64 = 1;
+const PAUSE_OBSERVER: u64 = 2;
+const AUDIO_TRACK_OBSERVER: u64 = 3;
+const SUBTITLE_TRACK_OBSERVER: u64 = 4;
+
+/// How much the volume changes by with each volume up or down command.
+const VOLUME_STEP: i64 = 5;
+/// How long messages are displayed over the video.
+const MESSAGE_DURATION_MS: u64 = 5000;
+
+/// Load the stream and run the session until the player exits or closes the IPC
+/// connection.
+///
+/// The stream and its external subtitles are loaded once the observers are
+/// registered, so no events are missed.
+///
+/// `Started` is reported once the player reports a position, followed by
+/// `Progress` every `progress_interval` and whenever playback is paused or resumed,
+/// or the audio or subtitle track changes.
+/// `Stopped` is always reported last with the final position, provided playback
+/// started.
+///
+/// Remote control `commands` are forwarded to the player until the channel
+/// closes.
+pub async fn run<S, F>(
+    connection: S,
+    stream: &PlaybackStream,
+    start_position: Duration,
+    progress_interval: Duration,
+    mut commands: mpsc::Receiver<RemoteCommand>,
+    mut report: impl FnMut(PlaybackEvent) -> F,
+) -> std::io::Result<()>
+where
+    S: AsyncRead + AsyncWrite,
+    F: Future<Output = ()>,
+{
+    let (reader, mut writer) = tokio::io::split(connection);
+    observe_property(&mut writer, TIME_POS_OBSERVER, "time-pos").await?;
+    observe_property(&mut writer, PAUSE_OBSERVER, "pause").await?;
+    observe_property(&mut writer, AUDIO_TRACK_OBSERVER, "aid").await?;
+    observe_property(&mut writer, SUBTITLE_TRACK_OBSERVER, "sid").await?;
+    load_stream(&mut writer, stream).await?;
+
+    let mut state = PlaybackReport::new(stream, start_position);
+    let mut started = false;
+    let mut next_progress = Instant::now() + progress_interval;
+    let mut lines = BufReader::new(reader).lines();
+
+    loop {
+        let line = tokio::select! {
+            result = lines.next_line() => match result {
+                Ok(Some(line)) => line,
+                Ok(None) => break,
+                Err(err) => {
+                    tracing::warn!(error = %err, "player IPC connection failed");
+                    break;
+                },
+            },
+            Some(command) = commands.recv() => {
+                if let Some(command) = player_command(stream, &command)
+                    && let Err(err) = write_command(&mut writer, command).await
+                {
+                    tracing::warn!(error = %err, "failed to send command to player");
+                }
+                continue;
+            },
+            _ = tokio::time::sleep_until(next_progress) => {
+                if started {
+                    report(PlaybackEvent::Progress(state.clone())).await;
+                }
+                next_progress = Instant::now() + progress_interval;
+                continue;
+            },
+        };
+
+        let Ok(message) = serde_json::from_str::<Value>(&line) else {
+            tracing::warn!(line = %line, "invalid player IPC message");
+            continue;
+        };
+
+        match PlayerEvent::parse(&message) {
+            Some(PlayerEvent::Position(position)) => {
+                state.position = position;
+                if !started {
+                    started = true;
+                    report(PlaybackEvent::Started(state.clone())).await;
+                    next_progress = Instant::now() + progress_interval;
+                }
+            },
+            Some(PlayerEvent::Paused(paused)) if paused != state.paused => {
+                state.paused = paused;
+                if started {
+                    report(PlaybackEvent::Progress(state.clone())).await;
+                    next_progress = Instant::now() + progress_interval;
+                }
+            },
+            Some(PlayerEvent::Paused(_)) => {},
+            Some(PlayerEvent::AudioTrack(track_id)) => {
+                let index = track_id.and_then(|id| stream.audio_stream_index(id));
+                if index != state.audio_stream_index {
+                    state.audio_stream_index = index;
+                    if started {
+                        report(PlaybackEvent::Progress(state.clone())).await;
+                    }
+                }
+            },
+            Some(PlayerEvent::SubtitleTrack(track_id)) => {
+                let index = track_id.and_then(|id| stream.subtitle_stream_index(id));
+                if index != state.subtitle_stream_index {
+                    state.subtitle_stream_index = index;
+                    if started {
+                        report(PlaybackEvent::Progress(state.clone())).await;
+                    }
+                }
+            },
+            Some(PlayerEvent::Ended) => break,
+            None => {},
+        }
+    }
+
+    if started {
+        report(PlaybackEvent::Stopped(state)).await;
+    }
+
+    Ok(())
+}
+
+/// Load the stream with its external subtitles.
+///
+/// The URLs carry the backend's access token, so they are sent over IPC rather
+/// than on the player's command line.
+async fn load_stream<W>(writer: &mut W, stream: &PlaybackStream) -> std::io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    // Subtitle files are added to the option rather than with `sub-add`, which
+    // only works once the file has loaded, so track IDs match `--sub-file`.
+    for url in stream.subtitle_files.iter() {
+        let command = json!(["change-list", "sub-files", "append", url.as_str()]);
+        write_command(writer, command).await?;
+    }
+    write_command(writer, json!(["loadfile", stream.url.as_str()])).await
+}
+
+async fn observe_property<W>(writer: &mut W, id: u64, name: &str) -> std::io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    write_command(writer, json!(["observe_property", id, name])).await
+}
+
+async fn write_command<W>(writer: &mut W, command: Value) -> std::io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    let command = json!({ "command": command });
+    let mut line = command.to_string();
+    line.push('\n');
+    writer.write_all(line.as_bytes()).await?;
+    writer.flush().await
+}
+
+/// Translate a remote control command into a player command, returning `None`
+/// if the player cannot handle it.
+fn player_command(stream: &PlaybackStream, command: &RemoteCommand) -> Option<Value> {
+    let command = match command {
+        RemoteCommand::Pause => json!(["set_property", "pause", true]),
+        RemoteCommand::Unpause => json!(["set_property", "pause", false]),
+        RemoteCommand::PlayPause => json!(["cycle", "pause"]),
+        RemoteCommand::Stop => json!(["quit"]),
+        RemoteCommand::Seek(position) => {
+            json!(["seek", position.as_secs_f64(), "absolute"])
+        },
+        RemoteCommand::SetAudioStreamIndex(index) => {
+            json!(["set_property", "aid", stream.audio_track_id(*index)?])
+        },
+        RemoteCommand::SetSubtitleStreamIndex(Some(index)) => {
+            json!(["set_property", "sid", stream.subtitle_track_id(*index)?])
+        },
+        RemoteCommand::SetSubtitleStreamIndex(None) => {
+            json!(["set_property", "sid", "no"])
+        },
+        RemoteCommand::SetVolume(volume) => json!(["set_property", "volume", volume]),
+        RemoteCommand::VolumeUp => json!(["add", "volume", VOLUME_STEP]),
+        RemoteCommand::VolumeDown => json!(["add", "volume", -VOLUME_STEP]),
+        RemoteCommand::Mute => json!(["set_property", "mute", true]),
+        RemoteCommand::Unmute => json!(["set_property", "mute", false]),
+        RemoteCommand::ToggleMute => json!(["cycle", "mute"]),
+        RemoteCommand::DisplayMessage { header, text } => {
+            let message = if header.is_empty() {
+                text.clone()
+            } else {
+                format!("{header}\n{text}")
+            };
+            json!(["show-text", message, MESSAGE_DURATION_MS])
+        },
+        // Starting playback is handled by the UI.
+        RemoteCommand::Play { .. } => return None,
+    };
+
+    Some(command)
+}
+
+#[derive(Debug, PartialEq)]
+enum PlayerEvent {
+    /// The playback position changed.
+    Position(Duration),
+    /// Playback was paused or resumed.
+    Paused(bool),
+    /// The audio track changed, `None` if audio is off.
+    AudioTrack(Option<u64>),
+    /// The subtitle track changed, `None` if subtitles are off.
+    SubtitleTrack(Option<u64>),
+    /// The file finished playing or the player is shutting down.
+    Ended,
+}
+
+impl PlayerEvent {
+    fn parse(message: &Value) -> Option<Self> {
+        match message.get("event")?.as_str()? {
+            "property-change" => {
+                let data = message.get("data")?;
+                match message.get("id")?.as_u64()? {
+                    TIME_POS_OBSERVER => {
+                        let secs = data.as_f64()?;
+                        Duration::try_from_secs_f64(secs).ok().map(Self::Position)
+                    },
+                    PAUSE_OBSERVER => data.as_bool().map(Self::Paused),
+                    // Tracks are either an ID or `false` when turned off.
+                    AUDIO_TRACK_OBSERVER => Some(Self::AudioTrack(data.as_u64())),
+                    SUBTITLE_TRACK_OBSERVER => Some(Self::SubtitleTrack(data.as_u64())),
+                    _ => None,
+                }
+            },
+            "end-file" | "shutdown" => Some(Self::Ended),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::playback::PlayMethod;
+
+    fn stream() -> PlaybackStream {
+        PlaybackStream {
+            item_id: "abc".into(),
+            url: "http://localhost/Videos/abc/stream".parse().unwrap(),
+            media_source_id: "source-1".into(),
+            play_session_id: Some("session-1".into()),
+            play_method: PlayMethod::DirectPlay,
+            audio_streams: vec![1, 2],
+            subtitle_streams: vec![3],
+            subtitle_files: Vec::new(),
+            tracks: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_session_reports_playback() {
+        let (client, peer) = tokio::io::duplex(4096);
+
+        // Act as mpv, checking the observers are registered, the stream loaded and
+        // the remote command forwarded before sending events.
+        let fake_player = async move {
+            let (reader, mut writer) = tokio::io::split(peer);
+            let mut lines = BufReader::new(reader).lines();
+            for property in ["time-pos", "pause", "aid", "sid"] {
+                let line = lines.next_line().await.unwrap().unwrap();
+                assert!(line.contains(property));
+            }
+            for expected in [
+                r#"{"command":["change-list","sub-files","append","http://localhost/Videos/abc/Subtitles/3/Stream.srt"]}"#,
+                r#"{"command":["loadfile","http://localhost/Videos/abc/stream"]}"#,
+            ] {
+                let line = lines.next_line().await.unwrap().unwrap();
+                assert_eq!(line, expected);
+            }
+            let line = lines.next_line().await.unwrap().unwrap();
+            assert_eq!(line, r#"{"command":["set_property","sid",1]}"#);
+
+            let events = [
+                r#"{"request_id":0,"error":"success"}"#,
+                r#"{"event":"property-change","id":2,"name":"pause","data":false}"#,
+                r#"{"event":"property-change","id":3,"name":"aid","data":1}"#,
+                r#"{"event":"property-change","id":4,"name":"sid","data":false}"#,
+                r#"{"event":"property-change","id":1,"name":"time-pos","data":null}"#,
+                r#"{"event":"property-change","id":1,"name":"time-pos","data":60.5}"#,
+                r#"{"event":"property-change","id":3,"name":"aid","data":2}"#,
+                r#"{"event":"property-change","id":2,"name":"pause","data":true}"#,
+                r#"{"event":"property-change","id":1,"name":"time-pos","data":61.0}"#,
+                r#"{"event":"end-file","reason":"quit"}"#,
+            ];
+            for event in events {
+                writer.write_all(event.as_bytes()).await.unwrap();
+                writer.write_all(b"\n").await.unwrap();
+            }
+        };
+
+        let (commands_tx, commands) = mpsc::channel(1);
+        commands_tx
+            .send(RemoteCommand::SetSubtitleStreamIndex(Some(3)))
+            .await
+            .unwrap();
+
+        let mut stream = stream();
+        stream.subtitle_files = vec![
+            "http://localhost/Videos/abc/Subtitles/3/Stream.srt"
+                .parse()
+                .unwrap(),
+        ];
+        let mut reported = Vec::new();
+        let session = run(
+            client,
+            &stream,
+            Duration::from_secs(60),
+            Duration::from_secs(3600),
+            commands,
+            |event| {
+                reported.push(event);
+                async {}
+            },
+        );
+
+        let (result, _) = iced::futures::join!(session, fake_player);
+        result.unwrap();
+
+        let mut expected = PlaybackReport::new(&stream, Duration::from_secs_f64(60.5));
+        expected.audio_stream_index = Some(1);
+        let started = PlaybackEvent::Started(expected.clone());
+        expected.audio_stream_index = Some(2);
+        let audio_changed = PlaybackEvent::Progress(expected.clone());
+        expected.paused = true;
+        let paused = PlaybackEvent::Progress(expected.clone());
+        expected.position = Duration::from_secs(61);
+        let stopped = PlaybackEvent::Stopped(expected);
+
+        assert_eq!(reported, vec![started, audio_changed, paused, stopped]);
+    }
+
+    #[tokio::test]
+    async fn test_session_without_playback_reports_nothing() {
+        let (client, peer) = tokio::io::duplex(4096);
+        drop(peer);
+
+        let (_commands_tx, commands) = mpsc::channel(1);
+        let mut reported = Vec::new();
+        let _ = run(
+            client,
+            &stream(),
+            Duration::ZERO,
+            Duration::from_secs(3600),
+            commands,
+            |event| {
+                reported.push(event);
+                async {}
+            },
+        )
+        .await;
+
+        assert!(reported.is_empty());
+    }
+}
@@ -1,33 +1,719 @@
-use bluebottle_ui::bar;
-use iced::widget::{container, row, space};
+use std::collections::{HashMap, HashSet};
+use std::hash::{BuildHasher, RandomState};
+use std::time::Duration;
+
+use bluebottle_ui::image::PosterSize;
+use bluebottle_ui::poster_grid::{GridViewport, poster_grid};
+use bluebottle_ui::{card, image};
+use iced::widget::{center, column, container, opaque, space, stack};
 use iced::{Element, Length, task};
 
-use crate::view;
+use crate::backends::{BackendId, registry};
+use crate::components::item_detail::{ItemDetail, ItemDetailMsg};
+use crate::components::library_toolbar::{LibraryToolbar, LibraryToolbarMsg};
+use crate::components::media_shelf::{MediaShelf, MediaShelfMsg};
+use crate::images::{self, ImageKey};
+use crate::live::LiveUpdate;
+use crate::models::live::RemoteCommand;
+use crate::models::media::{ItemId, ItemKind, ItemPage, ItemSummary, Library};
+use crate::models::playback::PlaybackRequest;
+use crate::models::profile::Quality;
+use crate::models::query::{FilterOptions, ItemQuery};
+use crate::models::settings::Density;
+use crate::models::tracks::TrackSelection;
+use crate::music::{self, PlayerCommand};
+use crate::storage::settings::SettingChange;
+use crate::storage::{
+    appearance,
+    library_items,
+    library_options,
+    playback_progress,
+    player_settings,
+};
+use crate::sync::{SyncEvent, SyncStatus};
+use crate::{playback, view};
 
-#[derive(Default)]
-pub struct LibraryViewScreen {}
+/// The number of items requested from the backend at once.
+const PAGE_SIZE: usize = 100;
+/// The size of the posters displayed in the grid.
+/// The maximum number of items displayed on each shelf.
+const SHELF_LIMIT: usize = 24;
+
+pub struct LibraryViewScreen {
+    library: Option<ActiveLibrary>,
+    toolbar: LibraryToolbar,
+    continue_watching: MediaShelf,
+    next_up: MediaShelf,
+    items: PagedItems,
+    /// Incremented whenever the loaded items are dropped, pages requested for an
+    /// older generation are stale and ignored.
+    generation: u64,
+    /// Seeds the order of a random sort, changed whenever the items are reloaded.
+    random_seed: u64,
+    viewport: GridViewport,
+    /// The size of the posters in the grid, set by the appearance's density.
+    poster_size: PosterSize,
+    item_detail: Option<ItemDetail>,
+}
+
+impl Default for LibraryViewScreen {
+    fn default() -> Self {
+        Self {
+            library: None,
+            toolbar: LibraryToolbar::default(),
+            continue_watching: MediaShelf::new("Continue Watching"),
+            next_up: MediaShelf::new("Next Up"),
+            items: PagedItems::default(),
+            generation: 0,
+            random_seed: 0,
+            viewport: GridViewport::default(),
+            poster_size: poster_size(appearance::load().density),
+            item_detail: None,
+        }
+    }
+}
 
 #[derive(Clone)]
-pub enum LibraryViewMsg {}
+pub enum LibraryViewMsg {
+    LibrariesLoaded(BackendId, Result<Vec<Library>, String>),
+    Viewport(GridViewport),
+    /// The artwork of an item in the grid has loaded.
+    ImageLoaded,
+    PageLoaded {
+        generation: u64,
+        page: usize,
+        result: Result<ItemPage, String>,
+    },
+    OpenItem(ItemId),
+    /// Show the details of a video item, which it is played from.
+    Play(ItemId),
+    ItemDetail(ItemDetailMsg),
+    /// Queue the tracks of an audio item, album or artist in the music player.
+    PlayMusic(ItemId, ItemKind),
+    MusicQueued(Result<(), String>),
+    PlaybackFinished(Result<(), String>),
+    ContinueWatching(MediaShelfMsg),
+    NextUp(MediaShelfMsg),
+    Toolbar(LibraryToolbarMsg),
+    FilterOptionsLoaded {
+        library_id: ItemId,
+        result: Result<FilterOptions, String>,
+    },
+    Sync(SyncEvent),
+    Live(LiveUpdate),
+    SettingChanged(SettingChange),
+}
 
 impl super::Screen<LibraryViewMsg> for LibraryViewScreen {
     fn nav_descriptor(&self) -> &str {
-        "Replace Me"
+        self.library
+            .as_ref()
+            .map(|active| active.library.name.as_str())
+            .unwrap_or("Library")
     }
 }
 
 impl view::View<LibraryViewMsg> for LibraryViewScreen {
-    fn update(&mut self, _message: LibraryViewMsg) -> task::Task<LibraryViewMsg> {
-        todo!()
+    fn update(&mut self, message: LibraryViewMsg) -> task::Task<LibraryViewMsg> {
+        match message {
+            LibraryViewMsg::LibrariesLoaded(backend_id, Ok(libraries)) => {
+                if let Some(library) = libraries.into_iter().next() {
+                    return self.open(backend_id, library);
+                }
+                tracing::warn!(backend_id = %backend_id, "backend has no libraries");
+            },
+            LibraryViewMsg::LibrariesLoaded(backend_id, Err(err)) => {
+                tracing::error!(backend_id = %backend_id, error = %err, "failed to load libraries");
+            },
+            LibraryViewMsg::Viewport(viewport) => {
+                self.viewport = viewport;
+                return self.request_visible();
+            },
+            LibraryViewMsg::ImageLoaded => {},
+            LibraryViewMsg::PageLoaded {
+                generation,
+                page,
+                result,
+            } => {
+                if generation != self.generation {
+                    return task::Task::none();
+                }
+
+                self.items.inflight.remove(&page);
+                match result {
+                    Ok(item_page) => {
+                        self.items.insert_page(page, item_page);
+                        return self.request_visible();
+                    },
+                    Err(err) => {
+                        tracing::error!(page = page, error = %err, "failed to load library page");
+                    },
+                }
+            },
+            LibraryViewMsg::OpenItem(item_id) => {
+                tracing::debug!(item_id = %item_id, "open item");
+            },
+            LibraryViewMsg::Play(item_id) => return self.open_detail(item_id),
+            LibraryViewMsg::ItemDetail(ItemDetailMsg::Play(start_position)) => {
+                let Some(detail) = self.item_detail.take() else {
+                    return task::Task::none();
+                };
+                detail.remember_choice();
+                return self.play_from(
+                    detail.item_id().clone(),
+                    start_position,
+                    detail.selection(),
+                    detail.quality(),
+                );
+            },
+            LibraryViewMsg::ItemDetail(ItemDetailMsg::Dismiss) => {
+                self.item_detail = None
+            },
+            LibraryViewMsg::ItemDetail(msg) => {
+                if let Some(detail) = self.item_detail.as_mut() {
+                    return detail.update(msg).map(LibraryViewMsg::ItemDetail);
+                }
+            },
+            LibraryViewMsg::PlayMusic(item_id, kind) => {
+                let Some(active) = self.library.as_ref() else {
+                    return task::Task::none();
+                };
+
+                let fut = music::play_item(active.backend_id, item_id, kind);
+                return task::Task::perform(fut, LibraryViewMsg::MusicQueued);
+            },
+            LibraryViewMsg::MusicQueued(result) => {
+                if let Err(err) = result {
+                    tracing::error!(error = %err, "failed to queue music");
+                }
+            },
+            LibraryViewMsg::PlaybackFinished(result) => {
+                if let Err(err) = result {
+                    tracing::error!(error = %err, "playback failed");
+                }
+                return self.refresh_shelves();
+            },
+            LibraryViewMsg::ContinueWatching(MediaShelfMsg::Open(item_id))
+            | LibraryViewMsg::NextUp(MediaShelfMsg::Open(item_id)) => {
+                return self.open_detail(item_id);
+            },
+            LibraryViewMsg::ContinueWatching(msg) => {
+                return self
+                    .continue_watching
+                    .update(msg)
+                    .map(LibraryViewMsg::ContinueWatching);
+            },
+            LibraryViewMsg::NextUp(msg) => {
+                return self.next_up.update(msg).map(LibraryViewMsg::NextUp);
+            },
+            LibraryViewMsg::Toolbar(msg) => {
+                let changes_query = msg.changes_query();
+                let task = self.toolbar.update(msg).map(LibraryViewMsg::Toolbar);
+                if !changes_query {
+                    return task;
+                }
+
+                if let Some(active) = self.library.as_ref() {
+                    library_options::save(
+                        active.backend_id,
+                        &active.library.id,
+                        self.toolbar.options(),
+                    );
+                }
+                return task.chain(self.reload());
+            },
+            LibraryViewMsg::FilterOptionsLoaded { library_id, result } => {
+                if !self.is_active_library(&library_id) {
+                    return task::Task::none();
+                }
+
+                match result {
+                    Ok(available) => self.toolbar.set_available(available),
+                    Err(err) => {
+                        tracing::error!(error = %err, "failed to load filter options");
+                    },
+                }
+            },
+            LibraryViewMsg::Sync(SyncEvent::Progress(progress)) => {
+                if progress.status != (SyncStatus::Complete { changed: true }) {
+                    return task::Task::none();
+                }
+
+                let Some(active) = self.library.as_ref() else {
+                    return task::Task::none();
+                };
+                if active.backend_id != progress.backend_id {
+                    return task::Task::none();
+                }
+
+                let is_active_library = active.library.id == progress.library_id;
+                let shelves = self.refresh_shelves();
+                if is_active_library {
+                    return task::Task::batch([shelves, self.refresh()]);
+                }
+                return shelves;
+            },
+            LibraryViewMsg::Sync(SyncEvent::Finished(_)) => {},
+            LibraryViewMsg::Live(LiveUpdate::StateChanged(backend_id)) => {
+                if self
+                    .library
+                    .as_ref()
+                    .is_some_and(|active| active.backend_id == backend_id)
+                {
+                    return self.refresh_shelves();
+                }
+            },
+            LibraryViewMsg::Live(LiveUpdate::ItemsUpdated {
+                backend_id,
+                item_ids,
+            }) => {
+                if let Some(detail) = self.item_detail.as_ref() {
+                    return detail
+                        .item_updated(backend_id, &item_ids)
+                        .map(LibraryViewMsg::ItemDetail);
+                }
+            },
+            LibraryViewMsg::Live(LiveUpdate::Play {
+                backend_id,
+                item_id,
+                start_position,
+            }) => {
+                // Replace whatever is playing, as other clients do.
+                playback::send_command(RemoteCommand::Stop);
+                self.item_detail = None;
+                return self.play_item(backend_id, item_id, start_position, None, None);
+            },
+            LibraryViewMsg::SettingChanged(change) => {
+                if let Some(detail) = self.item_detail.as_mut() {
+                    detail.setting_changed(&change);
+                }
+                let mut tasks = Vec::new();
+                if playback_progress::PROGRESS_THRESHOLDS.is_changed(&change) {
+                    tasks.push(self.refresh_shelves());
+                }
+                if appearance::APPEARANCE.is_changed(&change) {
+                    self.poster_size = poster_size(appearance::load().density);
+                    // More or fewer items are visible at the new size.
+                    tasks.push(self.request_visible());
+                }
+                return task::Task::batch(tasks);
+            },
+        }
+
+        task::Task::none()
     }
 
     fn view(&self) -> Element<'_, LibraryViewMsg> {
-        iced::widget::column![row![
-            bar::side(space(), space()),
-            container(space()).width(Length::Fill).height(Length::Fill)
-        ]]
-        .width(Length::Fill)
-        .height(Length::Fill)
-        .into()
+        if self.library.is_none() {
+            return container(space())
+                .width(Length::Fill)
+                .height(Length::Fill)
+                .into();
+        }
+
+        let grid = poster_grid(
+            self.viewport,
+            self.items.total_items,
+            self.poster_size,
+            |index| {
+                self.items.get(index).map(|entry| {
+                    let key = self.image_key(entry);
+                    grid_card(
+                        entry,
+                        key.as_ref().and_then(images::get),
+                        self.poster_size,
+                    )
+                })
+            },
+            LibraryViewMsg::Viewport,
+        );
+
+        let content = column![
+            self.toolbar.view().map(LibraryViewMsg::Toolbar),
+            self.continue_watching
+                .view()
+                .map(LibraryViewMsg::ContinueWatching),
+            self.next_up.view().map(LibraryViewMsg::NextUp),
+            grid,
+        ];
+
+        let content = container(content).width(Length::Fill).height(Length::Fill);
+
+        match self.item_detail.as_ref() {
+            Some(detail) => {
+                let detail = detail.view().map(LibraryViewMsg::ItemDetail);
+                stack![content, opaque(center(detail))].into()
+            },
+            None => content.into(),
+        }
+    }
+}
+
+impl LibraryViewScreen {
+    /// Load the first library of the first configured backend.
+    pub fn load(&mut self) -> task::Task<LibraryViewMsg> {
+        let Some((backend_id, backend)) = registry::first() else {
+            return task::Task::none();
+        };
+
+        let fut =
+            async move { backend.libraries().await.map_err(|err| err.to_string()) };
+        task::Task::perform(fut, move |result| {
+            LibraryViewMsg::LibrariesLoaded(backend_id, result)
+        })
     }
+
+    /// Open the given library, resetting any previously loaded items.
+    fn open(
+        &mut self,
+        backend_id: BackendId,
+        library: Library,
+    ) -> task::Task<LibraryViewMsg> {
+        let options = library_options::load(backend_id, &library.id).unwrap_or_default();
+        self.toolbar = LibraryToolbar::default();
+        self.toolbar.set_options(options);
+
+        let library_id = library.id.clone();
+        self.library = Some(ActiveLibrary {
+            backend_id,
+            library,
+        });
+        let shelves = self.refresh_shelves();
+
+        let local_options = library_items::filter_options(backend_id, &library_id);
+        let backend = registry::get(backend_id);
+        let filter_options = match (local_options, backend) {
+            (Some(available), _) => {
+                self.toolbar.set_available(available);
+                task::Task::none()
+            },
+            (None, Some(backend)) => {
+                let fut = {
+                    let library_id = library_id.clone();
+                    async move {
+                        backend
+                            .filter_options(library_id)
+                            .await
+                            .map_err(|err| err.to_string())
+                    }
+                };
+                task::Task::perform(fut, move |result| {
+                    LibraryViewMsg::FilterOptionsLoaded { library_id, result }
+                })
+            },
+            (None, None) => task::Task::none(),
+        };
+
+        task::Task::batch([shelves, filter_options, self.reload()])
+    }
+
+    /// Drop any loaded items and request the first page again, i.e. after the
+    /// sorting or filters change.
+    fn reload(&mut self) -> task::Task<LibraryViewMsg> {
+        self.items = PagedItems::default();
+        self.generation += 1;
+        self.random_seed = RandomState::new().hash_one(self.generation);
+        self.viewport.offset = 0.0;
+        self.request_page(0)
+    }
+
+    /// Re-read the loaded pages from the local copy of the library after it has
+    /// been synced, keeping the current scroll position.
+    fn refresh(&mut self) -> task::Task<LibraryViewMsg> {
+        let Some(active) = self.library.as_ref() else {
+            return task::Task::none();
+        };
+
+        if let Some(available) =
+            library_items::filter_options(active.backend_id, &active.library.id)
+        {
+            self.toolbar.set_available(available);
+        }
+
+        self.items.pages.clear();
+        self.items.inflight.clear();
+        self.generation += 1;
+        self.request_visible()
+    }
+
+    /// Reload the Continue Watching and Next Up shelves from the local copy.
+    fn refresh_shelves(&mut self) -> task::Task<LibraryViewMsg> {
+        let Some(active) = self.library.as_ref() else {
+            return task::Task::none();
+        };
+
+        let backend_id = active.backend_id;
+        let thresholds = playback_progress::load_thresholds();
+        let continue_watching = self
+            .continue_watching
+            .set_items(
+                backend_id,
+                playback_progress::continue_watching(
+                    backend_id,
+                    &thresholds,
+                    SHELF_LIMIT,
+                ),
+            )
+            .map(LibraryViewMsg::ContinueWatching);
+        let next_up = self
+            .next_up
+            .set_items(
+                backend_id,
+                playback_progress::next_up(backend_id, SHELF_LIMIT),
+            )
+            .map(LibraryViewMsg::NextUp);
+        task::Task::batch([continue_watching, next_up])
+    }
+
+    /// Show the details of the item, offering to resume it if it was partially
+    /// played.
+    fn open_detail(&mut self, item_id: ItemId) -> task::Task<LibraryViewMsg> {
+        let Some(active) = self.library.as_ref() else {
+            return task::Task::none();
+        };
+
+        let thresholds = playback_progress::load_thresholds();
+        let resume_position =
+            playback_progress::resume_point(active.backend_id, &item_id, &thresholds);
+        let (detail, task) = ItemDetail::open(
+            active.backend_id,
+            item_id.clone(),
+            self.item_name(&item_id),
+            resume_position,
+        );
+        self.item_detail = Some(detail);
+        task.map(LibraryViewMsg::ItemDetail)
+    }
+
+    /// Play an item of the active library's backend with the external player.
+    fn play_from(
+        &self,
+        item_id: ItemId,
+        start_position: Duration,
+        tracks: Option<TrackSelection>,
+        quality: Option<Quality>,
+    ) -> task::Task<LibraryViewMsg> {
+        let Some(active) = self.library.as_ref() else {
+            return task::Task::none();
+        };
+
+        self.play_item(active.backend_id, item_id, start_position, tracks, quality)
+    }
+
+    /// Play an item with the external player, using the preferred tracks and
+    /// quality setting unless `tracks` and `quality` are given.
+    fn play_item(
+        &self,
+        backend_id: BackendId,
+        item_id: ItemId,
+        start_position: Duration,
+        tracks: Option<TrackSelection>,
+        quality: Option<Quality>,
+    ) -> task::Task<LibraryViewMsg> {
+        // Both players would otherwise be heard at once.
+        music::send(PlayerCommand::Pause);
+
+        let title = self.item_name(&item_id);
+        let request = PlaybackRequest {
+            item_id,
+            start_position,
+            tracks,
+            quality,
+        };
+
+        let fut = playback::play(
+            backend_id,
+            request,
+            title,
+            player_settings::load(),
+            playback_progress::load_thresholds(),
+        );
+        task::Task::perform(fut, |result| {
+            LibraryViewMsg::PlaybackFinished(result.map_err(|err| err.to_string()))
+        })
+    }
+
+    fn item_name(&self, item_id: &str) -> String {
+        self.items
+            .find(item_id)
+            .map(|entry| &entry.item)
+            .or_else(|| self.continue_watching.find(item_id))
+            .or_else(|| self.next_up.find(item_id))
+            .map(|item| item.name.clone())
+            .unwrap_or_default()
+    }
+
+    fn is_active_library(&self, library_id: &str) -> bool {
+        self.library
+            .as_ref()
+            .is_some_and(|active| active.library.id == library_id)
+    }
+
+    /// Load the pages and artwork of the visible items.
+    fn request_visible(&mut self) -> task::Task<LibraryViewMsg> {
+        let pages = self.request_visible_pages();
+        task::Task::batch([pages, self.request_visible_images()])
+    }
+
+    fn request_visible_images(&self) -> task::Task<LibraryViewMsg> {
+        let items = self
+            .viewport
+            .visible_items(self.poster_size, self.items.total_items);
+        let tasks: Vec<_> = items
+            .filter_map(|index| self.items.get(index))
+            .filter_map(|entry| self.image_key(entry))
+            .filter(images::needs_load)
+            .map(|key| {
+                task::Task::perform(images::load(key), |_| LibraryViewMsg::ImageLoaded)
+            })
+            .collect();
+        task::Task::batch(tasks)
+    }
+
+    fn image_key(&self, entry: &GridEntry) -> Option<ImageKey> {
+        let active = self.library.as_ref()?;
+        Some(ImageKey::primary(
+            active.backend_id,
+            entry.item.id.clone(),
+            self.poster_size.dimensions(),
+        ))
+    }
+
+    fn request_visible_pages(&mut self) -> task::Task<LibraryViewMsg> {
+        let pages = self.viewport.visible_pages(
+            self.poster_size,
+            self.items.total_items,
+            PAGE_SIZE,
+        );
+        let tasks: Vec<_> = pages.map(|page| self.request_page(page)).collect();
+        task::Task::batch(tasks)
+    }
+
+    fn request_page(&mut self, page: usize) -> task::Task<LibraryViewMsg> {
+        let Some(active) = self.library.as_ref() else {
+            return task::Task::none();
+        };
+
+        if self.items.pages.contains_key(&page) || !self.items.inflight.insert(page) {
+            return task::Task::none();
+        }
+
+        let generation = self.generation;
+        let query = ItemQuery {
+            library_id: active.library.id.clone(),
+            start_index: page * PAGE_SIZE,
+            limit: PAGE_SIZE,
+            options: self.toolbar.options().clone(),
+            random_seed: self.random_seed,
+        };
+
+        let backend_id = active.backend_id;
+        let backend = registry::get(backend_id);
+        let fut = async move {
+            // Prefer the local copy once the library has been synced, the sync
+            // engine keeps it up to date in the background.
+            if let Some(item_page) =
+                library_items::query(backend_id, query.clone()).await
+            {
+                return Ok(item_page);
+            }
+
+            let Some(backend) = backend else {
+                return Err(format!("backend ({backend_id}) no longer exists"));
+            };
+            backend
+                .query_items(query)
+                .await
+                .map_err(|err| err.to_string())
+        };
+        task::Task::perform(fut, move |result| LibraryViewMsg::PageLoaded {
+            generation,
+            page,
+            result,
+        })
+    }
+}
+
+struct ActiveLibrary {
+    backend_id: BackendId,
+    library: Library,
+}
+
+/// The items of a library which have been loaded so far, split into pages.
+#[derive(Default)]
+struct PagedItems {
+    total_items: usize,
+    pages: HashMap<usize, Vec<GridEntry>>,
+    inflight: HashSet<usize>,
+}
+
+impl PagedItems {
+    fn get(&self, index: usize) -> Option<&GridEntry> {
+        self.pages.get(&(index / PAGE_SIZE))?.get(index % PAGE_SIZE)
+    }
+
+    fn find(&self, item_id: &str) -> Option<&GridEntry> {
+        self.pages
+            .values()
+            .flatten()
+            .find(|entry| entry.item.id == item_id)
+    }
+
+    fn insert_page(&mut self, page: usize, item_page: ItemPage) {
+        self.total_items = item_page.total_items;
+        let entries = item_page.items.into_iter().map(GridEntry::from).collect();
+        self.pages.insert(page, entries);
+    }
+}
+
+/// An item along with its pre-formatted display text.
+struct GridEntry {
+    item: ItemSummary,
+    subtext: String,
+}
+
+impl From<ItemSummary> for GridEntry {
+    fn from(item: ItemSummary) -> Self {
+        let subtext = item
+            .production_year
+            .map(|year| year.to_string())
+            .unwrap_or_default();
+        Self { item, subtext }
+    }
+}
+
+/// Returns the size of the grid's posters at the density.
+fn poster_size(density: Density) -> PosterSize {
+    match density {
+        Density::Compact => PosterSize::Small,
+        Density::Comfortable => PosterSize::Medium,
+        Density::Spacious => PosterSize::Large,
+    }
+}
+
+fn grid_card(
+    entry: &GridEntry,
+    artwork: Option<image::Handle>,
+    size: PosterSize,
+) -> Element<'_, LibraryViewMsg> {
+    let on_press = match entry.item.kind {
+        ItemKind::Movie | ItemKind::Episode => {
+            LibraryViewMsg::Play(entry.item.id.clone())
+        },
+        ItemKind::MusicAlbum | ItemKind::MusicArtist | ItemKind::Audio => {
+            LibraryViewMsg::PlayMusic(entry.item.id.clone(), entry.item.kind)
+        },
+        _ => LibraryViewMsg::OpenItem(entry.item.id.clone()),
+    };
+
+    let placeholder = entry.item.blurhash.as_deref().and_then(images::placeholder);
+    let artwork: Element<'_, LibraryViewMsg> = match (placeholder, artwork) {
+        (Some(placeholder), artwork) => {
+            image::poster_artwork(placeholder, artwork, size).into()
+        },
+        (None, Some(handle)) => image::poster(handle, size).into(),
+        (None, None) => image::poster_skeleton(size),
+    };
+
+    card::card(&entry.item.name, &entry.subtext, artwork, space(), on_press).into()
 }
@@ -1,16 +1,78 @@
+use std::collections::HashSet;
+
 use bluebottle_ui::{bar, button, spinner, text};
-use iced::widget::{column, container, row, space};
+use iced::widget::{column, container, progress_bar, row, space};
 use iced::{Center, Element, Length, padding, task};
 
+use crate::backends::{BackendId, registry};
+use crate::sync::{SyncEvent, SyncProgress, SyncStatus};
 use crate::{navigator, view};
 
 #[derive(Default)]
-pub struct LoadingScreen {}
+pub struct LoadingScreen {
+    /// The latest progress of each library, in the order they started syncing.
+    libraries: Vec<SyncProgress>,
+    /// The backends which have finished their first sync.
+    finished: HashSet<BackendId>,
+}
 
 #[derive(Clone)]
 pub enum LoadingMsg {
     NavigateLibrarySelect,
     NavigateSettings,
+    Sync(SyncEvent),
+}
+
+impl LoadingScreen {
+    /// Returns whether every registered backend has finished syncing.
+    pub fn is_complete(&self) -> bool {
+        registry::all()
+            .iter()
+            .all(|(backend_id, _)| self.finished.contains(backend_id))
+    }
+
+    fn record_progress(&mut self, progress: SyncProgress) {
+        let existing = self.libraries.iter_mut().find(|library| {
+            library.backend_id == progress.backend_id
+                && library.library_id == progress.library_id
+        });
+
+        match existing {
+            Some(library) => *library = progress,
+            None => self.libraries.push(progress),
+        }
+    }
+
+    fn view_library(progress: &SyncProgress) -> Element<'_, LoadingMsg> {
+        let status = match &progress.status {
+            SyncStatus::Running if progress.total_items == 0 => {
+                "Starting...".to_string()
+            },
+            SyncStatus::Running => {
+                format!("{} of {}", progress.synced_items, progress.total_items)
+            },
+            SyncStatus::Complete { .. } => "Done".to_string(),
+            SyncStatus::Failed(_) => "Failed, will retry later".to_string(),
+        };
+
+        let value = match progress.status {
+            SyncStatus::Running => progress.synced_items as f32,
+            _ => progress.total_items.max(1) as f32,
+        };
+        let total = progress.total_items.max(1) as f32;
+
+        column![
+            row![
+                text::label(progress.library_name.as_str()),
+                space().width(Length::Fill),
+                text::label(status),
+            ],
+            progress_bar(0.0..=total, value).girth(6),
+        ]
+        .spacing(4)
+        .width(360)
+        .into()
+    }
 }
 
 impl super::Screen<LoadingMsg> for LoadingScreen {
@@ -28,12 +90,32 @@ impl view::View<LoadingMsg> for LoadingScreen {
             LoadingMsg::NavigateSettings => {
                 navigator::navigate(navigator::ActiveScreen::Settings);
             },
+            LoadingMsg::Sync(SyncEvent::Progress(progress)) => {
+                self.record_progress(progress);
+            },
+            LoadingMsg::Sync(SyncEvent::Finished(backend_id)) => {
+                self.finished.insert(backend_id);
+
+                if self.is_complete()
+                    && navigator::active() == navigator::ActiveScreen::Loading
+                {
+                    navigator::navigate(navigator::ActiveScreen::LibraryView);
+                }
+            },
         };
 
         task::Task::none()
     }
 
     fn view(&self) -> Element<'_, LoadingMsg> {
+        let progress: Element<'_, LoadingMsg> = if self.libraries.is_empty() {
+            spinner::linear().into()
+        } else {
+            column(self.libraries.iter().map(Self::view_library))
+                .spacing(12)
+                .into()
+        };
+
         column![
             row![
                 bar::side(
@@ -58,7 +140,7 @@ impl view::View<LoadingMsg> for LoadingScreen {
                     column![
                         text::title(None, "Getting your library setup"),
                         text::paragraph("This will only take a few moments..."),
-                        spinner::linear(),
+                        progress,
                     ]
                     .spacing(16)
                     .align_x(Center)
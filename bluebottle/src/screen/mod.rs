@@ -6,6 +6,7 @@ use crate::view;
 pub mod library_select;
 pub mod library_view;
 pub mod loading;
+pub mod search;
 pub mod settings;
 pub mod setup;
 
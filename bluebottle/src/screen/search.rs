@@ -0,0 +1,335 @@
+use std::time::Duration;
+
+use bluebottle_ui::{button, pill, pill_box, scrollable, search, spinner, text};
+use iced::keyboard::{self, key};
+use iced::widget::{column, container, row, space};
+use iced::{Center, Element, Length, Subscription, task};
+
+use crate::backends::{BackendId, registry};
+use crate::models::media::{ItemId, ItemSummary};
+use crate::models::search::SearchResults;
+use crate::storage::{recent_searches, search_index};
+use crate::view;
+
+/// How long to wait after the user stops typing before searching the backends.
+const DEBOUNCE_DELAY: Duration = Duration::from_millis(300);
+/// The maximum number of results requested from each backend.
+const RESULT_LIMIT: usize = 60;
+
+#[derive(Default)]
+pub struct SearchScreen {
+    query: String,
+    /// Incremented on every change to the query, responses for an older
+    /// generation are stale and ignored.
+    generation: u64,
+    /// The number of backends which have not responded to the current search yet.
+    pending: usize,
+    results: SearchResults,
+    selected: Option<usize>,
+    recent_searches: Vec<String>,
+}
+
+#[derive(Clone)]
+pub enum SearchMsg {
+    QueryChanged(String),
+    Debounced(u64),
+    LocalResultsLoaded {
+        generation: u64,
+        items: Vec<ItemSummary>,
+    },
+    ResultsLoaded {
+        generation: u64,
+        backend_id: BackendId,
+        result: Result<Vec<ItemSummary>, String>,
+    },
+    SelectRecent(String),
+    ClearRecent,
+    SelectNext,
+    SelectPrevious,
+    Activate,
+    OpenItem(ItemId),
+}
+
+impl super::Screen<SearchMsg> for SearchScreen {
+    fn nav_descriptor(&self) -> &str {
+        "Search"
+    }
+}
+
+impl view::View<SearchMsg> for SearchScreen {
+    fn update(&mut self, message: SearchMsg) -> task::Task<SearchMsg> {
+        match message {
+            SearchMsg::QueryChanged(query) => return self.set_query(query),
+            SearchMsg::Debounced(generation) => {
+                if generation == self.generation {
+                    return self.search();
+                }
+            },
+            SearchMsg::LocalResultsLoaded { generation, items } => {
+                if generation == self.generation {
+                    self.results.extend(items);
+                }
+            },
+            SearchMsg::ResultsLoaded {
+                generation,
+                backend_id,
+                result,
+            } => {
+                if generation != self.generation {
+                    return task::Task::none();
+                }
+
+                self.pending = self.pending.saturating_sub(1);
+                match result {
+                    Ok(items) => self.results.extend(items),
+                    Err(err) => {
+                        tracing::error!(backend_id = %backend_id, error = %err, "search failed");
+                    },
+                }
+            },
+            SearchMsg::SelectRecent(query) => {
+                self.query = query;
+                self.generation += 1;
+                return task::Task::batch([self.search_local(), self.search()]);
+            },
+            SearchMsg::ClearRecent => {
+                recent_searches::clear();
+                self.recent_searches.clear();
+            },
+            SearchMsg::SelectNext => {
+                let len = self.results.len();
+                if len > 0 {
+                    self.selected = Some(self.selected.map_or(0, |i| (i + 1) % len));
+                }
+            },
+            SearchMsg::SelectPrevious => {
+                let len = self.results.len();
+                if len > 0 {
+                    self.selected =
+                        Some(self.selected.map_or(len - 1, |i| (i + len - 1) % len));
+                }
+            },
+            SearchMsg::Activate => {
+                let selected = self.selected.and_then(|index| self.results.get(index));
+                if let Some(item) = selected {
+                    let item_id = item.id.clone();
+                    return self.update(SearchMsg::OpenItem(item_id));
+                }
+                self.remember_query();
+            },
+            SearchMsg::OpenItem(item_id) => {
+                self.remember_query();
+                tracing::debug!(item_id = %item_id, "open item");
+            },
+        }
+
+        task::Task::none()
+    }
+
+    fn view(&self) -> Element<'_, SearchMsg> {
+        let input = container(search::search(
+            "Search movies, shows, people and music",
+            &self.query,
+            SearchMsg::QueryChanged,
+        ))
+        .max_width(640);
+
+        let content = if self.query.trim().is_empty() {
+            self.recent_searches_view()
+        } else {
+            self.results_view()
+        };
+
+        column![
+            container(input).width(Length::Fill).center_x(Length::Fill),
+            scrollable::scrollable(content).height(Length::Fill),
+        ]
+        .spacing(16)
+        .padding([16, 32])
+        .into()
+    }
+}
+
+impl SearchScreen {
+    /// Load the recent searches from storage.
+    pub fn load(&mut self) {
+        self.recent_searches = recent_searches::load();
+    }
+
+    /// Keyboard shortcuts for moving through and opening results.
+    pub fn subscription(&self) -> Subscription<SearchMsg> {
+        keyboard::listen().filter_map(|event| {
+            let keyboard::Event::KeyPressed { key, .. } = event else {
+                return None;
+            };
+
+            match key.as_ref() {
+                keyboard::Key::Named(key::Named::ArrowDown) => {
+                    Some(SearchMsg::SelectNext)
+                },
+                keyboard::Key::Named(key::Named::ArrowUp) => {
+                    Some(SearchMsg::SelectPrevious)
+                },
+                keyboard::Key::Named(key::Named::Enter) => Some(SearchMsg::Activate),
+                _ => None,
+            }
+        })
+    }
+
+    fn set_query(&mut self, query: String) -> task::Task<SearchMsg> {
+        self.query = query;
+        self.generation += 1;
+
+        let local = self.search_local();
+        if self.query.trim().is_empty() {
+            return local;
+        }
+
+        let generation = self.generation;
+        let debounce =
+            task::Task::perform(tokio::time::sleep(DEBOUNCE_DELAY), move |_| {
+                SearchMsg::Debounced(generation)
+            });
+        task::Task::batch([local, debounce])
+    }
+
+    /// Replace the results with matches from the local search index, these are
+    /// available almost instantly and while offline.
+    fn search_local(&mut self) -> task::Task<SearchMsg> {
+        self.reset_results();
+
+        let term = self.query.clone();
+        let generation = self.generation;
+        let fut = async move { search_index::search(&term, RESULT_LIMIT).await };
+        task::Task::perform(fut, move |items| SearchMsg::LocalResultsLoaded {
+            generation,
+            items: items.into_iter().map(|(_, item)| item).collect(),
+        })
+    }
+
+    /// Search every backend for the current query, adding any results not already
+    /// found by the local index.
+    fn search(&mut self) -> task::Task<SearchMsg> {
+        let term = self.query.trim().to_string();
+        if term.is_empty() {
+            return task::Task::none();
+        }
+
+        let generation = self.generation;
+        let tasks: Vec<_> = registry::all()
+            .into_iter()
+            .map(|(backend_id, backend)| {
+                let term = term.clone();
+                let fut = async move {
+                    backend
+                        .search(term, RESULT_LIMIT)
+                        .await
+                        .map_err(|err| err.to_string())
+                };
+                task::Task::perform(fut, move |result| SearchMsg::ResultsLoaded {
+                    generation,
+                    backend_id,
+                    result,
+                })
+            })
+            .collect();
+
+        self.pending = tasks.len();
+        task::Task::batch(tasks)
+    }
+
+    fn reset_results(&mut self) {
+        self.results.clear();
+        self.selected = None;
+        self.pending = 0;
+    }
+
+    fn remember_query(&mut self) {
+        let term = self.query.trim();
+        if term.is_empty() {
+            return;
+        }
+
+        recent_searches::record(term);
+        self.recent_searches
+            .retain(|existing| !existing.eq_ignore_ascii_case(term));
+        self.recent_searches.insert(0, term.to_string());
+        self.recent_searches
+            .truncate(recent_searches::MAX_RECENT_SEARCHES);
+    }
+
+    fn recent_searches_view(&self) -> Element<'_, SearchMsg> {
+        if self.recent_searches.is_empty() {
+            return text::paragraph("Start typing to search all of your libraries.")
+                .into();
+        }
+
+        let pills = self.recent_searches.iter().map(|term| {
+            pill::small(term, Some("history"))
+                .on_press(SearchMsg::SelectRecent(term.clone()))
+                .into()
+        });
+
+        column![
+            pill_box::pill_box("Recent Searches", pills),
+            button::standard("Clear", Some("delete"), false, SearchMsg::ClearRecent),
+        ]
+        .spacing(8)
+        .into()
+    }
+
+    fn results_view(&self) -> Element<'_, SearchMsg> {
+        if self.results.is_empty() {
+            if self.pending > 0 {
+                return spinner::linear().into();
+            }
+            return text::paragraph("No results found.").into();
+        }
+
+        let mut content = column![].spacing(16).width(Length::Fill);
+        let mut index = 0;
+
+        for (group, items) in self.results.groups() {
+            let entries = items.iter().map(|item| {
+                let selected = self.selected == Some(index);
+                index += 1;
+                result_row(item, group.icon(), selected)
+            });
+
+            content = content.push(
+                column![text::subheading(group.label()), column(entries).spacing(4)]
+                    .spacing(8),
+            );
+        }
+
+        if self.pending > 0 {
+            content = content.push(spinner::linear());
+        }
+
+        content.into()
+    }
+}
+
+fn result_row<'a>(
+    item: &'a ItemSummary,
+    icon: &'a str,
+    selected: bool,
+) -> Element<'a, SearchMsg> {
+    let year = item
+        .production_year
+        .map(|year| year.to_string())
+        .unwrap_or_default();
+
+    row![
+        button::standard(
+            &item.name,
+            Some(icon),
+            selected,
+            SearchMsg::OpenItem(item.id.clone())
+        ),
+        space().width(Length::Fill),
+        text::label(year),
+    ]
+    .align_y(Center)
+    .into()
+}
@@ -1,12 +1,93 @@
-use iced::{Element, task};
+use std::time::Duration;
 
-use crate::view;
+use bluebottle_ui::{button, input, pill, pill_box, scrollable, text};
+use iced::widget::{column, container, row, text as plain_text};
+use iced::{Center, Element, Length, task};
+
+use crate::backends::{BackendId, registry};
+use crate::models::media::{Library, LibraryKind};
+use crate::models::playback::PlayerSettings;
+use crate::models::profile::{Quality, QualitySetting};
+use crate::models::settings::{
+    AccentColor,
+    Appearance,
+    CacheSettings,
+    Density,
+    ThemeMode,
+    format_size,
+    format_ttl,
+};
+use crate::models::tracks::{SubtitleMode, TrackPreferences};
+use crate::storage::asset_cache::AssetCacheStats;
+use crate::storage::{
+    self,
+    appearance,
+    asset_cache,
+    cache_settings,
+    content_cache,
+    player_settings,
+    stream_quality,
+    track_preferences,
+};
+use crate::{sync, view};
+
+/// How long to wait for typing to pause before saving a text setting, so
+/// settings are not saved on every keystroke.
+const SAVE_DELAY: Duration = Duration::from_millis(500);
+
+/// The libraries of a backend, `None` while loading.
+type BackendLibraries = Option<Result<Vec<Library>, String>>;
 
 #[derive(Default)]
-pub struct SettingsScreen {}
+pub struct SettingsScreen {
+    appearance: Appearance,
+    libraries: Vec<(BackendId, BackendLibraries)>,
+    player: PlayerSettings,
+    player_args: String,
+    /// Incremented on every edit of a text setting, saves scheduled for an older
+    /// generation are superseded by a later one.
+    edit_generation: u64,
+    /// The backend whose user's preferences are edited.
+    backend_id: Option<BackendId>,
+    tracks: TrackPreferences,
+    quality: QualitySetting,
+    audio_language: String,
+    subtitle_language: String,
+    cache: CacheSettings,
+    /// The size of the asset cache, `None` while it is measured.
+    asset_cache_usage: Option<Result<AssetCacheStats, String>>,
+    /// The outcome of the last content cache clean up.
+    content_cache_status: Option<String>,
+    asset_limit_labels: Vec<String>,
+    asset_max_age_labels: Vec<String>,
+    content_ttl_labels: Vec<String>,
+}
 
 #[derive(Clone)]
-pub enum SettingsMsg {}
+pub enum SettingsMsg {
+    Theme(ThemeMode),
+    Accent(AccentColor),
+    Density(Density),
+    LibrariesLoaded(BackendId, Result<Vec<Library>, String>),
+    SyncNow(BackendId),
+    PlayerCommand(String),
+    PlayerArgs(String),
+    /// Save the text settings if they were not edited since.
+    SaveEdits(u64),
+    SubtitleMode(SubtitleMode),
+    AudioLanguage(String),
+    SubtitleLanguage(String),
+    Quality(QualitySetting),
+    AssetCacheLimit(u64),
+    AssetMaxAge(Duration),
+    ContentTtl(Duration),
+    AssetCacheMeasured(Result<AssetCacheStats, String>),
+    PruneContentCache,
+    PurgeContentCache,
+    ContentCacheCleaned(String),
+    /// Copy the text to the clipboard.
+    Copy(String),
+}
 
 impl super::Screen<SettingsMsg> for SettingsScreen {
     fn nav_descriptor(&self) -> &str {
@@ -15,11 +96,520 @@ impl super::Screen<SettingsMsg> for SettingsScreen {
 }
 
 impl view::View<SettingsMsg> for SettingsScreen {
-    fn update(&mut self, _message: SettingsMsg) -> task::Task<SettingsMsg> {
+    fn update(&mut self, message: SettingsMsg) -> task::Task<SettingsMsg> {
+        match message {
+            SettingsMsg::Theme(theme) => {
+                self.appearance.theme = theme;
+                return self.save_appearance();
+            },
+            SettingsMsg::Accent(accent) => {
+                self.appearance.accent = accent;
+                return self.save_appearance();
+            },
+            SettingsMsg::Density(density) => {
+                self.appearance.density = density;
+                return self.save_appearance();
+            },
+            SettingsMsg::LibrariesLoaded(backend_id, result) => {
+                if let Some((_, libraries)) =
+                    self.libraries.iter_mut().find(|(id, _)| *id == backend_id)
+                {
+                    *libraries = Some(result);
+                }
+            },
+            SettingsMsg::SyncNow(backend_id) => sync::request_sync(backend_id),
+            SettingsMsg::PlayerCommand(command) => {
+                self.player.command = command;
+                return self.schedule_save();
+            },
+            SettingsMsg::PlayerArgs(args) => {
+                self.player.args = args.split_whitespace().map(str::to_string).collect();
+                self.player_args = args;
+                return self.schedule_save();
+            },
+            SettingsMsg::SaveEdits(generation) => {
+                if generation == self.edit_generation {
+                    let player = player_settings::save_async(self.player.clone());
+                    return task::Task::batch([
+                        task::Task::future(player).discard(),
+                        self.save_tracks(),
+                    ]);
+                }
+            },
+            SettingsMsg::SubtitleMode(mode) => {
+                self.tracks.subtitle_mode = mode;
+                return self.save_tracks();
+            },
+            SettingsMsg::AudioLanguage(language) => {
+                self.tracks.audio_language = language_code(&language);
+                self.audio_language = language;
+                return self.schedule_save();
+            },
+            SettingsMsg::SubtitleLanguage(language) => {
+                self.tracks.subtitle_language = language_code(&language);
+                self.subtitle_language = language;
+                return self.schedule_save();
+            },
+            SettingsMsg::Quality(quality) => {
+                if let Some(backend_id) = self.backend_id {
+                    self.quality = quality;
+                    let save = stream_quality::save_async(backend_id, quality);
+                    return task::Task::future(save).discard();
+                }
+            },
+            SettingsMsg::AssetCacheLimit(limit) => {
+                self.cache.asset_cache_limit = limit;
+                return self.save_cache();
+            },
+            SettingsMsg::AssetMaxAge(max_age) => {
+                self.cache.asset_max_age = max_age;
+                return self.save_cache();
+            },
+            SettingsMsg::ContentTtl(ttl) => {
+                self.cache.content_ttl = ttl;
+                return task::Task::future(cache_settings::save_async(self.cache))
+                    .discard();
+            },
+            SettingsMsg::AssetCacheMeasured(result) => {
+                if let Err(err) = &result {
+                    tracing::warn!(error = %err, "failed to measure asset cache");
+                }
+                self.asset_cache_usage = Some(result);
+            },
+            SettingsMsg::PruneContentCache => {
+                let fut = async {
+                    let removed = tokio::task::spawn_blocking(content_cache::prune)
+                        .await
+                        .unwrap_or_default();
+                    format!("Removed {removed} expired responses.")
+                };
+                return task::Task::perform(fut, SettingsMsg::ContentCacheCleaned);
+            },
+            SettingsMsg::PurgeContentCache => {
+                let fut = async {
+                    let removed = tokio::task::spawn_blocking(content_cache::purge)
+                        .await
+                        .unwrap_or_default();
+                    format!("Removed {removed} cached responses.")
+                };
+                return task::Task::perform(fut, SettingsMsg::ContentCacheCleaned);
+            },
+            SettingsMsg::ContentCacheCleaned(status) => {
+                self.content_cache_status = Some(status);
+            },
+            SettingsMsg::Copy(content) => return iced::clipboard::write(content),
+        }
+
         task::Task::none()
     }
 
     fn view(&self) -> Element<'_, SettingsMsg> {
-        iced::widget::column![].into()
+        let content = column![
+            self.view_appearance(),
+            self.view_libraries(),
+            self.view_playback(),
+            self.view_caching(),
+            self.view_paths(),
+            self.view_about(),
+        ]
+        .spacing(32)
+        .padding(16)
+        .max_width(560);
+
+        scrollable::scrollable(container(content).center_x(Length::Fill)).into()
+    }
+}
+
+impl SettingsScreen {
+    /// Load the saved settings, along with the preferences of the first backend's
+    /// user.
+    pub fn load(&mut self) -> task::Task<SettingsMsg> {
+        self.appearance = appearance::load();
+        self.player = player_settings::load();
+        self.player_args = self.player.args.join(" ");
+        self.cache = cache_settings::load();
+        self.asset_limit_labels = CacheSettings::ASSET_CACHE_LIMITS
+            .into_iter()
+            .map(format_size)
+            .collect();
+        self.asset_max_age_labels = CacheSettings::ASSET_MAX_AGES
+            .into_iter()
+            .map(format_ttl)
+            .collect();
+        self.content_ttl_labels = CacheSettings::CONTENT_TTLS
+            .into_iter()
+            .map(format_ttl)
+            .collect();
+        self.content_cache_status = None;
+
+        self.backend_id = registry::first().map(|(backend_id, _)| backend_id);
+        if let Some(backend_id) = self.backend_id {
+            self.tracks = track_preferences::load(backend_id);
+            self.quality = stream_quality::load(backend_id);
+            self.audio_language = self.tracks.audio_language.clone().unwrap_or_default();
+            self.subtitle_language =
+                self.tracks.subtitle_language.clone().unwrap_or_default();
+        }
+
+        let backends = registry::all();
+        self.libraries = backends
+            .iter()
+            .map(|(backend_id, _)| (*backend_id, None))
+            .collect();
+        let libraries = backends.into_iter().map(|(backend_id, backend)| {
+            let fut =
+                async move { backend.libraries().await.map_err(|err| err.to_string()) };
+            task::Task::perform(fut, move |result| {
+                SettingsMsg::LibrariesLoaded(backend_id, result)
+            })
+        });
+
+        task::Task::batch(libraries.chain([self.measure_asset_cache()]))
+    }
+
+    fn save_appearance(&self) -> task::Task<SettingsMsg> {
+        task::Task::future(appearance::save_async(self.appearance)).discard()
+    }
+
+    fn save_tracks(&self) -> task::Task<SettingsMsg> {
+        let Some(backend_id) = self.backend_id else {
+            return task::Task::none();
+        };
+        let save = track_preferences::save_async(backend_id, self.tracks.clone());
+        task::Task::future(save).discard()
+    }
+
+    /// Save the text settings once typing pauses, superseding any save scheduled
+    /// for an earlier edit.
+    fn schedule_save(&mut self) -> task::Task<SettingsMsg> {
+        self.edit_generation += 1;
+        let generation = self.edit_generation;
+        task::Task::perform(tokio::time::sleep(SAVE_DELAY), move |_| {
+            SettingsMsg::SaveEdits(generation)
+        })
+    }
+
+    /// Save the cache settings, the janitor cleans the asset cache to the new
+    /// limits straight away and reports its size once done.
+    fn save_cache(&mut self) -> task::Task<SettingsMsg> {
+        self.asset_cache_usage = None;
+        task::Task::future(cache_settings::save_async(self.cache)).discard()
     }
+
+    fn measure_asset_cache(&mut self) -> task::Task<SettingsMsg> {
+        self.asset_cache_usage = None;
+        let fut = async {
+            tokio::task::spawn_blocking(asset_cache::stats)
+                .await
+                .unwrap_or_else(|err| Err(err.to_string()))
+        };
+        task::Task::perform(fut, SettingsMsg::AssetCacheMeasured)
+    }
+
+    fn view_appearance(&self) -> Element<'_, SettingsMsg> {
+        let themes = ThemeMode::ALL.into_iter().map(|theme| {
+            choice(
+                theme.label(),
+                self.appearance.theme == theme,
+                SettingsMsg::Theme(theme),
+            )
+        });
+        let accents = AccentColor::ALL.into_iter().map(|accent| {
+            choice(
+                accent.label(),
+                self.appearance.accent == accent,
+                SettingsMsg::Accent(accent),
+            )
+        });
+        let densities = Density::ALL.into_iter().map(|density| {
+            choice(
+                density.label(),
+                self.appearance.density == density,
+                SettingsMsg::Density(density),
+            )
+        });
+
+        column![
+            text::title(Some("palette"), "Appearance"),
+            pill_box::pill_box("Theme", themes),
+            pill_box::pill_box("Accent", accents),
+            pill_box::pill_box("Density", densities),
+            text::paragraph("Density sets the size of the posters in libraries."),
+        ]
+        .spacing(12)
+        .into()
+    }
+
+    fn view_libraries(&self) -> Element<'_, SettingsMsg> {
+        let mut content =
+            column![text::title(Some("video_library"), "Libraries")].spacing(12);
+        if self.libraries.is_empty() {
+            content = content.push(text::paragraph("No servers have been added yet."));
+        }
+
+        for (backend_id, libraries) in &self.libraries {
+            let mut backend = column![text::label(backend_id.to_string())].spacing(4);
+            match libraries {
+                None => backend = backend.push(text::paragraph("Loading libraries...")),
+                Some(Err(err)) => {
+                    backend = backend.push(text::paragraph(format!(
+                        "Failed to load libraries: {err}"
+                    )))
+                },
+                Some(Ok(libraries)) => {
+                    for library in libraries {
+                        backend = backend.push(
+                            row![
+                                plain_text(&library.name).width(Length::Fill),
+                                text::paragraph(library_kind_label(library.kind)),
+                            ]
+                            .align_y(Center),
+                        );
+                    }
+                },
+            }
+            backend = backend.push(button::standard(
+                "Sync now",
+                Some("sync"),
+                false,
+                SettingsMsg::SyncNow(*backend_id),
+            ));
+            content = content.push(backend);
+        }
+
+        content.into()
+    }
+
+    fn view_playback(&self) -> Element<'_, SettingsMsg> {
+        let mut content = column![
+            text::title(Some("play_circle"), "Playback"),
+            input::text_input(
+                "Player command, i.e. mpv",
+                &self.player.command,
+                SettingsMsg::PlayerCommand,
+            ),
+            input::text_input(
+                "Extra player arguments, i.e. --fullscreen",
+                &self.player_args,
+                SettingsMsg::PlayerArgs,
+            ),
+            text::paragraph(
+                "The player must accept mpv's command line options and JSON IPC."
+            ),
+        ]
+        .spacing(12);
+
+        if self.backend_id.is_none() {
+            return content.into();
+        }
+
+        let settings = std::iter::once(QualitySetting::Auto)
+            .chain(Quality::ALL.map(QualitySetting::Fixed))
+            .map(|quality| {
+                choice(
+                    quality.label(),
+                    self.quality == quality,
+                    SettingsMsg::Quality(quality),
+                )
+            });
+        let modes = SubtitleMode::ALL.into_iter().map(|mode| {
+            choice(
+                mode.label(),
+                self.tracks.subtitle_mode == mode,
+                SettingsMsg::SubtitleMode(mode),
+            )
+        });
+
+        content = content.extend([
+            pill_box::pill_box("Quality", settings),
+            text::paragraph(
+                "Auto streams the original files on fast connections and limits the \
+                 bitrate otherwise, based on a speed test against the server.",
+            )
+            .into(),
+            pill_box::pill_box("Show subtitles", modes),
+            input::text_input(
+                "Preferred audio language, i.e. jpn",
+                &self.audio_language,
+                SettingsMsg::AudioLanguage,
+            )
+            .into(),
+            input::text_input(
+                "Preferred subtitle language, i.e. eng",
+                &self.subtitle_language,
+                SettingsMsg::SubtitleLanguage,
+            )
+            .into(),
+        ]);
+        content.into()
+    }
+
+    fn view_caching(&self) -> Element<'_, SettingsMsg> {
+        let limits = CacheSettings::ASSET_CACHE_LIMITS
+            .into_iter()
+            .zip(&self.asset_limit_labels)
+            .map(|(limit, label)| {
+                choice(
+                    label,
+                    self.cache.asset_cache_limit == limit,
+                    SettingsMsg::AssetCacheLimit(limit),
+                )
+            });
+        let max_ages = CacheSettings::ASSET_MAX_AGES
+            .into_iter()
+            .zip(&self.asset_max_age_labels)
+            .map(|(max_age, label)| {
+                choice(
+                    label,
+                    self.cache.asset_max_age == max_age,
+                    SettingsMsg::AssetMaxAge(max_age),
+                )
+            });
+        let ttls = CacheSettings::CONTENT_TTLS
+            .into_iter()
+            .zip(&self.content_ttl_labels)
+            .map(|(ttl, label)| {
+                choice(
+                    label,
+                    self.cache.content_ttl == ttl,
+                    SettingsMsg::ContentTtl(ttl),
+                )
+            });
+
+        let usage = match &self.asset_cache_usage {
+            None => "Measuring the image cache...".to_string(),
+            Some(Ok(stats)) => {
+                let mut usage = format!(
+                    "{} images are using {} of {}.",
+                    stats.entries,
+                    format_size(stats.total_bytes),
+                    format_size(self.cache.asset_cache_limit),
+                );
+                if let Some(report) = stats.last_clean {
+                    usage.push_str(&format!(
+                        " The last clean up removed {} images, freeing {}.",
+                        report.removed,
+                        format_size(report.bytes_freed),
+                    ));
+                }
+                usage
+            },
+            Some(Err(_)) => "The size of the image cache is unknown.".to_string(),
+        };
+
+        let mut content = column![
+            text::title(Some("storage"), "Caching"),
+            pill_box::pill_box("Image cache size", limits),
+            pill_box::pill_box("Remove images unused for", max_ages),
+            text::paragraph(usage),
+            pill_box::pill_box("Keep server responses for", ttls),
+            row![
+                button::standard(
+                    "Remove expired",
+                    Some("auto_delete"),
+                    false,
+                    SettingsMsg::PruneContentCache,
+                ),
+                button::standard(
+                    "Clear all",
+                    Some("delete"),
+                    false,
+                    SettingsMsg::PurgeContentCache,
+                ),
+            ]
+            .spacing(8),
+        ]
+        .spacing(12);
+        if let Some(status) = &self.content_cache_status {
+            content = content.push(text::paragraph(status));
+        }
+
+        content.into()
+    }
+
+    fn view_paths(&self) -> Element<'_, SettingsMsg> {
+        let paths = storage::paths();
+        let entries = [
+            ("Configuration", paths.config_dir()),
+            ("Data", paths.data_dir()),
+            ("Image cache", paths.asset_cache_dir()),
+        ]
+        .map(|(label, path)| {
+            let path = path.display().to_string();
+            row![
+                column![text::label(label), text::paragraph(path.clone())]
+                    .spacing(2)
+                    .width(Length::Fill),
+                button::icon("content_copy", false, SettingsMsg::Copy(path)),
+            ]
+            .spacing(8)
+            .align_y(Center)
+            .into()
+        });
+
+        column![text::title(Some("folder"), "Storage")]
+            .extend(entries)
+            .spacing(12)
+            .into()
+    }
+
+    fn view_about(&self) -> Element<'_, SettingsMsg> {
+        let diagnostics = self.diagnostics();
+        column![
+            text::title(Some("info"), "About"),
+            text::paragraph(diagnostics.clone()),
+            button::standard(
+                "Copy diagnostics",
+                Some("content_copy"),
+                false,
+                SettingsMsg::Copy(diagnostics),
+            ),
+        ]
+        .spacing(12)
+        .into()
+    }
+
+    /// Returns the details worth including in a bug report.
+    fn diagnostics(&self) -> String {
+        let usage = match &self.asset_cache_usage {
+            Some(Ok(stats)) => format_size(stats.total_bytes),
+            _ => "unknown".to_string(),
+        };
+        format!(
+            "Bluebottle {}\nPlatform: {} {}\nServers: {}\nImage cache: {usage}",
+            env!("CARGO_PKG_VERSION"),
+            std::env::consts::OS,
+            std::env::consts::ARCH,
+            self.libraries.len(),
+        )
+    }
+}
+
+/// A pill picking a setting, marked if it is the current choice.
+fn choice(
+    label: &str,
+    selected: bool,
+    message: SettingsMsg,
+) -> Element<'_, SettingsMsg> {
+    if selected {
+        pill::small(label, Some("check")).into()
+    } else {
+        pill::small(label, None).on_press(message).into()
+    }
+}
+
+fn library_kind_label(kind: LibraryKind) -> &'static str {
+    match kind {
+        LibraryKind::Movies => "Movies",
+        LibraryKind::TvShows => "TV Shows",
+        LibraryKind::Music => "Music",
+        LibraryKind::Mixed => "Mixed",
+    }
+}
+
+/// Normalise a language code entered by the user, an empty code means there is
+/// no preference.
+fn language_code(language: &str) -> Option<String> {
+    let language = language.trim();
+    (!language.is_empty()).then(|| language.to_ascii_lowercase())
 }
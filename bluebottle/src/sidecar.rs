@@ -0,0 +1,213 @@
+//! Reads and writes the sidecar subtitle files stored next to the media files of
+//! libraries on this machine, named `<media name>.<language>.<format>` so both the
+//! backend and players pick them up.
+
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// The subtitle formats stored as sidecar files.
+pub const FORMATS: &[&str] = &["srt", "ass", "ssa"];
+
+#[derive(Debug, Clone, PartialEq)]
+/// A subtitle file stored next to a media file.
+pub struct Sidecar {
+    pub path: PathBuf,
+    /// The language code in the file name, if any.
+    pub language: Option<String>,
+    /// The lowercase file extension, i.e. `srt`.
+    pub format: String,
+}
+
+impl Sidecar {
+    /// Returns the file name of the sidecar.
+    pub fn file_name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+}
+
+/// Returns whether the media file can be read on this machine, in which case
+/// subtitles can be stored next to it.
+pub fn is_local(media_path: &Path) -> bool {
+    media_path.is_absolute() && media_path.is_file()
+}
+
+/// Returns the sidecar subtitles of a media file, sorted by file name.
+pub fn find(media_path: &Path) -> io::Result<Vec<Sidecar>> {
+    let (Some(directory), Some(stem)) = (media_path.parent(), file_stem(media_path))
+    else {
+        return Ok(Vec::new());
+    };
+
+    let mut sidecars = Vec::new();
+    for entry in std::fs::read_dir(directory)? {
+        let path = entry?.path();
+        if let Some(sidecar) = parse(&stem, path) {
+            sidecars.push(sidecar);
+        }
+    }
+    sidecars.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(sidecars)
+}
+
+/// Write subtitles next to a media file, numbering the file if a sidecar with
+/// the same language and format already exists.
+///
+/// The language comes from the backend, so it is rejected unless it only holds
+/// ASCII letters, digits and `-` and cannot escape the media file's directory.
+pub fn write(
+    media_path: &Path,
+    language: Option<&str>,
+    format: &str,
+    data: &[u8],
+) -> io::Result<Sidecar> {
+    let (Some(directory), Some(stem)) = (media_path.parent(), file_stem(media_path))
+    else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "media path has no file name",
+        ));
+    };
+    let format = format.to_ascii_lowercase();
+    if !FORMATS.contains(&format.as_str()) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("unsupported subtitle format: {format}"),
+        ));
+    }
+
+    if let Some(language) = language
+        && !is_language_code(language)
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid subtitle language: {language:?}"),
+        ));
+    }
+
+    let base = match language {
+        Some(language) => format!("{stem}.{}", language.to_ascii_lowercase()),
+        None => stem,
+    };
+    let mut path = directory.join(format!("{base}.{format}"));
+    let mut number = 2;
+    // Creating the file fails if it exists, rather than checking first, so an
+    // existing sidecar is never overwritten.
+    let mut file = loop {
+        match std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+        {
+            Ok(file) => break file,
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                path = directory.join(format!("{base}.{number}.{format}"));
+                number += 1;
+            },
+            Err(err) => return Err(err),
+        }
+    };
+
+    file.write_all(data)?;
+    Ok(Sidecar {
+        path,
+        language: language.map(str::to_ascii_lowercase),
+        format,
+    })
+}
+
+/// Returns whether the language can be used in a sidecar file name.
+fn is_language_code(language: &str) -> bool {
+    !language.is_empty()
+        && language
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+fn file_stem(media_path: &Path) -> Option<String> {
+    media_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+}
+
+/// Parse the path as a sidecar of the media file with the stem, the parts
+/// between the stem and the format may hold a language and flags like `forced`.
+fn parse(stem: &str, path: PathBuf) -> Option<Sidecar> {
+    let name = path.file_name()?.to_str()?;
+    let rest = name.strip_prefix(stem)?.strip_prefix('.')?;
+    let (tags, format) = match rest.rsplit_once('.') {
+        Some((tags, format)) => (Some(tags), format),
+        None => (None, rest),
+    };
+    let format = format.to_ascii_lowercase();
+    if !FORMATS.contains(&format.as_str()) || !path.is_file() {
+        return None;
+    }
+
+    let language = tags
+        .into_iter()
+        .flat_map(|tags| tags.split('.'))
+        .find(|tag| {
+            (2..=3).contains(&tag.len()) && tag.chars().all(|c| c.is_ascii_alphabetic())
+        })
+        .map(str::to_ascii_lowercase);
+
+    Some(Sidecar {
+        path,
+        language,
+        format,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_and_write() {
+        let dir = tempfile::tempdir().unwrap();
+        let media_path = dir.path().join("Episode 1.mkv");
+        std::fs::write(&media_path, b"").unwrap();
+        std::fs::write(dir.path().join("Episode 1.srt"), b"").unwrap();
+        std::fs::write(dir.path().join("Episode 1.JPN.forced.ass"), b"").unwrap();
+        std::fs::write(dir.path().join("Episode 1.nfo"), b"").unwrap();
+        std::fs::write(dir.path().join("Episode 10.eng.srt"), b"").unwrap();
+        assert!(is_local(&media_path));
+
+        let sidecars = find(&media_path).unwrap();
+        assert_eq!(
+            sidecars,
+            vec![
+                Sidecar {
+                    path: dir.path().join("Episode 1.JPN.forced.ass"),
+                    language: Some("jpn".into()),
+                    format: "ass".into(),
+                },
+                Sidecar {
+                    path: dir.path().join("Episode 1.srt"),
+                    language: None,
+                    format: "srt".into(),
+                },
+            ],
+        );
+
+        let sidecar = write(&media_path, Some("eng"), "SRT", b"1").unwrap();
+        assert_eq!(sidecar.file_name(), "Episode 1.eng.srt");
+        let sidecar = write(&media_path, Some("eng"), "srt", b"2").unwrap();
+        assert_eq!(sidecar.file_name(), "Episode 1.eng.2.srt");
+        assert_eq!(std::fs::read(&sidecar.path).unwrap(), b"2");
+        assert_eq!(find(&media_path).unwrap().len(), 4);
+
+        assert!(write(&media_path, None, "sub", b"").is_err());
+        assert!(write(&media_path, Some("pt-BR"), "srt", b"").is_ok());
+        for language in ["../../escape", "en/us", "", "en.forced"] {
+            let err = write(&media_path, Some(language), "srt", b"").unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+        assert_eq!(find(&media_path).unwrap().len(), 5);
+        assert!(!is_local(&dir.path().join("missing.mkv")));
+    }
+}
@@ -0,0 +1,18 @@
+//! Persists how the app looks.
+
+use super::settings::Setting;
+use crate::models::settings::Appearance;
+
+/// The appearance of the app.
+pub static APPEARANCE: Setting<Appearance> =
+    Setting::durable("appearance", Appearance::default);
+
+/// Load the appearance, falling back to the defaults if none is saved.
+pub fn load() -> Appearance {
+    APPEARANCE.entry().load()
+}
+
+/// Persist the appearance, waiting for it to be written.
+pub async fn save_async(appearance: Appearance) {
+    APPEARANCE.entry().save_async(&appearance).await;
+}
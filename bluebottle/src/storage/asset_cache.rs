@@ -1,82 +1,293 @@
+//! Caches assets fetched from backends, i.e. posters, as files on disk.
+//!
+//! Every asset is tracked in an index within the relaxed database, recording its
+//! size and when it was last used, so the cache can be pruned without listing
+//! the directory or trusting file access times.
+//!
+//! Writers hold the cache lock shared while the janitor holds it exclusively, so
+//! an asset is never pruned while it is being written.
+
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::str::FromStr;
-use std::time::UNIX_EPOCH;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, UNIX_EPOCH};
 
 use arrayvec::ArrayString;
+use parking_lot::{Mutex, RwLock};
 use snafu::ResultExt;
 
+use crate::models::settings::CacheSettings;
+
 type AssetId = ArrayString<64>;
 
+/// The extension of assets which are still being written.
+const PARTIAL_EXTENSION: &str = "part";
+
+static CACHE_LOCK: RwLock<()> = RwLock::new(());
+/// Keeps the partial files of concurrent writers of the same asset apart.
+static WRITE_COUNTER: AtomicU64 = AtomicU64::new(0);
+static LAST_CLEAN: Mutex<Option<CleanReport>> = Mutex::new(None);
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+/// The size of the asset cache.
+pub struct AssetCacheStats {
+    /// The number of cached assets.
+    pub entries: usize,
+    /// The total size of the cached assets in bytes.
+    pub total_bytes: u64,
+    /// The outcome of the last time the cache was cleaned, if it has been.
+    pub last_clean: Option<CleanReport>,
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+/// The outcome of cleaning the asset cache.
+pub struct CleanReport {
+    /// When the clean up finished in milliseconds.
+    pub cleaned_at: i64,
+    /// The number of assets removed.
+    pub removed: usize,
+    /// The number of bytes freed.
+    pub bytes_freed: u64,
+}
+
+/// An asset tracked by the cache index.
+pub(super) struct IndexedAsset {
+    pub asset_id: String,
+    pub size_bytes: u64,
+    /// When the asset was last read or written in milliseconds.
+    pub last_accessed_at: i64,
+}
+
 /// Try retrieve a cached entry with the given path.
 pub fn try_get(path: &str) -> Option<Vec<u8>> {
-    let asset_id: AssetId = blake3::hash(path.as_bytes()).to_hex();
-    let cache_path = super::directory::paths().asset_cache_dir();
-    let path = cache_path.join(asset_id);
-    std::fs::read(&path).ok()
+    let asset_id = asset_id(path);
+    let data = std::fs::read(file_path(&asset_id)).ok()?;
+    touch(asset_id);
+    Some(data)
+}
+
+/// Read a cached entry without marking it as used, i.e. when exporting it.
+pub(super) fn read(path: &str) -> Option<Vec<u8>> {
+    std::fs::read(file_path(&asset_id(path))).ok()
+}
+
+/// Returns the location of a cached entry on disk, if it exists.
+///
+/// This is used to hand assets to other processes, i.e. the desktop's media
+/// controls.
+pub fn try_get_file(path: &str) -> Option<PathBuf> {
+    let asset_id = asset_id(path);
+    let path = file_path(&asset_id);
+    if !path.is_file() {
+        return None;
+    }
+    touch(asset_id);
+    Some(path)
 }
 
 /// Try insert a new asset into the cache directory.
+///
+/// The asset is written to a partial file first, so readers never see a
+/// partially written asset.
 pub fn insert(path: &str, data: &[u8]) {
-    let asset_id: AssetId = blake3::hash(path.as_bytes()).to_hex();
-    let cache_path = super::directory::paths().asset_cache_dir();
-    let path = cache_path.join(asset_id);
-    if let Err(e) = std::fs::write(path, data) {
+    let asset_id = asset_id(path);
+    let _guard = CACHE_LOCK.read();
+
+    let write_id = WRITE_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let partial_path = super::directory::paths()
+        .asset_cache_dir()
+        .join(format!("{asset_id}.{write_id}.{PARTIAL_EXTENSION}"));
+    let result = std::fs::write(&partial_path, data)
+        .and_then(|_| std::fs::rename(&partial_path, file_path(&asset_id)));
+    if let Err(e) = result {
         tracing::warn!(error = %e, "failed to write asset to cache");
+        let _ = std::fs::remove_file(&partial_path);
+        return;
     }
+
+    // The state actor runs ops in order, so the entry is recorded before the
+    // janitor, which waits on the lock, can read the index.
+    let size_bytes = data.len() as u64;
+    let path = path.to_string();
+    super::submit_relaxed_state(move |state| {
+        let accessed_at = super::now();
+        if let Err(err) = state.upsert_asset_cache_entry(
+            &asset_id,
+            Some(&path),
+            size_bytes,
+            accessed_at,
+        ) {
+            tracing::error!(error = %err, "failed to index cached asset");
+        }
+    });
 }
 
-/// Prune the cache to be up to the given capacity.
+/// Remove any assets which have not been used within the max age, then the
+/// least recently used assets until the cache fits within its size limit.
+pub fn clean(settings: &CacheSettings) -> Result<CleanReport, String> {
+    let _guard = CACHE_LOCK.write();
+
+    let entries = list_indexed()?;
+    let expire_before = super::now() - settings.asset_max_age.as_millis() as i64;
+    let (expired, bytes_freed) =
+        select_for_removal(&entries, settings.asset_cache_limit, expire_before);
+
+    let mut removed = Vec::with_capacity(expired.len());
+    for asset_id in expired {
+        tracing::debug!(asset_id = %asset_id, "removing cached asset");
+
+        let path = super::directory::paths().asset_cache_dir().join(&asset_id);
+        match std::fs::remove_file(&path) {
+            Ok(()) => removed.push(asset_id),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                removed.push(asset_id)
+            },
+            Err(err) => {
+                tracing::warn!(asset_id = %asset_id, error = %err, "failed to remove cached asset");
+            },
+        }
+    }
+
+    let n_removed = removed.len();
+    super::with_relaxed_state(move |state| {
+        state
+            .remove_asset_cache_entries(&removed)
+            .map_err(|err| err.to_string())
+    })?;
+
+    let report = CleanReport {
+        cleaned_at: super::now(),
+        removed: n_removed,
+        bytes_freed,
+    };
+    *LAST_CLEAN.lock() = Some(report);
+    Ok(report)
+}
+
+/// Bring the index in line with the cache directory, i.e. after the app exited
+/// before the index was written.
 ///
-/// Returns the number of entries removed.
-pub fn prune_to(target_size: u64) -> Result<usize, snafu::Whatever> {
-    let mut all_entries = list_all_entries()?;
-    all_entries.sort_unstable_by_key(|entry| access_time(&entry.metadata));
-
-    let total_size: u64 = all_entries.iter().map(|entry| entry.metadata.len()).sum();
-    let delta_size = total_size.saturating_sub(target_size);
-    if delta_size == 0 {
-        return Ok(0);
+/// Assets missing from the index are added as last used when they were
+/// modified, entries of missing assets are removed along with any partial
+/// files left behind.
+pub fn reconcile() -> Result<(), String> {
+    let _guard = CACHE_LOCK.write();
+
+    let mut on_disk = list_files().map_err(|err| err.to_string())?;
+    let mut missing = Vec::new();
+    for entry in list_indexed()? {
+        let Some((size_bytes, accessed_at)) = on_disk.get_mut(entry.asset_id.as_str())
+        else {
+            missing.push(entry.asset_id);
+            continue;
+        };
+
+        if *size_bytes == entry.size_bytes {
+            on_disk.remove(entry.asset_id.as_str());
+        } else {
+            // Keep when the asset was last used, only its size is corrected.
+            *accessed_at = entry.last_accessed_at;
+        }
+    }
+
+    if missing.is_empty() && on_disk.is_empty() {
+        return Ok(());
     }
 
-    let mut removed = 0;
+    tracing::info!(
+        missing = missing.len(),
+        changed = on_disk.len(),
+        "reconciling asset cache index",
+    );
+
+    super::with_relaxed_state(move |state| {
+        state
+            .remove_asset_cache_entries(&missing)
+            .map_err(|err| err.to_string())?;
+        for (asset_id, (size_bytes, accessed_at)) in on_disk {
+            state
+                .upsert_asset_cache_entry(&asset_id, None, size_bytes, accessed_at)
+                .map_err(|err| err.to_string())?;
+        }
+        Ok(())
+    })
+}
+
+/// Returns the size of the cache.
+pub fn stats() -> Result<AssetCacheStats, String> {
+    let (entries, total_bytes) = super::with_relaxed_state(|state| {
+        state.asset_cache_usage().map_err(|err| err.to_string())
+    })?;
+    Ok(AssetCacheStats {
+        entries,
+        total_bytes,
+        last_clean: *LAST_CLEAN.lock(),
+    })
+}
+
+fn list_indexed() -> Result<Vec<IndexedAsset>, String> {
+    super::with_relaxed_state(|state| {
+        state
+            .list_asset_cache_entries()
+            .map_err(|err| err.to_string())
+    })
+}
+
+/// Pick the assets to remove, returning their IDs and total size.
+///
+/// The entries must be ordered least recently used first.
+fn select_for_removal(
+    entries: &[IndexedAsset],
+    size_limit: u64,
+    expire_before: i64,
+) -> (Vec<String>, u64) {
+    let mut remaining: u64 = entries.iter().map(|entry| entry.size_bytes).sum();
+    let mut removed = Vec::new();
     let mut bytes_freed = 0;
-    for entry in all_entries {
-        if bytes_freed > delta_size {
+
+    for entry in entries {
+        if remaining <= size_limit && entry.last_accessed_at >= expire_before {
             break;
         }
+        removed.push(entry.asset_id.clone());
+        remaining -= entry.size_bytes;
+        bytes_freed += entry.size_bytes;
+    }
 
-        tracing::debug!(asset_id = %entry.id, "removing cached asset");
+    (removed, bytes_freed)
+}
 
-        match std::fs::remove_file(&entry.path) {
-            Ok(_) => {
-                removed += 1;
-                bytes_freed += entry.metadata.len();
-            },
-            Err(err) => {
-                tracing::warn!(error = %entry.id, err = %err, "failed to remove cached asset");
-            },
+/// Mark the asset as used without waiting on the index.
+fn touch(asset_id: AssetId) {
+    super::submit_relaxed_state(move |state| {
+        if let Err(err) = state.touch_asset_cache_entry(&asset_id, super::now()) {
+            tracing::warn!(error = %err, "failed to mark cached asset as used");
         }
-    }
+    });
+}
 
-    Ok(removed)
+fn asset_id(path: &str) -> AssetId {
+    blake3::hash(path.as_bytes()).to_hex()
 }
 
-/// Returns the total size of the cached entries.
-pub fn usage() -> Result<u64, snafu::Whatever> {
-    let total = list_all_entries()?
-        .into_iter()
-        .map(|entry| entry.metadata.len())
-        .sum();
-    Ok(total)
+/// Returns the location of the asset in the cache directory.
+fn file_path(asset_id: &AssetId) -> PathBuf {
+    super::directory::paths()
+        .asset_cache_dir()
+        .join(asset_id.as_str())
 }
 
-/// List all entries within the cache along with their metadata.
-fn list_all_entries() -> Result<Vec<AssetEntry>, snafu::Whatever> {
+/// List the assets within the cache directory with their size and modified time,
+/// removing any partial files.
+///
+/// This must only be called while the cache lock is held exclusively.
+fn list_files() -> Result<HashMap<String, (u64, i64)>, snafu::Whatever> {
     let cache_directory = super::directory::paths().asset_cache_dir();
     let entries = std::fs::read_dir(cache_directory)
         .whatever_context("failed to read cache directory")?;
 
-    let mut all_entries = Vec::new();
+    let mut files = HashMap::new();
     for file in entries {
         let entry = match file {
             Ok(entry) => entry,
@@ -87,6 +298,12 @@ fn list_all_entries() -> Result<Vec<AssetEntry>, snafu::Whatever> {
         };
 
         let path = entry.path();
+        if path.extension().is_some_and(|ext| ext == PARTIAL_EXTENSION) {
+            tracing::debug!(path = %path.display(), "removing partial asset");
+            let _ = std::fs::remove_file(&path);
+            continue;
+        }
+
         let file_name = entry.file_name();
         let id = match AssetId::from_str(&file_name.to_string_lossy()) {
             Ok(asset_id) => asset_id,
@@ -104,35 +321,36 @@ fn list_all_entries() -> Result<Vec<AssetEntry>, snafu::Whatever> {
             continue;
         }
 
-        all_entries.push(AssetEntry { id, metadata, path });
+        files.insert(id.to_string(), (metadata.len(), modified_time(&metadata)));
     }
 
-    Ok(all_entries)
-}
-
-struct AssetEntry {
-    id: AssetId,
-    metadata: std::fs::Metadata,
-    path: PathBuf,
+    Ok(files)
 }
 
-fn access_time(metadata: &std::fs::Metadata) -> u64 {
+fn modified_time(metadata: &std::fs::Metadata) -> i64 {
     metadata
-        .accessed()
-        .map(|time| time.duration_since(UNIX_EPOCH).unwrap().as_millis() as u64)
-        .unwrap_or(0)
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .unwrap_or(Duration::ZERO)
+        .as_millis() as i64
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::storage::test_utils::temp_storage;
 
-    #[test]
-    fn test_insert() {
-        let tmp_dir = tempfile::tempdir().unwrap();
-        crate::storage::directory::init_paths(Some(tmp_dir.path().to_path_buf()))
-            .unwrap();
+    fn indexed(asset_id: &str, size_bytes: u64, last_accessed_at: i64) -> IndexedAsset {
+        IndexedAsset {
+            asset_id: asset_id.to_string(),
+            size_bytes,
+            last_accessed_at,
+        }
+    }
 
+    #[rstest::rstest]
+    fn test_insert(_temp_storage: tempfile::TempDir) {
         insert("test1.txt", b"hello world 1");
         insert("test2.txt", b"hello world 2");
 
@@ -140,38 +358,52 @@ mod tests {
         let content2 = try_get("test2.txt").unwrap();
         assert_eq!(content1, b"hello world 1");
         assert_eq!(content2, b"hello world 2");
+
+        let file = try_get_file("test1.txt").unwrap();
+        assert_eq!(std::fs::read(file).unwrap(), b"hello world 1");
+        assert!(try_get_file("missing.txt").is_none());
     }
 
-    #[test]
-    fn test_size_count() {
-        let tmp_dir = tempfile::tempdir().unwrap();
-        crate::storage::directory::init_paths(Some(tmp_dir.path().to_path_buf()))
-            .unwrap();
+    #[rstest::rstest]
+    fn test_reconcile(_temp_storage: tempfile::TempDir) {
+        // Left behind by an app which exited before the index was written.
+        let cache_dir = crate::storage::paths().asset_cache_dir();
+        std::fs::write(file_path(&asset_id("unindexed.txt")), b"hello world").unwrap();
+        std::fs::write(cache_dir.join("stale.0.part"), b"partial").unwrap();
 
-        insert("test1.txt", b"hello world 1");
-        insert("test2.txt", b"hello world 2");
+        let before = stats().unwrap();
+        reconcile().unwrap();
+        assert!(!cache_dir.join("stale.0.part").exists());
 
-        let total_size = usage().unwrap();
-        assert_eq!(total_size, 26);
+        let after = stats().unwrap();
+        assert_eq!(after.entries, before.entries + 1);
+        assert_eq!(after.total_bytes, before.total_bytes + 11);
     }
 
     #[test]
-    fn test_prune_to() {
-        let tmp_dir = tempfile::tempdir().unwrap();
-        crate::storage::directory::init_paths(Some(tmp_dir.path().to_path_buf()))
-            .unwrap();
-
-        insert("test1.txt", b"hello world 1");
-        std::thread::sleep(std::time::Duration::from_millis(200));
-        insert("test2.txt", b"hello world 2");
-
-        let total_size = usage().unwrap();
-        assert_eq!(total_size, 26);
-
-        let n_removed = prune_to(14).unwrap();
-        assert_eq!(n_removed, 1);
-
-        assert!(try_get("test1.txt").is_none());
-        assert!(try_get("test2.txt").is_some());
+    fn test_select_for_removal() {
+        let entries = [
+            indexed("oldest", 10, 100),
+            indexed("older", 10, 200),
+            indexed("newer", 10, 300),
+            indexed("newest", 10, 400),
+        ];
+
+        let (removed, freed) = select_for_removal(&entries, 40, 0);
+        assert!(removed.is_empty());
+        assert_eq!(freed, 0);
+
+        // The least recently used assets go first until the cache fits.
+        let (removed, freed) = select_for_removal(&entries, 25, 0);
+        assert_eq!(removed, vec!["oldest", "older"]);
+        assert_eq!(freed, 20);
+
+        // Expired assets go even when the cache fits.
+        let (removed, _) = select_for_removal(&entries, 40, 300);
+        assert_eq!(removed, vec!["oldest", "older"]);
+
+        let (removed, freed) = select_for_removal(&entries, 0, 0);
+        assert_eq!(removed.len(), 4);
+        assert_eq!(freed, 40);
     }
 }
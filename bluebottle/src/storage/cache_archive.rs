@@ -0,0 +1,673 @@
+//! Exports the cached content of a backend to a single archive, and imports it
+//! again, so a fresh install can browse a library before it has synced.
+//!
+//! An archive starts with [MAGIC] and the format version, followed by sections
+//! made up of:
+//!
+//! - The kind of section as a single byte.
+//! - The length of the payload as a little endian `u64`.
+//! - The payload.
+//! - The blake3 hash of the kind byte and payload.
+//!
+//! The first section is the [Manifest], the last holds the number of sections
+//! before it so truncated archives are rejected. The whole archive is verified
+//! before anything is imported.
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use rusqlite::ToSql;
+use rusqlite::types::{ToSqlOutput, Value, ValueRef};
+use snafu::{FromString, OptionExt, ResultExt};
+
+use super::migrations::RELAXED;
+use super::relaxed::RelaxedStateStorage;
+use crate::backends::BackendId;
+
+/// Identifies a file as a cache archive.
+const MAGIC: &[u8; 8] = b"BBCACHE\0";
+const FORMAT_VERSION: u32 = 1;
+/// Sections are read into memory, so their size is limited to reject corrupt
+/// lengths before allocating.
+const MAX_SECTION_LEN: u64 = 1 << 30;
+
+/// The tables holding the cached content of a backend, with the columns left
+/// out of the archive.
+pub(super) const ARCHIVED_TABLES: [(&str, &[&str]); 4] = [
+    ("backend_content_cache", &[]),
+    ("library_items", &[]),
+    ("library_sync_state", &[]),
+    // Row IDs are local to each database.
+    ("search_items", &["id"]),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// What was exported or imported.
+pub struct ArchiveSummary {
+    /// The backend the content belongs to.
+    pub backend_id: BackendId,
+    /// The number of table rows.
+    pub rows: usize,
+    /// The number of cached assets.
+    pub assets: usize,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[repr(u8)]
+enum SectionKind {
+    Manifest = 1,
+    Table = 2,
+    Asset = 3,
+    End = 4,
+}
+
+impl SectionKind {
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            1 => Some(Self::Manifest),
+            2 => Some(Self::Table),
+            3 => Some(Self::Asset),
+            4 => Some(Self::End),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, serde_derive::Serialize, serde_derive::Deserialize)]
+/// Describes the content of an archive.
+struct Manifest {
+    /// The backend the content was exported from.
+    backend_id: BackendId,
+    /// The version of the relaxed schema the rows were exported from.
+    schema_version: u32,
+    /// When the archive was exported in milliseconds.
+    exported_at: i64,
+}
+
+#[derive(
+    Debug, Clone, PartialEq, serde_derive::Serialize, serde_derive::Deserialize,
+)]
+/// The rows of a table belonging to a backend.
+pub(super) struct TableRows {
+    pub table: String,
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<SqlValue>>,
+}
+
+#[derive(
+    Debug, Clone, PartialEq, serde_derive::Serialize, serde_derive::Deserialize,
+)]
+/// A value of a table column.
+pub(super) enum SqlValue {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+    Blob(#[serde(with = "blob")] Vec<u8>),
+}
+
+impl From<Value> for SqlValue {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Null => Self::Null,
+            Value::Integer(value) => Self::Integer(value),
+            Value::Real(value) => Self::Real(value),
+            Value::Text(value) => Self::Text(value),
+            Value::Blob(value) => Self::Blob(value),
+        }
+    }
+}
+
+impl ToSql for SqlValue {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        let value = match self {
+            Self::Null => ValueRef::Null,
+            Self::Integer(value) => ValueRef::Integer(*value),
+            Self::Real(value) => ValueRef::Real(*value),
+            Self::Text(value) => ValueRef::Text(value.as_bytes()),
+            Self::Blob(value) => ValueRef::Blob(value),
+        };
+        Ok(ToSqlOutput::Borrowed(value))
+    }
+}
+
+/// Export the cached content of the backend to an archive at `output`.
+///
+/// The archive is written to a partial file first, so an interrupted export
+/// never leaves a truncated archive behind.
+pub fn export(
+    backend_id: BackendId,
+    output: &Path,
+) -> Result<ArchiveSummary, snafu::Whatever> {
+    let mut partial_path = output.as_os_str().to_owned();
+    partial_path.push(".part");
+    let partial_path = PathBuf::from(partial_path);
+
+    let result = write_archive(backend_id, &partial_path).and_then(|summary| {
+        std::fs::rename(&partial_path, output)
+            .whatever_context("move archive into place")?;
+        Ok(summary)
+    });
+    if result.is_err() {
+        let _ = std::fs::remove_file(&partial_path);
+    }
+    result
+}
+
+/// Import an archive written by [export], replacing the cached content of the
+/// backend.
+///
+/// The content may be imported for a backend other than the one it was exported
+/// from, i.e. the same server configured on another install.
+pub fn import(
+    input: &Path,
+    backend_id: BackendId,
+) -> Result<ArchiveSummary, snafu::Whatever> {
+    let (manifest, tables) = verify(input)?;
+    let source_id = manifest.backend_id;
+
+    let tables_len = tables.len();
+    let rows =
+        with_relaxed(move |state| state.import_backend_rows(backend_id, &tables))?;
+    tracing::debug!(tables = tables_len, rows, "imported cached tables");
+
+    let mut reader = open(input)?;
+    let mut assets = 0;
+    while let Some((kind, payload)) = reader.next_section()? {
+        if kind != SectionKind::Asset {
+            continue;
+        }
+
+        let (path, data) = split_asset(&payload)?;
+        let path = remap_path(path, source_id, backend_id);
+        super::asset_cache::insert(&path, data);
+        assets += 1;
+    }
+
+    Ok(ArchiveSummary {
+        backend_id,
+        rows,
+        assets,
+    })
+}
+
+fn write_archive(
+    backend_id: BackendId,
+    path: &Path,
+) -> Result<ArchiveSummary, snafu::Whatever> {
+    let file = File::create(path).whatever_context("create archive")?;
+    let mut writer = ArchiveWriter::new(BufWriter::new(file))?;
+
+    let manifest = Manifest {
+        backend_id,
+        schema_version: RELAXED.latest_version(),
+        exported_at: super::now(),
+    };
+    let payload = rmp_serde::to_vec(&manifest).whatever_context("serialize manifest")?;
+    writer.write_section(SectionKind::Manifest, &[&payload])?;
+
+    let mut rows = 0;
+    for (table, skipped) in ARCHIVED_TABLES {
+        let dump = with_relaxed(move |state| {
+            state.export_backend_rows(backend_id, table, skipped)
+        })?;
+        rows += dump.rows.len();
+
+        let payload = rmp_serde::to_vec(&dump)
+            .with_whatever_context(|_| format!("serialize {table} rows"))?;
+        writer.write_section(SectionKind::Table, &[&payload])?;
+    }
+
+    let paths = with_relaxed(move |state| state.list_backend_asset_paths(backend_id))?;
+    let mut assets = 0;
+    for path in paths {
+        // The asset may have been removed since it was indexed.
+        let Some(data) = super::asset_cache::read(&path) else {
+            continue;
+        };
+
+        let path_len = (path.len() as u32).to_le_bytes();
+        writer
+            .write_section(SectionKind::Asset, &[&path_len, path.as_bytes(), &data])?;
+        assets += 1;
+    }
+
+    let file = writer
+        .finish()?
+        .into_inner()
+        .map_err(|err| err.into_error())
+        .whatever_context("flush archive")?;
+    file.sync_all().whatever_context("sync archive")?;
+
+    Ok(ArchiveSummary {
+        backend_id,
+        rows,
+        assets,
+    })
+}
+
+/// Check every section of the archive, returning its manifest and tables.
+fn verify(input: &Path) -> Result<(Manifest, Vec<TableRows>), snafu::Whatever> {
+    let mut reader = open(input)?;
+
+    let manifest = match reader.next_section()? {
+        Some((SectionKind::Manifest, payload)) => {
+            rmp_serde::from_slice::<Manifest>(&payload)
+                .whatever_context("deserialize manifest")?
+        },
+        _ => snafu::whatever!("archive does not start with a manifest"),
+    };
+    if manifest.schema_version != RELAXED.latest_version() {
+        snafu::whatever!(
+            "archive was exported from schema version {}, expected {}",
+            manifest.schema_version,
+            RELAXED.latest_version(),
+        );
+    }
+
+    let mut tables = Vec::new();
+    while let Some((kind, payload)) = reader.next_section()? {
+        match kind {
+            SectionKind::Table => {
+                let dump = rmp_serde::from_slice(&payload)
+                    .whatever_context("deserialize table rows")?;
+                tables.push(dump);
+            },
+            SectionKind::Asset => {
+                split_asset(&payload)?;
+            },
+            SectionKind::Manifest | SectionKind::End => {
+                snafu::whatever!("archive contains an unexpected {kind:?} section");
+            },
+        }
+    }
+
+    Ok((manifest, tables))
+}
+
+fn open(input: &Path) -> Result<ArchiveReader<BufReader<File>>, snafu::Whatever> {
+    let file = File::open(input).whatever_context("open archive")?;
+    ArchiveReader::new(BufReader::new(file))
+}
+
+/// Split an asset section into the asset path and its data.
+fn split_asset(payload: &[u8]) -> Result<(&str, &[u8]), snafu::Whatever> {
+    let (path_len, rest) = payload
+        .split_first_chunk::<4>()
+        .whatever_context("asset section is truncated")?;
+    let (path, data) = rest
+        .split_at_checked(u32::from_le_bytes(*path_len) as usize)
+        .whatever_context("asset section is truncated")?;
+    let path = std::str::from_utf8(path).whatever_context("asset path is not UTF-8")?;
+    Ok((path, data))
+}
+
+/// Replace the backend ID within an asset path, as assets are cached under the
+/// ID of the backend they were fetched from.
+fn remap_path(path: &str, from: BackendId, to: BackendId) -> String {
+    let (from, to) = (from.to_string(), to.to_string());
+    path.split('/')
+        .map(|segment| {
+            if segment == from {
+                to.as_str()
+            } else {
+                segment
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Run an op on the relaxed state, converting its error to a string as
+/// [snafu::Whatever] cannot be sent back from the state actor.
+fn with_relaxed<F, T>(op: F) -> Result<T, snafu::Whatever>
+where
+    F: FnOnce(&RelaxedStateStorage) -> Result<T, snafu::Whatever> + Send + 'static,
+    T: Send + 'static,
+{
+    super::with_relaxed_state(move |state| op(state).map_err(|err| err.to_string()))
+        .map_err(snafu::Whatever::without_source)
+}
+
+struct ArchiveWriter<W> {
+    inner: W,
+    sections: u64,
+}
+
+impl<W: Write> ArchiveWriter<W> {
+    fn new(mut inner: W) -> Result<Self, snafu::Whatever> {
+        inner
+            .write_all(MAGIC)
+            .and_then(|_| inner.write_all(&FORMAT_VERSION.to_le_bytes()))
+            .whatever_context("write archive header")?;
+        Ok(Self { inner, sections: 0 })
+    }
+
+    /// Write a section with the payload made up of the given parts.
+    fn write_section(
+        &mut self,
+        kind: SectionKind,
+        parts: &[&[u8]],
+    ) -> Result<(), snafu::Whatever> {
+        let len: usize = parts.iter().map(|part| part.len()).sum();
+        if len as u64 > MAX_SECTION_LEN {
+            snafu::whatever!("{kind:?} section is too large to archive");
+        }
+
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&[kind as u8]);
+        for part in parts {
+            hasher.update(part);
+        }
+
+        let result = self
+            .inner
+            .write_all(&[kind as u8])
+            .and_then(|_| self.inner.write_all(&(len as u64).to_le_bytes()))
+            .and_then(|_| parts.iter().try_for_each(|part| self.inner.write_all(part)))
+            .and_then(|_| self.inner.write_all(hasher.finalize().as_bytes()));
+        result.whatever_context("write archive section")?;
+
+        self.sections += 1;
+        Ok(())
+    }
+
+    /// Write the end section and flush the archive.
+    fn finish(mut self) -> Result<W, snafu::Whatever> {
+        let sections = self.sections.to_le_bytes();
+        self.write_section(SectionKind::End, &[&sections])?;
+        self.inner.flush().whatever_context("flush archive")?;
+        Ok(self.inner)
+    }
+}
+
+struct ArchiveReader<R> {
+    inner: R,
+    sections: u64,
+    finished: bool,
+}
+
+impl<R: Read> ArchiveReader<R> {
+    fn new(mut inner: R) -> Result<Self, snafu::Whatever> {
+        let mut header = [0; MAGIC.len() + 4];
+        inner
+            .read_exact(&mut header)
+            .whatever_context("archive is truncated")?;
+
+        let (magic, version) = header.split_at(MAGIC.len());
+        if magic != MAGIC {
+            snafu::whatever!("file is not a cache archive");
+        }
+        let version = u32::from_le_bytes(version.try_into().unwrap());
+        if version != FORMAT_VERSION {
+            snafu::whatever!("unsupported archive format version {version}");
+        }
+
+        Ok(Self {
+            inner,
+            sections: 0,
+            finished: false,
+        })
+    }
+
+    /// Read and verify the next section, returning `None` once the end section
+    /// has been read.
+    fn next_section(
+        &mut self,
+    ) -> Result<Option<(SectionKind, Vec<u8>)>, snafu::Whatever> {
+        if self.finished {
+            return Ok(None);
+        }
+
+        let mut header = [0; 9];
+        self.inner
+            .read_exact(&mut header)
+            .whatever_context("archive is truncated")?;
+        let kind = SectionKind::from_byte(header[0])
+            .with_whatever_context(|| format!("unknown section kind {}", header[0]))?;
+        let len = u64::from_le_bytes(header[1..].try_into().unwrap());
+        if len > MAX_SECTION_LEN {
+            snafu::whatever!("{kind:?} section is too large");
+        }
+
+        let mut payload = vec![0; len as usize];
+        let mut hash = [0; blake3::OUT_LEN];
+        self.inner
+            .read_exact(&mut payload)
+            .and_then(|_| self.inner.read_exact(&mut hash))
+            .whatever_context("archive is truncated")?;
+
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&[kind as u8]);
+        hasher.update(&payload);
+        if hasher.finalize() != blake3::Hash::from_bytes(hash) {
+            snafu::whatever!("{kind:?} section {} is corrupt", self.sections);
+        }
+
+        if kind != SectionKind::End {
+            self.sections += 1;
+            return Ok(Some((kind, payload)));
+        }
+
+        let sections = <[u8; 8]>::try_from(payload.as_slice())
+            .ok()
+            .map(u64::from_le_bytes);
+        if sections != Some(self.sections) {
+            snafu::whatever!("archive is missing sections");
+        }
+        if self.inner.read(&mut [0]).whatever_context("read archive")? != 0 {
+            snafu::whatever!("archive has data after the end section");
+        }
+
+        self.finished = true;
+        Ok(None)
+    }
+}
+
+/// Serializes blobs as msgpack binary rather than an array of integers.
+mod blob {
+    use serde::de::{Deserializer, Error, Visitor};
+    use serde::ser::Serializer;
+
+    pub fn serialize<S: Serializer>(
+        value: &[u8],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(value)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<u8>, D::Error> {
+        deserializer.deserialize_byte_buf(BlobVisitor)
+    }
+
+    struct BlobVisitor;
+
+    impl<'de> Visitor<'de> for BlobVisitor {
+        type Value = Vec<u8>;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("a byte array")
+        }
+
+        fn visit_bytes<E: Error>(self, value: &[u8]) -> Result<Self::Value, E> {
+            Ok(value.to_vec())
+        }
+
+        fn visit_byte_buf<E: Error>(self, value: Vec<u8>) -> Result<Self::Value, E> {
+            Ok(value)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::models::media::{ItemKind, ItemSummary};
+    use crate::models::query::ItemQuery;
+    use crate::models::sync::ItemMetadata;
+    use crate::storage::search_index::{self, SearchDocument};
+    use crate::storage::test_utils::temp_storage;
+    use crate::storage::{asset_cache, content_cache, library_items};
+
+    fn movie(id: &str) -> ItemMetadata {
+        ItemMetadata {
+            summary: ItemSummary {
+                id: id.to_string(),
+                kind: ItemKind::Movie,
+                name: id.to_string(),
+                production_year: Some(2001),
+                blurhash: None,
+            },
+            parent_id: None,
+            series_id: None,
+            season_number: None,
+            episode_number: None,
+            sort_name: id.to_string(),
+            original_name: None,
+            people: vec!["Someone".to_string()],
+            genres: Vec::new(),
+            studios: Vec::new(),
+            official_rating: None,
+            community_rating: Some(7.5),
+            runtime_secs: Some(6000),
+            premiere_date: None,
+            date_added: None,
+            date_last_saved: None,
+            played: false,
+            playback_position_secs: 0,
+            last_played_at: None,
+            favourite: false,
+        }
+    }
+
+    fn store_library(backend_id: BackendId) {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let documents = ["first", "second"].map(|id| SearchDocument {
+            backend_id,
+            item: movie(id).summary,
+            original_name: None,
+            people: Vec::new(),
+        });
+        search_index::index(documents.to_vec());
+
+        runtime.block_on(async {
+            let items = vec![movie("first"), movie("second")];
+            library_items::store(backend_id, "movies".to_string(), items, 1)
+                .await
+                .unwrap();
+            let sync_state = library_items::LibrarySyncState {
+                cursor: None,
+                last_sync_at: 1,
+                last_full_sync_at: 1,
+            };
+            library_items::save_sync_state(backend_id, "movies".to_string(), sync_state)
+                .await
+                .unwrap();
+        });
+    }
+
+    fn item_ids(backend_id: BackendId) -> Option<Vec<String>> {
+        let query = ItemQuery {
+            library_id: "movies".to_string(),
+            start_index: 0,
+            limit: 10,
+            options: Default::default(),
+            random_seed: 0,
+        };
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let page = runtime.block_on(library_items::query(backend_id, query))?;
+        Some(page.items.into_iter().map(|item| item.id).collect())
+    }
+
+    #[rstest::rstest]
+    fn test_export_and_import(temp_storage: tempfile::TempDir) {
+        let backend_id = BackendId::now_v7();
+        store_library(backend_id);
+        content_cache::insert(
+            backend_id,
+            "/libraries",
+            &"libraries",
+            Some(Duration::from_secs(60)),
+        );
+        let asset_path = format!("images/{backend_id}/first/primary/10x10");
+        asset_cache::insert(&asset_path, b"poster");
+
+        let archive_path = temp_storage.path().join("cache.bbcache");
+        let exported = export(backend_id, &archive_path).unwrap();
+        assert_eq!(exported.backend_id, backend_id);
+        assert_eq!(exported.rows, 6);
+        assert_eq!(exported.assets, 1);
+
+        let imported_id = BackendId::now_v7();
+        let imported = import(&archive_path, imported_id).unwrap();
+        assert_eq!(imported.backend_id, imported_id);
+        assert_eq!(imported.rows, exported.rows);
+        assert_eq!(imported.assets, 1);
+
+        let expected = vec!["first".to_string(), "second".to_string()];
+        assert_eq!(item_ids(imported_id), Some(expected.clone()));
+        let entry: content_cache::CacheEntry<String> =
+            content_cache::try_get(imported_id, "/libraries").unwrap();
+        assert_eq!(entry.value, "libraries");
+        let asset_path = format!("images/{imported_id}/first/primary/10x10");
+        assert_eq!(asset_cache::try_get(&asset_path).unwrap(), b"poster");
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let results = runtime.block_on(search_index::search("second", 10));
+        assert!(
+            results
+                .iter()
+                .any(|(id, item)| *id == imported_id && item.id == "second")
+        );
+
+        // Importing again replaces the rows rather than duplicating them.
+        import(&archive_path, imported_id).unwrap();
+        assert_eq!(item_ids(imported_id), Some(expected));
+    }
+
+    #[rstest::rstest]
+    fn test_import_rejects_corrupt_archive(temp_storage: tempfile::TempDir) {
+        let backend_id = BackendId::now_v7();
+        store_library(backend_id);
+
+        let archive_path = temp_storage.path().join("cache.bbcache");
+        export(backend_id, &archive_path).unwrap();
+        let archive = std::fs::read(&archive_path).unwrap();
+
+        let mut corrupt = archive.clone();
+        let middle = corrupt.len() / 2;
+        corrupt[middle] ^= 0xFF;
+        std::fs::write(&archive_path, &corrupt).unwrap();
+        let imported_id = BackendId::now_v7();
+        assert!(import(&archive_path, imported_id).is_err());
+
+        std::fs::write(&archive_path, &archive[..archive.len() - 1]).unwrap();
+        assert!(import(&archive_path, imported_id).is_err());
+        assert_eq!(item_ids(imported_id), None);
+    }
+
+    #[test]
+    fn test_remap_path() {
+        let from = BackendId::now_v7();
+        let to = BackendId::now_v7();
+        assert_eq!(
+            remap_path(&format!("images/{from}/item/primary/10x10"), from, to),
+            format!("images/{to}/item/primary/10x10"),
+        );
+        assert_eq!(
+            remap_path(&format!("{from}/item/primary"), from, to),
+            format!("{to}/item/primary"),
+        );
+    }
+}
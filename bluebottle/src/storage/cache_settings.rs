@@ -0,0 +1,48 @@
+//! Persists the limits of the caches kept on disk.
+
+use super::settings::Setting;
+use crate::models::settings::CacheSettings;
+
+/// The limits of the caches.
+pub static CACHE_SETTINGS: Setting<CacheSettings> =
+    Setting::durable("cache_settings", CacheSettings::default);
+
+/// Load the cache settings, falling back to the defaults if none are saved.
+pub fn load() -> CacheSettings {
+    CACHE_SETTINGS.entry().load()
+}
+
+/// Load the cache settings from within an async context, falling back to the
+/// defaults if none are saved.
+pub async fn load_async() -> CacheSettings {
+    CACHE_SETTINGS.entry().load_async().await
+}
+
+/// Persist the cache settings, waiting for them to be written.
+pub async fn save_async(settings: CacheSettings) {
+    CACHE_SETTINGS.entry().save_async(&settings).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::storage::test_utils::temp_storage;
+
+    #[rstest::rstest]
+    fn test_save_and_load(_temp_storage: tempfile::TempDir) {
+        assert_eq!(load(), CacheSettings::default());
+
+        let settings = CacheSettings {
+            asset_cache_limit: 512,
+            content_ttl: Duration::from_secs(60),
+            asset_max_age: Duration::from_secs(3600),
+        };
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(save_async(settings));
+        assert_eq!(load(), settings);
+    }
+}
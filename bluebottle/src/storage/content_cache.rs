@@ -1,17 +1,74 @@
 //! Caches content responses from backends to minimise network requests.
+//!
+//! Entries are fresh for a while after they are cached, then stale until they
+//! expire. Stale entries can still be served while they are revalidated with
+//! the backend using their [Validators].
 
 use std::time::Duration;
 
 use crate::backends::BackendId;
 
-const DEFAULT_TTL: Duration = Duration::from_secs(7 * 24 * 3600); // 7 days
-
 /// A retrieved cache entry.
 pub struct CacheEntry<T> {
     /// The cached value.
     pub value: T,
     /// How long until the entry is expired.
     pub expires_in: Duration,
+    /// How long until the entry is stale and should be revalidated.
+    pub fresh_for: Duration,
+    /// Identifies the version of the entry when revalidating it.
+    pub validators: Validators,
+}
+
+impl<T> CacheEntry<T> {
+    /// Returns whether the entry should be revalidated with the backend.
+    pub fn is_stale(&self) -> bool {
+        self.fresh_for.is_zero()
+    }
+
+    /// Returns whether the entry has expired, and should only be used if the
+    /// backend cannot be reached.
+    pub fn is_expired(&self) -> bool {
+        self.expires_in.is_zero()
+    }
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+/// Identifies the version of a cached response, sent with conditional requests
+/// so the backend only responds with content if it changed.
+pub struct Validators {
+    /// The `ETag` header of the response.
+    pub etag: Option<String>,
+    /// The `Last-Modified` header of the response.
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    /// Returns `true` if the response cannot be revalidated.
+    pub fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+/// How long a response is cached for.
+pub struct CachePolicy {
+    /// How long the response is used without revalidating it.
+    pub fresh_for: Duration,
+    /// How long the response may be served while it is revalidated once it is
+    /// stale.
+    pub stale_for: Duration,
+    pub validators: Validators,
+}
+
+impl CachePolicy {
+    /// A policy keeping the response fresh for `ttl`, after which it expires.
+    pub fn ttl(ttl: Duration) -> Self {
+        Self {
+            fresh_for: ttl,
+            ..Self::default()
+        }
+    }
 }
 
 /// Attempt to get a cached response from the cache.
@@ -20,33 +77,93 @@ where
     T: serde::de::DeserializeOwned,
 {
     let cache_key = blake3::hash(path.as_bytes()).to_hex();
-    let content = super::with_relaxed_state(move |state| {
+    let entry = super::with_relaxed_state(move |state| {
         state.get_content_cache_entry(backend_id, &cache_key).ok()
-    });
+    })?;
 
-    content.and_then(|(buffer, expires_in)| {
-        let value = rmp_serde::from_slice(&buffer).ok()?;
-        Some(CacheEntry { value, expires_in })
+    let value = rmp_serde::from_slice(&entry.value).ok()?;
+    Some(CacheEntry {
+        value,
+        expires_in: entry.expires_in,
+        fresh_for: entry.fresh_for,
+        validators: entry.validators,
     })
 }
 
-/// Insert a new content response into the cache.
+/// Insert a new content response into the cache, expiring after the TTL set in
+/// the [CacheSettings](crate::models::settings::CacheSettings) if none is given.
 pub fn insert<T>(backend_id: BackendId, path: &str, content: &T, ttl: Option<Duration>)
 where
     T: serde::Serialize,
 {
-    let ttl = ttl.unwrap_or(DEFAULT_TTL);
+    let ttl = ttl.unwrap_or_else(|| super::cache_settings::load().content_ttl);
     let content = rmp_serde::to_vec(content).unwrap();
     let cache_key = blake3::hash(path.as_bytes()).to_hex();
     super::with_relaxed_state(move |state| {
+        let policy = CachePolicy::ttl(ttl);
         if let Err(err) =
-            state.add_content_cache_entry(backend_id, &cache_key, content, ttl)
+            state.add_content_cache_entry(backend_id, &cache_key, content, &policy)
         {
             tracing::error!(error = %err, "failed to content cache");
         }
     });
 }
 
+/// Attempt to get a cached response body from the cache.
+pub async fn try_get_body(
+    backend_id: BackendId,
+    url: &str,
+) -> Option<CacheEntry<Vec<u8>>> {
+    let cache_key = blake3::hash(url.as_bytes()).to_hex();
+    super::with_relaxed_state_async(move |state| {
+        state.get_content_cache_entry(backend_id, &cache_key).ok()
+    })
+    .await
+}
+
+/// Insert a response body into the cache, replacing any existing entry.
+pub async fn insert_body(
+    backend_id: BackendId,
+    url: &str,
+    body: Vec<u8>,
+    policy: CachePolicy,
+) {
+    let cache_key = blake3::hash(url.as_bytes()).to_hex();
+    super::with_relaxed_state_async(move |state| {
+        if let Err(err) =
+            state.add_content_cache_entry(backend_id, &cache_key, body, &policy)
+        {
+            tracing::error!(error = %err, "failed to content cache");
+        }
+    })
+    .await
+}
+
+/// Renew a cached response body the backend confirmed is unchanged.
+pub async fn renew_body(backend_id: BackendId, url: &str, policy: CachePolicy) {
+    let cache_key = blake3::hash(url.as_bytes()).to_hex();
+    super::with_relaxed_state_async(move |state| {
+        if let Err(err) =
+            state.renew_content_cache_entry(backend_id, &cache_key, &policy)
+        {
+            tracing::error!(error = %err, "failed to renew content cache entry");
+        }
+    })
+    .await
+}
+
+/// Remove a cached response body, used when the backend says it must not be
+/// stored.
+pub async fn remove_body(backend_id: BackendId, url: &str) {
+    let cache_key = blake3::hash(url.as_bytes()).to_hex();
+    super::with_relaxed_state_async(move |state| {
+        if let Err(err) = state.remove_content_cache_entry(backend_id, &cache_key) {
+            tracing::error!(error = %err, "failed to remove content cache entry");
+        }
+    })
+    .await
+}
+
 /// Prune any cache entries that have expired past their TTL.
 ///
 /// Returns the number of rows pruned.
@@ -69,6 +186,20 @@ pub fn purge() -> usize {
     })
 }
 
+/// Delete all content cached for the backend, used when the backend reports its
+/// content has changed.
+pub async fn purge_backend(backend_id: BackendId) -> usize {
+    super::with_relaxed_state_async(move |state| {
+        state
+            .purge_backend_content_cache(backend_id)
+            .unwrap_or_else(|err| {
+                tracing::error!(error = %err, "failed to purge backend content cache");
+                0
+            })
+    })
+    .await
+}
+
 #[cfg(test)]
 mod tests {
     use serde_json::json;
@@ -95,6 +226,45 @@ mod tests {
         assert_eq!(entry.value, content);
     }
 
+    #[rstest::rstest]
+    fn test_body_revalidation(_temp_storage: tempfile::TempDir) {
+        let backend_id = BackendId::now_v7();
+        let url = "https://example.com/Items?Limit=1";
+        let policy = CachePolicy {
+            fresh_for: Duration::ZERO,
+            stale_for: Duration::from_secs(60),
+            validators: Validators {
+                etag: Some("\"v1\"".to_string()),
+                last_modified: None,
+            },
+        };
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            insert_body(backend_id, url, b"body".to_vec(), policy.clone()).await;
+            let entry = try_get_body(backend_id, url).await.unwrap();
+            assert_eq!(entry.value, b"body");
+            assert!(entry.is_stale());
+            assert!(!entry.is_expired());
+            assert_eq!(entry.validators, policy.validators);
+
+            let renewed = CachePolicy {
+                fresh_for: Duration::from_secs(60),
+                ..CachePolicy::default()
+            };
+            renew_body(backend_id, url, renewed).await;
+            let entry = try_get_body(backend_id, url).await.unwrap();
+            assert_eq!(entry.value, b"body");
+            assert!(!entry.is_stale());
+            assert_eq!(entry.validators, policy.validators);
+
+            remove_body(backend_id, url).await;
+            assert!(try_get_body(backend_id, url).await.is_none());
+        });
+    }
+
     #[rstest::rstest]
     fn test_prune(_temp_storage: tempfile::TempDir) {
         let content = json!({
@@ -139,4 +309,23 @@ mod tests {
         assert_eq!(purge(), 1);
         assert!(try_get::<serde_json::Value>(backend_id, "/example").is_none());
     }
+
+    #[rstest::rstest]
+    fn test_purge_backend(_temp_storage: tempfile::TempDir) {
+        let content = json!({
+            "example": 1234,
+        });
+        let backend_id = BackendId::now_v7();
+        let other_backend_id = BackendId::now_v7();
+
+        insert(backend_id, "/example", &content, None);
+        insert(other_backend_id, "/example", &content, None);
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        assert_eq!(runtime.block_on(purge_backend(backend_id)), 1);
+        assert!(try_get::<serde_json::Value>(backend_id, "/example").is_none());
+        assert!(try_get::<serde_json::Value>(other_backend_id, "/example").is_some());
+    }
 }
@@ -1,45 +1,48 @@
+use std::path::{Path, PathBuf};
+
+use rusqlite::{OptionalExtension, params};
 use snafu::ResultExt;
 
-use crate::backends::BackendInitState;
+use super::interaction_backlog::InteractionEvent;
+use super::state::{ActorState, close_connection};
+use crate::backends::{BackendId, BackendInitState};
 
 /// System state storage backed by an SQLite database.
 pub struct DurableStateStorage {
     conn: rusqlite::Connection,
+    /// Where the database is copied before it is migrated.
+    backup_path: Option<PathBuf>,
 }
 
 impl DurableStateStorage {
     /// Creates a new [DurableStateStorage] instance located within the data directory.
     pub(super) fn open() -> Result<Self, snafu::Whatever> {
-        let conn = if cfg!(test) {
-            rusqlite::Connection::open_in_memory()
-                .whatever_context("open durable SQLite database")?
+        let (mut conn, backup_path) = if cfg!(test) {
+            let conn = rusqlite::Connection::open_in_memory()
+                .whatever_context("open durable SQLite database")?;
+            (conn, None)
         } else {
             let paths = super::directory::paths();
             let durable_path = paths.data_dir().join("durable.sqlite");
-            rusqlite::Connection::open(durable_path)
-                .whatever_context("open durable SQLite database")?
+            let conn = rusqlite::Connection::open(&durable_path)
+                .whatever_context("open durable SQLite database")?;
+            (conn, Some(durable_path.with_extension("sqlite.bak")))
         };
 
         conn.pragma_update(None, "journal_mode", "WAL")
             .whatever_context("update durable journal_mode pragma")?;
         conn.pragma_update(None, "synchronous", "FULL")
             .whatever_context("update durable synchronous pragma")?;
+        // Secrets moved out of backend contexts must not linger in freed pages.
+        conn.pragma_update(None, "secure_delete", true)
+            .whatever_context("update durable secure_delete pragma")?;
 
-        let this = Self { conn };
-        this.init_databases()?;
-        Ok(this)
-    }
-
-    fn init_databases(&self) -> Result<(), snafu::Whatever> {
-        static DURABLE_INIT_SQL: &str = include_str!("tables/durable_init.sql");
-
-        tracing::info!("initializing database");
-
-        self.conn
-            .execute_batch(DURABLE_INIT_SQL)
-            .whatever_context("initializing durable database")?;
-
-        Ok(())
+        super::migrations::migrate(
+            &mut conn,
+            &super::migrations::DURABLE,
+            backup_path.as_deref(),
+        )?;
+        Ok(Self { conn, backup_path })
     }
 
     /// Persist the provided backend init state to storage.
@@ -56,6 +59,75 @@ impl DurableStateStorage {
         Ok(())
     }
 
+    /// Replace the persisted context of the backend, along with its context in the
+    /// backup taken before the last migration.
+    ///
+    /// Contexts are only replaced to move secrets out of them, which must not be
+    /// left behind in the backup.
+    pub fn update_backend_context(
+        &self,
+        backend_id: BackendId,
+        context: &serde_json::Value,
+    ) -> Result<(), snafu::Whatever> {
+        self.conn
+            .execute(
+                "UPDATE backend_init_state SET context = ? WHERE backend_id = ?;",
+                (context, backend_id),
+            )
+            .with_whatever_context(|_| {
+                format!("update backend ({backend_id}) context")
+            })?;
+
+        if let Some(backup_path) = self.backup_path.as_deref() {
+            update_backup_context(backup_path, backend_id, context)?;
+        }
+        Ok(())
+    }
+
+    /// Set a user setting.
+    pub(super) fn set_setting(
+        &self,
+        key: &str,
+        value: &[u8],
+    ) -> Result<(), snafu::Whatever> {
+        let sql = r#"
+            INSERT INTO app_settings (k, v)
+            VALUES (?, ?)
+            ON CONFLICT (k)
+            DO UPDATE SET v = excluded.v;
+        "#;
+
+        self.conn
+            .prepare_cached(sql)
+            .whatever_context("prepare setting insert")?
+            .execute(params![key, value])
+            .whatever_context("execute setting insert")?;
+
+        Ok(())
+    }
+
+    /// Get a user setting, if it is set.
+    pub(super) fn get_setting(
+        &self,
+        key: &str,
+    ) -> Result<Option<Vec<u8>>, snafu::Whatever> {
+        self.conn
+            .prepare_cached("SELECT v FROM app_settings WHERE k = ?;")
+            .whatever_context("prepare setting select")?
+            .query_row(params![key], |row| row.get(0))
+            .optional()
+            .whatever_context("execute setting select")
+    }
+
+    /// Remove a user setting, reverting it to its default.
+    pub(super) fn remove_setting(&self, key: &str) -> Result<(), snafu::Whatever> {
+        self.conn
+            .execute("DELETE FROM app_settings WHERE k = ?;", params![key])
+            .whatever_context("execute setting delete")?;
+
+        Ok(())
+    }
+
     /// Retrieves all persisted backend init state from the storage.
     pub fn read_all_backend_init_state(
         &self,
@@ -76,14 +148,161 @@ impl DurableStateStorage {
         .collect::<Result<Vec<_>, rusqlite::Error>>()
         .whatever_context("deserialize context rows")
     }
+
+    /// Add an interaction to the backlog.
+    ///
+    /// Returns `false` if an event with the same ID is already in the backlog.
+    pub fn push_interaction(
+        &self,
+        event: &InteractionEvent,
+    ) -> Result<bool, snafu::Whatever> {
+        let interaction = serde_json::to_string(&event.interaction)
+            .whatever_context("serialize interaction")?;
+
+        let n = self
+            .conn
+            .execute(
+                r#"
+                INSERT OR IGNORE INTO user_interaction_backlog (event_id, backend_id, event, created_at)
+                VALUES (?, ?, ?, ?);
+                "#,
+                params![event.event_id, event.backend_id, interaction, event.created_at],
+            )
+            .with_whatever_context(|_| {
+                format!("insert interaction ({})", event.event_id)
+            })?;
+
+        Ok(n > 0)
+    }
+
+    /// Returns the backends with interactions waiting in the backlog.
+    pub fn pending_interaction_backends(
+        &self,
+    ) -> Result<Vec<BackendId>, snafu::Whatever> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT DISTINCT backend_id FROM user_interaction_backlog;")
+            .whatever_context("prepare backlog backends read")?;
+
+        stmt.query_map((), |row| row.get(0))
+            .whatever_context("retrieve backlog backend rows")?
+            .collect::<Result<Vec<_>, rusqlite::Error>>()
+            .whatever_context("deserialize backlog backend rows")
+    }
+
+    /// Returns the oldest interactions in the backlog of the backend, in the
+    /// order they were made.
+    pub fn pending_interactions(
+        &self,
+        backend_id: BackendId,
+        limit: usize,
+    ) -> Result<Vec<InteractionEvent>, snafu::Whatever> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                r#"
+                SELECT event_id, backend_id, event, created_at
+                FROM user_interaction_backlog
+                WHERE backend_id = ?
+                ORDER BY created_at, event_id
+                LIMIT ?;
+                "#,
+            )
+            .whatever_context("prepare backlog read")?;
+
+        let rows = stmt
+            .query_map(params![backend_id, limit as i64], |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get(3)?,
+                ))
+            })
+            .whatever_context("retrieve backlog rows")?
+            .collect::<Result<Vec<_>, rusqlite::Error>>()
+            .whatever_context("deserialize backlog rows")?;
+
+        let mut events = Vec::with_capacity(rows.len());
+        for (event_id, backend_id, interaction, created_at) in rows {
+            match serde_json::from_str(&interaction) {
+                Ok(interaction) => events.push(InteractionEvent {
+                    event_id,
+                    backend_id,
+                    interaction,
+                    created_at,
+                }),
+                Err(err) => {
+                    // The event can never be applied, so it is dropped rather than
+                    // blocking the rest of the backlog.
+                    tracing::error!(event_id = %event_id, error = %err, "discarding invalid interaction");
+                    self.remove_interaction(event_id)?;
+                },
+            }
+        }
+
+        Ok(events)
+    }
+
+    /// Remove an interaction from the backlog once it has been applied.
+    pub fn remove_interaction(
+        &self,
+        event_id: uuid::Uuid,
+    ) -> Result<(), snafu::Whatever> {
+        self.conn
+            .execute(
+                "DELETE FROM user_interaction_backlog WHERE event_id = ?;",
+                params![event_id],
+            )
+            .with_whatever_context(|_| format!("remove interaction ({event_id})"))?;
+
+        Ok(())
+    }
+}
+
+impl ActorState for DurableStateStorage {
+    fn close(&self) -> Result<(), snafu::Whatever> {
+        close_connection(&self.conn)
+    }
+}
+
+/// Replace the context of the backend in the backup at the path, if there is one.
+fn update_backup_context(
+    path: &Path,
+    backend_id: BackendId,
+    context: &serde_json::Value,
+) -> Result<(), snafu::Whatever> {
+    if !path.exists() {
+        return Ok(());
+    }
+
+    let conn = rusqlite::Connection::open_with_flags(
+        path,
+        rusqlite::OpenFlags::SQLITE_OPEN_READ_WRITE,
+    )
+    .whatever_context("open durable database backup")?;
+    conn.pragma_update(None, "secure_delete", true)
+        .whatever_context("update backup secure_delete pragma")?;
+    conn.execute(
+        "UPDATE backend_init_state SET context = ? WHERE backend_id = ?;",
+        (context, backend_id),
+    )
+    .with_whatever_context(|_| {
+        format!("update backend ({backend_id}) context in backup")
+    })?;
+
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
     use serde_json::json;
 
     use super::*;
     use crate::backends::BackendKind;
+    use crate::models::interaction::Interaction;
 
     #[test]
     fn test_single_backend_save_retrieve() {
@@ -124,4 +343,128 @@ mod tests {
         let states = storage.read_all_backend_init_state().unwrap();
         assert_eq!(states.len(), 2);
     }
+
+    #[test]
+    fn test_settings() {
+        let storage = DurableStateStorage::open().unwrap();
+        assert_eq!(storage.get_setting("key").unwrap(), None);
+
+        storage.set_setting("key", b"first").unwrap();
+        storage.set_setting("key", b"second").unwrap();
+        assert_eq!(
+            storage.get_setting("key").unwrap(),
+            Some(b"second".to_vec())
+        );
+
+        storage.remove_setting("key").unwrap();
+        assert_eq!(storage.get_setting("key").unwrap(), None);
+    }
+
+    #[test]
+    fn test_update_backend_context() {
+        let input_init_state = BackendInitState {
+            id: uuid::Uuid::now_v7(),
+            kind: BackendKind::Jellyfin,
+            context: json!({"test": 1234}),
+        };
+        let id = input_init_state.id;
+
+        let storage = DurableStateStorage::open().unwrap();
+        storage.save_backend_init_state(input_init_state).unwrap();
+        storage
+            .update_backend_context(id, &json!({"test": "updated"}))
+            .unwrap();
+
+        let states = storage.read_all_backend_init_state().unwrap();
+        assert_eq!(states[0].context, json!({"test": "updated"}));
+    }
+
+    #[test]
+    fn test_update_backend_context_scrubs_backup() {
+        let backend_id = uuid::Uuid::now_v7();
+        let dir = tempfile::tempdir().unwrap();
+        let backup_path = dir.path().join("durable.sqlite.bak");
+
+        let backup = rusqlite::Connection::open(&backup_path).unwrap();
+        backup
+            .execute_batch(include_str!("tables/fixtures/durable_baseline.sql"))
+            .unwrap();
+        backup
+            .execute(
+                "INSERT INTO backend_init_state (backend_id, kind, context) VALUES (?, 'jellyfin', ?);",
+                (backend_id, json!({"access_token": "plaintext-token"})),
+            )
+            .unwrap();
+        drop(backup);
+
+        let mut storage = DurableStateStorage::open().unwrap();
+        storage.backup_path = Some(backup_path.clone());
+        storage
+            .save_backend_init_state(BackendInitState {
+                id: backend_id,
+                kind: BackendKind::Jellyfin,
+                context: json!({"access_token": "plaintext-token"}),
+            })
+            .unwrap();
+        let secured = json!({"access_token": {"store": "keyring", "id": "token"}});
+        storage
+            .update_backend_context(backend_id, &secured)
+            .unwrap();
+
+        let backup = rusqlite::Connection::open(&backup_path).unwrap();
+        let context: serde_json::Value = backup
+            .query_row("SELECT context FROM backend_init_state;", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(context, secured);
+        drop(backup);
+
+        let bytes = std::fs::read(&backup_path).unwrap();
+        let token = b"plaintext-token";
+        assert!(!bytes.windows(token.len()).any(|window| window == token));
+
+        // Without a backup there is nothing to scrub.
+        std::fs::remove_file(&backup_path).unwrap();
+        storage
+            .update_backend_context(backend_id, &secured)
+            .unwrap();
+        assert!(!backup_path.exists());
+    }
+
+    #[test]
+    fn test_interaction_backlog() {
+        let backend_id = uuid::Uuid::now_v7();
+        let event = |created_at: i64, position: u64| InteractionEvent {
+            event_id: uuid::Uuid::now_v7(),
+            backend_id,
+            interaction: Interaction::Progress {
+                item_id: "abc".into(),
+                position: Duration::from_secs(position),
+            },
+            created_at,
+        };
+        let first = event(1, 10);
+        let second = event(2, 20);
+
+        let storage = DurableStateStorage::open().unwrap();
+        assert!(storage.push_interaction(&second).unwrap());
+        assert!(storage.push_interaction(&first).unwrap());
+        // Recording the same event twice is a no-op.
+        assert!(!storage.push_interaction(&first).unwrap());
+
+        assert_eq!(
+            storage.pending_interaction_backends().unwrap(),
+            vec![backend_id]
+        );
+        let events = storage.pending_interactions(backend_id, 10).unwrap();
+        assert_eq!(events, vec![first.clone(), second.clone()]);
+
+        storage.remove_interaction(first.event_id).unwrap();
+        let events = storage.pending_interactions(backend_id, 10).unwrap();
+        assert_eq!(events, vec![second.clone()]);
+
+        storage.remove_interaction(second.event_id).unwrap();
+        assert!(storage.pending_interaction_backends().unwrap().is_empty());
+    }
 }
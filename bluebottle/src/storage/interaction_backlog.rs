@@ -0,0 +1,134 @@
+//! The backlog of user interactions waiting to be applied to their backend.
+//!
+//! Every interaction is saved to durable storage before it is sent anywhere, so
+//! watch history made while offline survives until the backend can be reached.
+
+use std::sync::LazyLock;
+
+use tokio::sync::Notify;
+
+use crate::backends::BackendId;
+use crate::models::interaction::Interaction;
+
+/// Wakes the backlog worker when a new interaction is recorded.
+static RECORDED: LazyLock<Notify> = LazyLock::new(Notify::new);
+
+#[derive(Debug, Clone, PartialEq)]
+/// An interaction waiting in the backlog.
+pub struct InteractionEvent {
+    /// The unique ID of the event within the backlog, recording the same event
+    /// twice has no effect.
+    ///
+    /// The ID is not sent to the backend, an event may still be applied more than
+    /// once if the app exits before it is removed. That is harmless as
+    /// interactions set absolute values, see [Interaction].
+    pub event_id: uuid::Uuid,
+    pub backend_id: BackendId,
+    pub interaction: Interaction,
+    /// When the interaction was made, in milliseconds since the unix epoch.
+    pub created_at: i64,
+}
+
+/// Record an interaction, it is applied to the backend by the backlog worker.
+pub async fn record(
+    backend_id: BackendId,
+    interaction: Interaction,
+) -> Result<(), String> {
+    let event = InteractionEvent {
+        event_id: uuid::Uuid::now_v7(),
+        backend_id,
+        interaction,
+        created_at: super::now(),
+    };
+
+    super::with_durable_state_async(move |state| {
+        state
+            .push_interaction(&event)
+            .map_err(|err| err.to_string())
+    })
+    .await?;
+
+    RECORDED.notify_one();
+
+    Ok(())
+}
+
+/// Wait for a new interaction to be recorded.
+///
+/// If an interaction was recorded since the last call, this returns immediately.
+pub async fn recorded() {
+    RECORDED.notified().await
+}
+
+/// Returns the backends with interactions waiting in the backlog.
+pub async fn pending_backends() -> Vec<BackendId> {
+    super::with_durable_state_async(|state| {
+        state.pending_interaction_backends().unwrap_or_else(|err| {
+            tracing::error!(error = %err, "failed to read interaction backlog");
+            Vec::new()
+        })
+    })
+    .await
+}
+
+/// Returns the oldest interactions waiting for the backend, in the order they
+/// were made.
+pub async fn pending(backend_id: BackendId, limit: usize) -> Vec<InteractionEvent> {
+    super::with_durable_state_async(move |state| {
+        state
+            .pending_interactions(backend_id, limit)
+            .unwrap_or_else(|err| {
+                tracing::error!(error = %err, "failed to read interaction backlog");
+                Vec::new()
+            })
+    })
+    .await
+}
+
+/// Remove an interaction from the backlog once it no longer needs applying.
+pub async fn remove(event_id: uuid::Uuid) -> Result<(), String> {
+    super::with_durable_state_async(move |state| {
+        state
+            .remove_interaction(event_id)
+            .map_err(|err| err.to_string())
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::storage::test_utils::temp_storage;
+
+    #[rstest::rstest]
+    fn test_record_interactions(_temp_storage: tempfile::TempDir) {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+
+        runtime.block_on(async {
+            let backend_id = BackendId::now_v7();
+            let interaction = Interaction::Progress {
+                item_id: "abc".into(),
+                position: Duration::from_secs(90),
+            };
+            record(backend_id, interaction.clone()).await.unwrap();
+
+            // The worker is woken even though it was not waiting yet.
+            tokio::time::timeout(Duration::from_secs(1), recorded())
+                .await
+                .expect("worker should be notified");
+
+            assert!(pending_backends().await.contains(&backend_id));
+            let events = pending(backend_id, 10).await;
+            assert_eq!(events.len(), 1);
+            assert_eq!(events[0].interaction, interaction);
+
+            remove(events[0].event_id).await.unwrap();
+            assert!(pending(backend_id, 10).await.is_empty());
+        });
+    }
+}
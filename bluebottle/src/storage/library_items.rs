@@ -0,0 +1,164 @@
+//! A local copy of the items within each backend library, kept up to date by
+//! the [sync](crate::sync) engine.
+//!
+//! Screens read from the local copy so they can render immediately, even when
+//! the backend is unreachable.
+//!
+//! The async functions are used by the sync engine and return errors as strings,
+//! as [snafu::Whatever] cannot be sent back from the state actor.
+
+use crate::backends::BackendId;
+use crate::models::media::{ItemId, ItemPage};
+use crate::models::query::{FilterOptions, ItemQuery};
+use crate::models::sync::ItemMetadata;
+
+#[derive(Debug, Clone, PartialEq)]
+/// How far a library has been synced.
+pub struct LibrarySyncState {
+    /// The most recent backend timestamp an item was changed at, used to only
+    /// fetch changed items on the next sync.
+    pub cursor: Option<String>,
+    /// When the library was last synced, in milliseconds.
+    pub last_sync_at: i64,
+    /// When every item of the library was last fetched, in milliseconds.
+    pub last_full_sync_at: i64,
+}
+
+/// Store or update the synced items of a library.
+pub async fn store(
+    backend_id: BackendId,
+    library_id: String,
+    items: Vec<ItemMetadata>,
+    synced_at: i64,
+) -> Result<(), String> {
+    super::with_relaxed_state_async(move |state| {
+        state
+            .upsert_library_items(backend_id, &library_id, &items, synced_at)
+            .map_err(|err| err.to_string())
+    })
+    .await
+}
+
+/// Remove the items of a library which were not seen by the sync started at
+/// `synced_at`.
+///
+/// Returns the number of items removed.
+pub async fn remove_stale(
+    backend_id: BackendId,
+    library_id: String,
+    synced_at: i64,
+) -> Result<usize, String> {
+    super::with_relaxed_state_async(move |state| {
+        state
+            .remove_stale_library_items(backend_id, &library_id, synced_at)
+            .map_err(|err| err.to_string())
+    })
+    .await
+}
+
+/// Get the sync state of a library, if it has been synced before.
+pub async fn sync_state(
+    backend_id: BackendId,
+    library_id: String,
+) -> Result<Option<LibrarySyncState>, String> {
+    super::with_relaxed_state_async(move |state| {
+        state
+            .get_library_sync_state(backend_id, &library_id)
+            .map_err(|err| err.to_string())
+    })
+    .await
+}
+
+/// Persist the sync state of a library.
+pub async fn save_sync_state(
+    backend_id: BackendId,
+    library_id: String,
+    sync_state: LibrarySyncState,
+) -> Result<(), String> {
+    super::with_relaxed_state_async(move |state| {
+        state
+            .set_library_sync_state(backend_id, &library_id, &sync_state)
+            .map_err(|err| err.to_string())
+    })
+    .await
+}
+
+/// Returns whether a synced item is one of the user's favourites, `None` if the
+/// item has not been synced.
+pub async fn favourite(
+    backend_id: BackendId,
+    item_id: ItemId,
+) -> Result<Option<bool>, String> {
+    super::with_relaxed_state_async(move |state| {
+        state
+            .get_favourite(backend_id, &item_id)
+            .map_err(|err| err.to_string())
+    })
+    .await
+}
+
+/// Set whether a synced item is one of the user's favourites, so the local copy
+/// reflects the change before the backend applies it.
+pub async fn set_favourite(
+    backend_id: BackendId,
+    item_id: ItemId,
+    favourite: bool,
+) -> Result<(), String> {
+    super::with_relaxed_state_async(move |state| {
+        state
+            .set_favourite(backend_id, &item_id, favourite)
+            .map(|_| ())
+            .map_err(|err| err.to_string())
+    })
+    .await
+}
+
+/// Returns whether any library of the backend has been synced.
+pub fn has_synced(backend_id: BackendId) -> bool {
+    super::with_relaxed_state(move |state| {
+        state
+            .has_library_sync_state(backend_id)
+            .inspect_err(
+                |err| tracing::error!(error = %err, "failed to query library sync state"),
+            )
+            .unwrap_or(false)
+    })
+}
+
+/// Query a page of items from the local copy of a library.
+///
+/// Returns `None` if the library has not been synced yet.
+pub async fn query(backend_id: BackendId, query: ItemQuery) -> Option<ItemPage> {
+    super::with_relaxed_state_async(move |state| {
+        state
+            .get_library_sync_state(backend_id, &query.library_id)
+            .ok()??;
+
+        state
+            .query_library_items(backend_id, &query)
+            .inspect_err(
+                |err| tracing::error!(error = %err, "failed to query library items"),
+            )
+            .ok()
+    })
+    .await
+}
+
+/// Returns the values the local copy of a library can be filtered by.
+///
+/// Returns `None` if the library has not been synced yet.
+pub fn filter_options(backend_id: BackendId, library_id: &str) -> Option<FilterOptions> {
+    let library_id = library_id.to_string();
+    super::with_relaxed_state(move |state| {
+        state
+            .get_library_sync_state(backend_id, &library_id)
+            .ok()??;
+
+        state
+            .library_filter_options(backend_id, &library_id)
+            .inspect_err(
+                |err| tracing::error!(error = %err, "failed to query filter options"),
+            )
+            .ok()
+    })
+}
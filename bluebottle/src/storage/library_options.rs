@@ -0,0 +1,45 @@
+//! Persists the sorting and filtering options of each library view.
+
+use super::settings::Setting;
+use crate::backends::BackendId;
+use crate::models::query::LibraryViewOptions;
+
+static LIBRARY_VIEW_OPTIONS: Setting<LibraryViewOptions> =
+    Setting::relaxed("library_view_options", LibraryViewOptions::default);
+
+/// Load the persisted view options of a library, if any have been saved.
+pub fn load(backend_id: BackendId, library_id: &str) -> Option<LibraryViewOptions> {
+    LIBRARY_VIEW_OPTIONS
+        .keyed(format_args!("{backend_id}:{library_id}"))
+        .try_load()
+}
+
+/// Persist the view options of a library.
+pub fn save(backend_id: BackendId, library_id: &str, options: &LibraryViewOptions) {
+    LIBRARY_VIEW_OPTIONS
+        .keyed(format_args!("{backend_id}:{library_id}"))
+        .save(options);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::query::{ItemFilter, SortBy, SortOrder};
+    use crate::storage::test_utils::temp_storage;
+
+    #[rstest::rstest]
+    fn test_save_and_load(_temp_storage: tempfile::TempDir) {
+        let backend_id = BackendId::now_v7();
+        assert!(load(backend_id, "library-1").is_none());
+
+        let options = LibraryViewOptions {
+            sort_by: SortBy::DateAdded,
+            sort_order: SortOrder::Descending,
+            filters: vec![ItemFilter::Genre("Drama".into()), ItemFilter::Unplayed],
+        };
+        save(backend_id, "library-1", &options);
+
+        assert_eq!(load(backend_id, "library-1"), Some(options));
+        assert!(load(backend_id, "library-2").is_none());
+    }
+}
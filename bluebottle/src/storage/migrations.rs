@@ -0,0 +1,407 @@
+//! Versioned schema migrations of the SQLite databases.
+//!
+//! Each database records how many migrations were applied to it in its
+//! `user_version` pragma. Pending migrations are applied in order, each in a
+//! transaction along with the version bump, so a failed migration leaves the
+//! database at the last version which was fully applied.
+//!
+//! Databases created before versions were recorded are at version `0`, their
+//! version is identified from the tables they have instead.
+
+use std::path::Path;
+
+use rusqlite::{Connection, OptionalExtension};
+use snafu::ResultExt;
+
+/// A change to the schema of a database.
+pub(super) struct Migration {
+    /// A short description of the change, logged when it is applied.
+    pub description: &'static str,
+    pub sql: &'static str,
+}
+
+/// The migrations of a database, in the order they are applied.
+pub(super) struct Schema {
+    pub name: &'static str,
+    pub migrations: &'static [Migration],
+    /// Identifies the version of a database created before versions were
+    /// recorded, `0` if the database is new.
+    pub legacy_version: fn(&Connection) -> rusqlite::Result<u32>,
+}
+
+impl Schema {
+    /// Returns the version of a database with every migration applied.
+    pub fn latest_version(&self) -> u32 {
+        self.migrations.len() as u32
+    }
+}
+
+pub(super) static DURABLE: Schema = Schema {
+    name: "durable",
+    migrations: &[
+        Migration {
+            description: "backends and interaction backlog",
+            sql: include_str!("tables/durable/001_initial.sql"),
+        },
+        Migration {
+            description: "user settings",
+            sql: include_str!("tables/durable/002_settings.sql"),
+        },
+    ],
+    legacy_version: durable_legacy_version,
+};
+
+pub(super) static RELAXED: Schema = Schema {
+    name: "relaxed",
+    migrations: &[
+        Migration {
+            description: "content cache and key-value state",
+            sql: include_str!("tables/relaxed/001_initial.sql"),
+        },
+        Migration {
+            description: "offline search index",
+            sql: include_str!("tables/relaxed/002_search_index.sql"),
+        },
+        Migration {
+            description: "synced library items",
+            sql: include_str!("tables/relaxed/003_library_items.sql"),
+        },
+        Migration {
+            description: "series and playback state of library items",
+            sql: include_str!("tables/relaxed/004_library_item_progress.sql"),
+        },
+        Migration {
+            description: "asset cache index",
+            sql: include_str!("tables/relaxed/005_asset_cache.sql"),
+        },
+        Migration {
+            description: "BlurHashes of library items",
+            sql: include_str!("tables/relaxed/006_library_item_blurhash.sql"),
+        },
+        Migration {
+            description: "content cache validators",
+            sql: include_str!("tables/relaxed/007_content_cache_validators.sql"),
+        },
+        Migration {
+            description: "asset cache paths",
+            sql: include_str!("tables/relaxed/008_asset_cache_paths.sql"),
+        },
+    ],
+    legacy_version: relaxed_legacy_version,
+};
+
+/// Apply any pending migrations to the database, returning the version it was at.
+///
+/// If `backup_path` is given, existing databases are copied there before they
+/// are migrated.
+pub(super) fn migrate(
+    conn: &mut Connection,
+    schema: &Schema,
+    backup_path: Option<&Path>,
+) -> Result<u32, snafu::Whatever> {
+    let name = schema.name;
+    let latest = schema.latest_version();
+
+    let mut version = user_version(conn)
+        .with_whatever_context(|_| format!("read {name} database version"))?;
+    if version == 0 {
+        version = (schema.legacy_version)(conn)
+            .with_whatever_context(|_| format!("identify legacy {name} database"))?;
+        if version > 0 {
+            tracing::info!(database = name, version, "identified unversioned database");
+            conn.pragma_update(None, "user_version", version)
+                .with_whatever_context(|_| format!("record {name} database version"))?;
+        }
+    }
+
+    if version > latest {
+        snafu::whatever!(
+            "{name} database version {version} is newer than the latest known version {latest}"
+        );
+    }
+    if version == latest {
+        return Ok(version);
+    }
+
+    if let Some(backup_path) = backup_path.filter(|_| version > 0) {
+        backup(conn, backup_path)
+            .with_whatever_context(|_| format!("back up {name} database"))?;
+        tracing::info!(database = name, path = %backup_path.display(), "backed up database before migrating");
+    }
+
+    for (index, migration) in schema.migrations.iter().enumerate().skip(version as usize)
+    {
+        let next = index as u32 + 1;
+        tracing::info!(
+            database = name,
+            version = next,
+            description = migration.description,
+            "applying migration",
+        );
+
+        let tx = conn
+            .transaction()
+            .whatever_context("start migration transaction")?;
+        tx.execute_batch(migration.sql)
+            .with_whatever_context(|_| format!("apply {name} migration {next}"))?;
+        tx.pragma_update(None, "user_version", next)
+            .with_whatever_context(|_| format!("record {name} database version"))?;
+        tx.commit()
+            .with_whatever_context(|_| format!("commit {name} migration {next}"))?;
+    }
+
+    Ok(version)
+}
+
+fn user_version(conn: &Connection) -> rusqlite::Result<u32> {
+    conn.pragma_query_value(None, "user_version", |row| row.get(0))
+}
+
+/// Copy the database to the path, replacing any previous backup.
+fn backup(conn: &Connection, path: &Path) -> rusqlite::Result<()> {
+    if path.exists() {
+        std::fs::remove_file(path)
+            .map_err(|err| rusqlite::Error::ToSqlConversionFailure(err.into()))?;
+    }
+    conn.execute("VACUUM INTO ?", [path.to_string_lossy()])?;
+    Ok(())
+}
+
+fn durable_legacy_version(conn: &Connection) -> rusqlite::Result<u32> {
+    let version = if has_table(conn, "backend_init_state")? {
+        1
+    } else {
+        0
+    };
+    Ok(version)
+}
+
+fn relaxed_legacy_version(conn: &Connection) -> rusqlite::Result<u32> {
+    let version = if has_column(conn, "library_items", "series_id")? {
+        4
+    } else if has_table(conn, "library_items")? {
+        3
+    } else if has_table(conn, "search_items")? {
+        2
+    } else if has_table(conn, "app_kv_state")? {
+        1
+    } else {
+        0
+    };
+    Ok(version)
+}
+
+fn has_table(conn: &Connection, table: &str) -> rusqlite::Result<bool> {
+    conn.query_row(
+        "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?;",
+        [table],
+        |_| Ok(()),
+    )
+    .optional()
+    .map(|row| row.is_some())
+}
+
+fn has_column(conn: &Connection, table: &str, column: &str) -> rusqlite::Result<bool> {
+    conn.query_row(
+        "SELECT 1 FROM pragma_table_info(?) WHERE name = ?;",
+        [table, column],
+        |_| Ok(()),
+    )
+    .optional()
+    .map(|row| row.is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The schema of every release before versions were recorded, along with the
+    /// version it is identified as.
+    static RELAXED_FIXTURES: &[(&str, u32)] = &[
+        (include_str!("tables/fixtures/relaxed_baseline.sql"), 1),
+        (include_str!("tables/fixtures/relaxed_search_index.sql"), 2),
+        (include_str!("tables/fixtures/relaxed_library_items.sql"), 3),
+        (
+            include_str!("tables/fixtures/relaxed_library_progress.sql"),
+            4,
+        ),
+    ];
+
+    /// Returns the tables, indices and triggers of the database with their SQL,
+    /// ignoring `IF NOT EXISTS` which legacy databases were created with.
+    fn schema_of(conn: &Connection) -> Vec<(String, String)> {
+        let mut stmt = conn
+            .prepare(
+                "SELECT name, sql FROM sqlite_master WHERE sql IS NOT NULL ORDER BY name;",
+            )
+            .unwrap();
+        stmt.query_map([], |row| {
+            let sql: String = row.get(1)?;
+            Ok((row.get(0)?, sql.replace(" IF NOT EXISTS", "")))
+        })
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap()
+    }
+
+    fn table_columns(conn: &Connection, table: &str) -> Vec<String> {
+        let mut stmt = conn
+            .prepare("SELECT name FROM pragma_table_info(?) ORDER BY name;")
+            .unwrap();
+        stmt.query_map([table], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    fn migrated(schema: &Schema) -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        assert_eq!(migrate(&mut conn, schema, None).unwrap(), 0);
+        conn
+    }
+
+    #[test]
+    fn test_migrate_new_database() {
+        for schema in [&DURABLE, &RELAXED] {
+            let mut conn = migrated(schema);
+            assert_eq!(user_version(&conn).unwrap(), schema.latest_version());
+
+            // Already migrated databases are left alone.
+            let version = migrate(&mut conn, schema, None).unwrap();
+            assert_eq!(version, schema.latest_version());
+        }
+    }
+
+    #[test]
+    fn test_migrate_legacy_relaxed() {
+        let latest = migrated(&RELAXED);
+
+        for (sql, legacy_version) in RELAXED_FIXTURES {
+            let mut conn = Connection::open_in_memory().unwrap();
+            conn.execute_batch(sql).unwrap();
+            conn.execute("INSERT INTO app_kv_state (k, v) VALUES ('key', x'01');", [])
+                .unwrap();
+
+            let version = migrate(&mut conn, &RELAXED, None).unwrap();
+            assert_eq!(version, *legacy_version);
+            assert_eq!(user_version(&conn).unwrap(), RELAXED.latest_version());
+
+            let value: Vec<u8> = conn
+                .query_row("SELECT v FROM app_kv_state WHERE k = 'key';", [], |row| {
+                    row.get(0)
+                })
+                .unwrap();
+            assert_eq!(value, vec![1]);
+            assert_eq!(
+                table_columns(&conn, "library_items"),
+                table_columns(&latest, "library_items"),
+            );
+            let names = |conn: &Connection| -> Vec<String> {
+                schema_of(conn).into_iter().map(|(name, _)| name).collect()
+            };
+            assert_eq!(names(&conn), names(&latest));
+        }
+    }
+
+    #[test]
+    fn test_migrate_legacy_library_items() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(RELAXED_FIXTURES[2].0).unwrap();
+        conn.execute_batch(
+            r#"
+            INSERT INTO library_items (
+                backend_id, item_id, library_id, kind, name, sort_name, genres,
+                studios, played, favourite, synced_at
+            ) VALUES ('backend', 'item', 'movies', 'Movie', 'Name', 'name', '[]',
+                '[]', 0, 0, 1);
+            INSERT INTO library_sync_state (
+                backend_id, library_id, cursor, last_sync_at, last_full_sync_at
+            ) VALUES ('backend', 'movies', 'cursor', 1, 1);
+            "#,
+        )
+        .unwrap();
+
+        migrate(&mut conn, &RELAXED, None).unwrap();
+
+        let position: i64 = conn
+            .query_row(
+                "SELECT playback_position_secs FROM library_items WHERE item_id = 'item';",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(position, 0);
+        let sync_states: i64 = conn
+            .query_row("SELECT COUNT(*) FROM library_sync_state;", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(sync_states, 0);
+    }
+
+    #[test]
+    fn test_migrate_legacy_durable_with_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("durable.sqlite");
+        let backup_path = dir.path().join("durable.sqlite.bak");
+
+        let mut conn = Connection::open(&path).unwrap();
+        conn.execute_batch(include_str!("tables/fixtures/durable_baseline.sql"))
+            .unwrap();
+        conn.execute(
+            "INSERT INTO backend_init_state (backend_id, kind, context) VALUES ('id', 'jellyfin', '{}');",
+            [],
+        )
+        .unwrap();
+
+        assert_eq!(migrate(&mut conn, &DURABLE, Some(&backup_path)).unwrap(), 1);
+        assert_eq!(user_version(&conn).unwrap(), DURABLE.latest_version());
+        assert_eq!(schema_of(&conn), schema_of(&migrated(&DURABLE)));
+
+        let backup = Connection::open(&backup_path).unwrap();
+        assert_eq!(user_version(&backup).unwrap(), 1);
+        assert!(!has_table(&backup, "app_settings").unwrap());
+        let backends: i64 = backup
+            .query_row("SELECT COUNT(*) FROM backend_init_state;", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(backends, 1);
+        drop(backup);
+
+        // Up to date databases are left alone, along with their last backup.
+        std::fs::remove_file(&backup_path).unwrap();
+        migrate(&mut conn, &DURABLE, Some(&backup_path)).unwrap();
+        assert!(!backup_path.exists());
+
+        // Pretend a newer release added a migration, then went back to this one.
+        conn.pragma_update(None, "user_version", DURABLE.latest_version() + 1)
+            .unwrap();
+        // Databases from newer releases are refused rather than corrupted.
+        assert!(migrate(&mut conn, &DURABLE, None).is_err());
+    }
+
+    #[test]
+    fn test_failed_migration_rolls_back() {
+        static BROKEN: Schema = Schema {
+            name: "relaxed",
+            migrations: &[
+                Migration {
+                    description: "initial",
+                    sql: "CREATE TABLE first (id INTEGER);",
+                },
+                Migration {
+                    description: "broken",
+                    sql: "CREATE TABLE second (id INTEGER); INSERT INTO missing VALUES (1);",
+                },
+            ],
+            legacy_version: |_| Ok(0),
+        };
+
+        let mut conn = Connection::open_in_memory().unwrap();
+        assert!(migrate(&mut conn, &BROKEN, None).is_err());
+        assert_eq!(user_version(&conn).unwrap(), 1);
+        assert!(has_table(&conn, "first").unwrap());
+        assert!(!has_table(&conn, "second").unwrap());
+    }
+}
@@ -2,14 +2,39 @@ use std::path::PathBuf;
 
 use snafu::ResultExt;
 
-mod asset_cache;
-mod content_cache;
+pub mod appearance;
+pub mod asset_cache;
+pub mod cache_archive;
+pub mod cache_settings;
+pub mod content_cache;
 mod directory;
 mod durable;
+pub mod interaction_backlog;
+pub mod library_items;
+pub mod library_options;
+mod migrations;
+pub mod music_queue;
+pub mod music_settings;
+pub mod playback_progress;
+pub mod player_settings;
+pub mod recent_searches;
 mod relaxed;
+pub mod search_index;
+pub mod secrets;
+pub mod settings;
 mod state;
+pub mod stream_quality;
+pub mod track_preferences;
 
-pub use self::state::{submit_relaxed_state, with_durable_state, with_relaxed_state};
+pub use self::directory::paths;
+pub use self::state::{
+    submit_durable_state,
+    submit_relaxed_state,
+    with_durable_state,
+    with_durable_state_async,
+    with_relaxed_state,
+    with_relaxed_state_async,
+};
 
 /// Initialise the app storage system.
 pub fn init_storage(base_path: Option<PathBuf>) -> Result<(), snafu::Whatever> {
@@ -18,6 +43,13 @@ pub fn init_storage(base_path: Option<PathBuf>) -> Result<(), snafu::Whatever> {
     Ok(())
 }
 
+/// Close the app storage, waiting for pending writes to complete.
+///
+/// Storage must not be used after this is called.
+pub fn shutdown_storage() {
+    state::shutdown_state();
+}
+
 /// Returns a new timestamp in milliseconds.
 pub(super) fn now() -> i64 {
     let duration = std::time::SystemTime::now()
@@ -0,0 +1,54 @@
+//! Persists the music player's queue, so it can be picked up again after a
+//! restart.
+
+use super::settings::Setting;
+use crate::music::queue::SavedQueue;
+
+static MUSIC_QUEUE: Setting<Option<SavedQueue>> =
+    Setting::relaxed("music_queue", || None);
+
+/// Load the saved queue, if any.
+pub fn load() -> Option<SavedQueue> {
+    MUSIC_QUEUE.entry().load()
+}
+
+/// Persist the queue.
+pub fn save(saved: SavedQueue) {
+    MUSIC_QUEUE.entry().save(&Some(saved));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::backends::BackendId;
+    use crate::models::music::{AudioTrack, RepeatMode};
+    use crate::music::queue::Queue;
+    use crate::storage::test_utils::temp_storage;
+
+    #[rstest::rstest]
+    fn test_save_and_load(_temp_storage: tempfile::TempDir) {
+        assert_eq!(load(), None);
+
+        let track = AudioTrack {
+            item_id: "abc".into(),
+            name: "Track".into(),
+            artist: Some("Band".into()),
+            album: None,
+            album_id: None,
+            runtime: Some(Duration::from_secs(200)),
+            normalization_gain: Some(-3.0),
+        };
+        let mut queue = Queue::new(BackendId::now_v7(), vec![track.clone(), track], 1);
+        queue.set_repeat(RepeatMode::All);
+        queue.set_shuffle(true, 7);
+
+        let saved = SavedQueue {
+            queue,
+            position: Duration::from_secs(42),
+        };
+        save(saved.clone());
+        assert_eq!(load(), Some(saved));
+    }
+}
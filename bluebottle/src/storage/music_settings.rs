@@ -0,0 +1,17 @@
+//! Persists the music player settings.
+
+use super::settings::Setting;
+use crate::models::music::MusicSettings;
+
+static MUSIC_SETTINGS: Setting<MusicSettings> =
+    Setting::durable("music_settings", MusicSettings::default);
+
+/// Load the music settings, falling back to the defaults if none are saved.
+pub fn load() -> MusicSettings {
+    MUSIC_SETTINGS.entry().load()
+}
+
+/// Persist the music settings.
+pub fn save(settings: &MusicSettings) {
+    MUSIC_SETTINGS.entry().save(settings);
+}
@@ -0,0 +1,257 @@
+//! Tracks how far the user has got through each synced item, deciding what can
+//! be resumed and what appears in Continue Watching and Next Up.
+//!
+//! Positions synced from the backend and positions recorded locally are both
+//! judged by the same [ProgressThresholds].
+
+use std::time::Duration;
+
+use super::settings::Setting;
+use crate::backends::BackendId;
+use crate::models::interaction::UserData;
+use crate::models::media::ItemSummary;
+use crate::models::playback::{ProgressThresholds, WatchProgress};
+
+/// The thresholds deciding how far through an item the user has got.
+pub static PROGRESS_THRESHOLDS: Setting<ProgressThresholds> =
+    Setting::durable("progress_thresholds", ProgressThresholds::default);
+
+#[derive(Debug, Clone, PartialEq)]
+/// The playback state of a synced item.
+pub struct PlaybackState {
+    /// The position playback last stopped at.
+    pub position: Duration,
+    /// The runtime of the item, if known.
+    pub runtime: Option<Duration>,
+    pub played: bool,
+}
+
+/// Load the progress thresholds, falling back to the defaults if none are saved.
+pub fn load_thresholds() -> ProgressThresholds {
+    PROGRESS_THRESHOLDS.entry().load()
+}
+
+/// Persist the progress thresholds.
+pub fn save_thresholds(thresholds: &ProgressThresholds) {
+    PROGRESS_THRESHOLDS.entry().save(thresholds);
+}
+
+/// Returns the position the item can be resumed from, if any.
+pub fn resume_point(
+    backend_id: BackendId,
+    item_id: &str,
+    thresholds: &ProgressThresholds,
+) -> Option<Duration> {
+    let item_id = item_id.to_string();
+    let playback = super::with_relaxed_state(move |state| {
+        state
+            .get_playback_state(backend_id, &item_id)
+            .inspect_err(
+                |err| tracing::error!(error = %err, "failed to get playback state"),
+            )
+            .ok()
+            .flatten()
+    })?;
+
+    if playback.played {
+        return None;
+    }
+
+    match thresholds.classify(playback.position, playback.runtime) {
+        WatchProgress::InProgress(position) => Some(position),
+        WatchProgress::Unplayed | WatchProgress::Played => None,
+    }
+}
+
+/// Record where playback of an item stopped, updating the local copy so it is
+/// correct before the next sync.
+///
+/// Returns how far through the item playback got, judged by the `thresholds`.
+pub async fn record(
+    backend_id: BackendId,
+    item_id: String,
+    position: Duration,
+    thresholds: ProgressThresholds,
+) -> Result<WatchProgress, String> {
+    let played_at = super::now();
+    super::with_relaxed_state_async(move |state| {
+        let playback = state
+            .get_playback_state(backend_id, &item_id)
+            .map_err(|err| err.to_string())?;
+        let runtime = playback.and_then(|playback| playback.runtime);
+
+        let progress = thresholds.classify(position, runtime);
+        let (position, played) = match progress {
+            WatchProgress::Unplayed => (Duration::ZERO, false),
+            WatchProgress::InProgress(position) => (position, false),
+            WatchProgress::Played => (Duration::ZERO, true),
+        };
+
+        state
+            .set_playback_state(backend_id, &item_id, position, played, played_at)
+            .map(|_| progress)
+            .map_err(|err| err.to_string())
+    })
+    .await
+}
+
+/// Replace the local state of an item with the backend's, used when the backend
+/// holds newer state than an interaction waiting in the backlog.
+pub async fn overwrite(
+    backend_id: BackendId,
+    item_id: String,
+    user_data: UserData,
+) -> Result<(), String> {
+    super::with_relaxed_state_async(move |state| {
+        state
+            .set_user_data(backend_id, &item_id, &user_data)
+            .map(|_| ())
+            .map_err(|err| err.to_string())
+    })
+    .await
+}
+
+/// Returns partially played items, most recently played first.
+pub fn continue_watching(
+    backend_id: BackendId,
+    thresholds: &ProgressThresholds,
+    limit: usize,
+) -> Vec<ItemSummary> {
+    let thresholds = *thresholds;
+    super::with_relaxed_state(move |state| {
+        state
+            .query_continue_watching(backend_id, &thresholds, limit)
+            .unwrap_or_else(|err| {
+                tracing::error!(error = %err, "failed to query continue watching");
+                Vec::new()
+            })
+    })
+}
+
+/// Returns the next episode of each series the user is watching, most recently
+/// watched series first.
+pub fn next_up(backend_id: BackendId, limit: usize) -> Vec<ItemSummary> {
+    super::with_relaxed_state(move |state| {
+        state
+            .query_next_up(backend_id, limit)
+            .unwrap_or_else(|err| {
+                tracing::error!(error = %err, "failed to query next up");
+                Vec::new()
+            })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::media::ItemKind;
+    use crate::models::sync::ItemMetadata;
+    use crate::storage::test_utils::temp_storage;
+
+    fn movie(id: &str, runtime_secs: u64) -> ItemMetadata {
+        ItemMetadata {
+            summary: ItemSummary {
+                id: id.to_string(),
+                kind: ItemKind::Movie,
+                name: id.to_string(),
+                production_year: None,
+                blurhash: None,
+            },
+            parent_id: None,
+            series_id: None,
+            season_number: None,
+            episode_number: None,
+            sort_name: id.to_string(),
+            original_name: None,
+            people: Vec::new(),
+            genres: Vec::new(),
+            studios: Vec::new(),
+            official_rating: None,
+            community_rating: None,
+            runtime_secs: Some(runtime_secs),
+            premiere_date: None,
+            date_added: None,
+            date_last_saved: None,
+            played: false,
+            playback_position_secs: 0,
+            last_played_at: None,
+            favourite: false,
+        }
+    }
+
+    fn record_blocking(backend_id: BackendId, item_id: &str, position: Duration) {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let thresholds = ProgressThresholds::default();
+        runtime
+            .block_on(record(
+                backend_id,
+                item_id.to_string(),
+                position,
+                thresholds,
+            ))
+            .unwrap();
+    }
+
+    #[rstest::rstest]
+    fn test_record_progress(_temp_storage: tempfile::TempDir) {
+        let backend_id = BackendId::now_v7();
+        let thresholds = ProgressThresholds::default();
+        assert_eq!(load_thresholds(), thresholds);
+
+        let items = vec![movie("movie", 6000), movie("other", 6000)];
+        super::super::with_relaxed_state(move |state| {
+            state
+                .upsert_library_items(backend_id, "movies", &items, 1)
+                .unwrap();
+        });
+
+        assert_eq!(resume_point(backend_id, "movie", &thresholds), None);
+
+        let position = Duration::from_secs(2533);
+        record_blocking(backend_id, "movie", position);
+        assert_eq!(
+            resume_point(backend_id, "movie", &thresholds),
+            Some(position)
+        );
+        let items = continue_watching(backend_id, &thresholds, 10);
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].id, "movie");
+
+        // Finishing the movie clears the resume point.
+        record_blocking(backend_id, "movie", Duration::from_secs(5800));
+        assert_eq!(resume_point(backend_id, "movie", &thresholds), None);
+        assert!(continue_watching(backend_id, &thresholds, 10).is_empty());
+
+        // Stricter thresholds are applied to existing positions.
+        record_blocking(backend_id, "other", Duration::from_secs(600));
+        let strict = ProgressThresholds {
+            min_resume_percent: 20.0,
+            ..thresholds
+        };
+        save_thresholds(&strict);
+        assert_eq!(load_thresholds(), strict);
+        assert_eq!(resume_point(backend_id, "other", &strict), None);
+        assert!(continue_watching(backend_id, &strict, 10).is_empty());
+
+        // Newer state from the backend replaces the local state.
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let user_data = UserData {
+            played: false,
+            position: Duration::from_secs(3000),
+            favourite: true,
+            rating: None,
+            last_played_at: Some(super::super::now()),
+        };
+        runtime
+            .block_on(overwrite(backend_id, "other".to_string(), user_data))
+            .unwrap();
+        assert_eq!(
+            resume_point(backend_id, "other", &thresholds),
+            Some(Duration::from_secs(3000))
+        );
+    }
+}
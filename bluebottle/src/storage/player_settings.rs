@@ -0,0 +1,38 @@
+//! Persists which external player is used for video playback.
+
+use super::settings::Setting;
+use crate::models::playback::PlayerSettings;
+
+static PLAYER_SETTINGS: Setting<PlayerSettings> =
+    Setting::durable("player_settings", PlayerSettings::default);
+
+/// Load the player settings, falling back to the defaults if none are saved.
+pub fn load() -> PlayerSettings {
+    PLAYER_SETTINGS.entry().load()
+}
+
+/// Persist the player settings, waiting for them to be written.
+pub async fn save_async(settings: PlayerSettings) {
+    PLAYER_SETTINGS.entry().save_async(&settings).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::test_utils::temp_storage;
+
+    #[rstest::rstest]
+    fn test_save_and_load(_temp_storage: tempfile::TempDir) {
+        assert_eq!(load(), PlayerSettings::default());
+
+        let settings = PlayerSettings {
+            command: "/usr/local/bin/mpv".into(),
+            args: vec!["--fullscreen".into()],
+        };
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(save_async(settings.clone()));
+        assert_eq!(load(), settings);
+    }
+}
@@ -0,0 +1,63 @@
+//! Persists the most recent search terms entered by the user.
+
+use super::settings::Setting;
+
+static RECENT_SEARCHES: Setting<Vec<String>> =
+    Setting::relaxed("recent_searches", Vec::new);
+
+/// The maximum number of search terms remembered.
+pub const MAX_RECENT_SEARCHES: usize = 10;
+
+/// Load the recent search terms, most recent first.
+pub fn load() -> Vec<String> {
+    RECENT_SEARCHES.entry().load()
+}
+
+/// Record a new search term, moving it to the front if it was already present.
+pub fn record(term: &str) {
+    let term = term.trim().to_string();
+    if term.is_empty() {
+        return;
+    }
+
+    RECENT_SEARCHES.entry().update(move |terms| {
+        terms.retain(|existing| !existing.eq_ignore_ascii_case(&term));
+        terms.insert(0, term);
+        terms.truncate(MAX_RECENT_SEARCHES);
+    });
+}
+
+/// Forget all recent search terms.
+pub fn clear() {
+    RECENT_SEARCHES.entry().remove();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::test_utils::temp_storage;
+
+    #[rstest::rstest]
+    fn test_record_and_clear(_temp_storage: tempfile::TempDir) {
+        assert!(load().is_empty());
+
+        record("alien");
+        record("  ");
+        record("blade runner");
+        record("Alien");
+        assert_eq!(
+            load(),
+            vec!["Alien".to_string(), "blade runner".to_string()]
+        );
+
+        for i in 0..MAX_RECENT_SEARCHES + 5 {
+            record(&format!("term {i}"));
+        }
+        let terms = load();
+        assert_eq!(terms.len(), MAX_RECENT_SEARCHES);
+        assert_eq!(terms[0], format!("term {}", MAX_RECENT_SEARCHES + 4));
+
+        clear();
+        assert!(load().is_empty());
+    }
+}
@@ -1,10 +1,30 @@
 use std::cmp;
 use std::time::Duration;
 
-use rusqlite::params;
+use rusqlite::functions::FunctionFlags;
+use rusqlite::{OptionalExtension, ToSql, params, params_from_iter};
 use snafu::ResultExt;
 
+use super::asset_cache::IndexedAsset;
+use super::cache_archive::{ARCHIVED_TABLES, TableRows};
+use super::content_cache::{CacheEntry, CachePolicy, Validators};
+use super::library_items::LibrarySyncState;
+use super::playback_progress::PlaybackState;
+use super::search_index::SearchDocument;
+use super::state::{ActorState, close_connection};
 use crate::backends::BackendId;
+use crate::models::interaction::UserData;
+use crate::models::media::{ItemId, ItemKind, ItemPage, ItemSummary};
+use crate::models::playback::ProgressThresholds;
+use crate::models::query::{
+    FilterOptions,
+    ItemFilter,
+    ItemQuery,
+    LibraryViewOptions,
+    SortBy,
+    SortOrder,
+};
+use crate::models::sync::ItemMetadata;
 
 /// System state storage backed by an SQLite database.
 pub struct RelaxedStateStorage {
@@ -14,27 +34,16 @@ pub struct RelaxedStateStorage {
 impl RelaxedStateStorage {
     /// Creates a new [RelaxedStateStorage] instance located within the data directory.
     pub(super) fn open() -> Result<Self, snafu::Whatever> {
-        let conn = open_sqlite_connection()?;
+        let mut conn = open_sqlite_connection()?;
+        register_functions(&conn)?;
         conn.pragma_update(None, "journal_mode", "WAL")
             .whatever_context("update relaxed journal_mode pragma")?;
         conn.pragma_update(None, "synchronous", "OFF")
             .whatever_context("update relaxed synchronous pragma")?;
 
-        let this = Self { conn };
-        this.init_databases()?;
-        Ok(this)
-    }
-
-    fn init_databases(&self) -> Result<(), snafu::Whatever> {
-        static RELAXED_INIT_SQL: &str = include_str!("tables/relaxed_init.sql");
-
-        tracing::info!("initializing database");
-
-        self.conn
-            .execute_batch(RELAXED_INIT_SQL)
-            .whatever_context("initializing relaxed database")?;
-
-        Ok(())
+        // The relaxed state can be rebuilt from the backends, so it is not backed up.
+        super::migrations::migrate(&mut conn, &super::migrations::RELAXED, None)?;
+        Ok(Self { conn })
     }
 
     /// Retrieve an existing cached content entry.
@@ -42,9 +51,9 @@ impl RelaxedStateStorage {
         &self,
         backend_id: BackendId,
         path: &str,
-    ) -> Result<(Vec<u8>, Duration), snafu::Whatever> {
+    ) -> Result<CacheEntry<Vec<u8>>, snafu::Whatever> {
         let sql = r#"
-            SELECT content, expires_at
+            SELECT content, expires_at, stale_at, etag, last_modified
             FROM backend_content_cache
             WHERE backend_id = ? AND cache_key = ?;
         "#;
@@ -54,15 +63,30 @@ impl RelaxedStateStorage {
             .prepare_cached(sql)
             .whatever_context("prepared backend content")?;
 
-        let (content, expires_at) = stmt
+        let (value, expires_at, stale_at, validators) = stmt
             .query_row(params![backend_id, path], |row| {
-                Ok((row.get(0)?, row.get::<_, i64>(1)?))
+                let validators = Validators {
+                    etag: row.get(3)?,
+                    last_modified: row.get(4)?,
+                };
+                Ok((
+                    row.get(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, Option<i64>>(2)?,
+                    validators,
+                ))
             })
             .whatever_context("get backend content")?;
 
-        let expires_in = cmp::max(0, expires_at - super::now()) as u64;
+        let now = super::now();
+        let remaining = |at: i64| Duration::from_millis(cmp::max(0, at - now) as u64);
 
-        Ok((content, Duration::from_millis(expires_in)))
+        Ok(CacheEntry {
+            value,
+            expires_in: remaining(expires_at),
+            fresh_for: remaining(stale_at.unwrap_or(expires_at)),
+            validators,
+        })
     }
 
     /// Add a new content cache entry.
@@ -71,10 +95,11 @@ impl RelaxedStateStorage {
         backend_id: BackendId,
         path: &str,
         content: Vec<u8>,
-        ttl: Duration,
+        policy: &CachePolicy,
     ) -> Result<(), snafu::Whatever> {
         let now = super::now();
-        let expires_at = now + ttl.as_millis() as i64;
+        let stale_at = now + policy.fresh_for.as_millis() as i64;
+        let expires_at = stale_at + policy.stale_for.as_millis() as i64;
 
         let sql = r#"
             INSERT INTO backend_content_cache (
@@ -82,13 +107,19 @@ impl RelaxedStateStorage {
                 cache_key,
                 content,
                 updated_at,
-                expires_at
-            ) VALUES (?, ?, ?, ?, ?)
+                expires_at,
+                stale_at,
+                etag,
+                last_modified
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?)
             ON CONFLICT (backend_id, cache_key)
             DO UPDATE SET
                 content = excluded.content,
                 updated_at = excluded.updated_at,
-                expires_at = excluded.expires_at;
+                expires_at = excluded.expires_at,
+                stale_at = excluded.stale_at,
+                etag = excluded.etag,
+                last_modified = excluded.last_modified;
         "#;
 
         let mut stmt = self
@@ -96,8 +127,81 @@ impl RelaxedStateStorage {
             .prepare_cached(sql)
             .whatever_context("prepared backend content")?;
 
-        stmt.insert(params![backend_id, path, content, now, expires_at])
-            .whatever_context("insert backend content")?;
+        stmt.insert(params![
+            backend_id,
+            path,
+            content,
+            now,
+            expires_at,
+            stale_at,
+            policy.validators.etag,
+            policy.validators.last_modified,
+        ])
+        .whatever_context("insert backend content")?;
+
+        Ok(())
+    }
+
+    /// Renew a content cache entry the backend confirmed is unchanged, keeping its
+    /// validators unless the backend sent new ones.
+    ///
+    /// Returns `false` if there is no such entry.
+    pub(super) fn renew_content_cache_entry(
+        &self,
+        backend_id: BackendId,
+        path: &str,
+        policy: &CachePolicy,
+    ) -> Result<bool, snafu::Whatever> {
+        let now = super::now();
+        let stale_at = now + policy.fresh_for.as_millis() as i64;
+        let expires_at = stale_at + policy.stale_for.as_millis() as i64;
+
+        let sql = r#"
+            UPDATE backend_content_cache
+            SET
+                updated_at = ?3,
+                expires_at = ?4,
+                stale_at = ?5,
+                etag = COALESCE(?6, etag),
+                last_modified = COALESCE(?7, last_modified)
+            WHERE backend_id = ?1 AND cache_key = ?2;
+        "#;
+
+        let mut stmt = self
+            .conn
+            .prepare_cached(sql)
+            .whatever_context("prepared backend content renew")?;
+
+        let n = stmt
+            .execute(params![
+                backend_id,
+                path,
+                now,
+                expires_at,
+                stale_at,
+                policy.validators.etag,
+                policy.validators.last_modified,
+            ])
+            .whatever_context("renew backend content")?;
+
+        Ok(n > 0)
+    }
+
+    /// Remove a content cache entry.
+    pub(super) fn remove_content_cache_entry(
+        &self,
+        backend_id: BackendId,
+        path: &str,
+    ) -> Result<(), snafu::Whatever> {
+        let mut stmt = self
+            .conn
+            .prepare_cached(
+                "DELETE FROM backend_content_cache WHERE backend_id = ? AND cache_key = ?;",
+            )
+            .whatever_context("prepared backend content delete")?;
+
+        stmt.execute(params![backend_id, path])
+            .whatever_context("delete backend content")?;
 
         Ok(())
     }
@@ -132,10 +236,316 @@ impl RelaxedStateStorage {
         Ok(n)
     }
 
+    /// Purge the content cache of all entries belonging to the backend.
+    pub(super) fn purge_backend_content_cache(
+        &self,
+        backend_id: BackendId,
+    ) -> Result<usize, snafu::Whatever> {
+        let mut stmt = self
+            .conn
+            .prepare_cached("DELETE FROM backend_content_cache WHERE backend_id = ?;")
+            .whatever_context("prepared backend content")?;
+
+        let n = stmt
+            .execute(params![backend_id])
+            .whatever_context("execute backend purge query")?;
+
+        Ok(n)
+    }
+
+    /// Record an asset written to the cache, or update the size of an existing
+    /// one, marking it as last used at `accessed_at`.
+    ///
+    /// The path the asset was cached under is kept if none is given.
+    pub(super) fn upsert_asset_cache_entry(
+        &self,
+        asset_id: &str,
+        path: Option<&str>,
+        size_bytes: u64,
+        accessed_at: i64,
+    ) -> Result<(), snafu::Whatever> {
+        let sql = r#"
+            INSERT INTO asset_cache_entries (
+                asset_id,
+                path,
+                size_bytes,
+                created_at,
+                last_accessed_at
+            ) VALUES (?1, ?2, ?3, ?4, ?4)
+            ON CONFLICT (asset_id)
+            DO UPDATE SET
+                path = COALESCE(excluded.path, path),
+                size_bytes = excluded.size_bytes,
+                last_accessed_at = MAX(last_accessed_at, excluded.last_accessed_at);
+        "#;
+
+        let mut stmt = self
+            .conn
+            .prepare_cached(sql)
+            .whatever_context("prepared asset cache upsert")?;
+
+        stmt.execute(params![asset_id, path, size_bytes as i64, accessed_at])
+            .whatever_context("upsert asset cache entry")?;
+
+        Ok(())
+    }
+
+    /// Mark a cached asset as last used at `accessed_at`.
+    pub(super) fn touch_asset_cache_entry(
+        &self,
+        asset_id: &str,
+        accessed_at: i64,
+    ) -> Result<(), snafu::Whatever> {
+        let mut stmt = self
+            .conn
+            .prepare_cached(
+                "UPDATE asset_cache_entries SET last_accessed_at = ? WHERE asset_id = ?;",
+            )
+            .whatever_context("prepared asset cache touch")?;
+
+        stmt.execute(params![accessed_at, asset_id])
+            .whatever_context("touch asset cache entry")?;
+
+        Ok(())
+    }
+
+    /// List every cached asset, least recently used first.
+    pub(super) fn list_asset_cache_entries(
+        &self,
+    ) -> Result<Vec<IndexedAsset>, snafu::Whatever> {
+        let sql = r#"
+            SELECT asset_id, size_bytes, last_accessed_at
+            FROM asset_cache_entries
+            ORDER BY last_accessed_at ASC, asset_id ASC;
+        "#;
+
+        let mut stmt = self
+            .conn
+            .prepare_cached(sql)
+            .whatever_context("prepared asset cache select")?;
+
+        stmt.query_map([], |row| {
+            Ok(IndexedAsset {
+                asset_id: row.get(0)?,
+                size_bytes: row.get::<_, i64>(1)? as u64,
+                last_accessed_at: row.get(2)?,
+            })
+        })
+        .whatever_context("list asset cache entries")?
+        .collect::<Result<_, _>>()
+        .whatever_context("read asset cache entry")
+    }
+
+    /// Remove the cached assets from the index.
+    ///
+    /// Returns the number of entries removed.
+    pub(super) fn remove_asset_cache_entries(
+        &self,
+        asset_ids: &[String],
+    ) -> Result<usize, snafu::Whatever> {
+        let txn = self
+            .conn
+            .unchecked_transaction()
+            .whatever_context("begin asset cache transaction")?;
+
+        let mut n = 0;
+        {
+            let mut stmt = txn
+                .prepare_cached("DELETE FROM asset_cache_entries WHERE asset_id = ?;")
+                .whatever_context("prepared asset cache delete")?;
+
+            for asset_id in asset_ids {
+                n += stmt
+                    .execute(params![asset_id])
+                    .whatever_context("delete asset cache entry")?;
+            }
+        }
+
+        txn.commit()
+            .whatever_context("commit asset cache transaction")?;
+
+        Ok(n)
+    }
+
+    /// Returns the number of cached assets and their total size in bytes.
+    pub(super) fn asset_cache_usage(&self) -> Result<(usize, u64), snafu::Whatever> {
+        let sql = r#"
+            SELECT COUNT(*), COALESCE(SUM(size_bytes), 0)
+            FROM asset_cache_entries;
+        "#;
+
+        let mut stmt = self
+            .conn
+            .prepare_cached(sql)
+            .whatever_context("prepared asset cache usage")?;
+
+        stmt.query_row([], |row| {
+            Ok((row.get::<_, i64>(0)? as usize, row.get::<_, i64>(1)? as u64))
+        })
+        .whatever_context("get asset cache usage")
+    }
+
+    /// Returns the paths of the cached assets with the backend ID as one of their
+    /// path segments.
+    pub(super) fn list_backend_asset_paths(
+        &self,
+        backend_id: BackendId,
+    ) -> Result<Vec<String>, snafu::Whatever> {
+        let sql = r#"
+            SELECT path
+            FROM asset_cache_entries
+            WHERE '/' || path || '/' LIKE '%/' || ? || '/%'
+            ORDER BY path;
+        "#;
+
+        let mut stmt = self
+            .conn
+            .prepare_cached(sql)
+            .whatever_context("prepared backend asset select")?;
+
+        stmt.query_map(params![backend_id.to_string()], |row| row.get(0))
+            .whatever_context("list backend assets")?
+            .collect::<Result<_, _>>()
+            .whatever_context("read backend asset path")
+    }
+
+    /// Returns the rows of an archived table belonging to the backend, leaving
+    /// out the `skipped` columns.
+    pub(super) fn export_backend_rows(
+        &self,
+        backend_id: BackendId,
+        table: &str,
+        skipped: &[&str],
+    ) -> Result<TableRows, snafu::Whatever> {
+        let columns: Vec<String> = self
+            .table_columns(table)?
+            .into_iter()
+            .filter(|column| !skipped.contains(&column.as_str()))
+            .collect();
+        let sql = format!(
+            "SELECT {} FROM {table} WHERE backend_id = ?;",
+            columns.join(", "),
+        );
+
+        let mut stmt = self
+            .conn
+            .prepare(&sql)
+            .with_whatever_context(|_| format!("prepared {table} export"))?;
+
+        let rows = stmt
+            .query_map(params![backend_id], |row| {
+                (0..columns.len())
+                    .map(|index| row.get::<_, rusqlite::types::Value>(index))
+                    .map(|value| value.map(Into::into))
+                    .collect()
+            })
+            .with_whatever_context(|_| format!("export {table} rows"))?
+            .collect::<Result<_, _>>()
+            .with_whatever_context(|_| format!("read {table} row"))?;
+
+        Ok(TableRows {
+            table: table.to_string(),
+            columns,
+            rows,
+        })
+    }
+
+    /// Replace the rows of the archived tables belonging to the backend with the
+    /// imported rows, which are assigned to the backend.
+    ///
+    /// Returns the number of rows imported.
+    pub(super) fn import_backend_rows(
+        &self,
+        backend_id: BackendId,
+        tables: &[TableRows],
+    ) -> Result<usize, snafu::Whatever> {
+        let txn = self
+            .conn
+            .unchecked_transaction()
+            .whatever_context("begin import transaction")?;
+
+        for (table, _) in ARCHIVED_TABLES {
+            txn.execute(
+                &format!("DELETE FROM {table} WHERE backend_id = ?;"),
+                params![backend_id],
+            )
+            .with_whatever_context(|_| format!("clear {table} rows"))?;
+        }
+
+        let mut n = 0;
+        for dump in tables {
+            // Names are written into the SQL, so only known tables and columns
+            // are accepted.
+            let table = dump.table.as_str();
+            if !ARCHIVED_TABLES.iter().any(|(name, _)| *name == table) {
+                snafu::whatever!("archive contains unknown table {table}");
+            }
+            let known_columns = self.table_columns(table)?;
+            if let Some(column) = dump
+                .columns
+                .iter()
+                .find(|column| !known_columns.contains(column))
+            {
+                snafu::whatever!("archive contains unknown column {table}.{column}");
+            }
+            let Some(backend_column) = dump
+                .columns
+                .iter()
+                .position(|column| column == "backend_id")
+            else {
+                snafu::whatever!("archived {table} rows have no backend ID");
+            };
+
+            let sql = format!(
+                "INSERT INTO {table} ({}) VALUES ({});",
+                dump.columns.join(", "),
+                vec!["?"; dump.columns.len()].join(", "),
+            );
+            let mut stmt = txn
+                .prepare(&sql)
+                .with_whatever_context(|_| format!("prepared {table} import"))?;
+
+            for row in &dump.rows {
+                if row.len() != dump.columns.len() {
+                    snafu::whatever!(
+                        "archived {table} row has the wrong number of values"
+                    );
+                }
+                let values = row.iter().enumerate().map(|(index, value)| {
+                    if index == backend_column {
+                        &backend_id as &dyn ToSql
+                    } else {
+                        value as &dyn ToSql
+                    }
+                });
+                stmt.execute(params_from_iter(values))
+                    .with_whatever_context(|_| format!("import {table} row"))?;
+                n += 1;
+            }
+        }
+
+        txn.commit().whatever_context("commit import transaction")?;
+
+        Ok(n)
+    }
+
+    /// Returns the names of the columns of the table, in order.
+    fn table_columns(&self, table: &str) -> Result<Vec<String>, snafu::Whatever> {
+        let mut stmt = self
+            .conn
+            .prepare_cached("SELECT name FROM pragma_table_info(?) ORDER BY cid;")
+            .whatever_context("prepared table info")?;
+
+        stmt.query_map(params![table], |row| row.get(0))
+            .with_whatever_context(|_| format!("get {table} columns"))?
+            .collect::<Result<_, _>>()
+            .with_whatever_context(|_| format!("read {table} column"))
+    }
+
     /// Set a key value in the app state.
     pub(crate) fn set_key_value(
         &self,
-        key: &'static str,
+        key: &str,
         value: &[u8],
     ) -> Result<(), snafu::Whatever> {
         let sql = r#"
@@ -157,10 +567,7 @@ impl RelaxedStateStorage {
     }
 
     /// Get a key value in the app state.
-    pub(crate) fn get_key_value(
-        &self,
-        key: &'static str,
-    ) -> Result<Vec<u8>, snafu::Whatever> {
+    pub(crate) fn get_key_value(&self, key: &str) -> Result<Vec<u8>, snafu::Whatever> {
         let mut stmt = self
             .conn
             .prepare_cached("SELECT v FROM app_kv_state WHERE k = ?;")
@@ -172,111 +579,1603 @@ impl RelaxedStateStorage {
 
         Ok(value)
     }
-}
 
-fn open_sqlite_connection() -> Result<rusqlite::Connection, snafu::Whatever> {
-    if cfg!(test) {
-        return rusqlite::Connection::open_in_memory()
-            .whatever_context("open relaxed SQLite database");
-    };
+    /// Remove a key value from the app state.
+    pub(crate) fn remove_key_value(&self, key: &str) -> Result<(), snafu::Whatever> {
+        let mut stmt = self
+            .conn
+            .prepare_cached("DELETE FROM app_kv_state WHERE k = ?;")
+            .whatever_context("prepared key value delete")?;
 
-    let paths = super::directory::paths();
-    let relaxed_path = paths.data_dir().join("relaxed.sqlite");
+        stmt.execute(params![key])
+            .whatever_context("execute key delete query")?;
 
-    match rusqlite::Connection::open(&relaxed_path) {
-        Ok(conn) => Ok(conn),
-        Err(err) => {
-            tracing::warn!(error = %err, "relaxed state could not be opened, truncating and retrying...");
-            let _ = std::fs::remove_file(&relaxed_path);
-            rusqlite::Connection::open(&relaxed_path)
-                .whatever_context("failed to open relaxed state storage")
-        },
+        Ok(())
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
 
-    #[test]
-    fn test_add_and_get_content_cache() {
-        let backend_id = BackendId::now_v7();
+    /// Insert or replace documents within the search index.
+    pub(super) fn upsert_search_documents(
+        &self,
+        documents: &[SearchDocument],
+    ) -> Result<(), snafu::Whatever> {
+        let txn = self
+            .conn
+            .unchecked_transaction()
+            .whatever_context("begin search index transaction")?;
 
-        let storage = RelaxedStateStorage::open().unwrap();
-        storage
-            .add_content_cache_entry(
-                backend_id,
-                "/example",
-                b"hello, world!".to_vec(),
-                Duration::from_secs(300),
+        for document in documents {
+            upsert_search_item(
+                &txn,
+                document.backend_id,
+                &document.item,
+                document.original_name.as_deref(),
+                &document.people,
             )
-            .unwrap();
+            .whatever_context("upsert search document")?;
+        }
 
-        let (entry, expires_in) = storage
-            .get_content_cache_entry(backend_id, "/example")
-            .unwrap();
-        assert_eq!(entry, b"hello, world!");
-        assert!(expires_in <= Duration::from_secs(300));
+        txn.commit()
+            .whatever_context("commit search index transaction")?;
+
+        Ok(())
     }
 
-    #[test]
-    fn test_prune_content_cache() {
-        let backend_id = BackendId::now_v7();
+    /// Remove the given items of a backend from the search index.
+    pub(super) fn remove_search_documents(
+        &self,
+        backend_id: BackendId,
+        item_ids: &[ItemId],
+    ) -> Result<usize, snafu::Whatever> {
+        let txn = self
+            .conn
+            .unchecked_transaction()
+            .whatever_context("begin search index transaction")?;
 
-        let storage = RelaxedStateStorage::open().unwrap();
-        storage
-            .add_content_cache_entry(
-                backend_id,
-                "/example",
-                b"hello, world!".to_vec(),
-                Duration::from_millis(50),
-            )
-            .unwrap();
+        let mut n = 0;
+        {
+            let mut stmt = txn
+                .prepare_cached(
+                    "DELETE FROM search_items WHERE backend_id = ? AND item_id = ?;",
+                )
+                .whatever_context("prepared search document delete")?;
 
-        assert!(
-            storage
-                .get_content_cache_entry(backend_id, "/example")
-                .is_ok()
-        );
+            for item_id in item_ids {
+                n += stmt
+                    .execute(params![backend_id, item_id])
+                    .whatever_context("delete search document")?;
+            }
+        }
 
-        std::thread::sleep(Duration::from_millis(100));
-        let n = storage.prune_content_cache().unwrap();
-        assert_eq!(n, 1);
+        txn.commit()
+            .whatever_context("commit search index transaction")?;
 
-        assert!(
-            storage
-                .get_content_cache_entry(backend_id, "/example")
-                .is_err()
-        );
+        Ok(n)
     }
 
-    #[test]
-    fn test_purge_content_cache() {
-        let backend_id = BackendId::now_v7();
+    /// Remove all items of a backend from the search index.
+    pub(super) fn clear_search_documents(
+        &self,
+        backend_id: BackendId,
+    ) -> Result<usize, snafu::Whatever> {
+        let mut stmt = self
+            .conn
+            .prepare_cached("DELETE FROM search_items WHERE backend_id = ?;")
+            .whatever_context("prepared search document clear")?;
 
-        let storage = RelaxedStateStorage::open().unwrap();
-        storage
-            .add_content_cache_entry(
-                backend_id,
-                "/example",
-                b"hello, world!".to_vec(),
-                Duration::from_secs(50),
-            )
-            .unwrap();
+        let n = stmt
+            .execute(params![backend_id])
+            .whatever_context("execute search document clear")?;
 
-        assert!(
-            storage
-                .get_content_cache_entry(backend_id, "/example")
-                .is_ok()
-        );
+        Ok(n)
+    }
 
-        let n = storage.purge_content_cache().unwrap();
-        assert_eq!(n, 1);
+    /// Query the word based search index with an FTS5 match expression.
+    ///
+    /// Matches on the name are ranked above the original name, which is ranked
+    /// above people.
+    pub(super) fn query_search_index(
+        &self,
+        match_expr: &str,
+        limit: usize,
+    ) -> Result<Vec<SearchDocument>, snafu::Whatever> {
+        let sql = r#"
+            SELECT
+                s.backend_id,
+                s.item_id,
+                s.kind,
+                s.production_year,
+                s.name,
+                s.original_name,
+                s.people
+            FROM search_index
+            JOIN search_items s ON s.id = search_index.rowid
+            WHERE search_index MATCH ?
+            ORDER BY bm25(search_index, 10.0, 5.0, 1.0)
+            LIMIT ?;
+        "#;
+        self.query_search_documents(sql, match_expr, limit)
+    }
 
-        assert!(
-            storage
-                .get_content_cache_entry(backend_id, "/example")
-                .is_err()
+    /// Query the trigram search index with an FTS5 match expression.
+    pub(super) fn query_search_trigram_index(
+        &self,
+        match_expr: &str,
+        limit: usize,
+    ) -> Result<Vec<SearchDocument>, snafu::Whatever> {
+        let sql = r#"
+            SELECT
+                s.backend_id,
+                s.item_id,
+                s.kind,
+                s.production_year,
+                s.name,
+                s.original_name,
+                s.people
+            FROM search_index_trigram
+            JOIN search_items s ON s.id = search_index_trigram.rowid
+            WHERE search_index_trigram MATCH ?
+            ORDER BY bm25(search_index_trigram, 10.0, 5.0, 1.0)
+            LIMIT ?;
+        "#;
+        self.query_search_documents(sql, match_expr, limit)
+    }
+
+    /// Scan the search items for any containing the `LIKE` pattern.
+    ///
+    /// This is used for terms too short for the trigram index, the pattern must
+    /// escape wildcards with `\`.
+    pub(super) fn scan_search_documents(
+        &self,
+        like_pattern: &str,
+        limit: usize,
+    ) -> Result<Vec<SearchDocument>, snafu::Whatever> {
+        let sql = r#"
+            SELECT
+                backend_id,
+                item_id,
+                kind,
+                production_year,
+                name,
+                original_name,
+                people
+            FROM search_items
+            WHERE name LIKE ?1 ESCAPE '\'
+                OR original_name LIKE ?1 ESCAPE '\'
+                OR people LIKE ?1 ESCAPE '\'
+            LIMIT ?2;
+        "#;
+        self.query_search_documents(sql, like_pattern, limit)
+    }
+
+    fn query_search_documents(
+        &self,
+        sql: &str,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<SearchDocument>, snafu::Whatever> {
+        let mut stmt = self
+            .conn
+            .prepare_cached(sql)
+            .whatever_context("prepared search query")?;
+
+        stmt.query_map(params![query, limit as i64], |row| {
+            let original_name: String = row.get(5)?;
+            let people: String = row.get(6)?;
+            Ok(SearchDocument {
+                backend_id: row.get(0)?,
+                item: ItemSummary {
+                    id: row.get(1)?,
+                    kind: ItemKind::from_str_lossy(&row.get::<_, String>(2)?),
+                    production_year: row.get(3)?,
+                    name: row.get(4)?,
+                    blurhash: None,
+                },
+                original_name: (!original_name.is_empty()).then_some(original_name),
+                people: people
+                    .split('\n')
+                    .filter(|name| !name.is_empty())
+                    .map(String::from)
+                    .collect(),
+            })
+        })
+        .whatever_context("execute search query")?
+        .collect::<Result<Vec<_>, rusqlite::Error>>()
+        .whatever_context("deserialize search rows")
+    }
+
+    /// Insert or replace the synced items of a library, along with their search
+    /// index entries.
+    pub(super) fn upsert_library_items(
+        &self,
+        backend_id: BackendId,
+        library_id: &str,
+        items: &[ItemMetadata],
+        synced_at: i64,
+    ) -> Result<(), snafu::Whatever> {
+        let sql = r#"
+            INSERT INTO library_items (
+                backend_id,
+                item_id,
+                library_id,
+                parent_id,
+                series_id,
+                season_number,
+                episode_number,
+                kind,
+                name,
+                sort_name,
+                original_name,
+                production_year,
+                premiere_date,
+                date_added,
+                date_last_saved,
+                community_rating,
+                runtime_secs,
+                official_rating,
+                genres,
+                studios,
+                played,
+                playback_position_secs,
+                last_played_at,
+                favourite,
+                blurhash,
+                synced_at
+            ) VALUES (
+                ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?
+            )
+            ON CONFLICT (backend_id, item_id)
+            DO UPDATE SET
+                library_id = excluded.library_id,
+                parent_id = excluded.parent_id,
+                series_id = excluded.series_id,
+                season_number = excluded.season_number,
+                episode_number = excluded.episode_number,
+                kind = excluded.kind,
+                name = excluded.name,
+                sort_name = excluded.sort_name,
+                original_name = excluded.original_name,
+                production_year = excluded.production_year,
+                premiere_date = excluded.premiere_date,
+                date_added = excluded.date_added,
+                date_last_saved = excluded.date_last_saved,
+                community_rating = excluded.community_rating,
+                runtime_secs = excluded.runtime_secs,
+                official_rating = excluded.official_rating,
+                genres = excluded.genres,
+                studios = excluded.studios,
+                played = excluded.played,
+                playback_position_secs = excluded.playback_position_secs,
+                last_played_at = excluded.last_played_at,
+                favourite = excluded.favourite,
+                blurhash = excluded.blurhash,
+                synced_at = excluded.synced_at;
+        "#;
+
+        let txn = self
+            .conn
+            .unchecked_transaction()
+            .whatever_context("begin library items transaction")?;
+
+        {
+            let mut stmt = txn
+                .prepare_cached(sql)
+                .whatever_context("prepared library item upsert")?;
+
+            for item in items {
+                let summary = &item.summary;
+                stmt.execute(params![
+                    backend_id,
+                    summary.id,
+                    library_id,
+                    item.parent_id,
+                    item.series_id,
+                    item.season_number,
+                    item.episode_number,
+                    summary.kind.as_str(),
+                    summary.name,
+                    item.sort_name,
+                    item.original_name,
+                    summary.production_year,
+                    item.premiere_date,
+                    item.date_added,
+                    item.date_last_saved,
+                    item.community_rating,
+                    item.runtime_secs.map(|secs| secs as i64),
+                    item.official_rating,
+                    serde_json::to_string(&item.genres).unwrap(),
+                    serde_json::to_string(&item.studios).unwrap(),
+                    item.played,
+                    item.playback_position_secs as i64,
+                    item.last_played_at,
+                    item.favourite,
+                    summary.blurhash,
+                    synced_at,
+                ])
+                .whatever_context("upsert library item")?;
+
+                upsert_search_item(
+                    &txn,
+                    backend_id,
+                    summary,
+                    item.original_name.as_deref(),
+                    &item.people,
+                )
+                .whatever_context("upsert library item search document")?;
+            }
+        }
+
+        txn.commit()
+            .whatever_context("commit library items transaction")?;
+
+        Ok(())
+    }
+
+    /// Remove any items of a library which were last synced before `synced_before`.
+    ///
+    /// Returns the number of items removed.
+    pub(super) fn remove_stale_library_items(
+        &self,
+        backend_id: BackendId,
+        library_id: &str,
+        synced_before: i64,
+    ) -> Result<usize, snafu::Whatever> {
+        let txn = self
+            .conn
+            .unchecked_transaction()
+            .whatever_context("begin library items transaction")?;
+
+        txn.execute(
+            r#"
+            DELETE FROM search_items
+            WHERE backend_id = ?1 AND item_id IN (
+                SELECT item_id FROM library_items
+                WHERE backend_id = ?1 AND library_id = ?2 AND synced_at < ?3
+            );
+            "#,
+            params![backend_id, library_id, synced_before],
+        )
+        .whatever_context("delete stale search documents")?;
+
+        let n = txn
+            .execute(
+                r#"
+                DELETE FROM library_items
+                WHERE backend_id = ? AND library_id = ? AND synced_at < ?;
+                "#,
+                params![backend_id, library_id, synced_before],
+            )
+            .whatever_context("delete stale library items")?;
+
+        txn.commit()
+            .whatever_context("commit library items transaction")?;
+
+        Ok(n)
+    }
+
+    /// Get the sync state of a library, if it has been synced before.
+    pub(super) fn get_library_sync_state(
+        &self,
+        backend_id: BackendId,
+        library_id: &str,
+    ) -> Result<Option<LibrarySyncState>, snafu::Whatever> {
+        let sql = r#"
+            SELECT cursor, last_sync_at, last_full_sync_at
+            FROM library_sync_state
+            WHERE backend_id = ? AND library_id = ?;
+        "#;
+
+        let mut stmt = self
+            .conn
+            .prepare_cached(sql)
+            .whatever_context("prepared library sync state select")?;
+
+        stmt.query_row(params![backend_id, library_id], |row| {
+            Ok(LibrarySyncState {
+                cursor: row.get(0)?,
+                last_sync_at: row.get(1)?,
+                last_full_sync_at: row.get(2)?,
+            })
+        })
+        .optional()
+        .whatever_context("get library sync state")
+    }
+
+    /// Returns whether any library of the backend has been synced.
+    pub(super) fn has_library_sync_state(
+        &self,
+        backend_id: BackendId,
+    ) -> Result<bool, snafu::Whatever> {
+        let sql = r#"
+            SELECT EXISTS (SELECT 1 FROM library_sync_state WHERE backend_id = ?);
+        "#;
+
+        let mut stmt = self
+            .conn
+            .prepare_cached(sql)
+            .whatever_context("prepared library sync state exists")?;
+
+        stmt.query_row(params![backend_id], |row| row.get(0))
+            .whatever_context("check library sync state exists")
+    }
+
+    /// Set the sync state of a library.
+    pub(super) fn set_library_sync_state(
+        &self,
+        backend_id: BackendId,
+        library_id: &str,
+        state: &LibrarySyncState,
+    ) -> Result<(), snafu::Whatever> {
+        let sql = r#"
+            INSERT INTO library_sync_state (
+                backend_id,
+                library_id,
+                cursor,
+                last_sync_at,
+                last_full_sync_at
+            ) VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT (backend_id, library_id)
+            DO UPDATE SET
+                cursor = excluded.cursor,
+                last_sync_at = excluded.last_sync_at,
+                last_full_sync_at = excluded.last_full_sync_at;
+        "#;
+
+        let mut stmt = self
+            .conn
+            .prepare_cached(sql)
+            .whatever_context("prepared library sync state upsert")?;
+
+        stmt.execute(params![
+            backend_id,
+            library_id,
+            state.cursor,
+            state.last_sync_at,
+            state.last_full_sync_at,
+        ])
+        .whatever_context("set library sync state")?;
+
+        Ok(())
+    }
+
+    /// Query a page of the synced items displayed within a library view.
+    pub(super) fn query_library_items(
+        &self,
+        backend_id: BackendId,
+        query: &ItemQuery,
+    ) -> Result<ItemPage, snafu::Whatever> {
+        let (conditions, mut values) =
+            library_view_conditions(backend_id, &query.library_id, &query.options);
+        let order_by = library_view_order(&query.options);
+
+        let total_items: i64 = self
+            .conn
+            .prepare_cached(&format!(
+                "SELECT COUNT(*) FROM library_items WHERE {conditions};"
+            ))
+            .whatever_context("prepared library item count")?
+            .query_row(params_from_iter(values.iter()), |row| row.get(0))
+            .whatever_context("count library items")?;
+
+        if query.options.sort_by == SortBy::Random {
+            values.push(Box::new(query.random_seed as i64));
+        }
+        values.push(Box::new(query.limit as i64));
+        values.push(Box::new(query.start_index as i64));
+
+        let sql = format!(
+            r#"
+            SELECT item_id, kind, name, production_year, blurhash
+            FROM library_items
+            WHERE {conditions}
+            ORDER BY {order_by}
+            LIMIT ? OFFSET ?;
+            "#
+        );
+
+        let items = self
+            .conn
+            .prepare_cached(&sql)
+            .whatever_context("prepared library item query")?
+            .query_map(params_from_iter(values.iter()), item_summary_from_row)
+            .whatever_context("execute library item query")?
+            .collect::<Result<Vec<_>, rusqlite::Error>>()
+            .whatever_context("deserialize library item rows")?;
+
+        Ok(ItemPage {
+            start_index: query.start_index,
+            total_items: total_items as usize,
+            items,
+        })
+    }
+
+    /// Returns the playback position, runtime and played state of a synced item.
+    pub(super) fn get_playback_state(
+        &self,
+        backend_id: BackendId,
+        item_id: &str,
+    ) -> Result<Option<PlaybackState>, snafu::Whatever> {
+        let sql = r#"
+            SELECT playback_position_secs, runtime_secs, played
+            FROM library_items
+            WHERE backend_id = ? AND item_id = ?;
+        "#;
+
+        let mut stmt = self
+            .conn
+            .prepare_cached(sql)
+            .whatever_context("prepared playback state select")?;
+
+        stmt.query_row(params![backend_id, item_id], |row| {
+            Ok(PlaybackState {
+                position: Duration::from_secs(row.get::<_, i64>(0)? as u64),
+                runtime: row
+                    .get::<_, Option<i64>>(1)?
+                    .map(|secs| Duration::from_secs(secs as u64)),
+                played: row.get(2)?,
+            })
+        })
+        .optional()
+        .whatever_context("get playback state")
+    }
+
+    /// Record the playback position and played state of a synced item.
+    ///
+    /// Returns `false` if the item has not been synced.
+    pub(super) fn set_playback_state(
+        &self,
+        backend_id: BackendId,
+        item_id: &str,
+        position: Duration,
+        played: bool,
+        played_at: i64,
+    ) -> Result<bool, snafu::Whatever> {
+        let sql = r#"
+            UPDATE library_items
+            SET playback_position_secs = ?, played = played OR ?, last_played_at = ?
+            WHERE backend_id = ? AND item_id = ?;
+        "#;
+
+        let n = self
+            .conn
+            .prepare_cached(sql)
+            .whatever_context("prepared playback state update")?
+            .execute(params![
+                position.as_secs() as i64,
+                played,
+                played_at,
+                backend_id,
+                item_id,
+            ])
+            .whatever_context("update playback state")?;
+
+        Ok(n > 0)
+    }
+
+    /// Replace the local user state of a synced item with the backend's.
+    ///
+    /// Returns `false` if the item has not been synced.
+    pub(super) fn set_user_data(
+        &self,
+        backend_id: BackendId,
+        item_id: &str,
+        user_data: &UserData,
+    ) -> Result<bool, snafu::Whatever> {
+        let sql = r#"
+            UPDATE library_items
+            SET playback_position_secs = ?, played = ?, favourite = ?, last_played_at = ?
+            WHERE backend_id = ? AND item_id = ?;
+        "#;
+
+        let n = self
+            .conn
+            .prepare_cached(sql)
+            .whatever_context("prepared user data update")?
+            .execute(params![
+                user_data.position.as_secs() as i64,
+                user_data.played,
+                user_data.favourite,
+                user_data.last_played_at,
+                backend_id,
+                item_id,
+            ])
+            .whatever_context("update user data")?;
+
+        Ok(n > 0)
+    }
+
+    /// Returns whether a synced item is one of the user's favourites, `None` if
+    /// the item has not been synced.
+    pub(super) fn get_favourite(
+        &self,
+        backend_id: BackendId,
+        item_id: &str,
+    ) -> Result<Option<bool>, snafu::Whatever> {
+        self.conn
+            .prepare_cached(
+                "SELECT favourite FROM library_items WHERE backend_id = ? AND item_id = ?;",
+            )
+            .whatever_context("prepared favourite select")?
+            .query_row(params![backend_id, item_id], |row| row.get(0))
+            .optional()
+            .whatever_context("get favourite")
+    }
+
+    /// Set whether a synced item is one of the user's favourites.
+    ///
+    /// Returns `false` if the item has not been synced.
+    pub(super) fn set_favourite(
+        &self,
+        backend_id: BackendId,
+        item_id: &str,
+        favourite: bool,
+    ) -> Result<bool, snafu::Whatever> {
+        let n = self
+            .conn
+            .prepare_cached(
+                "UPDATE library_items SET favourite = ? WHERE backend_id = ? AND item_id = ?;",
+            )
+            .whatever_context("prepared favourite update")?
+            .execute(params![favourite, backend_id, item_id])
+            .whatever_context("update favourite")?;
+
+        Ok(n > 0)
+    }
+
+    /// Returns partially played items, most recently played first.
+    pub(super) fn query_continue_watching(
+        &self,
+        backend_id: BackendId,
+        thresholds: &ProgressThresholds,
+        limit: usize,
+    ) -> Result<Vec<ItemSummary>, snafu::Whatever> {
+        // Mirrors `ProgressThresholds::classify` so positions synced from the
+        // backend are judged the same way as local ones.
+        let sql = r#"
+            SELECT item_id, kind, name, production_year, blurhash
+            FROM library_items
+            WHERE backend_id = ?1
+                AND kind IN ('movie', 'episode')
+                AND played = 0
+                AND playback_position_secs > 0
+                AND (
+                    runtime_secs IS NULL
+                    OR runtime_secs = 0
+                    OR (
+                        runtime_secs >= ?2
+                        AND playback_position_secs * 100.0 >= ?3 * runtime_secs
+                        AND playback_position_secs * 100.0 < ?4 * runtime_secs
+                    )
+                )
+            ORDER BY last_played_at DESC
+            LIMIT ?5;
+        "#;
+
+        self.conn
+            .prepare_cached(sql)
+            .whatever_context("prepared continue watching query")?
+            .query_map(
+                params![
+                    backend_id,
+                    thresholds.min_resume_duration.as_secs() as i64,
+                    thresholds.min_resume_percent,
+                    thresholds.max_resume_percent,
+                    limit as i64,
+                ],
+                item_summary_from_row,
+            )
+            .whatever_context("execute continue watching query")?
+            .collect::<Result<Vec<_>, rusqlite::Error>>()
+            .whatever_context("deserialize continue watching rows")
+    }
+
+    /// Returns the next unplayed episode of each series the user is watching,
+    /// ordered by when the series was last watched.
+    pub(super) fn query_next_up(
+        &self,
+        backend_id: BackendId,
+        limit: usize,
+    ) -> Result<Vec<ItemSummary>, snafu::Whatever> {
+        let sql = r#"
+            WITH last_watched AS (
+                SELECT
+                    series_id,
+                    COALESCE(season_number, 0) AS season_number,
+                    COALESCE(episode_number, 0) AS episode_number,
+                    last_played_at,
+                    ROW_NUMBER() OVER (
+                        PARTITION BY series_id ORDER BY last_played_at DESC
+                    ) AS rank
+                FROM library_items
+                WHERE backend_id = ?1
+                    AND kind = 'episode'
+                    AND series_id IS NOT NULL
+                    AND played = 1
+                    AND last_played_at IS NOT NULL
+            ),
+            candidates AS (
+                SELECT
+                    episode.item_id,
+                    episode.kind,
+                    episode.name,
+                    episode.production_year,
+                    episode.blurhash,
+                    watched.last_played_at,
+                    ROW_NUMBER() OVER (
+                        PARTITION BY episode.series_id
+                        ORDER BY episode.season_number, episode.episode_number
+                    ) AS rank
+                FROM last_watched AS watched
+                JOIN library_items AS episode
+                    ON episode.backend_id = ?1
+                    AND episode.series_id = watched.series_id
+                WHERE watched.rank = 1
+                    AND episode.kind = 'episode'
+                    AND episode.played = 0
+                    AND episode.playback_position_secs = 0
+                    AND (
+                        COALESCE(episode.season_number, 0),
+                        COALESCE(episode.episode_number, 0)
+                    ) > (watched.season_number, watched.episode_number)
+            )
+            SELECT item_id, kind, name, production_year, blurhash
+            FROM candidates
+            WHERE rank = 1
+            ORDER BY last_played_at DESC
+            LIMIT ?2;
+        "#;
+
+        self.conn
+            .prepare_cached(sql)
+            .whatever_context("prepared next up query")?
+            .query_map(params![backend_id, limit as i64], item_summary_from_row)
+            .whatever_context("execute next up query")?
+            .collect::<Result<Vec<_>, rusqlite::Error>>()
+            .whatever_context("deserialize next up rows")
+    }
+
+    /// Returns the values the synced items of a library view can be filtered by.
+    pub(super) fn library_filter_options(
+        &self,
+        backend_id: BackendId,
+        library_id: &str,
+    ) -> Result<FilterOptions, snafu::Whatever> {
+        let (conditions, values) = library_view_conditions(
+            backend_id,
+            library_id,
+            &LibraryViewOptions::default(),
+        );
+
+        let distinct = |expr: &str,
+                        from: &str|
+         -> Result<Vec<rusqlite::types::Value>, snafu::Whatever> {
+            let sql = format!(
+                r#"
+                SELECT DISTINCT {expr}
+                FROM library_items{from}
+                WHERE {conditions} AND {expr} IS NOT NULL
+                ORDER BY 1;
+                "#
+            );
+            self.conn
+                .prepare_cached(&sql)
+                .whatever_context("prepared library filter options")?
+                .query_map(params_from_iter(values.iter()), |row| row.get(0))
+                .whatever_context("execute library filter options")?
+                .collect::<Result<Vec<_>, rusqlite::Error>>()
+                .whatever_context("deserialize library filter options")
+        };
+
+        let text = |values: Vec<rusqlite::types::Value>| {
+            values
+                .into_iter()
+                .filter_map(|value| match value {
+                    rusqlite::types::Value::Text(text) => Some(text),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        let years = distinct("production_year", "")?
+            .into_iter()
+            .filter_map(|value| match value {
+                rusqlite::types::Value::Integer(year) => u32::try_from(year).ok(),
+                _ => None,
+            })
+            .collect();
+
+        Ok(FilterOptions {
+            genres: text(distinct("genre.value", ", json_each(genres) AS genre")?),
+            years,
+            official_ratings: text(distinct("official_rating", "")?),
+            studios: text(distinct("studio.value", ", json_each(studios) AS studio")?),
+        })
+    }
+}
+
+/// The item kinds displayed within a library view.
+const LIBRARY_VIEW_KINDS: [ItemKind; 4] = [
+    ItemKind::Movie,
+    ItemKind::Series,
+    ItemKind::BoxSet,
+    ItemKind::MusicAlbum,
+];
+
+fn item_summary_from_row(row: &rusqlite::Row) -> rusqlite::Result<ItemSummary> {
+    Ok(ItemSummary {
+        id: row.get(0)?,
+        kind: ItemKind::from_str_lossy(&row.get::<_, String>(1)?),
+        name: row.get(2)?,
+        production_year: row.get(3)?,
+        blurhash: row.get(4)?,
+    })
+}
+
+/// Builds the `WHERE` conditions and their bound values selecting the items of a
+/// library view matching the filters.
+///
+/// Items match the genre, rating and studio filters if they match any of the
+/// selected values, as with Jellyfin's `|` delimited parameters, so the local
+/// copy and the backend agree on the results.
+fn library_view_conditions(
+    backend_id: BackendId,
+    library_id: &str,
+    options: &LibraryViewOptions,
+) -> (String, Vec<Box<dyn ToSql>>) {
+    let kinds = LIBRARY_VIEW_KINDS.map(|_| "?").join(", ");
+    let mut conditions = vec![
+        "backend_id = ?".to_string(),
+        "library_id = ?".to_string(),
+        format!("kind IN ({kinds})"),
+    ];
+    let mut values: Vec<Box<dyn ToSql>> =
+        vec![Box::new(backend_id), Box::new(library_id.to_string())];
+    values.extend(
+        LIBRARY_VIEW_KINDS.map(|kind| Box::new(kind.as_str()) as Box<dyn ToSql>),
+    );
+
+    let mut genres = Vec::new();
+    let mut official_ratings = Vec::new();
+    let mut studios = Vec::new();
+    for filter in options.filters.iter() {
+        match filter {
+            ItemFilter::Genre(genre) => genres.push(genre.clone()),
+            ItemFilter::Years { from, to } => {
+                conditions.push("production_year BETWEEN ? AND ?".into());
+                values.push(Box::new(*from));
+                values.push(Box::new(*to));
+            },
+            ItemFilter::Played => conditions.push("played".into()),
+            ItemFilter::Unplayed => conditions.push("NOT played".into()),
+            ItemFilter::Favourite => conditions.push("favourite".into()),
+            ItemFilter::OfficialRating(rating) => official_ratings.push(rating.clone()),
+            ItemFilter::Studio(studio) => studios.push(studio.clone()),
+        }
+    }
+
+    if !genres.is_empty() {
+        let placeholders = vec!["?"; genres.len()].join(", ");
+        conditions.push(format!(
+            "EXISTS (SELECT 1 FROM json_each(genres) WHERE value IN ({placeholders}))"
+        ));
+        values.extend(
+            genres
+                .into_iter()
+                .map(|genre| Box::new(genre) as Box<dyn ToSql>),
+        );
+    }
+    if !official_ratings.is_empty() {
+        let placeholders = vec!["?"; official_ratings.len()].join(", ");
+        conditions.push(format!("official_rating IN ({placeholders})"));
+        values.extend(
+            official_ratings
+                .into_iter()
+                .map(|rating| Box::new(rating) as Box<dyn ToSql>),
+        );
+    }
+    if !studios.is_empty() {
+        let placeholders = vec!["?"; studios.len()].join(", ");
+        conditions.push(format!(
+            "EXISTS (SELECT 1 FROM json_each(studios) WHERE value IN ({placeholders}))"
+        ));
+        values.extend(
+            studios
+                .into_iter()
+                .map(|studio| Box::new(studio) as Box<dyn ToSql>),
+        );
+    }
+
+    (conditions.join(" AND "), values)
+}
+
+/// Builds the `ORDER BY` clause of a library view.
+///
+/// A random sort orders by [shuffle_key] and binds the query's seed, as ordering
+/// by `random()` shuffles every page again.
+fn library_view_order(options: &LibraryViewOptions) -> String {
+    let dir = match options.sort_order {
+        SortOrder::Ascending => "ASC",
+        SortOrder::Descending => "DESC",
+    };
+
+    match options.sort_by {
+        SortBy::Name => format!("sort_name {dir}"),
+        SortBy::DateAdded => format!("date_added {dir}, sort_name"),
+        SortBy::ReleaseDate => {
+            format!("premiere_date {dir}, production_year {dir}, sort_name")
+        },
+        SortBy::Rating => format!("community_rating {dir}, sort_name"),
+        SortBy::Runtime => format!("runtime_secs {dir}, sort_name"),
+        SortBy::Random => "shuffle_key(?, item_id), item_id".to_string(),
+    }
+}
+
+/// Insert or replace an item within the search index.
+fn upsert_search_item(
+    conn: &rusqlite::Connection,
+    backend_id: BackendId,
+    item: &ItemSummary,
+    original_name: Option<&str>,
+    people: &[String],
+) -> rusqlite::Result<usize> {
+    let sql = r#"
+        INSERT INTO search_items (
+            backend_id,
+            item_id,
+            kind,
+            production_year,
+            name,
+            original_name,
+            people
+        ) VALUES (?, ?, ?, ?, ?, ?, ?)
+        ON CONFLICT (backend_id, item_id)
+        DO UPDATE SET
+            kind = excluded.kind,
+            production_year = excluded.production_year,
+            name = excluded.name,
+            original_name = excluded.original_name,
+            people = excluded.people;
+    "#;
+
+    conn.prepare_cached(sql)?.execute(params![
+        backend_id,
+        item.id,
+        item.kind.as_str(),
+        item.production_year,
+        item.name,
+        original_name.unwrap_or_default(),
+        people.join("\n"),
+    ])
+}
+
+impl ActorState for RelaxedStateStorage {
+    fn close(&self) -> Result<(), snafu::Whatever> {
+        close_connection(&self.conn)
+    }
+}
+
+/// Register the custom SQL functions used by the queries.
+fn register_functions(conn: &rusqlite::Connection) -> Result<(), snafu::Whatever> {
+    let flags = FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC;
+    conn.create_scalar_function("shuffle_key", 2, flags, |ctx| {
+        let seed: i64 = ctx.get(0)?;
+        let item_id: String = ctx.get(1)?;
+        Ok(shuffle_key(seed, &item_id))
+    })
+    .whatever_context("register shuffle_key function")
+}
+
+/// Returns a key ordering items pseudo-randomly, which is the same for every
+/// query with the same seed.
+fn shuffle_key(seed: i64, item_id: &str) -> i64 {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&seed.to_le_bytes());
+    hasher.update(item_id.as_bytes());
+    let hash = hasher.finalize();
+    i64::from_le_bytes(hash.as_bytes()[..8].try_into().unwrap())
+}
+
+fn open_sqlite_connection() -> Result<rusqlite::Connection, snafu::Whatever> {
+    if cfg!(test) {
+        return rusqlite::Connection::open_in_memory()
+            .whatever_context("open relaxed SQLite database");
+    };
+
+    let paths = super::directory::paths();
+    let relaxed_path = paths.data_dir().join("relaxed.sqlite");
+
+    match rusqlite::Connection::open(&relaxed_path) {
+        Ok(conn) => Ok(conn),
+        Err(err) => {
+            tracing::warn!(error = %err, "relaxed state could not be opened, truncating and retrying...");
+            let _ = std::fs::remove_file(&relaxed_path);
+            rusqlite::Connection::open(&relaxed_path)
+                .whatever_context("failed to open relaxed state storage")
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_and_get_content_cache() {
+        let backend_id = BackendId::now_v7();
+
+        let storage = RelaxedStateStorage::open().unwrap();
+        storage
+            .add_content_cache_entry(
+                backend_id,
+                "/example",
+                b"hello, world!".to_vec(),
+                &CachePolicy::ttl(Duration::from_secs(300)),
+            )
+            .unwrap();
+
+        let entry = storage
+            .get_content_cache_entry(backend_id, "/example")
+            .unwrap();
+        assert_eq!(entry.value, b"hello, world!");
+        assert!(entry.expires_in <= Duration::from_secs(300));
+        assert_eq!(entry.fresh_for, entry.expires_in);
+        assert_eq!(entry.validators, Validators::default());
+    }
+
+    #[test]
+    fn test_renew_content_cache() {
+        let backend_id = BackendId::now_v7();
+
+        let storage = RelaxedStateStorage::open().unwrap();
+        let policy = CachePolicy {
+            fresh_for: Duration::ZERO,
+            stale_for: Duration::from_secs(300),
+            validators: Validators {
+                etag: Some("\"v1\"".to_string()),
+                last_modified: Some("Tue, 01 Oct 2024 00:00:00 GMT".to_string()),
+            },
+        };
+        storage
+            .add_content_cache_entry(
+                backend_id,
+                "/example",
+                b"hello, world!".to_vec(),
+                &policy,
+            )
+            .unwrap();
+
+        let entry = storage
+            .get_content_cache_entry(backend_id, "/example")
+            .unwrap();
+        assert!(entry.is_stale());
+        assert!(!entry.is_expired());
+        assert_eq!(entry.validators, policy.validators);
+
+        let renewed = CachePolicy {
+            fresh_for: Duration::from_secs(60),
+            stale_for: Duration::from_secs(300),
+            validators: Validators {
+                etag: Some("\"v2\"".to_string()),
+                last_modified: None,
+            },
+        };
+        assert!(
+            storage
+                .renew_content_cache_entry(backend_id, "/example", &renewed)
+                .unwrap()
+        );
+        assert!(
+            !storage
+                .renew_content_cache_entry(backend_id, "/missing", &renewed)
+                .unwrap()
+        );
+
+        let entry = storage
+            .get_content_cache_entry(backend_id, "/example")
+            .unwrap();
+        assert_eq!(entry.value, b"hello, world!");
+        assert!(!entry.is_stale());
+        assert_eq!(entry.validators.etag.as_deref(), Some("\"v2\""));
+        assert_eq!(
+            entry.validators.last_modified,
+            policy.validators.last_modified
+        );
+
+        storage
+            .remove_content_cache_entry(backend_id, "/example")
+            .unwrap();
+        assert!(
+            storage
+                .get_content_cache_entry(backend_id, "/example")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_prune_content_cache() {
+        let backend_id = BackendId::now_v7();
+
+        let storage = RelaxedStateStorage::open().unwrap();
+        storage
+            .add_content_cache_entry(
+                backend_id,
+                "/example",
+                b"hello, world!".to_vec(),
+                &CachePolicy::ttl(Duration::from_millis(50)),
+            )
+            .unwrap();
+
+        assert!(
+            storage
+                .get_content_cache_entry(backend_id, "/example")
+                .is_ok()
+        );
+
+        std::thread::sleep(Duration::from_millis(100));
+        let n = storage.prune_content_cache().unwrap();
+        assert_eq!(n, 1);
+
+        assert!(
+            storage
+                .get_content_cache_entry(backend_id, "/example")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_purge_content_cache() {
+        let backend_id = BackendId::now_v7();
+
+        let storage = RelaxedStateStorage::open().unwrap();
+        storage
+            .add_content_cache_entry(
+                backend_id,
+                "/example",
+                b"hello, world!".to_vec(),
+                &CachePolicy::ttl(Duration::from_secs(50)),
+            )
+            .unwrap();
+
+        assert!(
+            storage
+                .get_content_cache_entry(backend_id, "/example")
+                .is_ok()
+        );
+
+        let n = storage.purge_content_cache().unwrap();
+        assert_eq!(n, 1);
+
+        assert!(
+            storage
+                .get_content_cache_entry(backend_id, "/example")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_asset_cache_entries() {
+        let storage = RelaxedStateStorage::open().unwrap();
+        storage
+            .upsert_asset_cache_entry("first", Some("a/first"), 10, 100)
+            .unwrap();
+        storage
+            .upsert_asset_cache_entry("second", None, 20, 50)
+            .unwrap();
+        storage
+            .upsert_asset_cache_entry("third", Some("b/third"), 30, 200)
+            .unwrap();
+        assert_eq!(storage.asset_cache_usage().unwrap(), (3, 60));
+
+        // Rewriting an asset never moves it back in time.
+        storage
+            .upsert_asset_cache_entry("third", None, 35, 10)
+            .unwrap();
+        storage.touch_asset_cache_entry("second", 300).unwrap();
+
+        let entries = storage.list_asset_cache_entries().unwrap();
+        let order: Vec<_> = entries
+            .iter()
+            .map(|entry| (entry.asset_id.as_str(), entry.last_accessed_at))
+            .collect();
+        assert_eq!(order, vec![("first", 100), ("third", 200), ("second", 300)]);
+        assert_eq!(storage.asset_cache_usage().unwrap(), (3, 65));
+
+        let n = storage
+            .remove_asset_cache_entries(&["first".to_string(), "missing".to_string()])
+            .unwrap();
+        assert_eq!(n, 1);
+        assert_eq!(storage.asset_cache_usage().unwrap(), (2, 55));
+    }
+
+    fn library_item(
+        id: &str,
+        kind: ItemKind,
+        name: &str,
+        year: u32,
+        genres: &[&str],
+        played: bool,
+    ) -> ItemMetadata {
+        ItemMetadata {
+            summary: ItemSummary {
+                id: id.to_string(),
+                kind,
+                name: name.to_string(),
+                production_year: Some(year),
+                blurhash: None,
+            },
+            parent_id: None,
+            series_id: None,
+            season_number: None,
+            episode_number: None,
+            sort_name: name.to_lowercase(),
+            original_name: None,
+            people: Vec::new(),
+            genres: genres.iter().map(|genre| genre.to_string()).collect(),
+            studios: Vec::new(),
+            official_rating: None,
+            community_rating: None,
+            runtime_secs: None,
+            premiere_date: None,
+            date_added: None,
+            date_last_saved: None,
+            played,
+            playback_position_secs: 0,
+            last_played_at: None,
+            favourite: false,
+        }
+    }
+
+    #[test]
+    fn test_query_library_items() {
+        let backend_id = BackendId::now_v7();
+
+        let storage = RelaxedStateStorage::open().unwrap();
+        storage
+            .upsert_library_items(
+                backend_id,
+                "movies",
+                &[
+                    library_item("1", ItemKind::Movie, "Alien", 1979, &["Horror"], true),
+                    library_item(
+                        "2",
+                        ItemKind::Movie,
+                        "Blade Runner",
+                        1982,
+                        &["Sci-Fi"],
+                        false,
+                    ),
+                    library_item(
+                        "3",
+                        ItemKind::Movie,
+                        "Aliens",
+                        1986,
+                        &["Sci-Fi"],
+                        false,
+                    ),
+                    library_item(
+                        "4",
+                        ItemKind::Episode,
+                        "Pilot",
+                        1990,
+                        &["Drama"],
+                        false,
+                    ),
+                ],
+                1,
+            )
+            .unwrap();
+
+        let mut query = ItemQuery {
+            library_id: "movies".into(),
+            start_index: 0,
+            limit: 10,
+            options: LibraryViewOptions::default(),
+            random_seed: 0,
+        };
+        let page = storage.query_library_items(backend_id, &query).unwrap();
+        let names: Vec<_> = page.items.iter().map(|item| item.name.as_str()).collect();
+        assert_eq!(page.total_items, 3);
+        assert_eq!(names, ["Alien", "Aliens", "Blade Runner"]);
+
+        query.options.sort_order = SortOrder::Descending;
+        query.options.add_filter(ItemFilter::Genre("Sci-Fi".into()));
+        query.options.add_filter(ItemFilter::Unplayed);
+        query.start_index = 1;
+        let page = storage.query_library_items(backend_id, &query).unwrap();
+        let names: Vec<_> = page.items.iter().map(|item| item.name.as_str()).collect();
+        assert_eq!(page.total_items, 2);
+        assert_eq!(names, ["Aliens"]);
+
+        let options = storage
+            .library_filter_options(backend_id, "movies")
+            .unwrap();
+        assert_eq!(options.genres, ["Horror", "Sci-Fi"]);
+        assert_eq!(options.years, [1979, 1982, 1986]);
+    }
+
+    #[test]
+    fn test_query_library_items_random_pages() {
+        let backend_id = BackendId::now_v7();
+
+        let storage = RelaxedStateStorage::open().unwrap();
+        let items: Vec<_> = (0..25)
+            .map(|n| {
+                let id = format!("item-{n}");
+                library_item(&id, ItemKind::Movie, &id, 2000, &[], false)
+            })
+            .collect();
+        storage
+            .upsert_library_items(backend_id, "movies", &items, 1)
+            .unwrap();
+
+        let mut query = ItemQuery {
+            library_id: "movies".into(),
+            start_index: 0,
+            limit: 10,
+            options: LibraryViewOptions {
+                sort_by: SortBy::Random,
+                ..LibraryViewOptions::default()
+            },
+            random_seed: 1234,
+        };
+        let mut shuffled = Vec::new();
+        for start_index in [0, 10, 20] {
+            query.start_index = start_index;
+            let page = storage.query_library_items(backend_id, &query).unwrap();
+            shuffled.extend(item_ids(page.items));
+        }
+
+        let mut sorted = shuffled.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(sorted.len(), 25, "pages overlap: {shuffled:?}");
+        assert_ne!(
+            shuffled,
+            item_ids(items.into_iter().map(|i| i.summary).collect())
+        );
+
+        query.start_index = 0;
+        let page = storage.query_library_items(backend_id, &query).unwrap();
+        assert_eq!(item_ids(page.items), shuffled[..10]);
+    }
+
+    #[test]
+    fn test_query_library_items_filters_match_any_value() {
+        let backend_id = BackendId::now_v7();
+
+        let mut items = vec![
+            library_item("1", ItemKind::Movie, "Alien", 1979, &["Horror"], false),
+            library_item("2", ItemKind::Movie, "Aliens", 1986, &["Action"], false),
+            library_item("3", ItemKind::Movie, "Amelie", 2001, &["Romance"], false),
+        ];
+        items[0].studios = vec!["Brandywine".into()];
+        items[1].studios = vec!["Brandywine".into(), "20th Century".into()];
+        items[2].studios = vec!["Claudie Ossard".into()];
+        items[0].official_rating = Some("R".into());
+        items[1].official_rating = Some("R".into());
+        items[2].official_rating = Some("15".into());
+
+        let storage = RelaxedStateStorage::open().unwrap();
+        storage
+            .upsert_library_items(backend_id, "movies", &items, 1)
+            .unwrap();
+
+        // The values of each filter are sent to Jellyfin `|` delimited, which
+        // matches items with any of them, the local copy must agree.
+        let cases: [(&[ItemFilter], &[&str]); 4] = [
+            (
+                &[
+                    ItemFilter::Genre("Horror".into()),
+                    ItemFilter::Genre("Action".into()),
+                ],
+                &["Alien", "Aliens"],
+            ),
+            (
+                &[
+                    ItemFilter::Studio("20th Century".into()),
+                    ItemFilter::Studio("Claudie Ossard".into()),
+                ],
+                &["Aliens", "Amelie"],
+            ),
+            (
+                &[
+                    ItemFilter::OfficialRating("R".into()),
+                    ItemFilter::OfficialRating("15".into()),
+                ],
+                &["Alien", "Aliens", "Amelie"],
+            ),
+            // Different filters must all match.
+            (
+                &[
+                    ItemFilter::Genre("Horror".into()),
+                    ItemFilter::Genre("Romance".into()),
+                    ItemFilter::Studio("Brandywine".into()),
+                ],
+                &["Alien"],
+            ),
+        ];
+        for (filters, expected) in cases {
+            let mut options = LibraryViewOptions::default();
+            for filter in filters {
+                options.add_filter(filter.clone());
+            }
+            let query = ItemQuery {
+                library_id: "movies".into(),
+                start_index: 0,
+                limit: 10,
+                options,
+                random_seed: 0,
+            };
+            let page = storage.query_library_items(backend_id, &query).unwrap();
+            let names: Vec<_> =
+                page.items.iter().map(|item| item.name.as_str()).collect();
+            assert_eq!(names, expected, "{filters:?}");
+        }
+    }
+
+    fn episode(
+        id: &str,
+        series_id: &str,
+        season: u32,
+        number: u32,
+        played: bool,
+        last_played_at: Option<i64>,
+    ) -> ItemMetadata {
+        let mut item = library_item(id, ItemKind::Episode, id, 2020, &[], played);
+        item.series_id = Some(series_id.to_string());
+        item.season_number = Some(season);
+        item.episode_number = Some(number);
+        item.runtime_secs = Some(1800);
+        item.last_played_at = last_played_at;
+        item
+    }
+
+    fn item_ids(items: Vec<ItemSummary>) -> Vec<String> {
+        items.into_iter().map(|item| item.id).collect()
+    }
+
+    #[test]
+    fn test_query_continue_watching() {
+        let backend_id = BackendId::now_v7();
+        let thresholds = ProgressThresholds::default();
+
+        let storage = RelaxedStateStorage::open().unwrap();
+        let mut items = Vec::new();
+        for (id, position, last_played_at) in [
+            ("barely-started", 60, 1),
+            ("halfway", 3000, 2),
+            ("almost-done", 5900, 3),
+            ("recent", 1200, 4),
+        ] {
+            let mut item = library_item(id, ItemKind::Movie, id, 2020, &[], false);
+            item.runtime_secs = Some(6000);
+            item.playback_position_secs = position;
+            item.last_played_at = Some(last_played_at);
+            items.push(item);
+        }
+        storage
+            .upsert_library_items(backend_id, "movies", &items, 1)
+            .unwrap();
+
+        let continue_watching = storage
+            .query_continue_watching(backend_id, &thresholds, 10)
+            .unwrap();
+        assert_eq!(item_ids(continue_watching), vec!["recent", "halfway"]);
+
+        // Finishing an item removes it.
+        storage
+            .set_playback_state(backend_id, "recent", Duration::ZERO, true, 5)
+            .unwrap();
+        let continue_watching = storage
+            .query_continue_watching(backend_id, &thresholds, 10)
+            .unwrap();
+        assert_eq!(item_ids(continue_watching), vec!["halfway"]);
+        assert_eq!(
+            storage.get_playback_state(backend_id, "recent").unwrap(),
+            Some(PlaybackState {
+                position: Duration::ZERO,
+                runtime: Some(Duration::from_secs(6000)),
+                played: true,
+            }),
+        );
+    }
+
+    #[test]
+    fn test_query_next_up() {
+        let backend_id = BackendId::now_v7();
+
+        let storage = RelaxedStateStorage::open().unwrap();
+        let items = vec![
+            // Watched the first two episodes, skipping ahead from an old rewatch.
+            episode("a-1-1", "series-a", 1, 1, true, Some(5)),
+            episode("a-1-2", "series-a", 1, 2, true, Some(10)),
+            episode("a-1-3", "series-a", 1, 3, false, None),
+            episode("a-2-1", "series-a", 2, 1, false, None),
+            // The next episode is in the following season.
+            episode("b-1-2", "series-b", 1, 2, true, Some(20)),
+            episode("b-2-1", "series-b", 2, 1, false, None),
+            // Finished the series.
+            episode("c-1-1", "series-c", 1, 1, true, Some(30)),
+            // Never started.
+            episode("d-1-1", "series-d", 1, 1, false, None),
+        ];
+        storage
+            .upsert_library_items(backend_id, "shows", &items, 1)
+            .unwrap();
+
+        let next_up = storage.query_next_up(backend_id, 10).unwrap();
+        assert_eq!(item_ids(next_up), vec!["b-2-1", "a-1-3"]);
+    }
+
+    #[test]
+    fn test_remove_stale_library_items() {
+        let backend_id = BackendId::now_v7();
+
+        let storage = RelaxedStateStorage::open().unwrap();
+        let alien = library_item("1", ItemKind::Movie, "Alien", 1979, &[], false);
+        let aliens = library_item("2", ItemKind::Movie, "Aliens", 1986, &[], false);
+        storage
+            .upsert_library_items(backend_id, "movies", &[alien.clone(), aliens], 1)
+            .unwrap();
+        storage
+            .upsert_library_items(backend_id, "movies", &[alien], 2)
+            .unwrap();
+
+        let n = storage
+            .remove_stale_library_items(backend_id, "movies", 2)
+            .unwrap();
+        assert_eq!(n, 1);
+
+        let matches = storage.query_search_index("\"aliens\"", 10).unwrap();
+        assert!(matches.is_empty());
+        let matches = storage.query_search_index("\"alien\"", 10).unwrap();
+        assert_eq!(matches.len(), 1);
+    }
+
+    #[test]
+    fn test_set_favourite() {
+        let backend_id = BackendId::now_v7();
+
+        let storage = RelaxedStateStorage::open().unwrap();
+        assert_eq!(storage.get_favourite(backend_id, "1").unwrap(), None);
+        assert!(!storage.set_favourite(backend_id, "1", true).unwrap());
+
+        let alien = library_item("1", ItemKind::Movie, "Alien", 1979, &[], false);
+        storage
+            .upsert_library_items(backend_id, "movies", &[alien], 1)
+            .unwrap();
+        assert_eq!(storage.get_favourite(backend_id, "1").unwrap(), Some(false));
+
+        assert!(storage.set_favourite(backend_id, "1", true).unwrap());
+        assert_eq!(storage.get_favourite(backend_id, "1").unwrap(), Some(true));
+    }
+
+    #[test]
+    fn test_library_sync_state() {
+        let backend_id = BackendId::now_v7();
+
+        let storage = RelaxedStateStorage::open().unwrap();
+        assert!(
+            storage
+                .get_library_sync_state(backend_id, "movies")
+                .unwrap()
+                .is_none()
+        );
+        assert!(!storage.has_library_sync_state(backend_id).unwrap());
+
+        let state = LibrarySyncState {
+            cursor: Some("2024-01-01T00:00:00.0000000Z".into()),
+            last_sync_at: 10,
+            last_full_sync_at: 5,
+        };
+        storage
+            .set_library_sync_state(backend_id, "movies", &state)
+            .unwrap();
+        assert_eq!(
+            storage
+                .get_library_sync_state(backend_id, "movies")
+                .unwrap(),
+            Some(state),
         );
+        assert!(storage.has_library_sync_state(backend_id).unwrap());
     }
 }
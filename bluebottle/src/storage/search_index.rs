@@ -0,0 +1,338 @@
+//! A local full text index of item metadata, allowing libraries to be searched
+//! instantly and without network access.
+//!
+//! Terms are first matched as word prefixes, then any remaining space in the
+//! results is filled by substring and fuzzy matches using a trigram index. Short
+//! terms which cannot be split into trigrams (common with CJK text) fall back to a
+//! substring scan.
+
+use std::collections::HashSet;
+
+use crate::backends::BackendId;
+use crate::models::media::{ItemId, ItemSummary};
+
+/// The minimum fraction of the term's trigrams that must appear in a fuzzy match.
+const MIN_FUZZY_SCORE: f32 = 0.4;
+/// The number of trigram candidates fetched per requested result, candidates
+/// are then scored and filtered.
+const FUZZY_CANDIDATE_FACTOR: usize = 4;
+
+#[derive(Debug, Clone)]
+/// An item within the search index.
+pub struct SearchDocument {
+    /// The backend the item belongs to.
+    pub backend_id: BackendId,
+    /// The item itself.
+    pub item: ItemSummary,
+    /// The name of the item in its original language, if it differs.
+    pub original_name: Option<String>,
+    /// The names of the people associated with the item, i.e. cast and crew.
+    pub people: Vec<String>,
+}
+
+/// Add or update documents within the index.
+pub fn index(documents: Vec<SearchDocument>) {
+    super::submit_relaxed_state(move |state| {
+        if let Err(err) = state.upsert_search_documents(&documents) {
+            tracing::error!(error = %err, "failed to index search documents");
+        }
+    });
+}
+
+/// Remove items of a backend from the index.
+pub fn remove(backend_id: BackendId, item_ids: Vec<ItemId>) {
+    super::submit_relaxed_state(move |state| {
+        if let Err(err) = state.remove_search_documents(backend_id, &item_ids) {
+            tracing::error!(error = %err, "failed to remove search documents");
+        }
+    });
+}
+
+/// Remove all items of a backend from the index.
+///
+/// Returns the number of items removed.
+pub async fn clear(backend_id: BackendId) -> usize {
+    super::with_relaxed_state_async(move |state| {
+        state
+            .clear_search_documents(backend_id)
+            .unwrap_or_else(|err| {
+                tracing::error!(error = %err, "failed to clear search documents");
+                0
+            })
+    })
+    .await
+}
+
+/// Search the index for items matching the term, returning at most `limit` items
+/// with the best matches first.
+pub async fn search(term: &str, limit: usize) -> Vec<(BackendId, ItemSummary)> {
+    let term = term.trim().to_lowercase();
+    if term.is_empty() || limit == 0 {
+        return Vec::new();
+    }
+
+    let documents = super::with_relaxed_state_async(move |state| {
+        search_documents(state, &term, limit).unwrap_or_else(|err| {
+            tracing::error!(error = %err, "failed to search local index");
+            Vec::new()
+        })
+    })
+    .await;
+
+    documents
+        .into_iter()
+        .map(|document| (document.backend_id, document.item))
+        .collect()
+}
+
+fn search_documents(
+    state: &super::relaxed::RelaxedStateStorage,
+    term: &str,
+    limit: usize,
+) -> Result<Vec<SearchDocument>, snafu::Whatever> {
+    let mut seen = HashSet::new();
+    let mut results = Vec::new();
+
+    if let Some(match_expr) = prefix_match_expr(term) {
+        for document in state.query_search_index(&match_expr, limit)? {
+            push_unique(&mut seen, &mut results, document);
+        }
+    }
+
+    if results.len() >= limit {
+        return Ok(results);
+    }
+
+    let candidates = match trigram_match_expr(term) {
+        Some(match_expr) => {
+            let candidate_limit = limit * FUZZY_CANDIDATE_FACTOR;
+            state.query_search_trigram_index(&match_expr, candidate_limit)?
+        },
+        None => state.scan_search_documents(&like_pattern(term), limit)?,
+    };
+
+    let mut scored: Vec<_> = candidates
+        .into_iter()
+        .map(|document| (match_score(term, &document), document))
+        .filter(|(score, _)| *score >= MIN_FUZZY_SCORE)
+        .collect();
+    scored.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+
+    for (_, document) in scored {
+        if results.len() >= limit {
+            break;
+        }
+        push_unique(&mut seen, &mut results, document);
+    }
+
+    Ok(results)
+}
+
+fn push_unique(
+    seen: &mut HashSet<(BackendId, ItemId)>,
+    results: &mut Vec<SearchDocument>,
+    document: SearchDocument,
+) {
+    if seen.insert((document.backend_id, document.item.id.clone())) {
+        results.push(document);
+    }
+}
+
+/// Builds an FTS5 expression matching every word of the term as a prefix.
+fn prefix_match_expr(term: &str) -> Option<String> {
+    let words: Vec<_> = term
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("\"{word}\"*"))
+        .collect();
+
+    (!words.is_empty()).then(|| words.join(" "))
+}
+
+/// Builds an FTS5 expression matching any trigram of the term, or `None` if the
+/// term is too short to contain a trigram.
+fn trigram_match_expr(term: &str) -> Option<String> {
+    let chars: Vec<char> = term.chars().collect();
+    if chars.len() < 3 {
+        return None;
+    }
+
+    let trigrams: HashSet<String> = chars
+        .windows(3)
+        .map(|window| window.iter().collect::<String>())
+        .collect();
+
+    let parts: Vec<_> = trigrams
+        .into_iter()
+        .map(|trigram| format!("\"{}\"", trigram.replace('"', "\"\"")))
+        .collect();
+
+    Some(parts.join(" OR "))
+}
+
+fn like_pattern(term: &str) -> String {
+    let escaped = term
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{escaped}%")
+}
+
+/// Scores how well the term matches the best field of the document, between
+/// `0.0` and `1.0`.
+fn match_score(term: &str, document: &SearchDocument) -> f32 {
+    std::iter::once(document.item.name.as_str())
+        .chain(document.original_name.as_deref())
+        .chain(document.people.iter().map(String::as_str))
+        .map(|field| field_score(term, &field.to_lowercase()))
+        .fold(0.0, f32::max)
+}
+
+/// Returns `1.0` if the field contains the term, otherwise the fraction of the
+/// term's padded word trigrams which also appear in the field.
+fn field_score(term: &str, field: &str) -> f32 {
+    if field.contains(term) {
+        return 1.0;
+    }
+
+    let term_trigrams = word_trigrams(term);
+    if term_trigrams.is_empty() {
+        return 0.0;
+    }
+
+    let field_trigrams = word_trigrams(field);
+    let matching = term_trigrams.intersection(&field_trigrams).count();
+    matching as f32 / term_trigrams.len() as f32
+}
+
+/// Returns the trigrams of each word, with words padded so the start and end of
+/// a word carry more weight.
+fn word_trigrams(text: &str) -> HashSet<[char; 3]> {
+    let mut trigrams = HashSet::new();
+    for word in text.split_whitespace() {
+        let padded: Vec<char> = "  "
+            .chars()
+            .chain(word.chars())
+            .chain(std::iter::once(' '))
+            .collect();
+        trigrams.extend(
+            padded
+                .windows(3)
+                .map(|window| [window[0], window[1], window[2]]),
+        );
+    }
+    trigrams
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::media::ItemKind;
+    use crate::storage::test_utils::temp_storage;
+
+    fn document(
+        backend_id: BackendId,
+        id: &str,
+        name: &str,
+        original_name: Option<&str>,
+        people: &[&str],
+    ) -> SearchDocument {
+        SearchDocument {
+            backend_id,
+            item: ItemSummary {
+                id: id.to_string(),
+                kind: ItemKind::Movie,
+                name: name.to_string(),
+                production_year: None,
+                blurhash: None,
+            },
+            original_name: original_name.map(String::from),
+            people: people.iter().map(|name| name.to_string()).collect(),
+        }
+    }
+
+    fn search_ids(term: &str) -> Vec<String> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime
+            .block_on(search(term, 10))
+            .into_iter()
+            .map(|(_, item)| item.id)
+            .collect()
+    }
+
+    #[rstest::rstest]
+    fn test_search_index(_temp_storage: tempfile::TempDir) {
+        let backend_id = BackendId::now_v7();
+        index(vec![
+            document(
+                backend_id,
+                "blade-runner",
+                "Blade Runner",
+                None,
+                &["Harrison Ford"],
+            ),
+            document(
+                backend_id,
+                "matrix",
+                "The Matrix",
+                None,
+                &["Keanu Reeves", "Carrie-Anne Moss"],
+            ),
+            document(
+                backend_id,
+                "spirited-away",
+                "Spirited Away",
+                Some("千と千尋の神隠し"),
+                &["Hayao Miyazaki"],
+            ),
+            document(backend_id, "amelie", "Amélie", None, &[]),
+        ]);
+
+        // Prefix matching across words.
+        assert_eq!(search_ids("bla run"), vec!["blade-runner"]);
+        // Diacritics are ignored.
+        assert_eq!(search_ids("amelie"), vec!["amelie"]);
+        // People are searchable.
+        assert_eq!(search_ids("keanu"), vec!["matrix"]);
+        // Fuzzy matching of misspelt terms.
+        assert_eq!(search_ids("matirx"), vec!["matrix"]);
+        // CJK substrings, both shorter and longer than a trigram.
+        assert_eq!(search_ids("千尋"), vec!["spirited-away"]);
+        assert_eq!(search_ids("神隠し"), vec!["spirited-away"]);
+        assert!(search_ids("zzzz").is_empty());
+
+        // Updates replace the existing document.
+        index(vec![document(
+            backend_id,
+            "matrix",
+            "The Matrix Reloaded",
+            None,
+            &[],
+        )]);
+        assert!(search_ids("keanu").is_empty());
+        assert_eq!(search_ids("reloaded"), vec!["matrix"]);
+
+        remove(backend_id, vec!["matrix".to_string()]);
+        assert!(search_ids("matrix").is_empty());
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        assert_eq!(runtime.block_on(clear(backend_id)), 3);
+        assert!(search_ids("blade").is_empty());
+    }
+
+    #[test]
+    fn test_match_expressions() {
+        assert_eq!(
+            prefix_match_expr("blade runner"),
+            Some("\"blade\"* \"runner\"*".into())
+        );
+        assert_eq!(prefix_match_expr("\"*()"), None);
+        assert_eq!(trigram_match_expr("ab"), None);
+        assert_eq!(trigram_match_expr("a\"b"), Some("\"a\"\"b\"".into()));
+        assert_eq!(like_pattern("100%_"), "%100\\%\\_%");
+    }
+}
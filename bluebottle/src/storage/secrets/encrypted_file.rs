@@ -0,0 +1,174 @@
+//! A file of secrets encrypted with a key derived from the user's passphrase,
+//! used when there is no desktop keyring to keep them in.
+//!
+//! The file holds a magic header, the Argon2 salt, the AES-GCM nonce and the
+//! encrypted secrets. The nonce is regenerated each time the file is written.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::PathBuf;
+
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use snafu::ResultExt;
+
+/// Identifies the file format, changed if the format ever is.
+const MAGIC: &[u8; 4] = b"BBS1";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+/// A file of secrets encrypted with a passphrase.
+pub struct EncryptedFile {
+    path: PathBuf,
+    passphrase: String,
+}
+
+/// The decrypted contents of the file.
+struct Contents {
+    salt: [u8; SALT_LEN],
+    secrets: BTreeMap<String, String>,
+}
+
+impl EncryptedFile {
+    pub fn new(path: PathBuf, passphrase: String) -> Self {
+        Self { path, passphrase }
+    }
+
+    /// Returns the secret stored under the ID.
+    pub fn get(&self, id: &str) -> Result<Option<String>, snafu::Whatever> {
+        let mut contents = self.read()?;
+        Ok(contents.secrets.remove(id))
+    }
+
+    /// Store the secret under the ID, replacing any existing secret.
+    pub fn set(&self, id: &str, secret: &str) -> Result<(), snafu::Whatever> {
+        let mut contents = self.read()?;
+        contents.secrets.insert(id.to_string(), secret.to_string());
+        self.write(&contents)
+    }
+
+    /// Remove the secret stored under the ID, if any.
+    pub fn remove(&self, id: &str) -> Result<(), snafu::Whatever> {
+        let mut contents = self.read()?;
+        if contents.secrets.remove(id).is_some() {
+            self.write(&contents)?;
+        }
+        Ok(())
+    }
+
+    fn read(&self) -> Result<Contents, snafu::Whatever> {
+        let data = match std::fs::read(&self.path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                let mut salt = [0; SALT_LEN];
+                OsRng.fill_bytes(&mut salt);
+                return Ok(Contents {
+                    salt,
+                    secrets: BTreeMap::new(),
+                });
+            },
+            Err(err) => {
+                return Err(err).whatever_context("read encrypted secrets file");
+            },
+        };
+
+        let Some(data) = data.strip_prefix(MAGIC) else {
+            snafu::whatever!("encrypted secrets file has an unknown format");
+        };
+        if data.len() < SALT_LEN + NONCE_LEN {
+            snafu::whatever!("encrypted secrets file is truncated");
+        }
+        let (salt, data) = data.split_at(SALT_LEN);
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        let salt: [u8; SALT_LEN] = salt.try_into().expect("salt length is checked");
+
+        let cipher = Aes256Gcm::new(&derive_key(&self.passphrase, &salt)?);
+        let Ok(plaintext) = cipher.decrypt(Nonce::from_slice(nonce), ciphertext) else {
+            snafu::whatever!("incorrect passphrase or corrupted secrets file");
+        };
+        let secrets = rmp_serde::from_slice(&plaintext)
+            .whatever_context("deserialize encrypted secrets")?;
+
+        Ok(Contents { salt, secrets })
+    }
+
+    fn write(&self, contents: &Contents) -> Result<(), snafu::Whatever> {
+        let plaintext = rmp_serde::to_vec(&contents.secrets)
+            .whatever_context("serialize encrypted secrets")?;
+        let cipher = Aes256Gcm::new(&derive_key(&self.passphrase, &contents.salt)?);
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let Ok(ciphertext) = cipher.encrypt(&nonce, plaintext.as_slice()) else {
+            snafu::whatever!("encrypt secrets");
+        };
+
+        let mut data =
+            Vec::with_capacity(MAGIC.len() + SALT_LEN + NONCE_LEN + ciphertext.len());
+        data.extend_from_slice(MAGIC);
+        data.extend_from_slice(&contents.salt);
+        data.extend_from_slice(&nonce);
+        data.extend_from_slice(&ciphertext);
+
+        // Replace the file in one go, so a crash can not leave it half written.
+        let temp_path = self.path.with_extension("tmp");
+        let mut file = private_file(&temp_path)
+            .whatever_context("create encrypted secrets file")?;
+        file.write_all(&data)
+            .whatever_context("write encrypted secrets file")?;
+        file.sync_all()
+            .whatever_context("sync encrypted secrets file")?;
+        std::fs::rename(&temp_path, &self.path)
+            .whatever_context("replace encrypted secrets file")?;
+
+        Ok(())
+    }
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<Key<Aes256Gcm>, snafu::Whatever> {
+    let mut key = Key::<Aes256Gcm>::default();
+    if let Err(err) = argon2::Argon2::default().hash_password_into(
+        passphrase.as_bytes(),
+        salt,
+        &mut key,
+    ) {
+        snafu::whatever!("derive secrets key: {err}");
+    }
+    Ok(key)
+}
+
+/// Create the file readable by the user alone.
+fn private_file(path: &std::path::Path) -> std::io::Result<std::fs::File> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_get_remove() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("secrets.enc");
+
+        let file = EncryptedFile::new(path.clone(), "passphrase".into());
+        assert_eq!(file.get("token").unwrap(), None);
+        file.set("token", "secret").unwrap();
+        file.set("other", "value").unwrap();
+        assert_eq!(file.get("token").unwrap().as_deref(), Some("secret"));
+
+        let data = std::fs::read(&path).unwrap();
+        assert!(data.starts_with(MAGIC));
+        assert!(!data.windows(6).any(|window| window == b"secret"));
+
+        file.remove("token").unwrap();
+        assert_eq!(file.get("token").unwrap(), None);
+        assert_eq!(file.get("other").unwrap().as_deref(), Some("value"));
+
+        let file = EncryptedFile::new(path, "wrong".into());
+        assert!(file.get("other").is_err());
+    }
+}
@@ -0,0 +1,259 @@
+//! A client of the freedesktop Secret Service API, which keeps secrets in the
+//! desktop keyring, i.e. GNOME Keyring or KWallet.
+//!
+//! Secrets are transferred with the `plain` algorithm, the session bus is only
+//! reachable by the user's own processes.
+
+use std::collections::HashMap;
+
+use iced::futures::StreamExt;
+use snafu::{OptionExt, ResultExt};
+use zbus::zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value};
+use zbus::{Connection, proxy};
+
+/// The attribute identifying the secrets stored by the app.
+const APPLICATION: &str = "bluebottle";
+/// The collection secrets are stored in, usually the user's login keyring.
+const DEFAULT_COLLECTION: &str = "/org/freedesktop/secrets/aliases/default";
+/// The path returned instead of a prompt when the user does not need to be asked.
+const NO_PROMPT: &str = "/";
+
+#[proxy(
+    interface = "org.freedesktop.Secret.Service",
+    default_service = "org.freedesktop.secrets",
+    default_path = "/org/freedesktop/secrets"
+)]
+trait Service {
+    fn open_session(
+        &self,
+        algorithm: &str,
+        input: &Value<'_>,
+    ) -> zbus::Result<(OwnedValue, OwnedObjectPath)>;
+
+    fn search_items(
+        &self,
+        attributes: HashMap<&str, &str>,
+    ) -> zbus::Result<(Vec<OwnedObjectPath>, Vec<OwnedObjectPath>)>;
+
+    fn unlock(
+        &self,
+        objects: &[ObjectPath<'_>],
+    ) -> zbus::Result<(Vec<OwnedObjectPath>, OwnedObjectPath)>;
+}
+
+#[proxy(
+    interface = "org.freedesktop.Secret.Collection",
+    default_service = "org.freedesktop.secrets"
+)]
+trait Collection {
+    fn create_item(
+        &self,
+        properties: HashMap<&str, Value<'_>>,
+        secret: &Secret,
+        replace: bool,
+    ) -> zbus::Result<(OwnedObjectPath, OwnedObjectPath)>;
+}
+
+#[proxy(
+    interface = "org.freedesktop.Secret.Item",
+    default_service = "org.freedesktop.secrets"
+)]
+trait Item {
+    fn get_secret(&self, session: &ObjectPath<'_>) -> zbus::Result<Secret>;
+
+    fn delete(&self) -> zbus::Result<OwnedObjectPath>;
+}
+
+#[proxy(
+    interface = "org.freedesktop.Secret.Prompt",
+    default_service = "org.freedesktop.secrets"
+)]
+trait Prompt {
+    fn prompt(&self, window_id: &str) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn completed(&self, dismissed: bool, result: Value<'_>) -> zbus::Result<()>;
+}
+
+#[derive(
+    Debug, zbus::zvariant::Type, serde_derive::Serialize, serde_derive::Deserialize,
+)]
+/// A secret as it is transferred over the bus.
+struct Secret {
+    session: OwnedObjectPath,
+    parameters: Vec<u8>,
+    value: Vec<u8>,
+    content_type: String,
+}
+
+/// An open session with the Secret Service.
+pub struct Keyring {
+    conn: Connection,
+    service: ServiceProxy<'static>,
+    session: OwnedObjectPath,
+}
+
+impl Keyring {
+    /// Connect to the Secret Service on the session bus, failing if there is no
+    /// keyring running.
+    pub async fn connect() -> Result<Self, snafu::Whatever> {
+        let conn = Connection::session()
+            .await
+            .whatever_context("connect to the session bus")?;
+        let service = ServiceProxy::new(&conn)
+            .await
+            .whatever_context("create Secret Service proxy")?;
+        let (_, session) = service
+            .open_session("plain", &Value::from(""))
+            .await
+            .whatever_context("open Secret Service session")?;
+
+        Ok(Self {
+            conn,
+            service,
+            session,
+        })
+    }
+
+    /// Store the secret under the ID, replacing any existing secret.
+    pub async fn store(
+        &self,
+        id: &str,
+        label: &str,
+        secret: &str,
+    ) -> Result<(), snafu::Whatever> {
+        let collection = CollectionProxy::builder(&self.conn)
+            .path(DEFAULT_COLLECTION)
+            .whatever_context("build collection path")?
+            .build()
+            .await
+            .whatever_context("create collection proxy")?;
+
+        let attributes: HashMap<String, String> = attributes(id)
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let properties = HashMap::from([
+            ("org.freedesktop.Secret.Item.Label", Value::from(label)),
+            (
+                "org.freedesktop.Secret.Item.Attributes",
+                Value::from(attributes),
+            ),
+        ]);
+        let secret = Secret {
+            session: self.session.clone(),
+            parameters: Vec::new(),
+            value: secret.as_bytes().to_vec(),
+            content_type: "text/plain; charset=utf8".to_string(),
+        };
+
+        let (_, prompt) = collection
+            .create_item(properties, &secret, true)
+            .await
+            .whatever_context("create keyring item")?;
+        self.prompt(prompt).await
+    }
+
+    /// Returns the secret stored under the ID, unlocking it if needed.
+    pub async fn load(&self, id: &str) -> Result<Option<String>, snafu::Whatever> {
+        let Some(item) = self.find(id).await? else {
+            return Ok(None);
+        };
+
+        let secret = self
+            .item(item)
+            .await?
+            .get_secret(&self.session)
+            .await
+            .whatever_context("get keyring secret")?;
+        let secret =
+            String::from_utf8(secret.value).whatever_context("decode keyring secret")?;
+        Ok(Some(secret))
+    }
+
+    /// Delete the secret stored under the ID, if any.
+    pub async fn delete(&self, id: &str) -> Result<(), snafu::Whatever> {
+        let Some(item) = self.find(id).await? else {
+            return Ok(());
+        };
+
+        let prompt = self
+            .item(item)
+            .await?
+            .delete()
+            .await
+            .whatever_context("delete keyring item")?;
+        self.prompt(prompt).await
+    }
+
+    async fn find(&self, id: &str) -> Result<Option<OwnedObjectPath>, snafu::Whatever> {
+        let (unlocked, locked) = self
+            .service
+            .search_items(attributes(id))
+            .await
+            .whatever_context("search keyring items")?;
+        if let Some(item) = unlocked.into_iter().next() {
+            return Ok(Some(item));
+        }
+        let Some(item) = locked.into_iter().next() else {
+            return Ok(None);
+        };
+
+        let (_, prompt) = self
+            .service
+            .unlock(&[item.as_ref()])
+            .await
+            .whatever_context("unlock keyring item")?;
+        self.prompt(prompt).await?;
+        Ok(Some(item))
+    }
+
+    async fn item(
+        &self,
+        path: OwnedObjectPath,
+    ) -> Result<ItemProxy<'_>, snafu::Whatever> {
+        ItemProxy::builder(&self.conn)
+            .path(path)
+            .whatever_context("build item path")?
+            .build()
+            .await
+            .whatever_context("create item proxy")
+    }
+
+    /// Show the prompt, i.e. asking the user to unlock the keyring, and wait for
+    /// the user to complete it.
+    async fn prompt(&self, prompt: OwnedObjectPath) -> Result<(), snafu::Whatever> {
+        if prompt.as_str() == NO_PROMPT {
+            return Ok(());
+        }
+
+        let proxy = PromptProxy::builder(&self.conn)
+            .path(prompt)
+            .whatever_context("build prompt path")?
+            .build()
+            .await
+            .whatever_context("create prompt proxy")?;
+        let mut completed = proxy
+            .receive_completed()
+            .await
+            .whatever_context("subscribe to prompt completion")?;
+        proxy
+            .prompt("")
+            .await
+            .whatever_context("show keyring prompt")?;
+
+        let signal = completed
+            .next()
+            .await
+            .whatever_context("keyring prompt was never completed")?;
+        let args = signal.args().whatever_context("read prompt completion")?;
+        if args.dismissed {
+            snafu::whatever!("keyring prompt was dismissed");
+        }
+        Ok(())
+    }
+}
+
+fn attributes(id: &str) -> HashMap<&str, &str> {
+    HashMap::from([("application", APPLICATION), ("secret_id", id)])
+}
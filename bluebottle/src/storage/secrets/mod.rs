@@ -0,0 +1,266 @@
+//! Keeps the secrets of backends, i.e. access tokens, out of the databases.
+//!
+//! Secrets are stored in the desktop keyring through the freedesktop Secret
+//! Service API. When there is no keyring they are stored in a file encrypted
+//! with the passphrase given at launch instead. Backend contexts only hold a
+//! [SecretRef] to their secrets.
+
+use std::future::Future;
+
+use parking_lot::{Mutex, RwLock};
+use snafu::OptionExt;
+
+use self::encrypted_file::EncryptedFile;
+use self::keyring::Keyring;
+use crate::backends::BackendId;
+
+mod encrypted_file;
+mod keyring;
+
+/// The passphrase of the encrypted secrets file, if the user gave one.
+static PASSPHRASE: RwLock<Option<String>> = RwLock::new(None);
+/// Serialises access to the encrypted secrets file.
+static FILE_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(
+    Debug, Clone, PartialEq, Eq, serde_derive::Serialize, serde_derive::Deserialize,
+)]
+#[serde(tag = "store", content = "id", rename_all = "snake_case")]
+/// Where a secret is stored, persisted in place of the secret itself.
+pub enum SecretRef {
+    Keyring(String),
+    EncryptedFile(String),
+}
+
+/// Set the passphrase of the encrypted secrets file, used when there is no
+/// desktop keyring.
+pub fn set_passphrase(passphrase: String) {
+    *PASSPHRASE.write() = Some(passphrase);
+}
+
+/// A keyring secrets are stored in when it is available.
+trait SecretStore {
+    fn store(&self, id: &str, label: &str, secret: &str) -> Result<(), snafu::Whatever>;
+
+    fn load(&self, id: &str) -> Result<Option<String>, snafu::Whatever>;
+
+    fn delete(&self, id: &str) -> Result<(), snafu::Whatever>;
+}
+
+/// The desktop keyring, reached through the Secret Service API.
+struct DesktopKeyring;
+
+impl SecretStore for DesktopKeyring {
+    fn store(&self, id: &str, label: &str, secret: &str) -> Result<(), snafu::Whatever> {
+        block_on(|| async { Keyring::connect().await?.store(id, label, secret).await })
+    }
+
+    fn load(&self, id: &str) -> Result<Option<String>, snafu::Whatever> {
+        block_on(|| async { Keyring::connect().await?.load(id).await })
+    }
+
+    fn delete(&self, id: &str) -> Result<(), snafu::Whatever> {
+        block_on(|| async { Keyring::connect().await?.delete(id).await })
+    }
+}
+
+/// Store a secret of the backend, returning the reference to persist instead.
+///
+/// The secret is stored in the desktop keyring if there is one, otherwise in
+/// the encrypted secrets file.
+pub fn store(
+    backend_id: BackendId,
+    name: &str,
+    secret: &str,
+) -> Result<SecretRef, snafu::Whatever> {
+    store_in(&DesktopKeyring, backend_id, name, secret)
+}
+
+/// Store a secret of the backend in the keyring, falling back to the encrypted
+/// secrets file if the keyring is unavailable.
+fn store_in(
+    keyring: &impl SecretStore,
+    backend_id: BackendId,
+    name: &str,
+    secret: &str,
+) -> Result<SecretRef, snafu::Whatever> {
+    let id = format!("{backend_id}/{name}");
+
+    let label = format!("Bluebottle {name} ({backend_id})");
+    match keyring.store(&id, &label, secret) {
+        Ok(()) => return Ok(SecretRef::Keyring(id)),
+        Err(err) => {
+            tracing::warn!(error = %err, "desktop keyring is unavailable, using the encrypted secrets file");
+        },
+    }
+
+    let _guard = FILE_LOCK.lock();
+    encrypted_file()?.set(&id, secret)?;
+    Ok(SecretRef::EncryptedFile(id))
+}
+
+/// Returns the secret the reference points to.
+pub fn load(reference: &SecretRef) -> Result<String, snafu::Whatever> {
+    load_from(&DesktopKeyring, reference)
+}
+
+fn load_from(
+    keyring: &impl SecretStore,
+    reference: &SecretRef,
+) -> Result<String, snafu::Whatever> {
+    let secret = match reference {
+        SecretRef::Keyring(id) => keyring.load(id)?,
+        SecretRef::EncryptedFile(id) => {
+            let _guard = FILE_LOCK.lock();
+            encrypted_file()?.get(id)?
+        },
+    };
+    secret.with_whatever_context(|| format!("secret {reference:?} does not exist"))
+}
+
+/// Delete the secret the reference points to, if it exists.
+pub fn delete(reference: &SecretRef) -> Result<(), snafu::Whatever> {
+    match reference {
+        SecretRef::Keyring(id) => DesktopKeyring.delete(id),
+        SecretRef::EncryptedFile(id) => {
+            let _guard = FILE_LOCK.lock();
+            encrypted_file()?.remove(id)
+        },
+    }
+}
+
+fn encrypted_file() -> Result<EncryptedFile, snafu::Whatever> {
+    let passphrase = PASSPHRASE.read().clone().whatever_context(
+        "no desktop keyring is available and no secrets passphrase was given",
+    )?;
+    let path = super::directory::paths().data_dir().join("secrets.enc");
+    Ok(EncryptedFile::new(path, passphrase))
+}
+
+/// Run the keyring operation to completion on its own thread, so secrets can be
+/// accessed whether or not the caller is within a runtime.
+fn block_on<F, Fut, T>(op: F) -> Result<T, snafu::Whatever>
+where
+    F: FnOnce() -> Fut + Send,
+    Fut: Future<Output = Result<T, snafu::Whatever>>,
+    T: Send,
+{
+    let result = std::thread::scope(|scope| {
+        scope
+            .spawn(|| {
+                let runtime = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .map_err(|err| format!("create keyring runtime: {err}"))?;
+                runtime
+                    .block_on(op())
+                    .map_err(|err| snafu::Report::from_error(err).to_string())
+            })
+            .join()
+    });
+
+    match result {
+        Ok(Ok(value)) => Ok(value),
+        Ok(Err(err)) => snafu::whatever!("{err}"),
+        Err(_) => snafu::whatever!("keyring thread panicked"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::storage::test_utils::temp_storage;
+
+    /// A keyring which keeps secrets in memory.
+    #[derive(Default)]
+    struct MemoryKeyring(Mutex<HashMap<String, String>>);
+
+    impl SecretStore for MemoryKeyring {
+        fn store(
+            &self,
+            id: &str,
+            _label: &str,
+            secret: &str,
+        ) -> Result<(), snafu::Whatever> {
+            self.0.lock().insert(id.to_string(), secret.to_string());
+            Ok(())
+        }
+
+        fn load(&self, id: &str) -> Result<Option<String>, snafu::Whatever> {
+            Ok(self.0.lock().get(id).cloned())
+        }
+
+        fn delete(&self, id: &str) -> Result<(), snafu::Whatever> {
+            self.0.lock().remove(id);
+            Ok(())
+        }
+    }
+
+    /// A keyring which is never available, i.e. there is no session bus.
+    struct UnavailableKeyring;
+
+    impl SecretStore for UnavailableKeyring {
+        fn store(
+            &self,
+            _id: &str,
+            _label: &str,
+            _secret: &str,
+        ) -> Result<(), snafu::Whatever> {
+            snafu::whatever!("no keyring")
+        }
+
+        fn load(&self, _id: &str) -> Result<Option<String>, snafu::Whatever> {
+            snafu::whatever!("no keyring")
+        }
+
+        fn delete(&self, _id: &str) -> Result<(), snafu::Whatever> {
+            snafu::whatever!("no keyring")
+        }
+    }
+
+    #[test]
+    fn test_store_in_keyring() {
+        let backend_id = BackendId::now_v7();
+        let keyring = MemoryKeyring::default();
+
+        let reference = store_in(&keyring, backend_id, "access_token", "token").unwrap();
+        assert_eq!(
+            reference,
+            SecretRef::Keyring(format!("{backend_id}/access_token"))
+        );
+        assert_eq!(load_from(&keyring, &reference).unwrap(), "token");
+    }
+
+    #[rstest::rstest]
+    fn test_store_falls_back_to_encrypted_file(_temp_storage: tempfile::TempDir) {
+        let backend_id = BackendId::now_v7();
+        set_passphrase("passphrase".into());
+
+        let reference =
+            store_in(&UnavailableKeyring, backend_id, "access_token", "token").unwrap();
+        assert_eq!(
+            reference,
+            SecretRef::EncryptedFile(format!("{backend_id}/access_token"))
+        );
+        assert_eq!(load_from(&UnavailableKeyring, &reference).unwrap(), "token");
+
+        delete(&reference).unwrap();
+        assert!(load_from(&UnavailableKeyring, &reference).is_err());
+    }
+
+    #[test]
+    fn test_secret_ref_serde() {
+        let reference = SecretRef::Keyring("backend/access_token".into());
+        let value = serde_json::to_value(&reference).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({"store": "keyring", "id": "backend/access_token"}),
+        );
+        assert_eq!(
+            serde_json::from_value::<SecretRef>(value).unwrap(),
+            reference
+        );
+    }
+}
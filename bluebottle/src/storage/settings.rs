@@ -0,0 +1,392 @@
+//! Typed settings stored as key-value pairs.
+//!
+//! Each setting is declared once by the module owning it, with its key, type,
+//! default and [Scope]. Values are encoded with MessagePack, and every save is
+//! announced to [subscribe]rs so views can react to settings changed elsewhere.
+
+use std::fmt::Display;
+use std::sync::LazyLock;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use tokio::sync::broadcast;
+
+/// The number of changes buffered for subscribers which fall behind.
+const CHANGES_CAPACITY: usize = 64;
+
+static CHANGES: LazyLock<broadcast::Sender<SettingChange>> =
+    LazyLock::new(|| broadcast::channel(CHANGES_CAPACITY).0);
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+/// Where a setting is stored.
+pub enum Scope {
+    /// UI state which can be lost without the user minding, i.e. the last screen
+    /// open, stored in the relaxed database.
+    Relaxed,
+    /// Preferences the user chose, stored in the durable database.
+    ///
+    /// Preferences saved in the relaxed database by older releases are moved
+    /// over the first time they are loaded.
+    Durable,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+/// Announces a setting was saved or removed.
+pub struct SettingChange {
+    key: String,
+}
+
+impl SettingChange {
+    /// Returns the key of the changed entry.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+/// Returns a receiver of every setting change from now on.
+pub fn subscribe() -> broadcast::Receiver<SettingChange> {
+    CHANGES.subscribe()
+}
+
+/// A declared setting, either a single entry or one entry per qualifier, i.e.
+/// per backend.
+pub struct Setting<T> {
+    name: &'static str,
+    scope: Scope,
+    default: fn() -> T,
+}
+
+impl<T> Setting<T> {
+    /// Declare a setting stored as UI state.
+    pub const fn relaxed(name: &'static str, default: fn() -> T) -> Self {
+        Self {
+            name,
+            scope: Scope::Relaxed,
+            default,
+        }
+    }
+
+    /// Declare a setting stored as a user preference.
+    pub const fn durable(name: &'static str, default: fn() -> T) -> Self {
+        Self {
+            name,
+            scope: Scope::Durable,
+            default,
+        }
+    }
+
+    /// Returns the single entry of the setting.
+    pub fn entry(&self) -> Entry<T> {
+        self.entry_at(self.name.to_string())
+    }
+
+    /// Returns the entry of the setting for the qualifier, i.e. a backend ID.
+    pub fn keyed(&self, qualifier: impl Display) -> Entry<T> {
+        self.entry_at(format!("{}:{qualifier}", self.name))
+    }
+
+    /// Returns whether the change is to any entry of the setting.
+    pub fn is_changed(&self, change: &SettingChange) -> bool {
+        match change.key.strip_prefix(self.name) {
+            Some(rest) => rest.is_empty() || rest.starts_with(':'),
+            None => false,
+        }
+    }
+
+    fn entry_at(&self, key: String) -> Entry<T> {
+        Entry {
+            key,
+            scope: self.scope,
+            default: self.default,
+        }
+    }
+}
+
+/// A stored value of a [Setting].
+pub struct Entry<T> {
+    key: String,
+    scope: Scope,
+    default: fn() -> T,
+}
+
+impl<T> Entry<T>
+where
+    T: Serialize + DeserializeOwned + Send + 'static,
+{
+    /// Load the value, falling back to the default if none is saved.
+    pub fn load(&self) -> T {
+        self.try_load().unwrap_or_else(self.default)
+    }
+
+    /// Load the value, if one is saved.
+    pub fn try_load(&self) -> Option<T> {
+        let key = self.key.clone();
+        let buffer = match self.scope {
+            Scope::Relaxed => {
+                super::with_relaxed_state(move |state| state.get_key_value(&key).ok())
+            },
+            Scope::Durable => {
+                let buffer = super::with_durable_state({
+                    let key = key.clone();
+                    move |state| read_durable(state, &key)
+                });
+                buffer.or_else(|| self.move_legacy_value())
+            },
+        };
+        decode(&self.key, &buffer?)
+    }
+
+    /// Load the value from within an async context, falling back to the default if
+    /// none is saved.
+    pub async fn load_async(&self) -> T {
+        self.try_load_async().await.unwrap_or_else(self.default)
+    }
+
+    /// Load the value from within an async context, if one is saved.
+    pub async fn try_load_async(&self) -> Option<T> {
+        let key = self.key.clone();
+        let buffer = match self.scope {
+            Scope::Relaxed => {
+                super::with_relaxed_state_async(move |state| {
+                    state.get_key_value(&key).ok()
+                })
+                .await
+            },
+            Scope::Durable => {
+                let buffer = super::with_durable_state_async({
+                    let key = key.clone();
+                    move |state| read_durable(state, &key)
+                })
+                .await;
+                match buffer {
+                    Some(buffer) => Some(buffer),
+                    None => {
+                        let buffer = super::with_relaxed_state_async(move |state| {
+                            state.get_key_value(&key).ok()
+                        })
+                        .await;
+                        self.adopt_legacy_value(buffer)
+                    },
+                }
+            },
+        };
+        decode(&self.key, &buffer?)
+    }
+
+    /// Save the value, without waiting for it to be written.
+    pub fn save(&self, value: &T) {
+        let buffer = rmp_serde::to_vec(value).unwrap();
+        self.write(Some(buffer));
+    }
+
+    /// Save the value from within an async context, waiting for it to be written.
+    pub async fn save_async(&self, value: &T) {
+        let buffer = rmp_serde::to_vec(value).unwrap();
+        let key = self.key.clone();
+        let result = match self.scope {
+            Scope::Relaxed => {
+                super::with_relaxed_state_async(move |state| {
+                    state
+                        .set_key_value(&key, &buffer)
+                        .map_err(|err| err.to_string())
+                })
+                .await
+            },
+            Scope::Durable => {
+                super::with_durable_state_async(move |state| {
+                    state
+                        .set_setting(&key, &buffer)
+                        .map_err(|err| err.to_string())
+                })
+                .await
+            },
+        };
+        if let Err(err) = result {
+            tracing::error!(key = %self.key, error = %err, "failed to save setting");
+        }
+        self.notify();
+    }
+
+    /// Update the saved value, or the default if none is saved.
+    ///
+    /// The value is loaded, updated and saved by the storage in one go, so
+    /// concurrent updates are not lost.
+    pub fn update<F>(&self, op: F)
+    where
+        F: FnOnce(&mut T) + Send + 'static,
+    {
+        let key = self.key.clone();
+        let default = self.default;
+        let update = move |buffer: Option<Vec<u8>>| {
+            let mut value = buffer
+                .and_then(|buffer| decode(&key, &buffer))
+                .unwrap_or_else(default);
+            op(&mut value);
+            rmp_serde::to_vec(&value).unwrap()
+        };
+
+        let key = self.key.clone();
+        match self.scope {
+            Scope::Relaxed => super::submit_relaxed_state(move |state| {
+                let buffer = update(state.get_key_value(&key).ok());
+                if let Err(err) = state.set_key_value(&key, &buffer) {
+                    tracing::error!(key = %key, error = %err, "failed to update setting");
+                }
+            }),
+            Scope::Durable => super::submit_durable_state(move |state| {
+                let buffer = update(read_durable(state, &key));
+                if let Err(err) = state.set_setting(&key, &buffer) {
+                    tracing::error!(key = %key, error = %err, "failed to update setting");
+                }
+            }),
+        }
+        self.notify();
+    }
+
+    /// Remove the saved value, reverting the setting to its default.
+    pub fn remove(&self) {
+        self.write(None);
+    }
+
+    /// Save or remove the value, announcing the change once it is queued so
+    /// subscribers loading the value see it.
+    fn write(&self, buffer: Option<Vec<u8>>) {
+        let key = self.key.clone();
+        match self.scope {
+            Scope::Relaxed => super::submit_relaxed_state(move |state| {
+                let result = match buffer {
+                    Some(buffer) => state.set_key_value(&key, &buffer),
+                    None => state.remove_key_value(&key),
+                };
+                if let Err(err) = result {
+                    tracing::error!(key = %key, error = %err, "failed to save setting");
+                }
+            }),
+            Scope::Durable => super::submit_durable_state(move |state| {
+                let result = match buffer {
+                    Some(buffer) => state.set_setting(&key, &buffer),
+                    None => state.remove_setting(&key),
+                };
+                if let Err(err) = result {
+                    tracing::error!(key = %key, error = %err, "failed to save setting");
+                }
+            }),
+        }
+        self.notify();
+    }
+
+    fn notify(&self) {
+        // Nobody may be subscribed, which is fine.
+        let _ = CHANGES.send(SettingChange {
+            key: self.key.clone(),
+        });
+    }
+
+    /// Returns the value older releases saved in the relaxed database, moving it
+    /// to the durable database.
+    fn move_legacy_value(&self) -> Option<Vec<u8>> {
+        let key = self.key.clone();
+        let buffer =
+            super::with_relaxed_state(move |state| state.get_key_value(&key).ok());
+        self.adopt_legacy_value(buffer)
+    }
+
+    fn adopt_legacy_value(&self, buffer: Option<Vec<u8>>) -> Option<Vec<u8>> {
+        let buffer = buffer?;
+        tracing::info!(key = %self.key, "moving setting to durable storage");
+
+        let key = self.key.clone();
+        let value = buffer.clone();
+        super::submit_durable_state(move |state| {
+            if let Err(err) = state.set_setting(&key, &value) {
+                tracing::error!(key = %key, error = %err, "failed to move setting");
+            }
+        });
+        let key = self.key.clone();
+        super::submit_relaxed_state(move |state| {
+            if let Err(err) = state.remove_key_value(&key) {
+                tracing::warn!(key = %key, error = %err, "failed to remove moved setting");
+            }
+        });
+
+        Some(buffer)
+    }
+}
+
+fn read_durable(
+    state: &super::durable::DurableStateStorage,
+    key: &str,
+) -> Option<Vec<u8>> {
+    state
+        .get_setting(key)
+        .inspect_err(
+            |err| tracing::error!(key = %key, error = %err, "failed to read setting"),
+        )
+        .ok()
+        .flatten()
+}
+
+fn decode<T: DeserializeOwned>(key: &str, buffer: &[u8]) -> Option<T> {
+    rmp_serde::from_slice(buffer)
+        .inspect_err(|err| tracing::warn!(key = %key, error = %err, "invalid setting"))
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::test_utils::temp_storage;
+
+    static RELAXED: Setting<Vec<String>> = Setting::relaxed("test_relaxed", Vec::new);
+    static DURABLE: Setting<u32> = Setting::durable("test_durable", || 5);
+
+    #[rstest::rstest]
+    fn test_settings(_temp_storage: tempfile::TempDir) {
+        let mut changes = subscribe();
+
+        assert!(RELAXED.entry().try_load().is_none());
+        RELAXED.entry().save(&vec!["first".to_string()]);
+        RELAXED
+            .entry()
+            .update(|values| values.push("second".to_string()));
+        assert_eq!(RELAXED.entry().load(), vec!["first", "second"]);
+
+        assert_eq!(DURABLE.keyed("a").load(), 5);
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(DURABLE.keyed("a").save_async(&1));
+        DURABLE.keyed("b").save(&2);
+        assert_eq!(DURABLE.keyed("a").load(), 1);
+        assert_eq!(DURABLE.keyed("b").load(), 2);
+        DURABLE.keyed("a").remove();
+        assert_eq!(DURABLE.keyed("a").load(), 5);
+
+        let change = changes.try_recv().unwrap();
+        assert_eq!(change.key(), "test_relaxed");
+        assert!(RELAXED.is_changed(&change));
+        assert!(!DURABLE.is_changed(&change));
+        changes.try_recv().unwrap();
+        let change = changes.try_recv().unwrap();
+        assert_eq!(change.key(), "test_durable:a");
+        assert!(DURABLE.is_changed(&change));
+    }
+
+    #[rstest::rstest]
+    fn test_legacy_relaxed_preference(_temp_storage: tempfile::TempDir) {
+        let buffer = rmp_serde::to_vec(&7_u32).unwrap();
+        super::super::submit_relaxed_state(move |state| {
+            state.set_key_value("test_durable:legacy", &buffer).unwrap();
+        });
+
+        assert_eq!(DURABLE.keyed("legacy").load(), 7);
+        let moved = super::super::with_durable_state(|state| {
+            state.get_setting("test_durable:legacy").unwrap()
+        });
+        assert_eq!(moved, Some(rmp_serde::to_vec(&7_u32).unwrap()));
+        let remaining = super::super::with_relaxed_state(|state| {
+            state.get_key_value("test_durable:legacy").ok()
+        });
+        assert_eq!(remaining, None);
+    }
+}
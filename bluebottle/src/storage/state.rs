@@ -1,4 +1,5 @@
-use std::sync::OnceLock;
+use std::sync::{OnceLock, mpsc as std_mpsc};
+use std::time::Duration;
 
 use snafu::ResultExt;
 use tokio::sync::{mpsc, oneshot};
@@ -6,11 +7,28 @@ use tokio::sync::{mpsc, oneshot};
 use super::durable::DurableStateStorage;
 use super::relaxed::RelaxedStateStorage;
 
+/// How long to wait for each actor to finish its pending ops on shutdown.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
 type StateOp<S> = Box<dyn FnOnce(&S) + Send>;
 
-static DURABLE_STATE: OnceLock<mpsc::Sender<StateOp<DurableStateStorage>>> =
+/// A message handled by a state actor.
+enum Command<S> {
+    Run(StateOp<S>),
+    /// Run any pending ops, close the storage and stop the actor, signalling
+    /// once done.
+    Shutdown(std_mpsc::Sender<()>),
+}
+
+/// A storage owned by a state actor.
+pub(super) trait ActorState: Send + 'static {
+    /// Flush, optimise and checkpoint the storage before the app exits.
+    fn close(&self) -> Result<(), snafu::Whatever>;
+}
+
+static DURABLE_STATE: OnceLock<mpsc::UnboundedSender<Command<DurableStateStorage>>> =
     OnceLock::new();
-static RELAXED_STATE: OnceLock<mpsc::Sender<StateOp<RelaxedStateStorage>>> =
+static RELAXED_STATE: OnceLock<mpsc::UnboundedSender<Command<RelaxedStateStorage>>> =
     OnceLock::new();
 
 /// Initialises the global app state using the [DirectoryPaths](super::directory::DirectoryPaths)
@@ -21,8 +39,8 @@ pub fn init_state() -> Result<(), snafu::Whatever> {
     let relaxed = RelaxedStateStorage::open()
         .whatever_context("open SQLite relaxed storage state")?;
 
-    let (durable_tx, durable_rx) = mpsc::channel(500);
-    let (relaxed_tx, relaxed_rx) = mpsc::channel(500);
+    let (durable_tx, durable_rx) = mpsc::unbounded_channel();
+    let (relaxed_tx, relaxed_rx) = mpsc::unbounded_channel();
 
     std::thread::Builder::new()
         .name("bluebottle-durable-state-actor".into())
@@ -45,78 +63,270 @@ pub fn init_state() -> Result<(), snafu::Whatever> {
     Ok(())
 }
 
-/// Gets a static reference to the global durable app state.
+/// Stop the state actors once they have run every pending op, closing the
+/// storage so the databases are left optimised and checkpointed.
+///
+/// Ops submitted after this is called panic.
+pub fn shutdown_state() {
+    if let Some(sender) = DURABLE_STATE.get() {
+        shutdown_actor("durable", sender);
+    }
+    if let Some(sender) = RELAXED_STATE.get() {
+        shutdown_actor("relaxed", sender);
+    }
+}
+
+/// Gets a static reference to the global durable app state, blocking the calling
+/// thread until the op completes.
+///
+/// Background tasks running on the tokio runtime should use
+/// [with_durable_state_async] instead, so they do not hold up the runtime.
 pub fn with_durable_state<F, T>(op: F) -> T
 where
     F: for<'a> FnOnce(&'a DurableStateStorage) -> T + Send + 'static,
     T: Send + 'static,
 {
-    let (tx, rx) = oneshot::channel();
+    let sender = DURABLE_STATE
+        .get()
+        .expect("state actor should be initialised");
 
-    let op = move |state: &DurableStateStorage| {
-        let result = op(state);
-        let _ = tx.send(result);
-    };
+    run_blocking(sender, op)
+}
 
+/// Gets a static reference to the global durable app state from within an async
+/// context, i.e. a background task running on the tokio runtime.
+pub async fn with_durable_state_async<F, T>(op: F) -> T
+where
+    F: for<'a> FnOnce(&'a DurableStateStorage) -> T + Send + 'static,
+    T: Send + 'static,
+{
     let sender = DURABLE_STATE
         .get()
         .expect("state actor should be initialised");
 
-    sender
-        .blocking_send(Box::new(op))
-        .expect("state actor shutdown panicked");
+    run_async(sender, op).await
+}
+
+/// Gets a reference to the global durable app state without waiting for the op to complete.
+///
+/// This never blocks, so it can be called from any context.
+pub fn submit_durable_state<F>(op: F)
+where
+    F: for<'a> FnOnce(&'a DurableStateStorage) + Send + 'static,
+{
+    let sender = DURABLE_STATE
+        .get()
+        .expect("state actor should be initialised");
 
-    rx.blocking_recv().expect("op panicked")
+    send(sender, Box::new(op));
 }
 
-/// Gets a static reference to the global relaxed app state.
+/// Gets a static reference to the global relaxed app state, blocking the calling
+/// thread until the op completes.
+///
+/// Background tasks running on the tokio runtime should use
+/// [with_relaxed_state_async] instead, so they do not hold up the runtime.
 pub fn with_relaxed_state<F, T>(op: F) -> T
 where
     F: for<'a> FnOnce(&'a RelaxedStateStorage) -> T + Send + 'static,
     T: Send + 'static,
 {
-    let (tx, rx) = oneshot::channel();
+    let sender = RELAXED_STATE
+        .get()
+        .expect("state actor should be initialised");
 
-    let op = move |state: &RelaxedStateStorage| {
-        let result = op(state);
-        let _ = tx.send(result);
-    };
+    run_blocking(sender, op)
+}
 
+/// Gets a static reference to the global relaxed app state from within an async
+/// context, i.e. a background task running on the tokio runtime.
+pub async fn with_relaxed_state_async<F, T>(op: F) -> T
+where
+    F: for<'a> FnOnce(&'a RelaxedStateStorage) -> T + Send + 'static,
+    T: Send + 'static,
+{
     let sender = RELAXED_STATE
         .get()
         .expect("state actor should be initialised");
 
-    sender
-        .blocking_send(Box::new(op))
-        .expect("state actor shutdown panicked");
-
-    rx.blocking_recv().expect("op panicked")
+    run_async(sender, op).await
 }
 
 /// Gets a reference to the global relaxed app state without waiting for the op to complete.
+///
+/// This never blocks, so it can be called from any context.
 pub fn submit_relaxed_state<F>(op: F)
 where
     F: for<'a> FnOnce(&'a RelaxedStateStorage) + Send + 'static,
 {
-    let op = move |state: &RelaxedStateStorage| {
-        op(state);
-    };
-
     let sender = RELAXED_STATE
         .get()
         .expect("state actor should be initialised");
 
-    sender
-        .blocking_send(Box::new(op))
-        .expect("state actor shutdown panicked");
+    send(sender, Box::new(op));
+}
+
+fn send<S>(sender: &mpsc::UnboundedSender<Command<S>>, op: StateOp<S>) {
+    if sender.send(Command::Run(op)).is_err() {
+        panic!("state actor has shut down");
+    }
+}
+
+/// Run the op, waiting on a standard channel which, unlike the tokio channels, does
+/// not panic when waited on from within the runtime.
+fn run_blocking<S, F, T>(sender: &mpsc::UnboundedSender<Command<S>>, op: F) -> T
+where
+    S: 'static,
+    F: for<'a> FnOnce(&'a S) -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let (tx, rx) = std_mpsc::sync_channel(1);
+    send(
+        sender,
+        Box::new(move |state: &S| {
+            let _ = tx.send(op(state));
+        }),
+    );
+
+    rx.recv().expect("op panicked")
+}
+
+async fn run_async<S, F, T>(sender: &mpsc::UnboundedSender<Command<S>>, op: F) -> T
+where
+    S: 'static,
+    F: for<'a> FnOnce(&'a S) -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let (tx, rx) = oneshot::channel();
+    send(
+        sender,
+        Box::new(move |state: &S| {
+            let _ = tx.send(op(state));
+        }),
+    );
+
+    rx.await.expect("op panicked")
+}
+
+fn shutdown_actor<S>(name: &str, sender: &mpsc::UnboundedSender<Command<S>>) {
+    let (tx, rx) = std_mpsc::channel();
+    if sender.send(Command::Shutdown(tx)).is_err() {
+        return;
+    }
+
+    match rx.recv_timeout(SHUTDOWN_TIMEOUT) {
+        Ok(()) => tracing::info!(actor = name, "state actor closed"),
+        Err(_) => {
+            tracing::warn!(actor = name, "timed out waiting for state actor to close")
+        },
+    }
 }
 
-fn state_runner_thread<S>(mut ops: mpsc::Receiver<StateOp<S>>, state: S) {
+fn state_runner_thread<S: ActorState>(
+    mut commands: mpsc::UnboundedReceiver<Command<S>>,
+    state: S,
+) {
     tracing::info!("state actor started");
 
-    while let Some(access) = ops.blocking_recv() {
-        access(&state)
+    while let Some(command) = commands.blocking_recv() {
+        let done = match command {
+            Command::Run(op) => {
+                op(&state);
+                continue;
+            },
+            Command::Shutdown(done) => done,
+        };
+
+        // Ops which raced the shutdown still run, nothing is accepted after it.
+        commands.close();
+        while let Ok(command) = commands.try_recv() {
+            if let Command::Run(op) = command {
+                op(&state);
+            }
+        }
+
+        if let Err(err) = state.close() {
+            tracing::error!(error = %err, "failed to close state storage");
+        }
+        let _ = done.send(());
+        return;
     }
 
     tracing::warn!("state actor shut down");
 }
+
+/// Flush the connection's dirty pages, let SQLite optimise its statistics and
+/// move the WAL into the database so it is left in a clean state.
+pub(super) fn close_connection(
+    conn: &rusqlite::Connection,
+) -> Result<(), snafu::Whatever> {
+    conn.cache_flush().whatever_context("flush SQLite cache")?;
+    conn.execute_batch("PRAGMA optimize;")
+        .whatever_context("optimize SQLite database")?;
+    conn.query_row("PRAGMA wal_checkpoint(TRUNCATE);", [], |_| Ok(()))
+        .whatever_context("checkpoint SQLite WAL")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use parking_lot::Mutex;
+
+    use super::*;
+
+    #[derive(Default, Clone)]
+    struct Recorder(Arc<Mutex<Vec<&'static str>>>);
+
+    impl ActorState for Recorder {
+        fn close(&self) -> Result<(), snafu::Whatever> {
+            self.0.lock().push("close");
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_shutdown_runs_pending_ops() {
+        let recorder = Recorder::default();
+        let (tx, rx) = mpsc::unbounded_channel();
+        let actor = {
+            let recorder = recorder.clone();
+            std::thread::spawn(move || state_runner_thread(rx, recorder))
+        };
+
+        let (done_tx, done_rx) = std_mpsc::channel();
+        tx.send(Command::Run(Box::new(|state: &Recorder| {
+            state.0.lock().push("first")
+        })))
+        .ok()
+        .unwrap();
+        tx.send(Command::Run(Box::new(|state: &Recorder| {
+            state.0.lock().push("second")
+        })))
+        .ok()
+        .unwrap();
+        tx.send(Command::Shutdown(done_tx)).ok().unwrap();
+
+        done_rx.recv_timeout(SHUTDOWN_TIMEOUT).unwrap();
+        actor.join().unwrap();
+        assert_eq!(*recorder.0.lock(), vec!["first", "second", "close"]);
+        assert!(tx.send(Command::Run(Box::new(|_: &Recorder| {}))).is_err());
+    }
+
+    #[test]
+    fn test_close_connection() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.sqlite");
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.pragma_update(None, "journal_mode", "WAL").unwrap();
+        conn.execute_batch(
+            "CREATE TABLE example (id INTEGER); INSERT INTO example VALUES (1);",
+        )
+        .unwrap();
+
+        close_connection(&conn).unwrap();
+        let wal = std::fs::metadata(dir.path().join("state.sqlite-wal")).unwrap();
+        assert_eq!(wal.len(), 0);
+    }
+}
@@ -0,0 +1,49 @@
+//! Persists the quality video is streamed from each backend at.
+
+use super::settings::Setting;
+use crate::backends::BackendId;
+use crate::models::profile::QualitySetting;
+
+/// The quality setting of each backend.
+pub static STREAM_QUALITY: Setting<QualitySetting> =
+    Setting::durable("stream_quality", QualitySetting::default);
+
+/// Load the quality setting of the backend, falling back to picking the quality
+/// automatically if none is saved.
+pub fn load(backend_id: BackendId) -> QualitySetting {
+    STREAM_QUALITY.keyed(backend_id).load()
+}
+
+/// Load the quality setting of the backend from within an async context, see
+/// [load].
+pub async fn load_async(backend_id: BackendId) -> QualitySetting {
+    STREAM_QUALITY.keyed(backend_id).load_async().await
+}
+
+/// Persist the quality setting of the backend, waiting for it to be written.
+pub async fn save_async(backend_id: BackendId, setting: QualitySetting) {
+    STREAM_QUALITY.keyed(backend_id).save_async(&setting).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::profile::Quality;
+    use crate::storage::test_utils::temp_storage;
+
+    #[rstest::rstest]
+    fn test_save_and_load(_temp_storage: tempfile::TempDir) {
+        let backend_id = BackendId::now_v7();
+        assert_eq!(load(backend_id), QualitySetting::Auto);
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(save_async(
+            backend_id,
+            QualitySetting::Fixed(Quality::Mbps8),
+        ));
+        assert_eq!(load(backend_id), QualitySetting::Fixed(Quality::Mbps8));
+        assert_eq!(load(BackendId::now_v7()), QualitySetting::Auto);
+    }
+}
@@ -0,0 +1,14 @@
+-- Backend libraries and their context required to be re-created.
+CREATE TABLE backend_init_state (
+    backend_id TEXT PRIMARY KEY,
+    kind TEXT,
+    context TEXT
+);
+
+-- User interactions pre-saved to a local DBs in order to apply later on.
+CREATE TABLE user_interaction_backlog (
+    event_id TEXT PRIMARY KEY,
+    backend_id TEXT,
+    event TEXT,
+    created_at BIGINT
+);
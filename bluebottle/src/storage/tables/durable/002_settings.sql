@@ -0,0 +1,5 @@
+-- User preferences, encoded with MessagePack and keyed by setting.
+CREATE TABLE app_settings (
+    k TEXT PRIMARY KEY,
+    v BLOB NOT NULL
+);
@@ -1,3 +1,5 @@
+-- The durable schema created by installs since the initial release, before schema versions
+-- were recorded.
 BEGIN;
 -- Backend libraries and their context required to be re-created.
 CREATE TABLE IF NOT EXISTS backend_init_state (
@@ -13,4 +15,4 @@ CREATE TABLE IF NOT EXISTS user_interaction_backlog (
     event TEXT,
     created_at BIGINT
 );
-COMMIT;
\ No newline at end of file
+COMMIT;
@@ -1,3 +1,5 @@
+-- The relaxed schema created by installs since the initial release, before schema versions
+-- were recorded.
 BEGIN;
 -- Holds onto RPC calls of backends with responses that
 -- can be cached, this reduces wait time and traffic on the network.
@@ -16,4 +18,4 @@ CREATE TABLE IF NOT EXISTS app_kv_state (
     v BLOB
 );
 
-COMMIT;
\ No newline at end of file
+COMMIT;
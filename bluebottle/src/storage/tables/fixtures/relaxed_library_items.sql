@@ -0,0 +1,122 @@
+-- The relaxed schema created by installs since the library sync engine, before schema versions
+-- were recorded.
+BEGIN;
+-- Holds onto RPC calls of backends with responses that
+-- can be cached, this reduces wait time and traffic on the network.
+CREATE TABLE IF NOT EXISTS backend_content_cache (
+    backend_id TEXT,
+    cache_key TEXT,
+    content BLOB,
+    updated_at BIGINT,
+    expires_at BIGINT,
+    PRIMARY KEY (backend_id, cache_key)
+);
+
+-- Useful, but not super important key-value pairs for app state.
+CREATE TABLE IF NOT EXISTS app_kv_state (
+    k TEXT PRIMARY KEY,
+    v BLOB
+);
+
+-- Searchable metadata of synced items, indexed by `search_index` and
+-- `search_index_trigram` so libraries can be searched without the network.
+CREATE TABLE IF NOT EXISTS search_items (
+    id INTEGER PRIMARY KEY,
+    backend_id TEXT NOT NULL,
+    item_id TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    production_year INTEGER,
+    name TEXT NOT NULL,
+    original_name TEXT NOT NULL,
+    people TEXT NOT NULL,
+    UNIQUE (backend_id, item_id)
+);
+
+-- Word based index, used for prefix matching.
+CREATE VIRTUAL TABLE IF NOT EXISTS search_index USING fts5 (
+    name,
+    original_name,
+    people,
+    content = 'search_items',
+    content_rowid = 'id',
+    tokenize = 'unicode61 remove_diacritics 2',
+    prefix = '2 3'
+);
+
+-- Trigram based index, used for substring matching of text without word
+-- boundaries (i.e. CJK) and fuzzy matching of misspelt terms.
+CREATE VIRTUAL TABLE IF NOT EXISTS search_index_trigram USING fts5 (
+    name,
+    original_name,
+    people,
+    content = 'search_items',
+    content_rowid = 'id',
+    tokenize = 'trigram'
+);
+
+CREATE TRIGGER IF NOT EXISTS search_items_insert AFTER INSERT ON search_items BEGIN
+    INSERT INTO search_index (rowid, name, original_name, people)
+    VALUES (new.id, new.name, new.original_name, new.people);
+    INSERT INTO search_index_trigram (rowid, name, original_name, people)
+    VALUES (new.id, new.name, new.original_name, new.people);
+END;
+
+CREATE TRIGGER IF NOT EXISTS search_items_delete AFTER DELETE ON search_items BEGIN
+    INSERT INTO search_index (search_index, rowid, name, original_name, people)
+    VALUES ('delete', old.id, old.name, old.original_name, old.people);
+    INSERT INTO search_index_trigram (search_index_trigram, rowid, name, original_name, people)
+    VALUES ('delete', old.id, old.name, old.original_name, old.people);
+END;
+
+CREATE TRIGGER IF NOT EXISTS search_items_update AFTER UPDATE ON search_items BEGIN
+    INSERT INTO search_index (search_index, rowid, name, original_name, people)
+    VALUES ('delete', old.id, old.name, old.original_name, old.people);
+    INSERT INTO search_index_trigram (search_index_trigram, rowid, name, original_name, people)
+    VALUES ('delete', old.id, old.name, old.original_name, old.people);
+    INSERT INTO search_index (rowid, name, original_name, people)
+    VALUES (new.id, new.name, new.original_name, new.people);
+    INSERT INTO search_index_trigram (rowid, name, original_name, people)
+    VALUES (new.id, new.name, new.original_name, new.people);
+END;
+
+-- Normalised metadata of every item synced from a backend library, allowing
+-- libraries to be displayed without waiting on the network.
+CREATE TABLE IF NOT EXISTS library_items (
+    backend_id TEXT NOT NULL,
+    item_id TEXT NOT NULL,
+    library_id TEXT NOT NULL,
+    parent_id TEXT,
+    kind TEXT NOT NULL,
+    name TEXT NOT NULL,
+    sort_name TEXT NOT NULL,
+    original_name TEXT,
+    production_year INTEGER,
+    premiere_date TEXT,
+    date_added TEXT,
+    date_last_saved TEXT,
+    community_rating REAL,
+    runtime_secs INTEGER,
+    official_rating TEXT,
+    -- JSON arrays of strings.
+    genres TEXT NOT NULL,
+    studios TEXT NOT NULL,
+    played BOOLEAN NOT NULL,
+    favourite BOOLEAN NOT NULL,
+    synced_at BIGINT NOT NULL,
+    PRIMARY KEY (backend_id, item_id)
+);
+
+CREATE INDEX IF NOT EXISTS library_items_library_idx
+ON library_items (backend_id, library_id, kind, sort_name);
+
+-- Tracks how far each library has been synced.
+CREATE TABLE IF NOT EXISTS library_sync_state (
+    backend_id TEXT NOT NULL,
+    library_id TEXT NOT NULL,
+    cursor TEXT,
+    last_sync_at BIGINT NOT NULL,
+    last_full_sync_at BIGINT NOT NULL,
+    PRIMARY KEY (backend_id, library_id)
+);
+
+COMMIT;
@@ -0,0 +1,16 @@
+-- Holds onto RPC calls of backends with responses that
+-- can be cached, this reduces wait time and traffic on the network.
+CREATE TABLE backend_content_cache (
+    backend_id TEXT,
+    cache_key TEXT,
+    content BLOB,
+    updated_at BIGINT,
+    expires_at BIGINT,
+    PRIMARY KEY (backend_id, cache_key)
+);
+
+-- Useful, but not super important key-value pairs for app state.
+CREATE TABLE app_kv_state (
+    k TEXT PRIMARY KEY,
+    v BLOB
+);
@@ -0,0 +1,60 @@
+-- Searchable metadata of synced items, indexed by `search_index` and
+-- `search_index_trigram` so libraries can be searched without the network.
+CREATE TABLE search_items (
+    id INTEGER PRIMARY KEY,
+    backend_id TEXT NOT NULL,
+    item_id TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    production_year INTEGER,
+    name TEXT NOT NULL,
+    original_name TEXT NOT NULL,
+    people TEXT NOT NULL,
+    UNIQUE (backend_id, item_id)
+);
+
+-- Word based index, used for prefix matching.
+CREATE VIRTUAL TABLE search_index USING fts5 (
+    name,
+    original_name,
+    people,
+    content = 'search_items',
+    content_rowid = 'id',
+    tokenize = 'unicode61 remove_diacritics 2',
+    prefix = '2 3'
+);
+
+-- Trigram based index, used for substring matching of text without word
+-- boundaries (i.e. CJK) and fuzzy matching of misspelt terms.
+CREATE VIRTUAL TABLE search_index_trigram USING fts5 (
+    name,
+    original_name,
+    people,
+    content = 'search_items',
+    content_rowid = 'id',
+    tokenize = 'trigram'
+);
+
+CREATE TRIGGER search_items_insert AFTER INSERT ON search_items BEGIN
+    INSERT INTO search_index (rowid, name, original_name, people)
+    VALUES (new.id, new.name, new.original_name, new.people);
+    INSERT INTO search_index_trigram (rowid, name, original_name, people)
+    VALUES (new.id, new.name, new.original_name, new.people);
+END;
+
+CREATE TRIGGER search_items_delete AFTER DELETE ON search_items BEGIN
+    INSERT INTO search_index (search_index, rowid, name, original_name, people)
+    VALUES ('delete', old.id, old.name, old.original_name, old.people);
+    INSERT INTO search_index_trigram (search_index_trigram, rowid, name, original_name, people)
+    VALUES ('delete', old.id, old.name, old.original_name, old.people);
+END;
+
+CREATE TRIGGER search_items_update AFTER UPDATE ON search_items BEGIN
+    INSERT INTO search_index (search_index, rowid, name, original_name, people)
+    VALUES ('delete', old.id, old.name, old.original_name, old.people);
+    INSERT INTO search_index_trigram (search_index_trigram, rowid, name, original_name, people)
+    VALUES ('delete', old.id, old.name, old.original_name, old.people);
+    INSERT INTO search_index (rowid, name, original_name, people)
+    VALUES (new.id, new.name, new.original_name, new.people);
+    INSERT INTO search_index_trigram (rowid, name, original_name, people)
+    VALUES (new.id, new.name, new.original_name, new.people);
+END;
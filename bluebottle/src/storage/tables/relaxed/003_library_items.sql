@@ -0,0 +1,39 @@
+-- Normalised metadata of every item synced from a backend library, allowing
+-- libraries to be displayed without waiting on the network.
+CREATE TABLE library_items (
+    backend_id TEXT NOT NULL,
+    item_id TEXT NOT NULL,
+    library_id TEXT NOT NULL,
+    parent_id TEXT,
+    kind TEXT NOT NULL,
+    name TEXT NOT NULL,
+    sort_name TEXT NOT NULL,
+    original_name TEXT,
+    production_year INTEGER,
+    premiere_date TEXT,
+    date_added TEXT,
+    date_last_saved TEXT,
+    community_rating REAL,
+    runtime_secs INTEGER,
+    official_rating TEXT,
+    -- JSON arrays of strings.
+    genres TEXT NOT NULL,
+    studios TEXT NOT NULL,
+    played BOOLEAN NOT NULL,
+    favourite BOOLEAN NOT NULL,
+    synced_at BIGINT NOT NULL,
+    PRIMARY KEY (backend_id, item_id)
+);
+
+CREATE INDEX library_items_library_idx
+ON library_items (backend_id, library_id, kind, sort_name);
+
+-- Tracks how far each library has been synced.
+CREATE TABLE library_sync_state (
+    backend_id TEXT NOT NULL,
+    library_id TEXT NOT NULL,
+    cursor TEXT,
+    last_sync_at BIGINT NOT NULL,
+    last_full_sync_at BIGINT NOT NULL,
+    PRIMARY KEY (backend_id, library_id)
+);
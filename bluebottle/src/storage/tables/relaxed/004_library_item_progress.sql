@@ -0,0 +1,14 @@
+-- Series placement and playback state of synced items, used by the Continue
+-- Watching and Next Up shelves.
+ALTER TABLE library_items ADD COLUMN series_id TEXT;
+ALTER TABLE library_items ADD COLUMN season_number INTEGER;
+ALTER TABLE library_items ADD COLUMN episode_number INTEGER;
+ALTER TABLE library_items ADD COLUMN playback_position_secs INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE library_items ADD COLUMN last_played_at BIGINT;
+
+CREATE INDEX library_items_series_idx
+ON library_items (backend_id, series_id, season_number, episode_number);
+
+-- Synced items are missing the new columns, so every library is synced in full
+-- again.
+DELETE FROM library_sync_state;
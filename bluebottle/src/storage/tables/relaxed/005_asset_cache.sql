@@ -0,0 +1,12 @@
+-- Tracks the assets cached on disk, so the cache can be pruned by when each
+-- asset was last used without relying on file access times, which are not
+-- updated on `noatime` mounts.
+CREATE TABLE asset_cache_entries (
+    asset_id TEXT PRIMARY KEY,
+    size_bytes INTEGER NOT NULL,
+    created_at BIGINT NOT NULL,
+    last_accessed_at BIGINT NOT NULL
+);
+
+CREATE INDEX asset_cache_entries_accessed_idx
+ON asset_cache_entries (last_accessed_at);
@@ -0,0 +1,7 @@
+-- The BlurHash of each synced item's primary image, displayed while the image
+-- loads.
+ALTER TABLE library_items ADD COLUMN blurhash TEXT;
+
+-- Synced items are missing their BlurHash, so every library is synced in full
+-- again.
+DELETE FROM library_sync_state;
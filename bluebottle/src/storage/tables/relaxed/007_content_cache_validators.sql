@@ -0,0 +1,8 @@
+-- Validators of cached backend responses and when they become stale, so stale
+-- responses can be served while they are revalidated with the server.
+ALTER TABLE backend_content_cache ADD COLUMN etag TEXT;
+ALTER TABLE backend_content_cache ADD COLUMN last_modified TEXT;
+ALTER TABLE backend_content_cache ADD COLUMN stale_at BIGINT;
+
+-- Entries cached before are fresh until they expire.
+UPDATE backend_content_cache SET stale_at = expires_at;
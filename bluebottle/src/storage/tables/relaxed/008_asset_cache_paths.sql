@@ -0,0 +1,3 @@
+-- The path each asset was cached under, so the assets of a backend can be found
+-- when its cache is exported. Assets cached before are not exported.
+ALTER TABLE asset_cache_entries ADD COLUMN path TEXT;
@@ -0,0 +1,105 @@
+//! Persists the audio and subtitle preferences of each backend's user, along with
+//! the choices remembered for each series.
+
+use super::settings::{Entry, Setting};
+use crate::backends::BackendId;
+use crate::models::media::ItemId;
+use crate::models::tracks::TrackPreferences;
+
+/// The user's default preferences, keyed by backend, and the preferences
+/// remembered for each series, keyed by backend and series.
+pub static TRACK_PREFERENCES: Setting<TrackPreferences> =
+    Setting::durable("track_preferences", TrackPreferences::default);
+
+/// Load the user's default preferences, falling back to the defaults if none are
+/// saved.
+pub fn load(backend_id: BackendId) -> TrackPreferences {
+    default_entry(backend_id).load()
+}
+
+/// Persist the user's default preferences, waiting for them to be written.
+pub async fn save_async(backend_id: BackendId, preferences: TrackPreferences) {
+    default_entry(backend_id).save_async(&preferences).await;
+}
+
+/// Load the preferences remembered for a series, if any.
+pub fn load_series(backend_id: BackendId, series_id: &str) -> Option<TrackPreferences> {
+    series_entry(backend_id, series_id).try_load()
+}
+
+/// Remember the preferences for the rest of a series.
+pub fn save_series(
+    backend_id: BackendId,
+    series_id: &str,
+    preferences: &TrackPreferences,
+) {
+    series_entry(backend_id, series_id).save(preferences);
+}
+
+/// Returns the preferences an item is played with, which are those remembered for
+/// its series if any, otherwise the user's defaults.
+pub fn resolve(backend_id: BackendId, series_id: Option<&ItemId>) -> TrackPreferences {
+    series_id
+        .and_then(|series_id| load_series(backend_id, series_id))
+        .unwrap_or_else(|| load(backend_id))
+}
+
+/// Returns the preferences an item is played with from within an async context,
+/// see [resolve].
+pub async fn resolve_async(
+    backend_id: BackendId,
+    series_id: Option<&ItemId>,
+) -> TrackPreferences {
+    if let Some(series_id) = series_id
+        && let Some(preferences) =
+            series_entry(backend_id, series_id).try_load_async().await
+    {
+        return preferences;
+    }
+    default_entry(backend_id).load_async().await
+}
+
+fn default_entry(backend_id: BackendId) -> Entry<TrackPreferences> {
+    TRACK_PREFERENCES.keyed(backend_id)
+}
+
+fn series_entry(backend_id: BackendId, series_id: &str) -> Entry<TrackPreferences> {
+    TRACK_PREFERENCES.keyed(format_args!("{backend_id}:{series_id}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::tracks::SubtitleMode;
+    use crate::storage::test_utils::temp_storage;
+
+    #[rstest::rstest]
+    fn test_save_and_resolve(_temp_storage: tempfile::TempDir) {
+        let backend_id = BackendId::now_v7();
+        let series_id = "series-1".to_string();
+        assert_eq!(load(backend_id), TrackPreferences::default());
+        assert!(load_series(backend_id, &series_id).is_none());
+
+        let defaults = TrackPreferences {
+            audio_language: Some("jpn".into()),
+            subtitle_mode: SubtitleMode::Always,
+            subtitle_language: Some("eng".into()),
+        };
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(save_async(backend_id, defaults.clone()));
+        assert_eq!(resolve(backend_id, Some(&series_id)), defaults);
+        assert_eq!(resolve(backend_id, None), defaults);
+
+        let series = TrackPreferences {
+            audio_language: Some("eng".into()),
+            subtitle_mode: SubtitleMode::OnlyForced,
+            subtitle_language: None,
+        };
+        save_series(backend_id, &series_id, &series);
+        assert_eq!(resolve(backend_id, Some(&series_id)), series);
+        assert_eq!(resolve(backend_id, Some(&"series-2".to_string())), defaults);
+        assert_eq!(load(BackendId::now_v7()), TrackPreferences::default());
+    }
+}
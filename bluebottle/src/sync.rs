@@ -0,0 +1,239 @@
+//! The background sync engine, keeping a local copy of every backend library.
+//!
+//! Each backend is synced independently, the first sync of a library fetches every
+//! item, later syncs only fetch items changed since the last one. A full sync is
+//! still performed periodically to drop deleted items and pick up changes which
+//! do not bump the item's save date, like played state.
+
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+
+use iced::Subscription;
+use iced::futures::channel::mpsc;
+use iced::futures::{SinkExt, Stream};
+use parking_lot::Mutex;
+use tokio::sync::Notify;
+
+use crate::backends::{Backend, BackendId, registry};
+use crate::models::media::{ItemId, Library};
+use crate::models::sync::SyncRequest;
+use crate::storage;
+use crate::storage::library_items::{self, LibrarySyncState};
+
+/// How long to wait between syncs of a backend.
+const SYNC_INTERVAL: Duration = Duration::from_secs(15 * 60);
+/// How long in milliseconds between full syncs of a library.
+const FULL_SYNC_INTERVAL_MS: i64 = 24 * 3600 * 1000;
+/// The number of items requested from the backend at once.
+const PAGE_SIZE: usize = 250;
+
+/// Wakes the sync of a backend before its next scheduled sync.
+static SYNC_REQUESTS: LazyLock<Mutex<HashMap<BackendId, Arc<Notify>>>> =
+    LazyLock::new(Default::default);
+
+#[derive(Debug, Clone)]
+/// An update from the sync engine.
+pub enum SyncEvent {
+    /// The sync of a library has progressed.
+    Progress(SyncProgress),
+    /// Every library of the backend has been synced, successfully or not.
+    Finished(BackendId),
+}
+
+#[derive(Debug, Clone)]
+/// The progress of a library sync.
+pub struct SyncProgress {
+    pub backend_id: BackendId,
+    pub library_id: ItemId,
+    pub library_name: String,
+    /// The number of items fetched so far.
+    pub synced_items: usize,
+    /// The number of items that need fetching, this is `0` until the first page
+    /// has been fetched.
+    pub total_items: usize,
+    pub status: SyncStatus,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyncStatus {
+    Running,
+    /// The sync completed, `changed` is set if any items were added, changed or
+    /// removed.
+    Complete {
+        changed: bool,
+    },
+    Failed(String),
+}
+
+/// Sync the backend as soon as possible, i.e. because it reported its libraries
+/// changed.
+///
+/// If the backend is already syncing, it syncs again once finished.
+pub fn request_sync(backend_id: BackendId) {
+    sync_requests(backend_id).notify_one();
+}
+
+fn sync_requests(backend_id: BackendId) -> Arc<Notify> {
+    SYNC_REQUESTS.lock().entry(backend_id).or_default().clone()
+}
+
+/// Runs the sync engine for every registered backend.
+pub fn subscription() -> Subscription<SyncEvent> {
+    let subscriptions = registry::all()
+        .into_iter()
+        .map(|(backend_id, _)| Subscription::run_with(backend_id, sync_stream));
+    Subscription::batch(subscriptions)
+}
+
+fn sync_stream(backend_id: &BackendId) -> impl Stream<Item = SyncEvent> + use<> {
+    let backend_id = *backend_id;
+    iced::stream::channel(32, async move |mut output: mpsc::Sender<SyncEvent>| {
+        let requests = sync_requests(backend_id);
+        loop {
+            match registry::get(backend_id) {
+                Some(backend) => {
+                    sync_backend(backend_id, backend.as_ref(), &mut output).await;
+                },
+                None => {
+                    tracing::warn!(backend_id = %backend_id, "backend no longer exists")
+                },
+            }
+
+            let _ = output.send(SyncEvent::Finished(backend_id)).await;
+            let _ = tokio::time::timeout(SYNC_INTERVAL, requests.notified()).await;
+        }
+    })
+}
+
+async fn sync_backend(
+    backend_id: BackendId,
+    backend: &dyn Backend,
+    output: &mut mpsc::Sender<SyncEvent>,
+) {
+    let libraries = match backend.libraries().await {
+        Ok(libraries) => libraries,
+        Err(err) => {
+            tracing::warn!(backend_id = %backend_id, error = %err, "failed to list libraries for sync");
+            return;
+        },
+    };
+
+    for library in libraries {
+        let mut progress = SyncProgress {
+            backend_id,
+            library_id: library.id.clone(),
+            library_name: library.name.clone(),
+            synced_items: 0,
+            total_items: 0,
+            status: SyncStatus::Running,
+        };
+        let _ = output.send(SyncEvent::Progress(progress.clone())).await;
+
+        let result =
+            sync_library(backend_id, backend, &library, &mut progress, output).await;
+        progress.status = match result {
+            Ok(changed) => SyncStatus::Complete { changed },
+            Err(err) => {
+                tracing::error!(
+                    backend_id = %backend_id,
+                    library_id = %library.id,
+                    error = %err,
+                    "library sync failed",
+                );
+                SyncStatus::Failed(err)
+            },
+        };
+        let _ = output.send(SyncEvent::Progress(progress)).await;
+    }
+}
+
+/// Sync a single library, returning whether any items changed.
+async fn sync_library(
+    backend_id: BackendId,
+    backend: &dyn Backend,
+    library: &Library,
+    progress: &mut SyncProgress,
+    output: &mut mpsc::Sender<SyncEvent>,
+) -> Result<bool, String> {
+    let started_at = storage::now();
+    let previous = library_items::sync_state(backend_id, library.id.clone()).await?;
+
+    let full_sync = previous.as_ref().is_none_or(|previous| {
+        started_at - previous.last_full_sync_at >= FULL_SYNC_INTERVAL_MS
+    });
+    let previous_cursor = previous.as_ref().and_then(|state| state.cursor.clone());
+    let changed_since = if full_sync {
+        None
+    } else {
+        previous_cursor.clone()
+    };
+
+    tracing::info!(
+        backend_id = %backend_id,
+        library_id = %library.id,
+        full_sync = full_sync,
+        "syncing library",
+    );
+
+    let mut cursor = previous_cursor.clone();
+    let mut changed = full_sync;
+    loop {
+        let request = SyncRequest {
+            library_id: library.id.clone(),
+            changed_since: changed_since.clone(),
+            start_index: progress.synced_items,
+            limit: PAGE_SIZE,
+        };
+        let page = backend
+            .sync_items(request)
+            .await
+            .map_err(|err| err.to_string())?;
+
+        let fetched = page.items.len();
+        for item in page.items.iter() {
+            // The backend returns items saved *on or after* the cursor, so the
+            // most recent items of the last sync are always returned again.
+            if item.date_last_saved > previous_cursor {
+                changed = true;
+            }
+            if item.date_last_saved > cursor {
+                cursor = item.date_last_saved.clone();
+            }
+        }
+
+        library_items::store(backend_id, library.id.clone(), page.items, started_at)
+            .await?;
+
+        progress.synced_items += fetched;
+        progress.total_items = page.total_items;
+        let _ = output.send(SyncEvent::Progress(progress.clone())).await;
+
+        if fetched == 0 || progress.synced_items >= page.total_items {
+            break;
+        }
+    }
+
+    // Items deleted while paging shift later items onto earlier pages, so some
+    // could have been missed, leave the stale items in place until the next
+    // full sync rather than dropping items which still exist.
+    if full_sync && progress.synced_items >= progress.total_items {
+        let removed =
+            library_items::remove_stale(backend_id, library.id.clone(), started_at)
+                .await?;
+        changed |= removed > 0;
+    }
+
+    let last_full_sync_at = match previous {
+        Some(previous) if !full_sync => previous.last_full_sync_at,
+        _ => started_at,
+    };
+    let state = LibrarySyncState {
+        cursor,
+        last_sync_at: started_at,
+        last_full_sync_at,
+    };
+    library_items::save_sync_state(backend_id, library.id.clone(), state).await?;
+
+    Ok(changed)
+}